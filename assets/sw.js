@@ -0,0 +1,102 @@
+// The service worker, which keeps the app and the posts of the last visit available offline.
+// Only what is the same for everyone is kept: the shell of the app, fetched without the session cookie,
+// and responses of the feed which the server marks as shareable, so that nothing personal stays readable offline.
+// Pages are fetched from the network and fall back to the shell or the offline page when offline,
+// the feed is fetched from the network first and falls back to the cache,
+// and assets under their hashed names never change and are served from the cache first.
+
+// Bump the version to discard everything cached by previous versions of this service worker
+const CACHE = "blogposts-v2";
+
+// The page shown when a page is requested offline which was never visited before
+const OFFLINE_PAGE = "/offline.html";
+
+// The shell of the app, cached when the service worker is installed
+const SHELL = ["/", OFFLINE_PAGE, "/main.css", "/manifest.webmanifest", "/header.svg", "/favicon.ico"];
+
+// The server function whose responses are kept, so that the feed can be read offline
+const FEED_ENDPOINT = "/api/fetch_blog_posts";
+
+// The message with which the app asks to forget what was cached, once its user logs out
+const LOGOUT_MESSAGE = "logout";
+
+self.addEventListener("install", (event) => {
+    event.waitUntil(cacheShell().then(() => self.skipWaiting()));
+});
+
+self.addEventListener("activate", (event) => {
+    event.waitUntil(
+        caches.keys()
+            .then((keys) => Promise.all(keys.filter((key) => key !== CACHE).map((key) => caches.delete(key))))
+            .then(() => self.clients.claim()),
+    );
+});
+
+self.addEventListener("message", (event) => {
+    if (event.data === LOGOUT_MESSAGE) {
+        event.waitUntil(caches.delete(CACHE).then(cacheShell));
+    }
+});
+
+self.addEventListener("fetch", (event) => {
+    const request = event.request;
+    const url = new URL(request.url);
+    if (request.method !== "GET" || url.origin !== self.location.origin) {
+        return;
+    }
+    if (url.pathname.startsWith("/static/")) {
+        event.respondWith(cacheFirst(request));
+    } else if (request.mode === "navigate") {
+        // Pages are rendered for whoever is logged in, so they are never cached besides the anonymous shell
+        event.respondWith(fetch(request).catch(() => offlinePage(url)));
+    } else if (url.pathname === FEED_ENDPOINT) {
+        event.respondWith(networkFirst(request).then((response) => response || Response.error()));
+    }
+});
+
+// Cache the shell of the app as anonymous visitors see it
+async function cacheShell() {
+    const cache = await caches.open(CACHE);
+    await cache.addAll(SHELL.map((url) => new Request(url, { credentials: "omit" })));
+}
+
+// The home page from the shell if it was requested, or else the offline page
+async function offlinePage(url) {
+    const shell = url.pathname === "/" ? await caches.match("/") : undefined;
+    return shell || caches.match(OFFLINE_PAGE);
+}
+
+// Whether the response is the same for everyone, which the server says by not marking it `private` or `no-store`
+function isShareable(response) {
+    const cacheControl = response.headers.get("Cache-Control") || "";
+    return response.ok && !/\b(private|no-store)\b/i.test(cacheControl);
+}
+
+// Respond from the cache, or fetch and cache the response if it is not cached yet
+async function cacheFirst(request) {
+    const cached = await caches.match(request);
+    if (cached) {
+        return cached;
+    }
+    const response = await fetch(request);
+    if (isShareable(response)) {
+        const cache = await caches.open(CACHE);
+        await cache.put(request, response.clone());
+    }
+    return response;
+}
+
+// Fetch and cache the response if it may be kept, or respond from the cache when offline.
+// Resolves to undefined if the request fails and nothing is cached for it.
+async function networkFirst(request) {
+    try {
+        const response = await fetch(request);
+        if (isShareable(response)) {
+            const cache = await caches.open(CACHE);
+            await cache.put(request, response.clone());
+        }
+        return response;
+    } catch (err) {
+        return caches.match(request);
+    }
+}
@@ -0,0 +1,295 @@
+# German messages of the user interface.
+
+## Common
+
+back-to-all-posts = Zurück zu allen Beiträgen
+back-to-blog = Zurück zum Blog
+cancel = Abbrechen
+close = Schließen
+edit = Bearbeiten
+delete = Löschen
+restore = Wiederherstellen
+undo = Rückgängig
+load-more = Mehr laden
+loading-posts = Beiträge werden geladen...
+post-heading = Beitrag { $id }
+posted-by = Verfasst von
+posted-on = am { $date }
+posted-by-user = Verfasst von { $username }
+posted-by-on = Verfasst von { $username } am { $date }
+avatar = Avatar
+avatar-of = Avatar von { $username }
+
+## Relative times
+
+relative-just-now = gerade eben
+relative-minutes = { $count ->
+    [one] vor 1 Minute
+   *[other] vor { $count } Minuten
+}
+relative-hours = { $count ->
+    [one] vor 1 Stunde
+   *[other] vor { $count } Stunden
+}
+relative-days = { $count ->
+    [one] vor 1 Tag
+   *[other] vor { $count } Tagen
+}
+relative-date = am { $date }
+
+## Pages
+
+page-not-found = Seite nicht gefunden
+page-not-found-text = Die angeforderte Seite existiert nicht.
+home-title = Willkommen im Blog
+trash = Papierkorb
+admin-title = Administration
+
+## Blog statistics
+
+blog-stats-summary = { $posts } Beiträge von { $authors } Autoren, { $mib } MiB an Bildern
+blog-stats-days = Beiträge pro Tag in den letzten 30 Tagen
+blog-stats-day = { $date }: { $count ->
+    [one] 1 Beitrag
+   *[other] { $count } Beiträge
+}
+
+## Feed filters
+
+feed-sort = Beiträge sortieren
+feed-sort-newest = Neueste zuerst
+feed-sort-oldest = Älteste zuerst
+feed-filter-author = Benutzername
+feed-filter-with-image = Nur mit Bild
+feed-filter-from = Von
+feed-filter-until = Bis
+feed-filter-reset = Filter zurücksetzen
+
+## Search
+
+search = Suchen
+search-placeholder = Beiträge durchsuchen...
+search-no-results = Keine Beiträge passen zu „{ $query }“
+search-results = Suchergebnisse für „{ $query }“
+search-error = Fehler bei der Suche nach Beiträgen
+searching = Suche läuft...
+
+## Post page
+
+post-deleted = Dieser Beitrag wurde gelöscht.
+post-not-found = Dieser Beitrag existiert nicht oder wurde gelöscht.
+post-error = Fehler beim Laden des Beitrags
+post-loading = Beitrag wird geladen...
+
+## Author page
+
+profile-error = Fehler beim Laden des Profils
+profile-post-count = { $count ->
+    [one] 1 Beitrag
+   *[other] { $count } Beiträge
+}
+
+## Trash
+
+trash-log-in = Melde dich an, um die Beiträge zu sehen, die du gelöscht hast.
+trash-empty = Der Papierkorb ist leer.
+trash-hint = Gelöschte Beiträge können wiederhergestellt werden, bis sie endgültig gelöscht werden.
+trash-error = Fehler beim Laden des Papierkorbs
+trash-loading = Papierkorb wird geladen...
+trash-deleted-on = Gelöscht am { $deleted }, wird am { $purged } endgültig gelöscht
+
+## Administration
+
+admin-log-in = Melde dich als Administrator an, um Beiträge zu moderieren.
+statistics = Statistiken
+statistics-loading = Statistiken werden geladen...
+statistics-visible-posts = Sichtbare Beiträge
+statistics-hidden-posts = Verborgene Beiträge
+statistics-trashed-posts = Beiträge im Papierkorb
+statistics-revisions = Überarbeitungen
+statistics-users = Benutzer
+statistics-likes = Likes
+statistics-tags = Tags
+statistics-open-reports = Offene Meldungen
+statistics-db-connections = Datenbankverbindungen
+statistics-db-connections-value = { $connections } ({ $idle } ungenutzt)
+statistics-stored-images = Gespeicherte Bilder
+statistics-stored-images-value = { $images } ({ $kib } KiB)
+orphaned-images-delete = Verwaiste Bilder löschen
+orphaned-images-deleting = Verwaiste Bilder werden gelöscht...
+orphaned-images-deleted = { $count ->
+    [one] 1 verwaistes Bild gelöscht
+   *[other] { $count } verwaiste Bilder gelöscht
+}
+reports = Meldungen
+reports-none = Es gibt keine offenen Meldungen.
+reports-loading = Meldungen werden geladen...
+report-post = Beitrag { $id } von { $username }
+report-summary = Gemeldet von { $reporter } am { $time }: { $reason }
+report-hide-post = Beitrag verbergen
+report-dismiss = Verwerfen
+moderation-posts = Beiträge
+moderation-select-first = Wähle zuerst Beiträge aus
+moderation-hide = Verbergen
+moderation-unhide = Anzeigen
+moderation-delete = Löschen
+moderation-restore = Wiederherstellen
+moderation-applied = { $count ->
+    [one] { $action } auf 1 Beitrag angewendet
+   *[other] { $action } auf { $count } Beiträge angewendet
+}
+moderation-in-trash = Seit { $date } im Papierkorb
+moderation-hidden = Verborgen
+moderation-visible = Sichtbar
+export = Export
+export-description = Alle Beiträge, die nicht im Papierkorb sind, mit ihren Bildern als tar-Archiv herunterladen.
+export-download = Archiv herunterladen
+user-data = Nutzerdaten
+user-data-description = Alle Daten eines Nutzers herunterladen oder unwiderruflich löschen, z. B. wenn er darum bittet.
+user-data-download = Daten herunterladen
+user-data-erase = Daten löschen
+user-data-erase-confirm = Das Konto von { $username } und alle seine Beiträge, auch die im Papierkorb, löschen? Das kann nicht rückgängig gemacht werden.
+user-data-erased = { $count ->
+    [one] Die Daten von { $username } mit 1 Beitrag wurden gelöscht
+   *[other] Die Daten von { $username } mit { $count } Beiträgen wurden gelöscht
+}
+api-keys = API-Schlüssel
+api-keys-none = Es gibt keine API-Schlüssel.
+api-keys-loading = API-Schlüssel werden geladen...
+api-key-name = Wofür der Schlüssel ist, z. B. der Name eines Bots
+api-key-create = Schlüssel erstellen
+api-key-created = Kopiere den neuen Schlüssel jetzt, er wird nicht noch einmal angezeigt:
+api-key-created-on = Erstellt am { $time }
+api-key-last-used = Zuletzt verwendet am { $time }
+api-key-never-used = Nie verwendet
+api-key-revoke = Widerrufen
+audit-log = Änderungsprotokoll
+audit-log-none = In diesem Zeitraum wurden keine Änderungen aufgezeichnet.
+audit-log-loading = Änderungsprotokoll wird geladen...
+audit-log-since = Von
+audit-log-until = Bis
+audit-log-all-actions = Alle Aktionen
+audit-log-server = Server
+audit-log-post = Beitrag { $id }
+audit-action-create = Erstellt
+audit-action-update = Bearbeitet
+audit-action-delete = Gelöscht
+audit-action-restore = Wiederhergestellt
+audit-action-purge = Endgültig gelöscht
+audit-action-hide = Verborgen
+audit-action-unhide = Wieder sichtbar
+audit-action-import = Importiert
+audit-action-erase-user = Nutzer gelöscht
+audit-action-create-api-key = API-Schlüssel erstellt
+audit-action-revoke-api-key = API-Schlüssel widerrufen
+
+## Account
+
+logged-in-as = Angemeldet als { $username }
+log-in = Anmelden
+log-out = Abmelden
+log-in-or-register = Anmelden oder registrieren
+register = Registrieren
+username = Benutzername
+password = Passwort
+please-wait = Bitte warten...
+theme-dark = Dunkles Design
+theme-light = Helles Design
+locale-switcher = Sprache
+
+## Writing posts
+
+log-in-to-post = , um einen Beitrag zu schreiben.
+posting-as = Du schreibst als { $username }
+whats-on-your-mind = Was gibt es Neues?
+post-text-placeholder = Schreib deinen Beitrag hier... (Markdown wird unterstützt)
+preview = Vorschau
+characters-remaining = { $count ->
+    [one] Noch 1 Zeichen
+   *[other] Noch { $count } Zeichen
+}
+characters-over = { $count ->
+    [one] 1 Zeichen zu viel
+   *[other] { $count } Zeichen zu viel
+}
+tags-placeholder = Tags, z. B. #rust #web (optional)
+image-alt-placeholder = Beschreibe das Bild für Menschen, die es nicht sehen können
+avatar-url-placeholder = Avatar-URL (optional)
+avatar-upload = ...oder lade einen Avatar hoch:
+avatar-cropped = Zugeschnittener Avatar
+avatar-crop-size = Größe
+avatar-crop-horizontal = Horizontal
+avatar-crop-vertical = Vertikal
+submit-post = Beitrag veröffentlichen
+posting = Wird veröffentlicht...
+resizing-image = Bild wird verkleinert...
+uploading-image = Bild wird hochgeladen...
+uploading-avatar = Avatar wird hochgeladen...
+upload-failed = Das Bild konnte nicht hochgeladen werden
+upload-offline = Das Bild konnte nicht hochgeladen werden, bitte prüfe deine Verbindung
+file-read-failed = Die Datei konnte nicht gelesen werden
+upload-cancelled = Das Hochladen wurde abgebrochen
+post-created = Beitrag veröffentlicht!
+post-queued = { $reason }. Dein Beitrag erscheint, sobald ein Administrator ihn freigibt.
+
+## Feed
+
+recent-posts = Neueste Beiträge
+posts-error = Fehler beim Laden der Beiträge
+more-posts-error = Fehler beim Laden weiterer Beiträge
+post-image = Bild des Beitrags
+edited = (bearbeitet)
+post-last-edited = Zuletzt bearbeitet am { $time }
+share = Teilen
+share-shared = Beitrag geteilt
+share-copied = Link in die Zwischenablage kopiert
+share-failed = Der Link konnte nicht kopiert werden
+post-moved-to-trash = Beitrag in den Papierkorb verschoben
+post-restored = Beitrag wiederhergestellt
+select-post = Diesen Beitrag auswählen
+posts-selected = { $count ->
+    [one] 1 Beitrag ausgewählt
+   *[other] { $count } Beiträge ausgewählt
+}
+delete-selected = Ausgewählte löschen
+posts-moved-to-trash = { $count ->
+    [one] 1 Beitrag in den Papierkorb verschoben
+   *[other] { $count } Beiträge in den Papierkorb verschoben
+}
+image-load-failed = Das Bild konnte nicht geladen werden
+report = Melden
+
+## Editing and history
+
+edit-placeholder = Schreib deinen Beitrag hier...
+save = Speichern
+saving = Wird gespeichert...
+history-title = Verlauf von Beitrag { $id }
+history-error = Fehler beim Laden des Verlaufs dieses Beitrags
+history-loading = Verlauf wird geladen...
+revision-replaced = Ersetzt am { $time }
+mentions-title = Erwähnt auf
+mention-received = am { $time }
+
+## Reports
+
+report-placeholder = Warum meldest du diesen Beitrag?
+report-send = Meldung senden
+report-thanks = Danke, ein Administrator wird sich diesen Beitrag ansehen.
+report-already-sent = Du hast diesen Beitrag bereits gemeldet, ein Administrator wird ihn sich ansehen.
+
+## Errors
+
+error-not-logged-in = Dafür musst du angemeldet sein.
+error-not-admin = Das dürfen nur Administratoren.
+error-post-not-found = Dieser Beitrag existiert nicht.
+error-not-post-owner = Du kannst nur deine eigenen Beiträge ändern.
+error-invalid-credentials = Benutzername oder Passwort ist falsch.
+error-invalid-api-key = Der API-Schlüssel ist ungültig oder wurde widerrufen.
+error-already-reported = Du hast diesen Beitrag bereits gemeldet.
+error-rate-limited = Du machst das zu oft, bitte versuche es in { $seconds } Sekunden erneut.
+error-invalid-challenge = Die Spam-Prüfung ist fehlgeschlagen, bitte versuche es erneut.
+error-unavailable = Der Server ist ausgelastet, bitte versuche es gleich noch einmal.
+error-timed-out = Die Anfrage hat zu lange gedauert, bitte versuche es erneut.
+error-internal = Auf dem Server ist etwas schiefgelaufen, bitte versuche es später erneut.
+error-network = Der Server ist nicht erreichbar, bitte prüfe deine Verbindung.
@@ -0,0 +1,299 @@
+# English messages of the user interface, which other locales fall back to.
+
+## Common
+
+back-to-all-posts = Back to all posts
+back-to-blog = Back to the blog
+cancel = Cancel
+close = Close
+edit = Edit
+delete = Delete
+restore = Restore
+undo = Undo
+load-more = Load more
+loading-posts = Loading posts...
+post-heading = Post { $id }
+# Followed by a link to the author and `posted-on`
+posted-by = Posted by
+posted-on = on { $date }
+posted-by-user = Posted by { $username }
+posted-by-on = Posted by { $username } on { $date }
+avatar = Avatar
+avatar-of = Avatar of { $username }
+
+## Relative times, which follow `posted-by` and the author
+
+relative-just-now = just now
+relative-minutes = { $count ->
+    [one] 1 minute ago
+   *[other] { $count } minutes ago
+}
+relative-hours = { $count ->
+    [one] 1 hour ago
+   *[other] { $count } hours ago
+}
+relative-days = { $count ->
+    [one] 1 day ago
+   *[other] { $count } days ago
+}
+relative-date = on { $date }
+
+## Pages
+
+page-not-found = Page not found
+page-not-found-text = The page you requested doesn't exist.
+home-title = Welcome to the blog
+trash = Trash
+admin-title = Administration
+
+## Blog statistics
+
+blog-stats-summary = { $posts } posts by { $authors } authors, { $mib } MiB of images
+blog-stats-days = Posts per day in the last 30 days
+blog-stats-day = { $date }: { $count ->
+    [one] 1 post
+   *[other] { $count } posts
+}
+
+## Feed filters
+
+feed-sort = Sort posts
+feed-sort-newest = Newest first
+feed-sort-oldest = Oldest first
+feed-filter-author = Username
+feed-filter-with-image = Only with image
+feed-filter-from = From
+feed-filter-until = Until
+feed-filter-reset = Reset filters
+
+## Search
+
+search = Search
+search-placeholder = Search posts...
+search-no-results = No posts match "{ $query }"
+search-results = Search results for "{ $query }"
+search-error = Error searching posts
+searching = Searching...
+
+## Post page
+
+post-deleted = This post was deleted.
+post-not-found = This post doesn't exist, or it was deleted.
+post-error = Error fetching post
+post-loading = Loading post...
+
+## Author page
+
+profile-error = Error fetching profile
+profile-post-count = { $count ->
+    [one] 1 post
+   *[other] { $count } posts
+}
+
+## Trash
+
+trash-log-in = Log in to see the posts you deleted.
+trash-empty = The trash is empty.
+trash-hint = Deleted posts can be restored until they are deleted permanently.
+trash-error = Error fetching the trash
+trash-loading = Loading trash...
+trash-deleted-on = Deleted on { $deleted }, will be deleted permanently on { $purged }
+
+## Administration
+
+admin-log-in = Log in as an administrator to moderate posts.
+statistics = Statistics
+statistics-loading = Loading statistics...
+statistics-visible-posts = Visible posts
+statistics-hidden-posts = Hidden posts
+statistics-trashed-posts = Posts in the trash
+statistics-revisions = Revisions
+statistics-users = Users
+statistics-likes = Likes
+statistics-tags = Tags
+statistics-open-reports = Open reports
+statistics-db-connections = Database connections
+statistics-db-connections-value = { $connections } ({ $idle } idle)
+statistics-stored-images = Stored images
+statistics-stored-images-value = { $images } ({ $kib } KiB)
+orphaned-images-delete = Delete orphaned images
+orphaned-images-deleting = Deleting orphaned images...
+orphaned-images-deleted = { $count ->
+    [one] Deleted 1 orphaned image
+   *[other] Deleted { $count } orphaned images
+}
+reports = Reports
+reports-none = There are no open reports.
+reports-loading = Loading reports...
+report-post = Post { $id } by { $username }
+report-summary = Reported by { $reporter } on { $time }: { $reason }
+report-hide-post = Hide post
+report-dismiss = Dismiss
+moderation-posts = Posts
+moderation-select-first = Select some posts first
+moderation-hide = Hide
+moderation-unhide = Unhide
+moderation-delete = Delete
+moderation-restore = Restore
+# The action is one of the moderation buttons above
+moderation-applied = { $count ->
+    [one] { $action } applied to 1 post
+   *[other] { $action } applied to { $count } posts
+}
+moderation-in-trash = In the trash since { $date }
+moderation-hidden = Hidden
+moderation-visible = Visible
+export = Export
+export-description = Download all posts which are not in the trash, with their images, as a tar archive.
+export-download = Download archive
+user-data = User data
+user-data-description = Download all data of a user, or erase it irreversibly, e.g. when they ask for it.
+user-data-download = Download data
+user-data-erase = Erase data
+user-data-erase-confirm = Erase the account of { $username } and all their posts, including those in the trash? This cannot be undone.
+user-data-erased = { $count ->
+    [one] Erased the data of { $username } with 1 post
+   *[other] Erased the data of { $username } with { $count } posts
+}
+api-keys = API keys
+api-keys-none = There are no API keys.
+api-keys-loading = Loading API keys...
+api-key-name = What the key is for, e.g. the name of a bot
+api-key-create = Create key
+api-key-created = Copy the new key now, it will not be shown again:
+api-key-created-on = Created on { $time }
+api-key-last-used = Last used on { $time }
+api-key-never-used = Never used
+api-key-revoke = Revoke
+audit-log = Audit log
+audit-log-none = No changes were recorded in this time.
+audit-log-loading = Loading audit log...
+audit-log-since = From
+audit-log-until = Until
+audit-log-all-actions = All actions
+audit-log-server = Server
+audit-log-post = Post { $id }
+audit-action-create = Created
+audit-action-update = Edited
+audit-action-delete = Deleted
+audit-action-restore = Restored
+audit-action-purge = Purged
+audit-action-hide = Hidden
+audit-action-unhide = Unhidden
+audit-action-import = Imported
+audit-action-erase-user = User erased
+audit-action-create-api-key = API key created
+audit-action-revoke-api-key = API key revoked
+
+## Account
+
+logged-in-as = Logged in as { $username }
+log-in = Log in
+log-out = Log out
+log-in-or-register = Log in or register
+register = Register
+username = Username
+password = Password
+please-wait = Please wait...
+theme-dark = Dark mode
+theme-light = Light mode
+locale-switcher = Language
+
+## Writing posts
+
+# Follows a link to the login page labelled with `log-in`
+log-in-to-post = { " " }to write a post.
+posting-as = Posting as { $username }
+whats-on-your-mind = What's on your mind?
+post-text-placeholder = Write your post here... (Markdown is supported)
+preview = Preview
+characters-remaining = { $count ->
+    [one] 1 character left
+   *[other] { $count } characters left
+}
+characters-over = { $count ->
+    [one] 1 character too many
+   *[other] { $count } characters too many
+}
+tags-placeholder = Tags, e.g. #rust #web (optional)
+image-alt-placeholder = Describe the image for people who cannot see it
+avatar-url-placeholder = Avatar URL (optional)
+avatar-upload = ...or upload an avatar:
+avatar-cropped = Cropped avatar
+avatar-crop-size = Size
+avatar-crop-horizontal = Horizontal
+avatar-crop-vertical = Vertical
+submit-post = Submit Post
+posting = Posting...
+resizing-image = Resizing image...
+uploading-image = Uploading image...
+uploading-avatar = Uploading avatar...
+upload-failed = Failed to upload the image
+upload-offline = Failed to upload the image, please check your connection
+file-read-failed = The file could not be read
+upload-cancelled = The upload was cancelled
+post-created = Post created!
+# The reason is given by the content filter
+post-queued = { $reason }. Your post will appear once an administrator approves it.
+
+## Feed
+
+recent-posts = Recent Posts
+posts-error = Error fetching posts
+more-posts-error = Error fetching more posts
+post-image = Post image
+edited = (edited)
+post-last-edited = Last edited on { $time }
+share = Share
+share-shared = Post shared
+share-copied = Link copied to the clipboard
+share-failed = Failed to copy the link
+post-moved-to-trash = Post moved to the trash
+post-restored = Post restored
+select-post = Select this post
+posts-selected = { $count ->
+    [one] 1 post selected
+   *[other] { $count } posts selected
+}
+delete-selected = Delete selected
+posts-moved-to-trash = { $count ->
+    [one] 1 post moved to the trash
+   *[other] { $count } posts moved to the trash
+}
+image-load-failed = The image could not be loaded
+report = Report
+
+## Editing and history
+
+edit-placeholder = Write your post here...
+save = Save
+saving = Saving...
+history-title = History of post { $id }
+history-error = Error fetching the history of this post
+history-loading = Loading history...
+revision-replaced = Replaced on { $time }
+mentions-title = Mentioned on
+mention-received = on { $time }
+
+## Reports
+
+report-placeholder = Why are you reporting this post?
+report-send = Send report
+report-thanks = Thank you, an administrator will look at this post.
+report-already-sent = You already reported this post, an administrator will look at it.
+
+## Errors
+
+error-not-logged-in = You must be logged in to do that.
+error-not-admin = Only administrators may do that.
+error-post-not-found = This post does not exist.
+error-not-post-owner = You can only change your own posts.
+error-invalid-credentials = Invalid username or password.
+error-invalid-api-key = The API key is invalid or was revoked.
+error-already-reported = You already reported this post.
+error-rate-limited = You're doing that too often, please try again in { $seconds } seconds.
+error-invalid-challenge = The anti-spam check failed, please try again.
+error-unavailable = The server is busy, please try again in a moment.
+error-timed-out = The request took too long, please try again.
+error-internal = Something went wrong on the server, please try again later.
+error-network = The server could not be reached, please check your connection.
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS blog_post
+(
+    id          SERIAL      PRIMARY KEY                 NOT NULL,
+    posted_on   DATE                                    NOT NULL,
+    username    VARCHAR(15)                             NOT NULL,
+    text        TEXT                                    NOT NULL,
+    image_uuid  TEXT,
+    avatar_uuid TEXT
+);
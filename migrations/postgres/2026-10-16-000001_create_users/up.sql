@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS users
+(
+    id            SERIAL      PRIMARY KEY                 NOT NULL,
+    username      VARCHAR(15)                             NOT NULL UNIQUE,
+    password_hash TEXT                                    NOT NULL,
+    created_on    DATE                                    NOT NULL
+);
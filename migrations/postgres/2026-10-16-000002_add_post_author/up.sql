@@ -0,0 +1,2 @@
+-- Posts created before accounts existed have no author
+ALTER TABLE blog_post ADD COLUMN user_id INTEGER REFERENCES users (id);
@@ -0,0 +1 @@
+DROP TABLE post_like;
\ No newline at end of file
@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS tag
+(
+    id          SERIAL      PRIMARY KEY                 NOT NULL,
+    name        VARCHAR(30)                             NOT NULL UNIQUE
+);
+
+CREATE TABLE IF NOT EXISTS post_tag
+(
+    post_id     INTEGER     NOT NULL    REFERENCES blog_post (id)   ON DELETE CASCADE,
+    tag_id      INTEGER     NOT NULL    REFERENCES tag (id)         ON DELETE CASCADE,
+    PRIMARY KEY (post_id, tag_id)
+);
@@ -0,0 +1 @@
+DROP INDEX blog_post_search_index;
\ No newline at end of file
@@ -0,0 +1,3 @@
+-- Full-text index over blog posts, matching the expression used by the search query
+CREATE INDEX IF NOT EXISTS blog_post_search_index ON blog_post
+    USING GIN (to_tsvector('english', text || ' ' || username));
@@ -0,0 +1 @@
+DROP TABLE blog_post_revision;
\ No newline at end of file
@@ -0,0 +1,13 @@
+-- Reports of abusive blog posts, which stay open until an administrator resolves or dismisses them
+CREATE TABLE IF NOT EXISTS report
+(
+    id          SERIAL      PRIMARY KEY                 NOT NULL,
+    post_id     INTEGER     NOT NULL    REFERENCES blog_post (id)   ON DELETE CASCADE,
+    reporter_id INTEGER     NOT NULL    REFERENCES users (id)       ON DELETE CASCADE,
+    reason      TEXT                                    NOT NULL,
+    reported_at TIMESTAMP                               NOT NULL,
+    resolution  VARCHAR(10),
+    resolved_at TIMESTAMP,
+    -- Every user can report a blog post only once
+    UNIQUE (post_id, reporter_id)
+);
@@ -0,0 +1 @@
+ALTER TABLE blog_post DROP COLUMN image_alt;
@@ -0,0 +1,2 @@
+-- Describes the image of the post for people who cannot see it
+ALTER TABLE blog_post ADD COLUMN image_alt TEXT;
@@ -0,0 +1,2 @@
+DROP INDEX blog_post_slug;
+ALTER TABLE blog_post DROP COLUMN slug;
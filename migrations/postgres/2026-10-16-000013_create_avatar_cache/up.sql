@@ -0,0 +1,7 @@
+-- Avatars downloaded from URLs, by the SHA-256 hash of their URL, so that they are not downloaded again for every post
+CREATE TABLE IF NOT EXISTS avatar_cache
+(
+    url_hash    VARCHAR(64) PRIMARY KEY NOT NULL,
+    avatar_uuid TEXT                    NOT NULL,
+    cached_at   TIMESTAMP               NOT NULL
+);
@@ -0,0 +1,4 @@
+-- The size in pixels of the image of the post, so that clients can reserve space for it before it loads.
+-- Posts whose image was saved before the size was recorded have neither.
+ALTER TABLE blog_post ADD COLUMN image_width INTEGER;
+ALTER TABLE blog_post ADD COLUMN image_height INTEGER;
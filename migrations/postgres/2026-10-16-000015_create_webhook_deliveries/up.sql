@@ -0,0 +1,20 @@
+-- Deliveries of post events to the configured webhooks, which are retried until they succeed or run out of attempts,
+-- and kept afterwards as a log for debugging
+CREATE TABLE IF NOT EXISTS webhook_delivery
+(
+    id              SERIAL      PRIMARY KEY                 NOT NULL,
+    webhook_url     TEXT                                    NOT NULL,
+    event           VARCHAR(20)                             NOT NULL,
+    post_id         INTEGER                                 NOT NULL,
+    payload         TEXT                                    NOT NULL,
+    -- `pending` until the delivery succeeded (`delivered`) or ran out of attempts (`failed`)
+    status          VARCHAR(10)                             NOT NULL,
+    attempts        INTEGER                                 NOT NULL,
+    next_attempt_at TIMESTAMP,
+    response_status INTEGER,
+    last_error      TEXT,
+    created_at      TIMESTAMP                               NOT NULL,
+    delivered_at    TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS webhook_delivery_due ON webhook_delivery (status, next_attempt_at);
@@ -0,0 +1,12 @@
+-- Webmentions of blog posts by other sites, which are verified to link to the blog post before they are stored
+CREATE TABLE IF NOT EXISTS webmention
+(
+    id          SERIAL      PRIMARY KEY                 NOT NULL,
+    post_id     INTEGER     NOT NULL    REFERENCES blog_post (id)   ON DELETE CASCADE,
+    -- The URL of the page which mentions the blog post
+    source      TEXT                                    NOT NULL,
+    -- The title of the page, if it has one
+    title       TEXT,
+    received_at TIMESTAMP                               NOT NULL,
+    UNIQUE (post_id, source)
+);
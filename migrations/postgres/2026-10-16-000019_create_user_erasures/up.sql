@@ -0,0 +1,12 @@
+-- A record of every user whose data was erased on request, which outlives the erased data
+CREATE TABLE IF NOT EXISTS user_erasure
+(
+    id          SERIAL      PRIMARY KEY                 NOT NULL,
+    -- The username of the erased user, which is all that is kept of them
+    username    TEXT                                    NOT NULL,
+    -- The administrator who erased the data
+    erased_by   TEXT                                    NOT NULL,
+    erased_at   TIMESTAMP                               NOT NULL,
+    -- The number of erased blog posts, including those in the trash
+    posts       INTEGER                                 NOT NULL
+);
@@ -0,0 +1,17 @@
+-- Who changed which blog posts how and when, which is kept after the blog posts are purged or erased
+CREATE TABLE IF NOT EXISTS audit_log
+(
+    id          SERIAL      PRIMARY KEY                 NOT NULL,
+    -- e.g. `create`, `update` or `delete`
+    action      VARCHAR(20)                             NOT NULL,
+    -- The username of whoever made the change, or NULL for changes made by the server itself
+    actor       TEXT,
+    -- The IP address from which the change was requested, or NULL for changes made by the server itself
+    ip          TEXT,
+    -- Not a foreign key, since the entry outlives the blog post
+    post_id     INTEGER,
+    summary     TEXT                                    NOT NULL,
+    occurred_at TIMESTAMP                               NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS audit_log_occurred_at ON audit_log (occurred_at);
@@ -0,0 +1,3 @@
+-- Search results mark matches with these control characters, so they are removed from the text of blog posts
+UPDATE blog_post SET text = replace(replace(text, chr(2), ''), chr(3), '')
+WHERE strpos(text, chr(2)) > 0 OR strpos(text, chr(3)) > 0;
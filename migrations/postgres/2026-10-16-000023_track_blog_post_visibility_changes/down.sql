@@ -0,0 +1 @@
+ALTER TABLE blog_post DROP COLUMN visibility_changed_at;
@@ -0,0 +1 @@
+DROP TABLE blog_post;
\ No newline at end of file
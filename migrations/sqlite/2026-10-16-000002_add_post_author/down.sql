@@ -0,0 +1 @@
+ALTER TABLE blog_post DROP COLUMN user_id;
\ No newline at end of file
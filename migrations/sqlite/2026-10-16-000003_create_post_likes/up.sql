@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS post_like
+(
+    post_id     INTEGER     NOT NULL    REFERENCES blog_post (id)   ON DELETE CASCADE,
+    user_id     INTEGER     NOT NULL    REFERENCES users (id)       ON DELETE CASCADE,
+    PRIMARY KEY (post_id, user_id)
+);
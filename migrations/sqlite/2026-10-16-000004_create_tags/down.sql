@@ -0,0 +1,2 @@
+DROP TABLE post_tag;
+DROP TABLE tag;
\ No newline at end of file
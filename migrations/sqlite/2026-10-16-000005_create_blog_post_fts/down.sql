@@ -0,0 +1,4 @@
+DROP TRIGGER blog_post_fts_update;
+DROP TRIGGER blog_post_fts_delete;
+DROP TRIGGER blog_post_fts_insert;
+DROP TABLE blog_post_fts;
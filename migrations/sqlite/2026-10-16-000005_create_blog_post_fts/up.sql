@@ -0,0 +1,24 @@
+-- Full-text index over blog posts, stored externally in `blog_post` and kept in sync by triggers
+CREATE VIRTUAL TABLE IF NOT EXISTS blog_post_fts USING fts5
+(
+    text,
+    username,
+    content = 'blog_post',
+    content_rowid = 'id'
+);
+
+CREATE TRIGGER IF NOT EXISTS blog_post_fts_insert AFTER INSERT ON blog_post BEGIN
+    INSERT INTO blog_post_fts (rowid, text, username) VALUES (new.id, new.text, new.username);
+END;
+
+CREATE TRIGGER IF NOT EXISTS blog_post_fts_delete AFTER DELETE ON blog_post BEGIN
+    INSERT INTO blog_post_fts (blog_post_fts, rowid, text, username) VALUES ('delete', old.id, old.text, old.username);
+END;
+
+CREATE TRIGGER IF NOT EXISTS blog_post_fts_update AFTER UPDATE ON blog_post BEGIN
+    INSERT INTO blog_post_fts (blog_post_fts, rowid, text, username) VALUES ('delete', old.id, old.text, old.username);
+    INSERT INTO blog_post_fts (rowid, text, username) VALUES (new.id, new.text, new.username);
+END;
+
+-- Index the posts which existed before the index
+INSERT INTO blog_post_fts (blog_post_fts) VALUES ('rebuild');
@@ -0,0 +1 @@
+ALTER TABLE blog_post DROP COLUMN deleted_at;
\ No newline at end of file
@@ -0,0 +1,2 @@
+-- Deleted posts are kept in the trash until they are purged after the retention window
+ALTER TABLE blog_post ADD COLUMN deleted_at TIMESTAMP;
@@ -0,0 +1,10 @@
+-- The previous texts of edited blog posts, each with the time at which it was replaced
+CREATE TABLE IF NOT EXISTS blog_post_revision
+(
+    id          INTEGER     PRIMARY KEY AUTOINCREMENT   NOT NULL,
+    post_id     INTEGER     NOT NULL    REFERENCES blog_post (id)   ON DELETE CASCADE,
+    text        TEXT                                    NOT NULL,
+    edited_at   TIMESTAMP                               NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS blog_post_revision_post_id ON blog_post_revision (post_id);
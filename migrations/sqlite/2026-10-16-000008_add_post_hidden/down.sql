@@ -0,0 +1 @@
+ALTER TABLE blog_post DROP COLUMN hidden;
\ No newline at end of file
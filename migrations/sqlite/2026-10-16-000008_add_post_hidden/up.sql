@@ -0,0 +1,2 @@
+-- Hidden posts are withheld from everyone but administrators, without deleting them
+ALTER TABLE blog_post ADD COLUMN hidden BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,4 @@
+-- The human-readable part of the permalink of the post, generated from its first words when it is created.
+-- Posts created before slugs existed have none.
+ALTER TABLE blog_post ADD COLUMN slug TEXT;
+CREATE UNIQUE INDEX blog_post_slug ON blog_post (slug);
@@ -0,0 +1,3 @@
+-- The exact time at which the post was published, in UTC.
+-- Posts published before it was recorded only have the date in posted_on.
+ALTER TABLE blog_post ADD COLUMN posted_at TIMESTAMP;
@@ -0,0 +1,2 @@
+ALTER TABLE blog_post DROP COLUMN image_height;
+ALTER TABLE blog_post DROP COLUMN image_width;
@@ -0,0 +1,12 @@
+-- Keys with which bots and command-line tools authenticate to the REST API on behalf of a user.
+-- Only the SHA-256 hash of a key is stored, along with its first characters so that it can be recognized.
+CREATE TABLE IF NOT EXISTS api_key
+(
+    id           INTEGER     PRIMARY KEY AUTOINCREMENT   NOT NULL,
+    user_id      INTEGER     NOT NULL    REFERENCES users (id)       ON DELETE CASCADE,
+    name         TEXT                                    NOT NULL,
+    key_hash     VARCHAR(64)                             NOT NULL UNIQUE,
+    prefix       VARCHAR(12)                             NOT NULL,
+    created_at   TIMESTAMP                               NOT NULL,
+    last_used_at TIMESTAMP
+);
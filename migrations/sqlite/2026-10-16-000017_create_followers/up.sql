@@ -0,0 +1,9 @@
+-- Remote ActivityPub actors, e.g. Mastodon users, who follow the blog and are sent its new posts
+CREATE TABLE IF NOT EXISTS follower
+(
+    id          INTEGER     PRIMARY KEY AUTOINCREMENT   NOT NULL,
+    actor_id    TEXT                                    NOT NULL UNIQUE,
+    -- The shared inbox of the server of the actor if it has one, so that every server is sent a post only once
+    inbox       TEXT                                    NOT NULL,
+    followed_at TIMESTAMP                               NOT NULL
+);
@@ -0,0 +1,3 @@
+DROP INDEX blog_post_deleted_at;
+DROP INDEX blog_post_posted_on;
+DROP INDEX blog_post_username;
@@ -0,0 +1,7 @@
+-- The feeds are paginated by ID, so the filtered feeds are indexed by their filter followed by the ID
+-- Profile pages and the author filter of the feed
+CREATE INDEX IF NOT EXISTS blog_post_username ON blog_post (username, id);
+-- The day range filter of the feed and the posts per day of the statistics
+CREATE INDEX IF NOT EXISTS blog_post_posted_on ON blog_post (posted_on);
+-- The trash, which is listed and purged by the time the posts were deleted
+CREATE INDEX IF NOT EXISTS blog_post_deleted_at ON blog_post (deleted_at);
@@ -0,0 +1,3 @@
+-- Search results mark matches with these control characters, so they are removed from the text of blog posts
+UPDATE blog_post SET text = replace(replace(text, char(2), ''), char(3), '')
+WHERE instr(text, char(2)) > 0 OR instr(text, char(3)) > 0;
@@ -0,0 +1,3 @@
+-- When the blog post was last hidden, unhidden, moved to the trash or restored,
+-- so that the version of the feeds changes even when the number of visible posts stays the same
+ALTER TABLE blog_post ADD COLUMN visibility_changed_at TIMESTAMP;
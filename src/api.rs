@@ -4,77 +4,692 @@
 //! For the client, they are compiled as API calls to the server.
 //! This is the reason for the local imports in this module.
 
-use crate::model::{AvatarImagePath, BlogPost, BlogPostId, CreateBlogPostParams, PostImagePath};
+use crate::model::{AdminStatistics, ApiKeyId, ApiKeyInfo, AuditAction, AuditEntry, BlogPost, BlogPostDetail, BlogPostId, BlogPostPage, BlogStats, Challenge, FeedOptions, CreateApiKeyParams, CreateBlogPostParams, Credentials, ModerationAction, ModerationPage, NewApiKey, OpenReport, PostLikes, PostRevision, PostSubmission, ReportId, ReportResolution, SearchResult, TrashedPost, UpdateBlogPostParams, User, UserProfile, Webmention};
 use dioxus::prelude::*;
+use dioxus::prelude::server_fn::codec::GetUrl;
 
-/// API endpoint to fetch all blog posts.
-/// TODO: Implement pagination and streaming.
-#[server(endpoint="fetch_blog_posts")]
-pub async fn fetch_blog_posts() -> Result<Vec<BlogPost>, ServerFnError> {
+/// API endpoint to fetch a page of blog posts, sorted and filtered by the `options`.
+/// Pass `None` as the cursor to fetch the first page,
+/// and the `next_cursor` of the previous page to fetch the page after it.
+/// The `limit` is clamped between 1 and `MAX_PAGE_SIZE`.
+/// The default options fetch every blog post, newest first.
+/// The feeds are fetched with `GET`, so that browsers can cache them and revalidate them with their ETag.
+/// TODO: Implement streaming.
+#[server(endpoint="fetch_blog_posts", input=GetUrl)]
+pub async fn fetch_blog_posts(
+    cursor: Option<BlogPostId>,
+    limit: i64,
+    options: FeedOptions,
+) -> Result<BlogPostPage, ServerFnError> {
+    fetch_page(cursor, limit, options.into()).await
+}
+
+/// API endpoint to fetch a page of the blog posts with the provided tag, newest first.
+/// Pagination works the same as for `fetch_blog_posts`.
+#[server(endpoint="fetch_posts_by_tag", input=GetUrl)]
+pub async fn fetch_posts_by_tag(tag: String, cursor: Option<BlogPostId>, limit: i64) -> Result<BlogPostPage, ServerFnError> {
+    use crate::server::persistence::database::PostFilter;
+    
+    let filter = PostFilter { tag: Some(tag.to_lowercase()), ..PostFilter::default() };
+    fetch_page(cursor, limit, filter).await
+}
+
+/// API endpoint to fetch a page of the blog posts by the author with the provided username, newest first.
+/// Pagination works the same as for `fetch_blog_posts`.
+#[server(endpoint="fetch_posts_by_username", input=GetUrl)]
+pub async fn fetch_posts_by_username(username: String, cursor: Option<BlogPostId>, limit: i64) -> Result<BlogPostPage, ServerFnError> {
+    use crate::server::persistence::database::PostFilter;
+    
+    let filter = PostFilter { author: Some(username), ..PostFilter::default() };
+    fetch_page(cursor, limit, filter).await
+}
+
+/// API endpoint to fetch the profile of the author with the provided username.
+/// Authors without visible blog posts have a profile without posts or avatar.
+#[server(endpoint="fetch_user_profile")]
+pub async fn fetch_user_profile(username: String) -> Result<UserProfile, ServerFnError> {
+    use crate::error::AppError;
     use crate::server::Database;
     
-    // Fetch all blog posts from the database
     let database: Database = extract().await?;
-    let posts = database.fetch_all().await?;
+    let profile = database.fetch_profile(username).await.map_err(AppError::from)?;
+    Ok(profile)
+}
+
+/// API endpoint to fetch a single blog post as seen by the logged-in user, if any.
+/// Returns `None` if the blog post does not exist, or is hidden or in the trash.
+#[server(endpoint="fetch_blog_post")]
+pub async fn fetch_blog_post(post_id: BlogPostId) -> Result<Option<BlogPostDetail>, ServerFnError> {
+    use crate::server::{posts, CurrentUser, ServerState};
+    
+    let state: ServerState = extract().await?;
+    let viewer: Option<CurrentUser> = extract().await?;
+    let viewer = viewer.map(|CurrentUser(user)| user.id);
+    Ok(posts::fetch_post(&state, post_id, viewer).await?)
+}
+
+/// API endpoint to fetch many blog posts at once as seen by the logged-in user, if any, in the order of the IDs.
+/// Blog posts which do not exist, or are hidden or in the trash, are skipped,
+/// and only the first `MAX_PAGE_SIZE` IDs are fetched.
+#[server(endpoint="fetch_blog_posts_by_ids")]
+pub async fn fetch_blog_posts_by_ids(post_ids: Vec<BlogPostId>) -> Result<Vec<BlogPostDetail>, ServerFnError> {
+    use crate::error::AppError;
+    use crate::model::MAX_PAGE_SIZE;
+    use crate::server::{CurrentUser, Database};
+    
+    let mut post_ids = post_ids;
+    post_ids.truncate(MAX_PAGE_SIZE as usize);
+    let database: Database = extract().await?;
+    let viewer: Option<CurrentUser> = extract().await?;
+    let viewer = viewer.map(|CurrentUser(user)| user.id);
+    let posts = database.fetch_details(post_ids, viewer).await.map_err(AppError::from)?;
     Ok(posts)
 }
 
+/// API endpoint to search the text and username of all blog posts.
+/// Returns the best matches with the matching words highlighted.
+#[server(endpoint="search_posts")]
+pub async fn search_posts(query: String) -> Result<Vec<SearchResult>, ServerFnError> {
+    use crate::error::AppError;
+    use crate::model::MAX_SEARCH_RESULTS;
+    use crate::server::Database;
+    
+    if query.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let database: Database = extract().await?;
+    let results = database.search(query, MAX_SEARCH_RESULTS).await.map_err(AppError::from)?;
+    Ok(results)
+}
+
+/// API endpoint to suggest the usernames starting with the prefix under which blog posts were published,
+/// e.g. to complete the username typed into a form.
+#[server(endpoint="suggest_usernames", input=GetUrl)]
+pub async fn suggest_usernames(prefix: String) -> Result<Vec<String>, ServerFnError> {
+    use crate::error::AppError;
+    use crate::model::MAX_USERNAME_SUGGESTIONS;
+    use crate::server::Database;
+    
+    if prefix.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let database: Database = extract().await?;
+    let usernames = database.suggest_usernames(prefix, MAX_USERNAME_SUGGESTIONS).await.map_err(AppError::from)?;
+    Ok(usernames)
+}
+
+/// API endpoint to fetch statistics about the blog: the number of blog posts in total and per day,
+/// the number of authors and the size of the stored images as last measured in the background.
+#[server(endpoint="fetch_blog_stats", input=GetUrl)]
+pub async fn fetch_blog_stats() -> Result<BlogStats, ServerFnError> {
+    use crate::error::AppError;
+    use crate::model::STATS_DAYS;
+    use crate::server::usage::StorageUsage;
+    use crate::server::Database;
+    
+    let database: Database = extract().await?;
+    let usage: StorageUsage = extract().await?;
+    let stats = database.blog_stats(STATS_DAYS).await.map_err(AppError::from)?;
+    Ok(BlogStats { image_bytes: usage.stored_bytes(), ..stats })
+}
+
+/// Fetch a page of the blog posts matching the filter as seen by the logged-in user, if any.
+/// If the browser already has the page and no blog post changed since, it is told to reuse it instead.
+#[cfg(feature = "server")]
+async fn fetch_page(
+    cursor: Option<BlogPostId>,
+    limit: i64,
+    filter: crate::server::persistence::database::PostFilter,
+) -> Result<BlogPostPage, ServerFnError> {
+    use crate::error::AppError;
+    use crate::server::{etag, posts, CurrentUser, ServerState};
+    
+    let state: ServerState = extract().await?;
+    let viewer: Option<CurrentUser> = extract().await?;
+    let viewer = viewer.map(|CurrentUser(user)| user.id);
+    let version = state.database.feed_version(viewer).await.map_err(AppError::from)?;
+    if etag::not_modified(&version.etag(), viewer.is_some()) {
+        return Ok(BlogPostPage::default());
+    }
+    Ok(posts::fetch_page(&state, cursor, limit, filter, viewer).await?)
+}
+
+/// API endpoint to fetch a proof-of-work challenge, which must be solved to create a blog post.
+/// Each challenge can only be used for one blog post.
+#[server(endpoint="fetch_challenge")]
+pub async fn fetch_challenge() -> Result<Challenge, ServerFnError> {
+    use crate::server::challenge::ChallengeIssuer;
+    
+    let challenges: ChallengeIssuer = extract().await?;
+    Ok(challenges.issue())
+}
+
+/// API endpoint to fetch the length in pixels of the longest side to which images are downscaled
+/// in the browser before they are uploaded, or `None` if they are uploaded as picked.
+#[server(endpoint="fetch_max_image_dimension")]
+pub async fn fetch_max_image_dimension() -> Result<Option<u32>, ServerFnError> {
+    use crate::server::images::ImagePolicy;
+
+    let policy: ImagePolicy = extract().await?;
+    Ok(policy.max_dimension)
+}
+
+/// API endpoint to fetch the maximum number of characters of a blog post, as counted by `post_length`.
+#[server(endpoint="fetch_max_post_length")]
+pub async fn fetch_max_post_length() -> Result<usize, ServerFnError> {
+    use crate::server::filter::ContentFilter;
+
+    let filter: ContentFilter = extract().await?;
+    Ok(filter.max_length())
+}
+
 /// API endpoint to create a blog post.
+/// The blog post is attributed to the logged-in user.
+/// Returns whether the blog post was published, or why the content filter queued or rejected it.
 #[server(endpoint="create_blog_post")]
-pub async fn create_blog_post(params: CreateBlogPostParams) -> Result<BlogPost, ServerFnError> {
-    use crate::model::InsertBlogPost;
-    use crate::server::{images, Database};
-    use tracing::debug;
-    
-    debug!("Creating blog post");
-    let database: Database = extract().await?;
-    // Save images to the file system and get their UUIDs
-    debug!("Processing images");
-    let (image_uuid, avatar_uuid) = images::process_images(params.image, params.avatar_url).await?;
-    debug!("Images processed: image: {image_uuid:?}, avatar: {avatar_uuid:?}");
-    // Insert the blog post into the database
-    let to_persist = InsertBlogPost::new(params.text, params.username, image_uuid, avatar_uuid);
-    let post = database.save(to_persist).await?;
+pub async fn create_blog_post(params: CreateBlogPostParams) -> Result<PostSubmission, ServerFnError> {
+    use crate::error::AppError;
+    use crate::server::{check_rate_limit, current_user, posts, ServerState};
+    
+    let ip = check_rate_limit().await?;
+    let author = current_user().await?;
+    let state: ServerState = extract().await?;
+    let submission = posts::create(&state, author, ip, params).await.inspect_err(|err| {
+        if let AppError::ImageTooLarge(_) = err {
+            server_context().response_parts_mut().status = err.status();
+        }
+    })?;
+    Ok(submission)
+}
+
+/// API endpoint to update the text of a blog post.
+/// Only the author of the blog post may update it.
+/// Edits which fail the content filter are always rejected, since the blog post is already published.
+/// Returns the updated blog post.
+#[server(endpoint="update_blog_post")]
+pub async fn update_blog_post(post_id: BlogPostId, params: UpdateBlogPostParams) -> Result<BlogPost, ServerFnError> {
+    use crate::error::AppError;
+    use crate::server::audit::AuditLog;
+    use crate::server::feed_cache::FeedCache;
+    use crate::server::filter::ContentFilter;
+    use crate::server::webhooks::{WebhookEvent, Webhooks};
+    use crate::server::webmention::Webmentions;
+    use crate::server::auth::Admins;
+    use crate::server::{check_rate_limit, current_user, posts, rest, Database};
+    use crate::validation::{Field, Validate, ValidationErrors};
+    
+    let ip = check_rate_limit().await?;
+    params.validate().map_err(AppError::from)?;
+    let user = current_user().await?;
+    let database: Database = extract().await?;
+    let admins: Admins = extract().await?;
+    let filter: ContentFilter = extract().await?;
+    filter.check_length(&params.text).map_err(|violation| AppError::from(ValidationErrors::of(Field::Text, violation.to_string())))?;
+    let original = database.fetch(post_id).await.map_err(AppError::from)?;
+    let previous_length = original.as_ref().map_or(0, |post| post.text.chars().count());
+    posts::authorize_owner(original, &user, &admins)?;
+    if let Some(violation) = filter.check(&params.text, &user.username).await {
+        return Err(AppError::ContentRejected(violation.to_string()).into());
+    }
+    let post = database.update(post_id, params.into()).await.map_err(AppError::from)?;
+    let audit: AuditLog = extract().await?;
+    let summary = format!("Edited the text from {} to {} characters", previous_length, post.text.chars().count());
+    audit.record(&user, ip, AuditAction::Update, &[post_id], &summary).await;
+    let cache: FeedCache = extract().await?;
+    cache.invalidate(&[post_id]).await;
+    // Webhooks get the blog post with its tags and likes, and are not told about edits of hidden blog posts
+    if let Some(detail) = database.fetch_detail(post_id, None).await.map_err(AppError::from)? {
+        let updated = rest::Post::from(detail);
+        // Links added by the edit are announced to the linked pages like those of new blog posts
+        let webmentions: Webmentions = extract().await?;
+        webmentions.send(&updated);
+        let webhooks: Webhooks = extract().await?;
+        webhooks.publish(WebhookEvent::Updated { post: updated });
+    }
     Ok(post)
 }
 
-/// API endpoint to delete a blog post.
+/// API endpoint to fetch the previous texts of an edited blog post, most recent first.
+/// Returns an empty list if the blog post was never edited.
+#[server(endpoint="fetch_post_history")]
+pub async fn fetch_post_history(post_id: BlogPostId) -> Result<Vec<PostRevision>, ServerFnError> {
+    use crate::error::AppError;
+    use crate::server::Database;
+    
+    let database: Database = extract().await?;
+    let revisions = database.fetch_revisions(post_id).await.map_err(AppError::from)?;
+    Ok(revisions)
+}
+
+/// API endpoint to fetch the pages on other sites which link to a blog post, as announced by Webmentions.
+/// Returns an empty list if the blog post was never mentioned, or is hidden or in the trash.
+#[server(endpoint="fetch_webmentions")]
+pub async fn fetch_webmentions(post_id: BlogPostId) -> Result<Vec<Webmention>, ServerFnError> {
+    use crate::error::AppError;
+    use crate::server::Database;
+    
+    let database: Database = extract().await?;
+    let mentions = database.fetch_webmentions(post_id).await.map_err(AppError::from)?;
+    Ok(mentions)
+}
+
+/// API endpoint to delete a blog post by moving it to the trash.
+/// Only the author of the blog post may delete it.
+/// The blog post can be restored until it is purged from the trash after the retention window.
 #[server(endpoint="delete_blog_post")]
 pub async fn delete_blog_post(post_id: BlogPostId) -> Result<(), ServerFnError> {
-    use crate::server::{images, Database};
+    use crate::server::{check_rate_limit, current_user, posts, ServerState};
+    
+    let ip = check_rate_limit().await?;
+    let user = current_user().await?;
+    let state: ServerState = extract().await?;
+    Ok(posts::delete(&state, &user, ip, post_id).await?)
+}
+
+/// API endpoint to delete many blog posts at once by moving them to the trash.
+/// Blog posts which do not exist, were written by someone else or are already in the trash are skipped.
+/// At most `MAX_PAGE_SIZE` blog posts can be deleted at once, more are rejected as invalid.
+/// Returns the IDs of the deleted blog posts.
+#[server(endpoint="delete_blog_posts")]
+pub async fn delete_blog_posts(post_ids: Vec<BlogPostId>) -> Result<Vec<BlogPostId>, ServerFnError> {
+    use crate::server::{check_rate_limit, current_user, posts, ServerState};
+    
+    let ip = check_rate_limit().await?;
+    let user = current_user().await?;
+    let state: ServerState = extract().await?;
+    Ok(posts::delete_many(&state, &user, ip, post_ids).await?)
+}
+
+/// API endpoint to restore a blog post from the trash.
+/// Only the author of the blog post may restore it.
+/// Returns the restored blog post.
+#[server(endpoint="restore_blog_post")]
+pub async fn restore_blog_post(post_id: BlogPostId) -> Result<BlogPost, ServerFnError> {
+    use crate::error::AppError;
+    use crate::server::audit::AuditLog;
+    use crate::server::auth::Admins;
+    use crate::server::feed_cache::FeedCache;
+    use crate::server::{check_rate_limit, current_user, posts, Database};
     
+    let ip = check_rate_limit().await?;
+    let user = current_user().await?;
     let database: Database = extract().await?;
-    let deleted = database.delete(post_id).await?;
-    // Try to delete the images from the file system
-    // It's not a big deal if this fails, so we ignore the result
-    let _ = tokio::join!(
-        images::delete(deleted.image_uuid.as_ref()),
-        images::delete(deleted.avatar_uuid.as_ref())
-    );
-    Ok(())
+    let admins: Admins = extract().await?;
+    posts::authorize_owner(database.fetch_trashed(post_id).await.map_err(AppError::from)?, &user, &admins)?;
+    let post = database.restore(post_id).await.map_err(AppError::from)?;
+    let audit: AuditLog = extract().await?;
+    audit.record(&user, ip, AuditAction::Restore, &[post_id], "Restored from the trash").await;
+    let cache: FeedCache = extract().await?;
+    cache.invalidate(&[post_id]).await;
+    Ok(post)
+}
+
+/// API endpoint to fetch the blog posts in the trash which the logged-in user may restore,
+/// most recently deleted first.
+#[server(endpoint="fetch_trash")]
+pub async fn fetch_trash() -> Result<Vec<TrashedPost>, ServerFnError> {
+    use crate::error::AppError;
+    use crate::server::current_user;
+    use crate::server::trash::Trash;
+    
+    let user = current_user().await?;
+    let trash: Trash = extract().await?;
+    let posts = trash.fetch(user.id).await.map_err(AppError::from)?;
+    Ok(posts)
 }
 
-// Image server functions.
-// Server functions cannot be generic, so we need to define a separate function for each image type.
+/// API endpoint to like a blog post, or remove the like if the logged-in user already liked it.
+/// Returns the resulting likes of the blog post.
+#[server(endpoint="toggle_like")]
+pub async fn toggle_like(post_id: BlogPostId) -> Result<PostLikes, ServerFnError> {
+    use crate::error::AppError;
+    use crate::server::feed_cache::FeedCache;
+    use crate::server::{check_rate_limit, current_user, Database};
+    
+    check_rate_limit().await?;
+    let user = current_user().await?;
+    let database: Database = extract().await?;
+    if database.fetch(post_id).await.map_err(AppError::from)?.is_none() {
+        return Err(AppError::PostNotFound.into());
+    }
+    let likes = database.toggle_like(post_id, user.id).await.map_err(AppError::from)?;
+    // Anonymous visitors see the number of likes
+    let cache: FeedCache = extract().await?;
+    cache.invalidate(&[post_id]).await;
+    Ok(likes)
+}
 
-/// API endpoint to fetch a post image.
-/// The image is returned as a base64-encoded string.
-#[server(endpoint="load_post_image")]
-pub async fn load_post_image(uuid: PostImagePath) -> Result<String, ServerFnError> {
-    use base64::{Engine, engine::general_purpose::STANDARD_NO_PAD as Base64};
-    crate::server::images::load(&uuid)
-        .await
-        .map(|bytes| Base64.encode(&bytes))
-        .map_err(Into::into)
+/// API endpoint to report a blog post to the administrators, e.g. because it is spam or abusive.
+/// Every user may report each blog post only once.
+#[server(endpoint="report_post")]
+pub async fn report_post(post_id: BlogPostId, reason: String) -> Result<(), ServerFnError> {
+    use crate::error::AppError;
+    use crate::model::validate_report_reason;
+    use crate::server::persistence::database::DatabaseError;
+    use crate::server::{check_rate_limit, current_user, Database};
+    use diesel::result::{DatabaseErrorKind, Error::DatabaseError as SqlError};
+    
+    check_rate_limit().await?;
+    validate_report_reason(&reason).map_err(AppError::from)?;
+    let user = current_user().await?;
+    let database: Database = extract().await?;
+    if database.fetch(post_id).await.map_err(AppError::from)?.is_none() {
+        return Err(AppError::PostNotFound.into());
+    }
+    match database.create_report(post_id, user.id, reason.trim().to_string()).await {
+        Ok(()) => Ok(()),
+        Err(DatabaseError::Sql(SqlError(DatabaseErrorKind::UniqueViolation, _))) => Err(AppError::AlreadyReported.into()),
+        Err(err) => Err(AppError::from(err).into()),
+    }
 }
 
-/// API endpoint to fetch an avatar image.
-/// The image is returned as a base64-encoded string.
-#[server(endpoint="load_avatar_image")]
-pub async fn load_avatar_image(uuid: AvatarImagePath) -> Result<String, ServerFnError> {
-    use base64::{Engine, engine::general_purpose::STANDARD_NO_PAD as Base64};
-    crate::server::images::load(&uuid)
+/// API endpoint to register a new user account.
+/// The new user is logged in immediately.
+/// Returns the created user, or an error if the username is already taken.
+#[server(endpoint="register")]
+pub async fn register(credentials: Credentials) -> Result<User, ServerFnError> {
+    use crate::error::AppError;
+    use crate::model::InsertUser;
+    use crate::server::persistence::database::DatabaseError;
+    use crate::server::{auth, check_rate_limit, session, CurrentUser, Database};
+    use crate::validation::{Field, Validate, ValidationErrors};
+    use diesel::result::{DatabaseErrorKind, Error::DatabaseError as SqlError};
+    
+    check_rate_limit().await?;
+    credentials.validate().map_err(AppError::from)?;
+    let database: Database = extract().await?;
+    let session = session().await?;
+    let password_hash = auth::hash_password(credentials.password)
         .await
-        .map(|bytes| Base64.encode(&bytes))
-        .map_err(Into::into)
+        .map_err(AppError::from)?;
+    let user = match database.create_user(InsertUser::new(credentials.username, password_hash)).await {
+        Ok(user) => user,
+        Err(DatabaseError::Sql(SqlError(DatabaseErrorKind::UniqueViolation, _))) => {
+            let errors = ValidationErrors::of(Field::Username, "That username is already taken");
+            return Err(AppError::from(errors).into());
+        }
+        Err(err) => return Err(AppError::from(err).into()),
+    };
+    CurrentUser::log_in(&session, user.clone()).await.map_err(AppError::from)?;
+    Ok(user)
+}
+
+/// API endpoint to log in to an existing user account.
+/// Returns the user if the credentials are valid.
+#[server(endpoint="login")]
+pub async fn login(credentials: Credentials) -> Result<User, ServerFnError> {
+    use crate::error::AppError;
+    use crate::server::{auth, check_rate_limit, session, CurrentUser, Database};
+    
+    // Rate limiting also slows down guessing passwords
+    check_rate_limit().await?;
+    let database: Database = extract().await?;
+    let session = session().await?;
+    let user = auth::authenticate(&database, credentials).await?;
+    CurrentUser::log_in(&session, user.clone()).await.map_err(AppError::from)?;
+    Ok(user)
+}
+
+/// API endpoint to log out of the current session.
+#[server(endpoint="logout")]
+pub async fn logout() -> Result<(), ServerFnError> {
+    use crate::error::AppError;
+    use crate::server::{session, CurrentUser};
+    
+    let session = session().await?;
+    CurrentUser::log_out(&session).await.map_err(AppError::from)?;
+    Ok(())
+}
+
+/// API endpoint to fetch the user logged in to the current session, if any.
+/// The client calls this on startup to restore its login state from the session cookie.
+#[server(endpoint="fetch_current_user")]
+pub async fn fetch_current_user() -> Result<Option<User>, ServerFnError> {
+    use crate::server::CurrentUser;
+    
+    let user: Option<CurrentUser> = extract().await?;
+    Ok(user.map(|CurrentUser(user)| user))
+}
+
+/// API endpoint to delete stored images which are not referenced by any blog post.
+/// This also happens periodically in the background, but administrators may trigger it early.
+/// Returns the number of deleted images.
+#[server(endpoint="collect_orphaned_images")]
+pub async fn collect_orphaned_images() -> Result<usize, ServerFnError> {
+    use crate::error::AppError;
+    use crate::server::admin_user;
+    use crate::server::gc::ImageCollector;
+    use tracing::info;
+    
+    let admin = admin_user().await?;
+    let collector: ImageCollector = extract().await?;
+    info!("Collecting orphaned images on behalf of {}", admin.username);
+    let deleted = collector.collect().await.map_err(AppError::from)?;
+    Ok(deleted)
+}
+
+/// API endpoint to fetch a page of all blog posts, including hidden posts and posts in the trash, newest first.
+/// Only administrators may call this.
+/// Pagination works the same as for `fetch_blog_posts`.
+#[server(endpoint="admin_fetch_posts")]
+pub async fn admin_fetch_posts(cursor: Option<BlogPostId>, limit: i64) -> Result<ModerationPage, ServerFnError> {
+    use crate::error::AppError;
+    use crate::model::MAX_PAGE_SIZE;
+    use crate::server::{admin_user, Database};
+    
+    admin_user().await?;
+    let database: Database = extract().await?;
+    let page = database.fetch_moderation_page(cursor, limit.clamp(1, MAX_PAGE_SIZE)).await.map_err(AppError::from)?;
+    Ok(page)
+}
+
+/// API endpoint to apply a moderation action to many blog posts at once.
+/// Only administrators may call this.
+/// Hidden and deleted blog posts are removed from open feeds right away,
+/// while unhidden and restored blog posts only reappear once the feeds are fetched again.
+/// At most `MAX_PAGE_SIZE` blog posts can be moderated at once, more are rejected as invalid.
+/// Returns the number of blog posts to which the action applied.
+#[server(endpoint="admin_moderate_posts")]
+pub async fn admin_moderate_posts(post_ids: Vec<BlogPostId>, action: ModerationAction) -> Result<usize, ServerFnError> {
+    use crate::error::AppError;
+    use crate::model::{PostEvent, MAX_PAGE_SIZE};
+    use crate::server::audit::AuditLog;
+    use crate::server::events::PostEvents;
+    use crate::server::feed_cache::FeedCache;
+    use crate::server::webhooks::{WebhookEvent, Webhooks};
+    use crate::server::{admin_user, check_rate_limit, Database};
+    use crate::validation::{Field, ValidationErrors};
+    use tracing::info;
+    
+    let ip = check_rate_limit().await?;
+    let admin = admin_user().await?;
+    if post_ids.len() > MAX_PAGE_SIZE as usize {
+        let message = format!("At most {MAX_PAGE_SIZE} blog posts can be moderated at once");
+        return Err(AppError::from(ValidationErrors::of(Field::Posts, message)).into());
+    }
+    let database: Database = extract().await?;
+    let events: PostEvents = extract().await?;
+    info!("Applying {:?} to posts {:?} on behalf of {}", action, post_ids, admin.username);
+    // Only the blog posts which the action changed are announced, not every requested ID
+    let affected = database.moderate(post_ids, action).await.map_err(AppError::from)?;
+    if affected.is_empty() {
+        return Ok(0);
+    }
+    let audit_action = match action {
+        ModerationAction::Hide => AuditAction::Hide,
+        ModerationAction::Unhide => AuditAction::Unhide,
+        ModerationAction::Delete => AuditAction::Delete,
+        ModerationAction::Restore => AuditAction::Restore,
+    };
+    let audit: AuditLog = extract().await?;
+    audit.record(&admin, ip, audit_action, &affected, "Moderated by an administrator").await;
+    let cache: FeedCache = extract().await?;
+    cache.invalidate(&affected).await;
+    if matches!(action, ModerationAction::Hide | ModerationAction::Delete) {
+        let webhooks: Webhooks = extract().await?;
+        for &post_id in &affected {
+            webhooks.publish(WebhookEvent::Deleted { post_id });
+            events.publish(PostEvent::Deleted(post_id));
+        }
+    }
+    Ok(affected.len())
+}
+
+/// API endpoint to fetch statistics about the database and image store.
+/// Only administrators may call this.
+#[server(endpoint="admin_fetch_statistics")]
+pub async fn admin_fetch_statistics() -> Result<AdminStatistics, ServerFnError> {
+    use crate::error::AppError;
+    use crate::server::storage::ImageStorage;
+    use crate::server::{admin_user, Database};
+    
+    admin_user().await?;
+    let database: Database = extract().await?;
+    let storage: ImageStorage = extract().await?;
+    let (statistics, images) = tokio::join!(database.statistics(), storage.list());
+    let images = images.map_err(AppError::from)?;
+    let pool = database.pool_state();
+    Ok(AdminStatistics {
+        db_connections: pool.connections,
+        db_idle_connections: pool.idle_connections,
+        stored_images: images.len(),
+        stored_bytes: images.iter().map(|image| image.size).sum(),
+        ..statistics.map_err(AppError::from)?
+    })
+}
+
+/// API endpoint to fetch the reports which no administrator has handled yet, oldest first.
+/// Only administrators may call this.
+#[server(endpoint="admin_fetch_reports")]
+pub async fn admin_fetch_reports() -> Result<Vec<OpenReport>, ServerFnError> {
+    use crate::error::AppError;
+    use crate::server::{admin_user, Database};
+    
+    admin_user().await?;
+    let database: Database = extract().await?;
+    let reports = database.fetch_open_reports().await.map_err(AppError::from)?;
+    Ok(reports)
+}
+
+/// API endpoint to close an open report.
+/// Resolving a report hides the reported blog post, dismissing it leaves the blog post visible.
+/// Only administrators may call this.
+#[server(endpoint="admin_resolve_report")]
+pub async fn admin_resolve_report(report_id: ReportId, resolution: ReportResolution) -> Result<(), ServerFnError> {
+    use crate::error::AppError;
+    use crate::server::audit::AuditLog;
+    use crate::server::feed_cache::FeedCache;
+    use crate::server::{admin_user, check_rate_limit, Database};
+    use tracing::info;
+    
+    let ip = check_rate_limit().await?;
+    let admin = admin_user().await?;
+    let database: Database = extract().await?;
+    info!("Closing report {} as {} on behalf of {}", report_id, resolution.as_str(), admin.username);
+    let post_id = database.resolve_report(report_id, resolution).await.map_err(AppError::from)?;
+    if resolution == ReportResolution::Resolve {
+        let cache: FeedCache = extract().await?;
+        cache.invalidate(&[post_id]).await;
+        let audit: AuditLog = extract().await?;
+        let summary = format!("Hidden on resolving report {report_id}");
+        audit.record(&admin, ip, AuditAction::Hide, &[post_id], &summary).await;
+    }
+    Ok(())
+}
+
+/// API endpoint to fetch all API keys, newest first.
+/// Only administrators may call this.
+#[server(endpoint="admin_fetch_api_keys")]
+pub async fn admin_fetch_api_keys() -> Result<Vec<ApiKeyInfo>, ServerFnError> {
+    use crate::error::AppError;
+    use crate::server::{admin_user, Database};
+    
+    admin_user().await?;
+    let database: Database = extract().await?;
+    let keys = database.fetch_api_keys().await.map_err(AppError::from)?;
+    Ok(keys)
+}
+
+/// API endpoint to create an API key, which authenticates requests to the REST API on behalf of the user.
+/// Returns the key, which is never shown again, or a validation error if no user with the username exists.
+/// Only administrators may call this.
+#[server(endpoint="admin_create_api_key")]
+pub async fn admin_create_api_key(params: CreateApiKeyParams) -> Result<NewApiKey, ServerFnError> {
+    use crate::error::AppError;
+    use crate::server::audit::AuditLog;
+    use crate::server::{admin_user, api_keys, check_rate_limit, Database};
+    use crate::validation::{Field, Validate, ValidationErrors};
+    use tracing::info;
+    
+    let ip = check_rate_limit().await?;
+    let admin = admin_user().await?;
+    params.validate().map_err(AppError::from)?;
+    let database: Database = extract().await?;
+    let (key, to_persist) = api_keys::generate(params.name.trim().to_string());
+    let Some(info) = database.create_api_key(params.username, to_persist).await.map_err(AppError::from)? else {
+        let errors = ValidationErrors::of(Field::Username, "No user with that username exists");
+        return Err(AppError::from(errors).into());
+    };
+    info!("Created API key {} for {} on behalf of {}", info.prefix, info.username, admin.username);
+    let audit: AuditLog = extract().await?;
+    let summary = format!("Created API key {} named {} for {}", info.prefix, info.name, info.username);
+    audit.record(&admin, ip, AuditAction::CreateApiKey, &[], &summary).await;
+    Ok(NewApiKey { key, info })
+}
+
+/// API endpoint to irreversibly erase the account with the username and every blog post by that name,
+/// including those in the trash, with their images, e.g. when the user asks for it under the GDPR.
+/// A record of the erasure is kept. The data can be downloaded from `/api/v1/users/{username}/export` first.
+/// Returns the number of erased blog posts, or a validation error if there is no user or blog post by that name.
+/// Only administrators may call this.
+#[server(endpoint="admin_erase_user")]
+pub async fn admin_erase_user(username: String) -> Result<usize, ServerFnError> {
+    use crate::server::{admin_user, check_rate_limit, erasure, ServerState};
+    
+    let ip = check_rate_limit().await?;
+    let admin = admin_user().await?;
+    let state: ServerState = extract().await?;
+    let erased = erasure::erase_user(&state, &admin, ip, username).await?;
+    Ok(erased)
+}
+
+/// API endpoint to revoke an API key, so that it no longer authenticates requests.
+/// Revoking a key which does not exist does nothing.
+/// Only administrators may call this.
+#[server(endpoint="admin_revoke_api_key")]
+pub async fn admin_revoke_api_key(key_id: ApiKeyId) -> Result<(), ServerFnError> {
+    use crate::error::AppError;
+    use crate::server::audit::AuditLog;
+    use crate::server::{admin_user, check_rate_limit, Database};
+    use tracing::info;
+    
+    let ip = check_rate_limit().await?;
+    let admin = admin_user().await?;
+    let database: Database = extract().await?;
+    if database.revoke_api_key(key_id).await.map_err(AppError::from)? {
+        info!("Revoked API key {} on behalf of {}", key_id, admin.username);
+        let audit: AuditLog = extract().await?;
+        audit.record(&admin, ip, AuditAction::RevokeApiKey, &[], &format!("Revoked API key {key_id}")).await;
+    }
+    Ok(())
+}
+
+/// API endpoint to fetch the most recent `MAX_AUDIT_ENTRIES` entries of the audit log, newest first,
+/// which occurred at or after `since` and before `until`, either of which may be left open,
+/// optionally only those of one action.
+/// Only administrators may call this.
+#[server(endpoint="admin_fetch_audit_log")]
+pub async fn admin_fetch_audit_log(
+    since: Option<time::PrimitiveDateTime>,
+    until: Option<time::PrimitiveDateTime>,
+    action: Option<AuditAction>,
+) -> Result<Vec<AuditEntry>, ServerFnError> {
+    use crate::error::AppError;
+    use crate::model::MAX_AUDIT_ENTRIES;
+    use crate::server::{admin_user, Database};
+    
+    admin_user().await?;
+    let database: Database = extract().await?;
+    let entries = database.fetch_audit_log(since, until, action, MAX_AUDIT_ENTRIES).await.map_err(AppError::from)?;
+    Ok(entries)
 }
@@ -0,0 +1,391 @@
+//! Command-line client of the REST API under `/api/v1`, which is built with the `cli` feature:
+//! `cargo run --bin blog --features cli -- <command>`.
+//! It shares the models and validation rules of the web app, so that posts are checked by the same rules before they are sent.
+//!
+//! The server is given by `--url` or the `BLOG_URL` environment variable, and defaults to `http://localhost:8080`.
+//! Commands which change data authenticate with the API key in `BLOG_API_KEY` if it is set,
+//! or else log in as the user given by `--username` with the password in `BLOG_PASSWORD`, which is asked for if it is not set.
+
+#[cfg(feature = "server")]
+compile_error!("The CLI is built without the `server` feature, e.g. with `cargo build --bin blog --features cli`");
+
+// The models and validation rules are shared with the web app, of which the CLI only uses a few
+#[allow(dead_code)]
+#[path = "../model.rs"]
+mod model;
+#[allow(dead_code)]
+#[path = "../validation.rs"]
+mod validation;
+
+use model::{parse_tags, BlogPostId, Challenge, CreateBlogPostParams, Credentials, UploadToken};
+use reqwest::blocking::multipart::Form;
+use reqwest::blocking::{RequestBuilder, Response};
+use reqwest::header::{COOKIE, SET_COOKIE};
+use reqwest::{Method, StatusCode};
+use serde::Deserialize;
+use std::io::{BufRead, Read};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+use validation::{Validate, ValidationErrors};
+
+/// The server which is used unless `--url` or `BLOG_URL` is given.
+const DEFAULT_URL: &str = "http://localhost:8080";
+
+/// How many characters of the text of a post are shown when listing posts.
+const PREVIEW_LENGTH: usize = 80;
+
+/// The file an export is saved to unless `--output` is given.
+const DEFAULT_EXPORT_PATH: &str = "blog-export.tar";
+
+/// How long exporting or importing an archive may take, since it includes every image of the blog.
+const ARCHIVE_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+const USAGE: &str = "\
+Usage:
+  blog list [--tag <tag>] [--author <username>] [--limit <count>]
+  blog post <text> [--image <file>] [--alt <description>] [--tag <tag>]... [--username <username>]
+  blog delete <id> [--username <username>]
+  blog export [--output <file>] [--username <username>]
+  blog import <file> [--username <username>]
+
+Pass `-` as the text to read it from standard input.
+Exporting saves all posts and their images as a tar archive, to blog-export.tar by default, and requires an administrator.
+Importing reads such an archive, or a JSON export of Ghost or WordPress, and also requires an administrator.
+All commands accept --url <url> to choose the server, which defaults to BLOG_URL or http://localhost:8080.
+Commands which change data authenticate with BLOG_API_KEY if it is set,
+or else log in as --username with the password in BLOG_PASSWORD, which is asked for if it is not set.";
+
+/// Why a command failed.
+#[derive(Debug, derive_more::From, derive_more::Display)]
+enum CliError {
+    #[display("{}", _0)]
+    #[from(skip)]
+    Usage(String),
+    #[display("The post is invalid:\n{}", _0)]
+    Validation(ValidationErrors),
+    #[display("Failed to access {}: {}", _0.display(), _1)]
+    #[from(skip)]
+    Io(PathBuf, std::io::Error),
+    #[display("Request failed: {}", _0)]
+    Request(reqwest::Error),
+    #[display("The server answered with {}: {} ({})", status, message, code)]
+    #[from(skip)]
+    Api { status: StatusCode, code: String, message: String },
+}
+
+/// A blog post as returned by the API, with the fields which are shown.
+#[derive(Debug, Deserialize)]
+struct Post {
+    id: BlogPostId,
+    author: String,
+    text: String,
+    posted_on: String,
+    path: String,
+    tags: Vec<String>,
+    likes: i64,
+}
+
+/// A page of blog posts as returned by `GET /posts`.
+#[derive(Debug, Deserialize)]
+struct PostList {
+    posts: Vec<Post>,
+}
+
+/// The response of `POST /posts`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+enum Submission {
+    Published { post: Post },
+    Queued { reason: String },
+}
+
+/// The response of `POST /uploads`.
+#[derive(Debug, Deserialize)]
+struct Upload {
+    token: UploadToken,
+}
+
+/// The response of `POST /import`.
+#[derive(Debug, Deserialize)]
+struct ImportSummary {
+    imported: Vec<BlogPostId>,
+    skipped: Vec<SkippedPost>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SkippedPost {
+    entry: usize,
+    reason: String,
+}
+
+/// The body of error responses.
+#[derive(Debug, Deserialize)]
+struct ErrorBody {
+    error: ErrorDetail,
+}
+
+#[derive(Debug, Deserialize)]
+struct ErrorDetail {
+    code: String,
+    message: String,
+}
+
+/// The options given on the command line, all of which are optional.
+#[derive(Debug, Default)]
+struct Options {
+    url: Option<String>,
+    username: Option<String>,
+    image: Option<PathBuf>,
+    alt: Option<String>,
+    tags: Vec<String>,
+    author: Option<String>,
+    limit: Option<i64>,
+    output: Option<PathBuf>,
+}
+
+/// How requests which change data are authenticated.
+#[derive(Debug)]
+enum Auth {
+    /// Sent as `Authorization: Bearer <key>`.
+    ApiKey(String),
+    /// The session cookie set when logging in, e.g. `id=...`.
+    Session(String),
+}
+
+/// Sends requests to the REST API of the server.
+#[derive(Debug)]
+struct Client {
+    http: reqwest::blocking::Client,
+    base_url: String,
+    auth: Option<Auth>,
+}
+
+impl Client {
+    fn new(base_url: String) -> Self {
+        Self {
+            http: reqwest::blocking::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            auth: None,
+        }
+    }
+
+    /// Build a request to the path under `/api/v1`, authenticated if `authenticate` was called.
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let request = self.http.request(method, format!("{}/api/v1{}", self.base_url, path));
+        match &self.auth {
+            Some(Auth::ApiKey(key)) => request.bearer_auth(key),
+            Some(Auth::Session(cookie)) => request.header(COOKIE, cookie),
+            None => request,
+        }
+    }
+
+    /// Authenticate further requests with the API key in `BLOG_API_KEY`,
+    /// or else by logging in as the user with the password in `BLOG_PASSWORD` or typed in.
+    fn authenticate(&mut self, username: Option<&str>) -> Result<(), CliError> {
+        if let Ok(key) = std::env::var("BLOG_API_KEY") {
+            self.auth = Some(Auth::ApiKey(key));
+            return Ok(());
+        }
+        let username = username.ok_or_else(|| CliError::Usage("Pass --username or set BLOG_API_KEY".to_string()))?;
+        let password = match std::env::var("BLOG_PASSWORD") {
+            Ok(password) => password,
+            Err(_) => read_password(username)?,
+        };
+        let credentials = Credentials { username: username.to_string(), password };
+        let response = check(self.request(Method::POST, "/session").json(&credentials).send()?)?;
+        // Only the name and value of the cookie are sent back, without its attributes
+        let cookie = response
+            .headers()
+            .get(SET_COOKIE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(';').next())
+            .ok_or_else(|| CliError::Usage("The server did not set a session cookie".to_string()))?;
+        self.auth = Some(Auth::Session(cookie.to_string()));
+        Ok(())
+    }
+
+    /// Upload the image file, and return the token to attach it to a post with.
+    fn upload(&self, path: &Path) -> Result<UploadToken, CliError> {
+        let form = Form::new().file("image", path).map_err(|err| CliError::Io(path.to_path_buf(), err))?;
+        let upload: Upload = check(self.request(Method::POST, "/uploads").multipart(form).send()?)?.json()?;
+        Ok(upload.token)
+    }
+}
+
+fn main() -> ExitCode {
+    match run(std::env::args().skip(1).collect()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Run the command given by the arguments.
+fn run(args: Vec<String>) -> Result<(), CliError> {
+    let (command, options) = parse_args(args)?;
+    let base_url = options
+        .url
+        .clone()
+        .or_else(|| std::env::var("BLOG_URL").ok())
+        .unwrap_or_else(|| DEFAULT_URL.to_string());
+    let mut client = Client::new(base_url);
+    match command.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+        ["list"] => list(&client, &options),
+        ["post", text] => create_post(&mut client, text, options),
+        ["delete", post_id] => {
+            let post_id = post_id
+                .parse()
+                .map_err(|_| CliError::Usage(format!("The post ID must be a number, not {post_id}")))?;
+            delete_post(&mut client, post_id, &options)
+        }
+        ["export"] => export(&mut client, &options),
+        ["import", path] => import(&mut client, Path::new(path), &options),
+        _ => Err(CliError::Usage(USAGE.to_string())),
+    }
+}
+
+/// Split the arguments into the command with its arguments, and the options, which all take a value.
+fn parse_args(args: Vec<String>) -> Result<(Vec<String>, Options), CliError> {
+    let mut command = Vec::new();
+    let mut options = Options::default();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let Some(name) = arg.strip_prefix("--") else {
+            command.push(arg);
+            continue;
+        };
+        let value = args.next().ok_or_else(|| CliError::Usage(format!("--{name} needs a value\n\n{USAGE}")))?;
+        match name {
+            "url" => options.url = Some(value),
+            "username" => options.username = Some(value),
+            "image" => options.image = Some(PathBuf::from(value)),
+            "alt" => options.alt = Some(value),
+            "tag" => options.tags.push(value),
+            "author" => options.author = Some(value),
+            "output" => options.output = Some(PathBuf::from(value)),
+            "limit" => {
+                let limit = value.parse().map_err(|_| CliError::Usage(format!("--limit must be a number, not {value}")))?;
+                options.limit = Some(limit);
+            }
+            _ => return Err(CliError::Usage(format!("Unknown option --{name}\n\n{USAGE}"))),
+        }
+    }
+    Ok((command, options))
+}
+
+/// List the newest posts, filtered by the tag and author, if given.
+fn list(client: &Client, options: &Options) -> Result<(), CliError> {
+    let mut query = Vec::new();
+    if let Some(tag) = options.tags.first() {
+        query.push(("tag", tag.clone()));
+    }
+    if let Some(author) = &options.author {
+        query.push(("author", author.clone()));
+    }
+    if let Some(limit) = options.limit {
+        query.push(("limit", limit.to_string()));
+    }
+    let page: PostList = check(client.request(Method::GET, "/posts").query(&query).send()?)?.json()?;
+    for post in page.posts {
+        let tags = if post.tags.is_empty() { String::new() } else { format!(" [{}]", post.tags.join(", ")) };
+        let preview: String = post.text.lines().next().unwrap_or_default().chars().take(PREVIEW_LENGTH).collect();
+        println!("#{} on {} by {}, {} likes{}", post.id, post.posted_on, post.author, post.likes, tags);
+        println!("    {preview}");
+    }
+    Ok(())
+}
+
+/// Create a post with the text, or the text read from standard input if it is `-`,
+/// and the image, its description and the tags given as options.
+fn create_post(client: &mut Client, text: &str, options: Options) -> Result<(), CliError> {
+    let text = if text == "-" {
+        let mut text = String::new();
+        std::io::stdin()
+            .read_to_string(&mut text)
+            .map_err(|err| CliError::Io(PathBuf::from("standard input"), err))?;
+        text
+    } else {
+        text.to_string()
+    };
+    let mut params = CreateBlogPostParams {
+        text,
+        image_alt: options.alt,
+        tags: parse_tags(&options.tags.join(",")),
+        ..CreateBlogPostParams::default()
+    };
+    // Check the post before uploading the image or solving the challenge, which both take a while
+    params.validate()?;
+    client.authenticate(options.username.as_deref())?;
+    if let Some(image) = &options.image {
+        params.image_upload = Some(client.upload(image)?);
+    }
+    let challenge: Challenge = check(client.request(Method::GET, "/challenge").send()?)?.json()?;
+    params.challenge = challenge.solve();
+    let submission: Submission = check(client.request(Method::POST, "/posts").json(&params).send()?)?.json()?;
+    match submission {
+        Submission::Published { post } => println!("Published post #{} at {}{}", post.id, client.base_url, post.path),
+        Submission::Queued { reason } => println!("{reason}. The post will appear once an administrator approves it."),
+    }
+    Ok(())
+}
+
+/// Move the post, which must have been written by the authenticated user, to the trash.
+fn delete_post(client: &mut Client, post_id: BlogPostId, options: &Options) -> Result<(), CliError> {
+    client.authenticate(options.username.as_deref())?;
+    check(client.request(Method::DELETE, &format!("/posts/{post_id}")).send()?)?;
+    println!("Moved post #{post_id} to the trash");
+    Ok(())
+}
+
+/// Download an archive of all posts with their images into the file given by `--output`.
+/// Only administrators may export the blog.
+fn export(client: &mut Client, options: &Options) -> Result<(), CliError> {
+    client.authenticate(options.username.as_deref())?;
+    let path = options.output.clone().unwrap_or_else(|| PathBuf::from(DEFAULT_EXPORT_PATH));
+    let mut response = check(client.request(Method::GET, "/export").timeout(ARCHIVE_TIMEOUT).send()?)?;
+    let mut file = std::fs::File::create(&path).map_err(|err| CliError::Io(path.clone(), err))?;
+    let bytes = std::io::copy(&mut response, &mut file).map_err(|err| CliError::Io(path.clone(), err))?;
+    println!("Saved the archive of {} bytes to {}", bytes, path.display());
+    Ok(())
+}
+
+/// Import the posts of the archive or JSON export in the file, and list those which were skipped.
+/// Only administrators may import posts.
+fn import(client: &mut Client, path: &Path, options: &Options) -> Result<(), CliError> {
+    let file = std::fs::File::open(path).map_err(|err| CliError::Io(path.to_path_buf(), err))?;
+    client.authenticate(options.username.as_deref())?;
+    let request = client.request(Method::POST, "/import").timeout(ARCHIVE_TIMEOUT).body(file);
+    let summary: ImportSummary = check(request.send()?)?.json()?;
+    for skipped in &summary.skipped {
+        println!("Skipped entry {}: {}", skipped.entry, skipped.reason);
+    }
+    println!("Imported {} posts, skipped {}", summary.imported.len(), summary.skipped.len());
+    Ok(())
+}
+
+/// Returns the response if it succeeded, or else the error which the server answered with.
+fn check(response: Response) -> Result<Response, CliError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let error = match response.json::<ErrorBody>() {
+        Ok(body) => body.error,
+        Err(_) => ErrorDetail { code: "unknown".to_string(), message: "The response was not a JSON error".to_string() },
+    };
+    Err(CliError::Api { status, code: error.code, message: error.message })
+}
+
+/// Ask for the password of the user on standard input.
+fn read_password(username: &str) -> Result<String, CliError> {
+    eprint!("Password for {username}: ");
+    let mut password = String::new();
+    std::io::stdin()
+        .lock()
+        .read_line(&mut password)
+        .map_err(|err| CliError::Io(PathBuf::from("standard input"), err))?;
+    Ok(password.trim_end_matches(['\r', '\n']).to_string())
+}
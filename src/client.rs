@@ -1,20 +1,55 @@
 //! Frontend application code.
 
 use crate::api::*;
-use crate::model::{BlogPost, CreateBlogPostParams};
+use crate::error::AppError;
+use crate::i18n::{t, Locale};
+use crate::markdown;
+use crate::validation::{Field, Validate, ValidationErrors};
+use crate::model::{
+    parse_tags, post_length, validate_report_reason, AdminStatistics, ApiKeyId, ApiKeyInfo, AuditAction, AuditEntry,
+    AvatarCrop, BlogPost, BlogPostDetail, BlogPostId, BlogPostPage, BlogStats, Challenge, ChallengeSolution,
+    CreateApiKeyParams, CreateBlogPostParams, Credentials, FeedOptions, FeedSort, ModeratedPost, ModerationAction,
+    OpenReport, PostEvent, PostLikes, PostRevision, PostSubmission, ReportId, ReportResolution, SearchResult,
+    TrashedPost, UpdateBlogPostParams, UploadToken, User, UserProfile, Webmention, ACCEPTED_IMAGE_TYPES,
+    DEFAULT_PAGE_SIZE, MAX_API_KEY_NAME_LENGTH, MAX_IMAGE_ALT_LENGTH, MAX_PAGE_SIZE, MAX_POST_LENGTH,
+    MAX_REPORT_REASON_LENGTH, MAX_USERNAME_LENGTH,
+};
 use dioxus::prelude::*;
 use dioxus_logger::tracing::{error, info};
 use std::borrow::Cow;
+use std::collections::HashSet;
+use std::rc::Rc;
 use tracing::debug;
 
 /// The routes for the frontend application.
 /// / or /home -> HomePage
+/// /login -> LoginPage
+/// /post/:id -> PostDetail
+/// /post/:id/:slug -> SluggedPostDetail
+/// /tag/:name -> TagPage
+/// /user/:username -> UserPage
+/// /trash -> TrashPage
+/// /admin -> AdminPage
 /// /... -> PageNotFound
 #[derive(Debug, Clone, Routable)]
 enum Route {
     #[redirect("/", || Route::HomePage)]
     #[route("/home")]
     HomePage,
+    #[route("/login")]
+    LoginPage,
+    #[route("/post/:id")]
+    PostDetail { id: BlogPostId },
+    #[route("/post/:id/:slug")]
+    SluggedPostDetail { id: BlogPostId, slug: String },
+    #[route("/tag/:name")]
+    TagPage { name: String },
+    #[route("/user/:username")]
+    UserPage { username: String },
+    #[route("/trash")]
+    TrashPage,
+    #[route("/admin")]
+    AdminPage,
     #[route("/:..route")]
     PageNotFound { route: Vec<String> },
 }
@@ -22,203 +57,557 @@ enum Route {
 /// The main entry point for the frontend application.
 #[allow(non_snake_case)]
 pub fn Webapp() -> Element {
+    // The logged-in user, if any, shared with all components via context
+    let mut current_user = use_context_provider(|| Signal::new(None::<User>));
+    // The color theme, which is only known in the browser, so the server renders the page for the light theme
+    let mut theme = use_context_provider(|| Signal::new(Theme::Light));
+    use_effect(move || {
+        if let Some(initial) = initial_theme() {
+            theme.set(initial);
+        }
+    });
+    use_effect(move || apply_theme(theme()));
+    // The locale, which is likewise only known in the browser, so the server renders the page in English
+    let mut locale = use_context_provider(|| Signal::new(Locale::default()));
+    use_effect(move || {
+        if let Some(initial) = initial_locale() {
+            locale.set(initial);
+        }
+    });
+    use_effect(move || apply_locale(locale()));
+    // Lets the app be installed and the posts of the last visit be read offline
+    use_future(register_service_worker);
+    // The current time, which relative times such as "3 hours ago" are computed from, advanced regularly
+    let mut now = use_context_provider(|| Signal::new(current_time()));
+    use_future(move || async move {
+        loop {
+            sleep(CLOCK_TICK).await;
+            now.set(current_time());
+        }
+    });
+    // Restore the logged-in user from the session cookie
+    use_future(move || async move {
+        match fetch_current_user().await {
+            Ok(user) => current_user.set(user),
+            Err(err) => error!("Failed to fetch current user: {:?}", err),
+        }
+    });
+    // The notifications shown on top of every page, shared with all components via context
+    use_context_provider(Toasts::new);
     rsx! {
         body {
             Router::<Route> {}
+            ToastList {}
         }
     }
 }
 
+/// The blog posts of the static export, which its pages are rendered from instead of fetching them.
+/// Provided via context while the pages are rendered for the export, which leave out what only works with the server,
+/// such as the forms and the account status.
+#[derive(Clone)]
+#[cfg_attr(not(feature = "server"), allow(dead_code))]
+struct StaticExport {
+    /// All exported blog posts, newest first, as a single page of the feed.
+    posts: Rc<BlogPostPage>,
+    /// The route of the page which is rendered.
+    route: Route,
+}
+
+impl StaticExport {
+    /// The exported blog post with the ID along with its likes, tags and last edit, or `None` if it was not exported.
+    fn detail(&self, id: BlogPostId) -> Option<BlogPostDetail> {
+        let post = self.posts.posts.iter().find(|post| post.id == id)?.clone();
+        Some(BlogPostDetail {
+            likes: self.posts.likes.get(&id).copied().unwrap_or_default(),
+            tags: self.posts.tags.get(&id).cloned().unwrap_or_default(),
+            last_edited: self.posts.last_edited.get(&id).copied(),
+            post,
+        })
+    }
+}
+
+/// Render the home page listing all blog posts of the page, or the page of one of them, to HTML for the static export.
+/// The pages are rendered by the same components as in the app, as a visitor who is not logged in sees them.
+#[cfg(feature = "server")]
+pub async fn render_static_page(posts: Rc<BlogPostPage>, post: Option<&BlogPost>) -> String {
+    let route = post.map_or(Route::HomePage, post_route);
+    let mut dom = VirtualDom::new(StaticSite).with_root_context(StaticExport { posts, route });
+    dom.rebuild_in_place();
+    // The page of a blog post suspends until the blog post is loaded, which it is from the export right away
+    dom.wait_for_suspense().await;
+    dioxus::ssr::render(&dom)
+}
+
+/// The root of the pages of the static export, which provides the same context as the app and renders the page
+/// at the route of the export.
+#[cfg(feature = "server")]
 #[component]
-fn PageNotFound(route: Vec<String>) -> Element {
+fn StaticSite() -> Element {
+    let export = use_context::<StaticExport>();
+    use_context_provider(|| Signal::new(None::<User>));
+    use_context_provider(|| Signal::new(Theme::Light));
+    use_context_provider(|| Signal::new(Locale::default()));
+    use_context_provider(|| Signal::new(current_time()));
+    use_context_provider(Toasts::new);
     rsx! {
-        div { class: "container",
-            h1 { "Page not found" }
-            p { "The page you requested doesn't exist." }
+        Router::<Route> {
+            config: move || RouterConfig::default().history(MemoryHistory::with_initial_path(export.route.clone())),
+        }
+    }
+}
+
+/// How long a toast is shown before it is removed, which matches its fade-out animation in `main.css`.
+const TOAST_DURATION: std::time::Duration = std::time::Duration::from_secs(4);
+
+/// How long a toast with an action is shown, which is longer so that there is time to take the action.
+/// This also matches the fade-out animation in `main.css`.
+const ACTION_TOAST_DURATION: std::time::Duration = std::time::Duration::from_secs(8);
+
+/// Whether a toast confirms, informs or reports a failure, which determines its color.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ToastKind {
+    Success,
+    Info,
+    Error,
+}
+
+impl ToastKind {
+    fn class(self) -> &'static str {
+        match self {
+            ToastKind::Success => "toast success",
+            ToastKind::Info => "toast",
+            ToastKind::Error => "toast error",
+        }
+    }
+}
+
+/// An action which the user can take from a toast while it is shown.
+#[derive(Debug, Clone, Copy)]
+enum ToastAction {
+    /// Restore the blog post which was just moved to the trash, and show it in the feed again.
+    UndoDelete { post_id: BlogPostId, changes: FeedChanges },
+}
+
+impl ToastAction {
+    async fn run(self, toasts: Toasts, locale: Locale) {
+        match self {
+            ToastAction::UndoDelete { post_id, changes } => match restore_blog_post(post_id).await {
+                Ok(_) => {
+                    info!("Restored post with id: {}", post_id);
+                    changes.unremove(post_id);
+                    toasts.success(t!(locale, "post-restored"));
+                }
+                Err(err) => {
+                    error!("Failed to restore post with id {}: {:?}", post_id, err);
+                    toasts.error(error_message(err, locale));
+                }
+            },
+        }
+    }
+}
+
+/// A short notification shown at the bottom of the page, which fades out by itself.
+#[derive(Debug, Clone)]
+struct Toast {
+    /// The key of the toast, which is unique among all toasts shown since the app started.
+    key: u32,
+    kind: ToastKind,
+    message: String,
+    /// The action offered by a button on the toast, if any.
+    action: Option<ToastAction>,
+}
+
+impl Toast {
+    /// The CSS classes of the toast, which fades out slower if it has an action.
+    fn class(&self) -> String {
+        match self.action {
+            Some(_) => format!("{} with-action", self.kind.class()),
+            None => self.kind.class().to_string(),
         }
     }
 }
 
+/// The toasts which are currently shown.
+/// Provided via context by the app, so that every component can notify the user,
+/// e.g. of a failure which has no form to be shown in.
+#[derive(Debug, Clone, Copy)]
+struct Toasts {
+    /// The toasts in the order in which they were shown.
+    shown: Signal<Vec<Toast>>,
+    /// The key of the next toast.
+    next_key: Signal<u32>,
+}
+
+impl Toasts {
+    fn new() -> Self {
+        Self {
+            shown: Signal::new(Vec::new()),
+            next_key: Signal::new(0),
+        }
+    }
+
+    fn success(self, message: impl Into<String>) {
+        self.show(ToastKind::Success, message.into(), None);
+    }
+
+    /// Confirm that something was done, with a button to take the action, e.g. to undo it.
+    fn success_with_action(self, message: impl Into<String>, action: ToastAction) {
+        self.show(ToastKind::Success, message.into(), Some(action));
+    }
+
+    fn info(self, message: impl Into<String>) {
+        self.show(ToastKind::Info, message.into(), None);
+    }
+
+    fn error(self, message: impl Into<String>) {
+        self.show(ToastKind::Error, message.into(), None);
+    }
+
+    /// Show the toast, and remove it once it faded out.
+    fn show(mut self, kind: ToastKind, message: String, action: Option<ToastAction>) {
+        let key = *self.next_key.peek();
+        *self.next_key.write() += 1;
+        self.shown.write().push(Toast { key, kind, message, action });
+        let duration = if action.is_some() { ACTION_TOAST_DURATION } else { TOAST_DURATION };
+        // The component which showed the toast may be gone by then, e.g. the blog post which was just deleted
+        spawn_forever(async move {
+            sleep(duration).await;
+            self.dismiss(key);
+        });
+    }
+
+    /// Remove the toast before it faded out, e.g. because its action was taken.
+    fn dismiss(mut self, key: u32) {
+        self.shown.write().retain(|toast| toast.key != key);
+    }
+}
+
+/// The toasts of the app, through which the calling component can notify the user.
+fn use_toasts() -> Toasts {
+    use_context::<Toasts>()
+}
+
+/// The toasts which are currently shown, stacked at the bottom of the page with the newest last.
 #[component]
-fn HomePage() -> Element {
-    let mut fetch_blog_posts = use_resource(fetch_blog_posts);
+fn ToastList() -> Element {
+    let toasts = use_toasts();
+    let locale = use_locale();
     rsx! {
-        div { class: "container",
-            h1 { class: "header",
-                "Welcome to the blog"
-            }
-            BlogPostForm {
-                oncreate: move |_| fetch_blog_posts.restart(),
-            }
-            BlogPostFeed {
-                posts: fetch_blog_posts.read_unchecked().clone(),
+        div { class: "toasts", role: "status",
+            for toast in toasts.shown() {
+                div { key: "{toast.key}",
+                    class: toast.class(),
+                    "{toast.message}"
+                    if let Some(action) = toast.action {
+                        button {
+                            onclick: move |_| async move {
+                                // Taking the action twice would fail, so the button is gone right away
+                                toasts.dismiss(toast.key);
+                                action.run(toasts, locale).await;
+                            },
+                            {t!(locale, "undo")}
+                        }
+                    }
+                }
             }
         }
     }
 }
 
 #[component]
-fn BlogPostForm(
-    oncreate: EventHandler<BlogPost>,
-) -> Element {
-    let mut text_input = use_signal(String::new);
-    let mut username_input = use_signal(String::new);
-    let mut image_input = use_signal(|| None);
-    let mut avatar_input = use_signal(String::new);
-    let mut message = use_signal(|| ("red", None));
+fn PageNotFound(route: Vec<String>) -> Element {
+    let locale = use_locale();
+    rsx! {
+        div { class: "container",
+            h1 { {t!(locale, "page-not-found")} }
+            p { {t!(locale, "page-not-found-text")} }
+        }
+    }
+}
 
-    let handle_submit = move |_| async move {
-        message.set(("yellow", Some(Cow::from("Posting..."))));
+/// The source of the blog posts shown in a feed.
+#[derive(Debug, Clone, PartialEq)]
+enum Feed {
+    /// All blog posts, sorted and filtered by the options.
+    All(FeedOptions),
+    /// The blog posts with the tag.
+    Tag(String),
+    /// The blog posts by the author with the username.
+    Author(String),
+}
 
-        let params = CreateBlogPostParams {
-            text: text_input().clone(),
-            username: username_input().clone(),
-            image: image_input().clone(),
-            avatar_url: if avatar_input().is_empty() {
-                None
-            } else {
-                Some(avatar_input().clone())
-            },
-        };
+impl Feed {
+    /// Whether the blog post with the tags belongs in this feed.
+    fn includes(&self, post: &BlogPost, tags: &[String]) -> bool {
+        match self {
+            Feed::All(options) => options.includes(post),
+            Feed::Tag(tag) => tags.contains(tag),
+            Feed::Author(username) => post.username == *username,
+        }
+    }
 
-        if let Err(msg) = params.validate() {
-            message.set(("red", Some(Cow::from(msg))));
-            return;
+    /// Fetch the page of blog posts in this feed after the cursor.
+    async fn fetch_page(self, cursor: Option<BlogPostId>) -> Result<BlogPostPage, ServerFnError> {
+        match self {
+            Feed::All(options) => fetch_blog_posts(cursor, DEFAULT_PAGE_SIZE, options).await,
+            Feed::Tag(tag) => fetch_posts_by_tag(tag, cursor, DEFAULT_PAGE_SIZE).await,
+            Feed::Author(username) => fetch_posts_by_username(username, cursor, DEFAULT_PAGE_SIZE).await,
         }
+    }
+}
 
-        match create_blog_post(params).await {
-            Ok(post) => {
-                info!("Created post: {:?}", post);
-                message.set(("green", Some(Cow::from("Post created!"))));
-                text_input.set(String::new());
-                username_input.set(String::new());
-                image_input.set(None);
-                avatar_input.set(String::new());
-                oncreate(post);
+#[component]
+fn HomePage() -> Element {
+    // The sorting and filters chosen in the filter bar; changing them fetches the feed again
+    let options = use_signal(FeedOptions::default);
+    // Only the first page is fetched here; further pages are loaded on demand by the feed
+    let fetch_first_page = use_resource(move || Feed::All(options()).fetch_page(None));
+    // Keyed by the options so that the feed only receives the new posts which match them
+    let feed_key = format!("{:?}", options());
+    // New posts are added to the feed right away, without fetching it again
+    use_context_provider(FeedChanges::new);
+    // The submitted search query; the feed is replaced by the search results while it is not empty
+    let mut search_query = use_signal(String::new);
+    // The static export lists all of its blog posts instead, without anything which needs the server
+    let static_export = try_use_context::<StaticExport>();
+    let locale = use_locale();
+    if let Some(export) = static_export {
+        return rsx! {
+            div { class: "container",
+                div { class: "header",
+                    h1 { {t!(locale, "home-title")} }
+                }
+                BlogPostFeed {
+                    feed: Feed::All(FeedOptions::default()),
+                    page: Some(Ok((*export.posts).clone())),
+                }
             }
-            Err(err) => {
-                error!("Failed to create post: {:?}", err);
-                message.set(("red", Some(Cow::from(err.to_string()))));
+        };
+    }
+    rsx! {
+        div { class: "container",
+            div { class: "header",
+                h1 { {t!(locale, "home-title")} }
+                AccountStatus {}
+            }
+            BlogStatsWidget {}
+            BlogPostForm {}
+            SearchBox {
+                onsearch: move |query: String| search_query.set(query),
+            }
+            if search_query().trim().is_empty() {
+                FeedFilterBar { options }
+                BlogPostFeed {
+                    key: "{feed_key}",
+                    feed: Feed::All(options()),
+                    page: fetch_first_page.read_unchecked().clone(),
+                }
+            } else {
+                // Keyed by the query so that every search fetches its own results
+                SearchResults { key: "{search_query}", query: search_query() }
             }
         }
-    };
+    }
+}
 
+/// A small summary of the statistics of the blog, with a bar for the number of blog posts on each of the last days.
+/// Nothing is shown until the statistics are loaded, or if they cannot be.
+#[component]
+fn BlogStatsWidget() -> Element {
+    let stats = use_resource(fetch_blog_stats);
+    let locale = use_locale();
+    let Some(Ok(stats)) = &*stats.read_unchecked() else {
+        return None;
+    };
+    let busiest = stats.posts_per_day.iter().map(|&(_, posts)| posts).max().unwrap_or(0).max(1);
+    let summary = t!(
+        locale,
+        "blog-stats-summary",
+        posts = stats.total_posts,
+        authors = stats.authors,
+        mib = format!("{:.1}", stats.image_bytes as f64 / (1024.0 * 1024.0)),
+    );
     rsx! {
-        form { class: "blog-post-form",
-
-            // Username Input
-            div {
-                label { "Who are you?" }
-                label { "What's on your mind?" }
+        div { class: "blog-stats",
+            p { "{summary}" }
+            div { class: "blog-stats-days",
+                aria_label: t!(locale, "blog-stats-days"),
+                for (day, posts) in stats.posts_per_day.iter().copied() {
+                    div {
+                        key: "{day}",
+                        class: "blog-stats-day",
+                        title: t!(locale, "blog-stats-day", date = locale.format_date(day), count = posts),
+                        style: "height: {posts * 100 / busiest}%",
+                    }
+                }
             }
+        }
+    }
+}
 
-            // Text Area for the Post
-            div {
-                input {
-                    r#type: "text",
-                    value: "{username_input}",
-                    placeholder: "Enter your username",
-                    oninput: move |evt| username_input.set(evt.value()),
+/// Controls to sort the feed of all blog posts and filter it by author, image and the day they were published.
+/// Every change is applied right away, except for the author, which is applied when the field loses focus.
+#[component]
+fn FeedFilterBar(options: Signal<FeedOptions>) -> Element {
+    let mut options = options;
+    // The author is only applied when the field loses focus, but completed while typing
+    let mut author_input = use_signal(|| options.peek().author.clone().unwrap_or_default());
+    let locale = use_locale();
+    let current = options();
+    let day_value = |day: Option<time::Date>| day.map(|day| day.to_string()).unwrap_or_default();
+    rsx! {
+        div { class: "feed-filter-bar",
+            select {
+                aria_label: t!(locale, "feed-sort"),
+                onchange: move |evt| {
+                    options.write().sort = if evt.value() == "oldest" { FeedSort::Oldest } else { FeedSort::Newest };
+                },
+                option { value: "newest",
+                    selected: current.sort == FeedSort::Newest,
+                    {t!(locale, "feed-sort-newest")}
                 }
-                textarea {
-                    value: "{text_input}",
-                    placeholder: "Write your post here...",
-                    oninput: move |evt| text_input.set(evt.value()),
+                option { value: "oldest",
+                    selected: current.sort == FeedSort::Oldest,
+                    {t!(locale, "feed-sort-oldest")}
                 }
             }
-
-            div {
-                // Image File Upload
+            input {
+                r#type: "text",
+                placeholder: t!(locale, "feed-filter-author"),
+                value: "{author_input}",
+                list: "feed-filter-authors",
+                oninput: move |evt| author_input.set(evt.value()),
+                onchange: move |evt| {
+                    options.write().author = Some(evt.value()).filter(|author| !author.trim().is_empty());
+                },
+            }
+            UsernameSuggestions { id: "feed-filter-authors", prefix: author_input }
+            label {
                 input {
-                    r#type: "file",
-                    accept: "image/png",
-                    onchange: move |evt| {
-                        async move {
-                            if let Some(file_engine) = evt.files() {
-                                let files = file_engine.files();
-                                for file_name in &files {
-                                    debug!("User picked file: {:?}", file_name);
-                                    if let Some(bytes) = file_engine.read_file(file_name).await {
-                                        debug!("Uploaded {}B", bytes.len());
-                                        image_input.set(Some(bytes));
-                                    }
-                                }
-                            }
-                        }
-                    },
+                    r#type: "checkbox",
+                    checked: current.with_image,
+                    onchange: move |evt| options.write().with_image = evt.checked(),
                 }
-
-                // Avatar URL
+                {t!(locale, "feed-filter-with-image")}
+            }
+            label {
+                {t!(locale, "feed-filter-from")}
                 input {
-                    r#type: "url",
-                    value: "{avatar_input}",
-                    placeholder: "Avatar URL (optional)",
-                    oninput: move |evt| avatar_input.set(evt.value()),
-                }
-                if !avatar_input().is_empty() {
-                    div {
-                        img {
-                            src: "{avatar_input}",
-                            alt: "Avatar",
-                            width: "50",
-                        }
-                    }
+                    r#type: "date",
+                    value: day_value(current.posted_from),
+                    oninput: move |evt| options.write().posted_from = parse_day(&evt.value()),
                 }
             }
-
-            // Submit Button
-            div {
-                button { class: "post-btn",
-                    r#type: "submit",
-                    prevent_default: "onclick",
-                    onclick: handle_submit,
-                    "Submit Post"
+            label {
+                {t!(locale, "feed-filter-until")}
+                input {
+                    r#type: "date",
+                    value: day_value(current.posted_until),
+                    oninput: move |evt| options.write().posted_until = parse_day(&evt.value()),
                 }
-                if let Some(error_msg) = message().1 {
-                    div { class: "error",
-                        color: "{message().0}",
-                        "{error_msg}"
-                    }
+            }
+            if current != FeedOptions::default() {
+                button {
+                    onclick: move |_| {
+                        author_input.set(String::new());
+                        options.set(FeedOptions::default());
+                    },
+                    {t!(locale, "feed-filter-reset")}
                 }
             }
         }
     }
 }
 
+/// A list of the usernames starting with the prefix, which completes an input referring to it by its ID.
 #[component]
-fn BlogPostFeed(
-    posts: Option<Result<Vec<BlogPost>, ServerFnError>>,
+fn UsernameSuggestions(id: String, prefix: Signal<String>) -> Element {
+    let suggestions = use_resource(move || suggest_usernames(prefix()));
+    let usernames = match &*suggestions.read_unchecked() {
+        Some(Ok(usernames)) => usernames.clone(),
+        _ => Vec::new(),
+    };
+    rsx! {
+        datalist { id: "{id}",
+            for username in usernames {
+                option { key: "{username}", value: "{username}" }
+            }
+        }
+    }
+}
+
+/// A search box which submits its query when the search button is clicked or enter is pressed.
+/// Clearing the search submits an empty query.
+#[component]
+fn SearchBox(
+    onsearch: EventHandler<String>,
 ) -> Element {
-    match posts {
-        Some(Ok(posts)) => {
-            let posts = posts.into_iter().map(|post| {
-                let deleted = use_signal(|| false);
-                (post, deleted)
-            });
-            rsx! {
-                div {
-                    h2 { "Recent Posts" }
-                    ul {
-                        for (post, deleted) in posts {
-                            li { key: "{post.id.clone()}", hidden: deleted,
-                                Post { post, deleted }
-                            }
+    let mut query_input = use_signal(String::new);
+    let locale = use_locale();
+    rsx! {
+        form { class: "search-box",
+            input {
+                r#type: "search",
+                value: "{query_input}",
+                placeholder: t!(locale, "search-placeholder"),
+                oninput: move |evt| {
+                    query_input.set(evt.value());
+                    if evt.value().is_empty() {
+                        onsearch(String::new());
+                    }
+                },
+            }
+            button {
+                r#type: "submit",
+                prevent_default: "onclick",
+                onclick: move |_| onsearch(query_input()),
+                {t!(locale, "search")}
+            }
+        }
+    }
+}
+
+/// The results of a full-text search, with the matching words highlighted.
+#[component]
+fn SearchResults(query: String) -> Element {
+    let search = use_resource({
+        let query = query.clone();
+        move || search_posts(query.clone())
+    });
+    let locale = use_locale();
+    match &*search.read_unchecked() {
+        Some(Ok(results)) if results.is_empty() => rsx! {
+            div {
+                h2 { {t!(locale, "search-no-results", query = query.as_str())} }
+            }
+        },
+        Some(Ok(results)) => rsx! {
+            div {
+                h2 { {t!(locale, "search-results", query = query.as_str())} }
+                ul {
+                    for result in results.iter().cloned() {
+                        li { key: "{result.post.id}",
+                            SearchResultItem { result }
                         }
                     }
                 }
             }
         },
-        Some(Err(_)) => rsx! {
-            div {
-                h2 { color: "red",
-                    "Error fetching posts"
+        Some(Err(err)) => {
+            error!("Failed to search posts for {:?}: {:?}", query, err);
+            rsx! {
+                div {
+                    h2 { color: "red",
+                        {t!(locale, "search-error")}
+                    }
                 }
             }
         },
         None => rsx! {
             div {
                 h2 { color: "gray",
-                    "Loading posts..."
+                    {t!(locale, "searching")}
                 }
             }
         },
@@ -226,64 +615,3254 @@ fn BlogPostFeed(
 }
 
 #[component]
-fn Post(
-    post: BlogPost,
-    deleted: Signal<bool>,
-) -> Element {
-    let post_image_uuid = post.image_uuid.clone();
-    let load_post_image = use_resource(move || {
-        let post_image_uuid = post_image_uuid.clone();
-        async move {
-            if let Some(image_uuid) = &post_image_uuid {
-                load_post_image(image_uuid.clone()).await.map(Some)
-            } else {
-                Ok(None)
+fn SearchResultItem(result: SearchResult) -> Element {
+    let locale = use_locale();
+    rsx! {
+        div {
+            h3 {
+                Link { to: post_route(&result.post), {t!(locale, "post-heading", id = result.post.id)} }
+            }
+            p {
+                {t!(locale, "posted-by")}
+                " "
+                Link { to: Route::UserPage { username: result.post.username.clone() }, "{result.post.username}" }
+                " "
+                {t!(locale, "posted-on", date = locale.format_date(result.post.posted_on))}
+            }
+            p {
+                for (segment, matched) in result.segments() {
+                    if matched {
+                        mark { "{segment}" }
+                    } else {
+                        span { "{segment}" }
+                    }
+                }
             }
         }
-    });
-    let avatar_image_uuid = post.avatar_uuid.clone();
-    let load_avatar_image = use_resource(move || {
-        let avatar_image_uuid = avatar_image_uuid.clone();
+    }
+}
+
+/// The route to the page of the blog post, which includes its slug if it has one.
+fn post_route(post: &BlogPost) -> Route {
+    match &post.slug {
+        Some(slug) => Route::SluggedPostDetail { id: post.id, slug: slug.clone() },
+        None => Route::PostDetail { id: post.id },
+    }
+}
+
+/// The page of a blog post without its slug, which only blog posts written before slugs existed are canonically at.
+#[component]
+fn PostDetail(id: BlogPostId) -> Element {
+    rsx! {
+        PostPage { id }
+    }
+}
+
+/// The page of a blog post at its permalink. Only the ID identifies the blog post, the slug is there for humans.
+#[component]
+fn SluggedPostDetail(id: BlogPostId, slug: String) -> Element {
+    rsx! {
+        PostPage { id, slug }
+    }
+}
+
+/// A single blog post on its own page, which is where permalinks to blog posts lead.
+#[component]
+fn PostPage(id: BlogPostId, slug: Option<String>) -> Element {
+    let static_export = try_use_context::<StaticExport>();
+    let locale = use_locale();
+    rsx! {
+        div { class: "container",
+            div { class: "header",
+                h1 { {t!(locale, "post-heading", id = id)} }
+                if static_export.is_none() {
+                    AccountStatus {}
+                }
+            }
+            Link { to: Route::HomePage, {t!(locale, "back-to-all-posts")} }
+            // Keyed by the ID so that navigating between posts fetches each of them
+            PostDetailContent { key: "{id}", id, slug }
+        }
+    }
+}
+
+/// The blog post with the ID, with its image at full size.
+/// The blog post is fetched while rendering on the server, so that permalinks render completely without the client.
+/// If the page was reached with an outdated slug, its URL is replaced with the canonical one.
+#[component]
+fn PostDetailContent(id: BlogPostId, slug: Option<String>) -> Element {
+    // There is no feed on this page, but deleting the blog post hides it just the same
+    let changes = use_context_provider(FeedChanges::new);
+    let navigator = use_navigator();
+    let locale = use_locale();
+    // The static export renders the blog post from the export instead
+    let static_export = try_use_context::<StaticExport>();
+    let fetch_post = use_server_future(move || {
+        let static_export = static_export.clone();
         async move {
-            if let Some(avatar_uuid) = &avatar_image_uuid {
-                load_avatar_image(avatar_uuid.clone()).await.map(Some)
-            } else {
-                Ok(None)
+            match static_export {
+                Some(export) => Ok(export.detail(id)),
+                None => fetch_blog_post(id).await,
+            }
+        }
+    })?;
+    use_effect(move || {
+        if let Some(Ok(Some(detail))) = &*fetch_post.read() {
+            if detail.post.slug != slug {
+                navigator.replace(post_route(&detail.post));
             }
         }
     });
-    rsx! {
-        div {
-            h3 { "Post {post.id}" }
-            p { "Posted by {post.username} on {post.posted_on}" }
-            if let Some(Ok(Some(avatar))) = &*load_avatar_image.read_unchecked() {
-                img {
-                    src: format!("data:image/png;base64,{}", avatar),
-                    alt: "Avatar",
-                    width: "50",
-                }
+    match &*fetch_post.read_unchecked() {
+        Some(Ok(Some(detail))) if changes.is_removed(detail.post.id) => rsx! {
+            p { {t!(locale, "post-deleted")} }
+        },
+        Some(Ok(Some(detail))) => rsx! {
+            Post {
+                post: detail.post.clone(),
+                likes: detail.likes,
+                tags: detail.tags.clone(),
+                last_edited: detail.last_edited,
+                full_image: true,
             }
-            p { "{post.text}" }
-            if let Some(Ok(Some(image))) = &*load_post_image.read_unchecked() {
-                img {
-                    src: format!("data:image/png;base64,{}", image),
-                    alt: "Post image",
-                    width: "200",
+            PostMentions { post_id: detail.post.id }
+        },
+        Some(Ok(None)) => rsx! {
+            p { {t!(locale, "post-not-found")} }
+        },
+        Some(Err(err)) => {
+            error!("Failed to fetch post {}: {:?}", id, err);
+            rsx! {
+                p { color: "red",
+                    {t!(locale, "post-error")}
                 }
             }
+        },
+        None => rsx! {
+            p { color: "gray",
+                {t!(locale, "post-loading")}
+            }
+        },
+    }
+}
+
+#[component]
+fn TagPage(name: String) -> Element {
+    let locale = use_locale();
+    rsx! {
+        div { class: "container",
+            div { class: "header",
+                h1 { "#{name}" }
+                AccountStatus {}
+            }
+            Link { to: Route::HomePage, {t!(locale, "back-to-all-posts")} }
+            // Keyed by the tag so that navigating between tags refetches the feed
+            TagFeed { key: "{name}", name: name.clone() }
+        }
+    }
+}
+
+/// The feed of blog posts with the tag.
+#[component]
+fn TagFeed(name: String) -> Element {
+    let feed = Feed::Tag(name);
+    let fetch_first_page = use_resource({
+        let feed = feed.clone();
+        move || feed.clone().fetch_page(None)
+    });
+    use_context_provider(FeedChanges::new);
+    rsx! {
+        BlogPostFeed {
+            feed,
+            page: fetch_first_page.read_unchecked().clone(),
+        }
+    }
+}
+
+#[component]
+fn UserPage(username: String) -> Element {
+    let locale = use_locale();
+    rsx! {
+        div { class: "container",
+            div { class: "header",
+                h1 { "{username}" }
+                AccountStatus {}
+            }
+            Link { to: Route::HomePage, {t!(locale, "back-to-all-posts")} }
+            // Keyed by the username so that navigating between authors fetches each of them
+            UserFeed { key: "{username}", username: username.clone() }
+        }
+    }
+}
+
+/// The profile of the author with the username, followed by the feed of their blog posts.
+#[component]
+fn UserFeed(username: String) -> Element {
+    let fetch_profile = use_resource({
+        let username = username.clone();
+        move || fetch_user_profile(username.clone())
+    });
+    let feed = Feed::Author(username);
+    let fetch_first_page = use_resource({
+        let feed = feed.clone();
+        move || feed.clone().fetch_page(None)
+    });
+    use_context_provider(FeedChanges::new);
+    // Authors can select their blog posts on their profile to delete them at once
+    use_context_provider(PostSelection::new);
+    let locale = use_locale();
+    let profile = match &*fetch_profile.read_unchecked() {
+        Some(Ok(profile)) => rsx! {
+            UserProfileSummary { profile: profile.clone() }
+        },
+        Some(Err(err)) => {
+            error!("Failed to fetch user profile: {:?}", err);
+            rsx! {
+                p { color: "red",
+                    {t!(locale, "profile-error")}
+                }
+            }
+        },
+        None => rsx! {},
+    };
+    rsx! {
+        {profile}
+        SelectionBar {}
+        BlogPostFeed {
+            feed,
+            page: fetch_first_page.read_unchecked().clone(),
+        }
+    }
+}
+
+/// The blog posts which the logged-in user selected to delete at once.
+/// Provided via context by the pages whose blog posts can be selected, which show a checkbox on the selectable ones.
+#[derive(Debug, Clone, Copy)]
+struct PostSelection {
+    selected: Signal<HashSet<BlogPostId>>,
+}
+
+impl PostSelection {
+    fn new() -> Self {
+        Self { selected: Signal::new(HashSet::new()) }
+    }
+
+    /// Select the blog post, or deselect it if it is selected.
+    fn toggle(mut self, post_id: BlogPostId) {
+        let mut selected = self.selected.write();
+        if !selected.remove(&post_id) {
+            selected.insert(post_id);
+        }
+    }
+
+    fn contains(&self, post_id: BlogPostId) -> bool {
+        self.selected.read().contains(&post_id)
+    }
+
+    fn clear(mut self) {
+        self.selected.write().clear();
+    }
+}
+
+/// How many blog posts are selected, with a button to delete them at once.
+/// Nothing is shown while no blog post is selected.
+#[component]
+fn SelectionBar() -> Element {
+    let selection = use_context::<PostSelection>();
+    let changes = use_context::<FeedChanges>();
+    let toasts = use_toasts();
+    let mut deleting = use_signal(|| false);
+    let locale = use_locale();
+    let count = selection.selected.read().len();
+    let delete = move |_| async move {
+        let post_ids: Vec<BlogPostId> = selection.selected.read().iter().copied().collect();
+        deleting.set(true);
+        // The server moves at most a page of blog posts to the trash at once
+        let mut deleted = Vec::new();
+        let mut failed = None;
+        for batch in post_ids.chunks(MAX_PAGE_SIZE as usize) {
+            match delete_blog_posts(batch.to_vec()).await {
+                Ok(batch) => deleted.extend(batch),
+                Err(err) => {
+                    failed = Some(err);
+                    break;
+                }
+            }
+        }
+        info!("Moved posts {:?} to the trash", deleted);
+        for &post_id in &deleted {
+            changes.remove(post_id);
+        }
+        match failed {
+            None => {
+                selection.clear();
+                toasts.success(t!(locale, "posts-moved-to-trash", count = deleted.len()));
+            }
+            Some(err) => {
+                error!("Failed to delete posts: {:?}", err);
+                toasts.error(error_message(err, locale));
+            }
+        }
+        deleting.set(false);
+    };
+    if count == 0 {
+        return rsx! {};
+    }
+    rsx! {
+        div { class: "selection-bar",
+            span { {t!(locale, "posts-selected", count = count)} }
+            button { disabled: deleting(), onclick: delete, {t!(locale, "delete-selected")} }
+            button { disabled: deleting(), onclick: move |_| selection.clear(), {t!(locale, "cancel")} }
+        }
+    }
+}
+
+/// The avatar of an author and how many blog posts they wrote.
+#[component]
+fn UserProfileSummary(profile: UserProfile) -> Element {
+    let locale = use_locale();
+    rsx! {
+        div { class: "user-profile",
+            if let Some(avatar) = &profile.avatar_uuid {
+                img {
+                    src: avatar.url(),
+                    alt: t!(locale, "avatar-of", username = profile.username.as_str()),
+                    width: "80",
+                }
+            }
+            p { {t!(locale, "profile-post-count", count = profile.post_count)} }
+        }
+    }
+}
+
+#[component]
+fn TrashPage() -> Element {
+    let current_user = use_context::<Signal<Option<User>>>();
+    let locale = use_locale();
+    rsx! {
+        div { class: "container",
+            div { class: "header",
+                h1 { {t!(locale, "trash")} }
+                AccountStatus {}
+            }
+            Link { to: Route::HomePage, {t!(locale, "back-to-all-posts")} }
+            if current_user().is_some() {
+                TrashList {}
+            } else {
+                p { {t!(locale, "trash-log-in")} }
+            }
+        }
+    }
+}
+
+/// The blog posts in the trash of the logged-in user, each with a button to restore it.
+#[component]
+fn TrashList() -> Element {
+    let trash = use_resource(fetch_trash);
+    let locale = use_locale();
+    match &*trash.read_unchecked() {
+        Some(Ok(posts)) if posts.is_empty() => rsx! {
+            p { {t!(locale, "trash-empty")} }
+        },
+        Some(Ok(posts)) => rsx! {
+            p { {t!(locale, "trash-hint")} }
+            ul {
+                for trashed in posts.iter().cloned() {
+                    li { key: "{trashed.post.id}",
+                        TrashedPostItem { trashed }
+                    }
+                }
+            }
+        },
+        Some(Err(err)) => {
+            error!("Failed to fetch trash: {:?}", err);
+            rsx! {
+                p { color: "red",
+                    {t!(locale, "trash-error")}
+                }
+            }
+        },
+        None => rsx! {
+            p { color: "gray",
+                {t!(locale, "trash-loading")}
+            }
+        },
+    }
+}
+
+/// A blog post in the trash, which disappears from the trash once it is restored.
+#[component]
+fn TrashedPostItem(trashed: TrashedPost) -> Element {
+    let mut restored = use_signal(|| false);
+    let mut message = use_signal(|| None::<String>);
+    let post_id = trashed.post.id;
+    let locale = use_locale();
+    let posted_by = t!(
+        locale,
+        "posted-by-on",
+        username = trashed.post.username.as_str(),
+        date = locale.format_date(trashed.post.posted_on),
+    );
+    let deleted_on = t!(
+        locale,
+        "trash-deleted-on",
+        deleted = locale.format_date(trashed.deleted_at.date()),
+        purged = locale.format_date(trashed.purge_after.date()),
+    );
+    rsx! {
+        div { hidden: restored,
+            h3 { {t!(locale, "post-heading", id = post_id)} }
+            p { "{posted_by}" }
+            div { class: "blog-post-text",
+                dangerous_inner_html: markdown::render(&trashed.post.text),
+            }
+            p { color: "gray",
+                "{deleted_on}"
+            }
             div { class: "blog-post-actions",
                 button {
                     onclick: move |_| async move {
-                        if delete_blog_post(post.id).await.is_ok() {
-                            info!("Deleted post with id: {}", post.id);
-                            deleted.set(true);
-                        } else {
-                            error!("Failed to delete post with id: {}", post.id);
+                        match restore_blog_post(post_id).await {
+                            Ok(_) => {
+                                info!("Restored post with id: {}", post_id);
+                                restored.set(true);
+                            }
+                            Err(err) => {
+                                error!("Failed to restore post with id {}: {:?}", post_id, err);
+                                message.set(Some(error_message(err, locale)));
+                            }
                         }
                     },
-                    "Delete"
+                    {t!(locale, "restore")}
+                }
+            }
+            if let Some(error_msg) = message() {
+                div { class: "error",
+                    color: "red",
+                    "{error_msg}"
+                }
+            }
+        }
+    }
+}
+
+/// The dashboard where administrators moderate posts and inspect the server.
+/// The server rejects everyone else, so no check is done here beyond being logged in.
+#[component]
+fn AdminPage() -> Element {
+    let current_user = use_context::<Signal<Option<User>>>();
+    // Incremented whenever posts are moderated, so that the statistics are refetched
+    let changes = use_signal(|| 0u32);
+    let locale = use_locale();
+    rsx! {
+        div { class: "container",
+            div { class: "header",
+                h1 { {t!(locale, "admin-title")} }
+                AccountStatus {}
+            }
+            Link { to: Route::HomePage, {t!(locale, "back-to-all-posts")} }
+            if current_user().is_some() {
+                AdminStatisticsPanel { changes }
+                ReportList { changes }
+                ModerationList { changes }
+                ApiKeyList {}
+                ExportPanel {}
+                UserDataPanel { changes }
+                AuditLogPanel { changes }
+            } else {
+                p { {t!(locale, "admin-log-in")} }
+            }
+        }
+    }
+}
+
+/// Statistics about the database and image store, with a button to delete orphaned images.
+#[component]
+fn AdminStatisticsPanel(changes: Signal<u32>) -> Element {
+    let mut statistics = use_resource(move || {
+        let _ = changes();
+        admin_fetch_statistics()
+    });
+    let mut message = use_signal(|| None::<(&'static str, String)>);
+    let locale = use_locale();
+    let collect_images = move |_| async move {
+        message.set(Some(("yellow", t!(locale, "orphaned-images-deleting"))));
+        match collect_orphaned_images().await {
+            Ok(deleted) => {
+                message.set(Some(("green", t!(locale, "orphaned-images-deleted", count = deleted))));
+                statistics.restart();
+            }
+            Err(err) => {
+                error!("Failed to collect orphaned images: {:?}", err);
+                message.set(Some(("red", error_message(err, locale))));
+            }
+        }
+    };
+    let content = match &*statistics.read_unchecked() {
+        Some(Ok(stats)) => rsx! {
+            AdminStatisticsTable { stats: stats.clone() }
+        },
+        Some(Err(err)) => {
+            let msg = error_message(err.clone(), locale);
+            rsx! {
+                p { color: "red", "{msg}" }
+            }
+        },
+        None => rsx! {
+            p { color: "gray", {t!(locale, "statistics-loading")} }
+        },
+    };
+    rsx! {
+        div { class: "admin-statistics",
+            h2 { {t!(locale, "statistics")} }
+            {content}
+            div { class: "blog-post-actions",
+                button { onclick: collect_images, {t!(locale, "orphaned-images-delete")} }
+            }
+            if let Some((color, msg)) = message() {
+                p { color: "{color}", "{msg}" }
+            }
+        }
+    }
+}
+
+#[component]
+fn AdminStatisticsTable(stats: AdminStatistics) -> Element {
+    let locale = use_locale();
+    let connections = t!(
+        locale,
+        "statistics-db-connections-value",
+        connections = stats.db_connections,
+        idle = stats.db_idle_connections,
+    );
+    let stored_images = t!(
+        locale,
+        "statistics-stored-images-value",
+        images = stats.stored_images,
+        kib = stats.stored_bytes / 1024,
+    );
+    rsx! {
+        table {
+            tr { th { {t!(locale, "statistics-visible-posts")} } td { "{stats.visible_posts}" } }
+            tr { th { {t!(locale, "statistics-hidden-posts")} } td { "{stats.hidden_posts}" } }
+            tr { th { {t!(locale, "statistics-trashed-posts")} } td { "{stats.trashed_posts}" } }
+            tr { th { {t!(locale, "statistics-revisions")} } td { "{stats.revisions}" } }
+            tr { th { {t!(locale, "statistics-users")} } td { "{stats.users}" } }
+            tr { th { {t!(locale, "statistics-likes")} } td { "{stats.likes}" } }
+            tr { th { {t!(locale, "statistics-tags")} } td { "{stats.tags}" } }
+            tr { th { {t!(locale, "statistics-open-reports")} } td { "{stats.open_reports}" } }
+            tr { th { {t!(locale, "statistics-db-connections")} } td { "{connections}" } }
+            tr { th { {t!(locale, "statistics-stored-images")} } td { "{stored_images}" } }
+        }
+    }
+}
+
+/// The reports which no administrator has handled yet, oldest first.
+#[component]
+fn ReportList(changes: Signal<u32>) -> Element {
+    let mut changes = changes;
+    let mut reports = use_resource(move || {
+        let _ = changes();
+        admin_fetch_reports()
+    });
+    let mut message = use_signal(|| None::<(&'static str, String)>);
+    let locale = use_locale();
+    let resolve = move |report_id: ReportId, resolution: ReportResolution| async move {
+        match admin_resolve_report(report_id, resolution).await {
+            Ok(()) => {
+                info!("Closed report {} as {:?}", report_id, resolution);
+                message.set(None);
+                // Resolving hides the post, which the statistics and moderation list should reflect
+                *changes.write() += 1;
+                reports.restart();
+            }
+            Err(err) => {
+                error!("Failed to close report {}: {:?}", report_id, err);
+                message.set(Some(("red", error_message(err, locale))));
+            }
+        }
+    };
+    let content = match &*reports.read_unchecked() {
+        Some(Ok(reports)) if reports.is_empty() => rsx! {
+            p { color: "gray", {t!(locale, "reports-none")} }
+        },
+        Some(Ok(reports)) => rsx! {
+            table {
+                for report in reports.iter().cloned() {
+                    OpenReportRow {
+                        key: "{report.id}",
+                        report,
+                        onresolve: move |(report_id, resolution)| resolve(report_id, resolution),
+                    }
+                }
+            }
+        },
+        Some(Err(err)) => {
+            let msg = error_message(err.clone(), locale);
+            rsx! {
+                p { color: "red", "{msg}" }
+            }
+        },
+        None => rsx! {
+            p { color: "gray", {t!(locale, "reports-loading")} }
+        },
+    };
+    rsx! {
+        div { class: "moderation",
+            h2 { {t!(locale, "reports")} }
+            if let Some((color, msg)) = message() {
+                p { color: "{color}", "{msg}" }
+            }
+            {content}
+        }
+    }
+}
+
+/// A row of the report list, with buttons to hide the reported post or dismiss the report.
+#[component]
+fn OpenReportRow(report: OpenReport, onresolve: EventHandler<(ReportId, ReportResolution)>) -> Element {
+    let report_id = report.id;
+    let locale = use_locale();
+    let reported_post = t!(locale, "report-post", id = report.post.id, username = report.post.username.as_str());
+    let summary = t!(
+        locale,
+        "report-summary",
+        reporter = report.reporter.as_str(),
+        time = locale.format_timestamp(report.reported_at),
+        reason = report.reason.as_str(),
+    );
+    let preview: String = report.post.text.chars().take(80).collect();
+    rsx! {
+        tr {
+            td { "{reported_post}" }
+            td { "{preview}" }
+            td { "{summary}" }
+            td {
+                button {
+                    onclick: move |_| onresolve((report_id, ReportResolution::Resolve)),
+                    {t!(locale, "report-hide-post")}
+                }
+                button {
+                    onclick: move |_| onresolve((report_id, ReportResolution::Dismiss)),
+                    {t!(locale, "report-dismiss")}
+                }
+            }
+        }
+    }
+}
+
+/// A link to download an archive of all blog posts with their images, for backups and migrations.
+/// The browser sends the session cookie along, which authenticates the download like a REST API request.
+#[component]
+fn ExportPanel() -> Element {
+    let locale = use_locale();
+    rsx! {
+        div { class: "admin-export",
+            h2 { {t!(locale, "export")} }
+            p { {t!(locale, "export-description")} }
+            a {
+                href: "/api/v1/export",
+                download: "",
+                {t!(locale, "export-download")}
+            }
+        }
+    }
+}
+
+/// A form to download all data of a user, or to erase it irreversibly, e.g. when they ask for it under the GDPR.
+/// Erasing asks for confirmation first, since unlike deleting blog posts it cannot be undone.
+#[component]
+fn UserDataPanel(changes: Signal<u32>) -> Element {
+    let mut username_input = use_signal(String::new);
+    let mut confirming = use_signal(|| false);
+    let mut message = use_signal(|| None::<(&'static str, String)>);
+    let locale = use_locale();
+    let erase = move |_| async move {
+        let username = username_input();
+        confirming.set(false);
+        match admin_erase_user(username.clone()).await {
+            Ok(posts) => {
+                info!("Erased the data of {} with {} posts", username, posts);
+                message.set(Some(("green", t!(locale, "user-data-erased", username = username, count = posts))));
+                username_input.set(String::new());
+                // The erased posts disappear from the statistics and the moderation list
+                *changes.write() += 1;
+            }
+            Err(err) => {
+                error!("Failed to erase the data of {}: {:?}", username, err);
+                message.set(Some(("red", error_message(err, locale))));
+            }
+        }
+    };
+    let username = username_input();
+    let download = format!("/api/v1/users/{}/export", encode_path_segment(username.trim()));
+    rsx! {
+        div { class: "admin-export",
+            h2 { {t!(locale, "user-data")} }
+            p { {t!(locale, "user-data-description")} }
+            input {
+                r#type: "text",
+                value: "{username}",
+                placeholder: t!(locale, "username"),
+                maxlength: "{MAX_USERNAME_LENGTH}",
+                list: "user-data-usernames",
+                oninput: move |evt| {
+                    username_input.set(evt.value());
+                    confirming.set(false);
+                },
+            }
+            UsernameSuggestions { id: "user-data-usernames", prefix: username_input }
+            if !username.trim().is_empty() {
+                div {
+                    a {
+                        href: "{download}",
+                        download: "",
+                        {t!(locale, "user-data-download")}
+                    }
+                    if confirming() {
+                        p { color: "red", {t!(locale, "user-data-erase-confirm", username = username.clone())} }
+                        button { onclick: erase, {t!(locale, "user-data-erase")} }
+                        button { onclick: move |_| confirming.set(false), {t!(locale, "cancel")} }
+                    } else {
+                        button { onclick: move |_| confirming.set(true), {t!(locale, "user-data-erase")} }
+                    }
+                }
+            }
+            if let Some((color, msg)) = message() {
+                p { color: "{color}", "{msg}" }
+            }
+        }
+    }
+}
+
+/// Percent-encode the text for use as a segment of the path of a URL, keeping only the unreserved characters.
+fn encode_path_segment(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.' | b'~') {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    encoded
+}
+
+/// The API keys with which bots and command-line tools authenticate to the REST API, newest first,
+/// with a form to create a key and buttons to revoke keys.
+/// A created key is shown once above the list, since the server only keeps its hash.
+#[component]
+fn ApiKeyList() -> Element {
+    let mut keys = use_resource(admin_fetch_api_keys);
+    let mut username_input = use_signal(String::new);
+    let mut name_input = use_signal(String::new);
+    let mut created_key = use_signal(|| None::<String>);
+    let mut message = use_signal(|| ("red", None));
+    let mut field_errors = use_signal(ValidationErrors::default);
+    let locale = use_locale();
+    let create = move |_| async move {
+        let params = CreateApiKeyParams { username: username_input(), name: name_input() };
+        if let Err(errors) = params.validate() {
+            message.set(("red", None));
+            field_errors.set(errors);
+            return;
+        }
+        field_errors.set(ValidationErrors::default());
+        match admin_create_api_key(params).await {
+            Ok(new_key) => {
+                info!("Created API key {} for {}", new_key.info.prefix, new_key.info.username);
+                message.set(("red", None));
+                created_key.set(Some(new_key.key));
+                name_input.set(String::new());
+                keys.restart();
+            }
+            Err(err) => {
+                error!("Failed to create API key: {:?}", err);
+                show_error(err, locale, message, field_errors);
+            }
+        }
+    };
+    let revoke = move |key_id: ApiKeyId| async move {
+        match admin_revoke_api_key(key_id).await {
+            Ok(()) => {
+                info!("Revoked API key {}", key_id);
+                keys.restart();
+            }
+            Err(err) => {
+                error!("Failed to revoke API key {}: {:?}", key_id, err);
+                message.set(("red", Some(Cow::from(error_message(err, locale)))));
+            }
+        }
+    };
+    let content = match &*keys.read_unchecked() {
+        Some(Ok(keys)) if keys.is_empty() => rsx! {
+            p { color: "gray", {t!(locale, "api-keys-none")} }
+        },
+        Some(Ok(keys)) => rsx! {
+            table {
+                for api_key in keys.iter().cloned() {
+                    ApiKeyRow {
+                        key: "{api_key.id}",
+                        api_key,
+                        onrevoke: move |key_id| revoke(key_id),
+                    }
                 }
             }
+        },
+        Some(Err(err)) => {
+            let msg = error_message(err.clone(), locale);
+            rsx! {
+                p { color: "red", "{msg}" }
+            }
+        },
+        None => rsx! {
+            p { color: "gray", {t!(locale, "api-keys-loading")} }
+        },
+    };
+    let (color, msg) = message();
+    rsx! {
+        div { class: "moderation",
+            h2 { {t!(locale, "api-keys")} }
+            form { class: "login-form",
+                input {
+                    r#type: "text",
+                    value: "{username_input}",
+                    placeholder: t!(locale, "username"),
+                    maxlength: "{MAX_USERNAME_LENGTH}",
+                    oninput: move |evt| username_input.set(evt.value()),
+                }
+                FieldError { errors: field_errors, field: Field::Username }
+                input {
+                    r#type: "text",
+                    value: "{name_input}",
+                    placeholder: t!(locale, "api-key-name"),
+                    maxlength: "{MAX_API_KEY_NAME_LENGTH}",
+                    oninput: move |evt| name_input.set(evt.value()),
+                }
+                FieldError { errors: field_errors, field: Field::KeyName }
+                div {
+                    button { class: "post-btn",
+                        r#type: "submit",
+                        prevent_default: "onclick",
+                        onclick: create,
+                        {t!(locale, "api-key-create")}
+                    }
+                }
+            }
+            if let Some(msg) = msg {
+                p { color: "{color}", "{msg}" }
+            }
+            if let Some(key) = created_key() {
+                p { color: "green", {t!(locale, "api-key-created")} }
+                p { code { "{key}" } }
+            }
+            {content}
+        }
+    }
+}
+
+/// A row of the API key list, with a button to revoke the key.
+#[component]
+fn ApiKeyRow(api_key: ApiKeyInfo, onrevoke: EventHandler<ApiKeyId>) -> Element {
+    let key_id = api_key.id;
+    let locale = use_locale();
+    let created = t!(locale, "api-key-created-on", time = locale.format_timestamp(api_key.created_at));
+    let last_used = match api_key.last_used_at {
+        Some(last_used_at) => t!(locale, "api-key-last-used", time = locale.format_timestamp(last_used_at)),
+        None => t!(locale, "api-key-never-used"),
+    };
+    rsx! {
+        tr {
+            td { "{api_key.name}" }
+            td { "{api_key.username}" }
+            td { code { "{api_key.prefix}…" } }
+            td { "{created}" }
+            td { "{last_used}" }
+            td {
+                button {
+                    onclick: move |_| onrevoke(key_id),
+                    {t!(locale, "api-key-revoke")}
+                }
+            }
+        }
+    }
+}
+
+/// The most recent entries of the audit log, newest first,
+/// filtered by the days in which they occurred and by their action.
+#[component]
+fn AuditLogPanel(changes: Signal<u32>) -> Element {
+    let mut since_input = use_signal(String::new);
+    let mut until_input = use_signal(String::new);
+    let mut action_filter = use_signal(|| None::<AuditAction>);
+    let entries = use_resource(move || {
+        let _ = changes();
+        // Both ends of the range are whole days in UTC, including the last day
+        let since = parse_day(&since_input()).map(|day| day.midnight());
+        let until = parse_day(&until_input()).and_then(|day| day.next_day()).map(|day| day.midnight());
+        admin_fetch_audit_log(since, until, action_filter())
+    });
+    let locale = use_locale();
+    let content = match &*entries.read_unchecked() {
+        Some(Ok(entries)) if entries.is_empty() => rsx! {
+            p { color: "gray", {t!(locale, "audit-log-none")} }
+        },
+        Some(Ok(entries)) => rsx! {
+            table {
+                for entry in entries.iter().cloned() {
+                    AuditEntryRow { key: "{entry.id}", entry }
+                }
+            }
+        },
+        Some(Err(err)) => {
+            let msg = error_message(err.clone(), locale);
+            rsx! {
+                p { color: "red", "{msg}" }
+            }
+        },
+        None => rsx! {
+            p { color: "gray", {t!(locale, "audit-log-loading")} }
+        },
+    };
+    rsx! {
+        div { class: "moderation",
+            h2 { {t!(locale, "audit-log")} }
+            label {
+                {t!(locale, "audit-log-since")}
+                input {
+                    r#type: "date",
+                    value: "{since_input}",
+                    oninput: move |evt| since_input.set(evt.value()),
+                }
+            }
+            label {
+                {t!(locale, "audit-log-until")}
+                input {
+                    r#type: "date",
+                    value: "{until_input}",
+                    oninput: move |evt| until_input.set(evt.value()),
+                }
+            }
+            select {
+                onchange: move |evt| action_filter.set(AuditAction::from_name(&evt.value())),
+                option { value: "", {t!(locale, "audit-log-all-actions")} }
+                for action in AuditAction::ALL {
+                    option { key: "{action.as_str()}",
+                        value: action.as_str(),
+                        selected: action_filter() == Some(action),
+                        {audit_action_label(action, locale)}
+                    }
+                }
+            }
+            {content}
+        }
+    }
+}
+
+/// A row of the audit log.
+#[component]
+fn AuditEntryRow(entry: AuditEntry) -> Element {
+    let locale = use_locale();
+    let actor = entry.actor.clone().unwrap_or_else(|| t!(locale, "audit-log-server"));
+    let post = entry.post_id.map(|post_id| t!(locale, "audit-log-post", id = post_id));
+    rsx! {
+        tr {
+            td { {locale.format_timestamp(entry.occurred_at)} }
+            td { {audit_action_label(entry.action, locale)} }
+            td { "{actor}" }
+            td { {entry.ip.clone().unwrap_or_default()} }
+            td {
+                if let Some(post) = post {
+                    "{post}"
+                }
+            }
+            td { "{entry.summary}" }
+        }
+    }
+}
+
+/// The name of the action as shown in the audit log.
+fn audit_action_label(action: AuditAction, locale: Locale) -> String {
+    t!(locale, &format!("audit-action-{}", action.as_str().replace('_', "-")))
+}
+
+/// Parse the value of a date input, which is empty if no date was picked.
+fn parse_day(value: &str) -> Option<time::Date> {
+    time::Date::parse(value, time::macros::format_description!("[year]-[month]-[day]")).ok()
+}
+
+/// All blog posts, including hidden posts and posts in the trash,
+/// with checkboxes to select posts and buttons to moderate the selected posts at once.
+#[component]
+fn ModerationList(changes: Signal<u32>) -> Element {
+    let mut changes = changes;
+    let mut posts = use_signal(Vec::<ModeratedPost>::new);
+    let mut next_cursor = use_signal(|| None::<BlogPostId>);
+    let mut selected = use_signal(HashSet::<BlogPostId>::new);
+    let mut message = use_signal(|| None::<(&'static str, String)>);
+    let locale = use_locale();
+    // Load the page after the cursor, or replace all loaded posts with the first page if there is no cursor
+    let load = move |cursor: Option<BlogPostId>| async move {
+        match admin_fetch_posts(cursor, DEFAULT_PAGE_SIZE).await {
+            Ok(page) => {
+                if cursor.is_some() {
+                    posts.write().extend(page.posts);
+                } else {
+                    posts.set(page.posts);
+                }
+                next_cursor.set(page.next_cursor);
+            }
+            Err(err) => {
+                error!("Failed to fetch posts for moderation: {:?}", err);
+                message.set(Some(("red", error_message(err, locale))));
+            }
+        }
+    };
+    use_future(move || load(None));
+    let apply = move |action: ModerationAction| async move {
+        let post_ids: Vec<BlogPostId> = selected().into_iter().collect();
+        if post_ids.is_empty() {
+            message.set(Some(("red", t!(locale, "moderation-select-first"))));
+            return;
+        }
+        // The server moderates at most a page of blog posts at once
+        let mut affected = 0;
+        let mut failed = None;
+        for batch in post_ids.chunks(MAX_PAGE_SIZE as usize) {
+            match admin_moderate_posts(batch.to_vec(), action).await {
+                Ok(count) => affected += count,
+                Err(err) => {
+                    failed = Some(err);
+                    break;
+                }
+            }
+        }
+        info!("Applied {:?} to {} posts", action, affected);
+        match failed {
+            None => {
+                let label = t!(locale, moderation_label(action));
+                message.set(Some(("green", t!(locale, "moderation-applied", action = label, count = affected))));
+                selected.write().clear();
+            }
+            Some(err) => {
+                error!("Failed to apply {:?}: {:?}", action, err);
+                message.set(Some(("red", error_message(err, locale))));
+            }
+        }
+        if affected > 0 {
+            *changes.write() += 1;
+            load(None).await;
+        }
+    };
+    rsx! {
+        div { class: "moderation",
+            h2 { {t!(locale, "moderation-posts")} }
+            div { class: "blog-post-actions",
+                button { onclick: move |_| apply(ModerationAction::Hide), {t!(locale, "moderation-hide")} }
+                button { onclick: move |_| apply(ModerationAction::Unhide), {t!(locale, "moderation-unhide")} }
+                button { onclick: move |_| apply(ModerationAction::Delete), {t!(locale, "moderation-delete")} }
+                button { onclick: move |_| apply(ModerationAction::Restore), {t!(locale, "moderation-restore")} }
+            }
+            if let Some((color, msg)) = message() {
+                p { color: "{color}", "{msg}" }
+            }
+            table {
+                for moderated in posts() {
+                    ModeratedPostRow { key: "{moderated.post.id}", moderated, selected }
+                }
+            }
+            if let Some(cursor) = next_cursor() {
+                div { class: "load-more",
+                    button { class: "load-more-btn",
+                        onclick: move |_| load(Some(cursor)),
+                        {t!(locale, "load-more")}
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The ID of the message labelling the button which applies the moderation action.
+fn moderation_label(action: ModerationAction) -> &'static str {
+    match action {
+        ModerationAction::Hide => "moderation-hide",
+        ModerationAction::Unhide => "moderation-unhide",
+        ModerationAction::Delete => "moderation-delete",
+        ModerationAction::Restore => "moderation-restore",
+    }
+}
+
+/// A row of the moderation list, whose checkbox adds the post to or removes it from the selection.
+#[component]
+fn ModeratedPostRow(moderated: ModeratedPost, selected: Signal<HashSet<BlogPostId>>) -> Element {
+    let mut selected = selected;
+    let post_id = moderated.post.id;
+    let locale = use_locale();
+    let status = match (moderated.deleted_at, moderated.hidden) {
+        (Some(deleted_at), _) => t!(locale, "moderation-in-trash", date = locale.format_date(deleted_at.date())),
+        (None, true) => t!(locale, "moderation-hidden"),
+        (None, false) => t!(locale, "moderation-visible"),
+    };
+    let posted_on = locale.format_date(moderated.post.posted_on);
+    let preview: String = moderated.post.text.chars().take(80).collect();
+    rsx! {
+        tr {
+            td {
+                input {
+                    r#type: "checkbox",
+                    checked: selected().contains(&post_id),
+                    onchange: move |_| {
+                        let mut selected = selected.write();
+                        if !selected.remove(&post_id) {
+                            selected.insert(post_id);
+                        }
+                    },
+                }
+            }
+            td { "{post_id}" }
+            td { "{moderated.post.username}" }
+            td { "{posted_on}" }
+            td { "{status}" }
+            td { "{preview}" }
+        }
+    }
+}
+
+/// Shows who is logged in, or a link to the login page if nobody is, next to the theme toggle and the locale switcher.
+#[component]
+fn AccountStatus() -> Element {
+    let mut current_user = use_context::<Signal<Option<User>>>();
+    let locale = use_locale();
+    match current_user() {
+        Some(user) => rsx! {
+            div { class: "account-status",
+                span { {t!(locale, "logged-in-as", username = user.username.as_str())} }
+                Link { to: Route::TrashPage, {t!(locale, "trash")} }
+                button {
+                    onclick: move |_| async move {
+                        match logout().await {
+                            Ok(()) => {
+                                current_user.set(None);
+                                forget_offline_data();
+                            }
+                            Err(err) => error!("Failed to log out: {:?}", err),
+                        }
+                    },
+                    {t!(locale, "log-out")}
+                }
+                ThemeToggle {}
+                LocaleSwitcher {}
+            }
+        },
+        None => rsx! {
+            div { class: "account-status",
+                Link { to: Route::LoginPage, {t!(locale, "log-in-or-register")} }
+                ThemeToggle {}
+                LocaleSwitcher {}
+            }
+        },
+    }
+}
+
+/// The color theme of the app.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Theme {
+    Light,
+    Dark,
+}
+
+impl Theme {
+    fn toggled(self) -> Self {
+        match self {
+            Theme::Light => Theme::Dark,
+            Theme::Dark => Theme::Light,
+        }
+    }
+
+    /// The value of the `data-theme` attribute which the stylesheet selects the theme by.
+    #[cfg(feature = "web")]
+    fn name(self) -> &'static str {
+        match self {
+            Theme::Light => "light",
+            Theme::Dark => "dark",
+        }
+    }
+}
+
+/// Switches between the light and the dark theme, and remembers the choice for later visits.
+#[component]
+fn ThemeToggle() -> Element {
+    let mut theme = use_context::<Signal<Theme>>();
+    let locale = use_locale();
+    let label = match theme() {
+        Theme::Light => t!(locale, "theme-dark"),
+        Theme::Dark => t!(locale, "theme-light"),
+    };
+    rsx! {
+        button {
+            onclick: move |_| {
+                let toggled = theme().toggled();
+                theme.set(toggled);
+                store_theme(toggled);
+            },
+            "{label}"
+        }
+    }
+}
+
+/// The locale of the user interface, which the calling component is rendered again in whenever it changes.
+fn use_locale() -> Locale {
+    use_context::<Signal<Locale>>()()
+}
+
+/// How often the current time used for relative times is advanced.
+const CLOCK_TICK: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// The current time in UTC, which the calling component is rendered again in whenever the clock advances.
+fn use_now() -> time::PrimitiveDateTime {
+    use_context::<Signal<time::PrimitiveDateTime>>()()
+}
+
+/// Returns the current time in UTC, like the timestamps from the server.
+fn current_time() -> time::PrimitiveDateTime {
+    let now = time::OffsetDateTime::now_utc();
+    time::PrimitiveDateTime::new(now.date(), now.time())
+}
+
+/// Shows how long ago the timestamp was, e.g. "3 hours ago", with the exact time on hover.
+#[component]
+fn RelativeTime(timestamp: time::PrimitiveDateTime) -> Element {
+    let locale = use_locale();
+    let now = use_now();
+    // The pages of the static export are read long after they were rendered, so they show the exact time instead
+    if try_use_context::<StaticExport>().is_some() {
+        return rsx! {
+            time { {locale.format_timestamp(timestamp)} }
+        };
+    }
+    rsx! {
+        time { title: locale.format_timestamp(timestamp),
+            {locale.format_relative(timestamp, now)}
+        }
+    }
+}
+
+/// Switches the language of the user interface, and remembers the choice for later visits.
+#[component]
+fn LocaleSwitcher() -> Element {
+    let mut locale = use_context::<Signal<Locale>>();
+    let current = locale();
+    rsx! {
+        select {
+            aria_label: t!(current, "locale-switcher"),
+            onchange: move |evt| {
+                if let Some(chosen) = Locale::from_code(&evt.value()) {
+                    locale.set(chosen);
+                    store_locale(chosen);
+                }
+            },
+            for choice in Locale::ALL {
+                option { key: "{choice.code()}",
+                    value: choice.code(),
+                    selected: choice == current,
+                    "{choice.native_name()}"
+                }
+            }
+        }
+    }
+}
+
+#[component]
+fn LoginPage() -> Element {
+    let mut current_user = use_context::<Signal<Option<User>>>();
+    let mut username_input = use_signal(String::new);
+    let mut password_input = use_signal(String::new);
+    let mut message = use_signal(|| ("red", None));
+    let mut field_errors = use_signal(ValidationErrors::default);
+    let locale = use_locale();
+
+    // Registering and logging in take the same credentials and only differ in the server function
+    let handle_submit = move |register_account: bool| async move {
+        let credentials = Credentials {
+            username: username_input().clone(),
+            password: password_input().clone(),
+        };
+
+        if register_account {
+            if let Err(errors) = credentials.validate() {
+                message.set(("red", None));
+                field_errors.set(errors);
+                return;
+            }
+        }
+
+        field_errors.set(ValidationErrors::default());
+        message.set(("yellow", Some(Cow::from(t!(locale, "please-wait")))));
+        let result = if register_account {
+            register(credentials).await
+        } else {
+            login(credentials).await
+        };
+        match result {
+            Ok(user) => {
+                info!("Logged in as {}", user.username);
+                current_user.set(Some(user));
+                navigator().push(Route::HomePage);
+            }
+            Err(err) => {
+                error!("Failed to log in: {:?}", err);
+                show_error(err, locale, message, field_errors);
+            }
+        }
+    };
+
+    rsx! {
+        div { class: "container",
+            h1 { class: "header",
+                {t!(locale, "log-in")}
+            }
+            form { class: "login-form",
+                input {
+                    r#type: "text",
+                    value: "{username_input}",
+                    placeholder: t!(locale, "username"),
+                    autocomplete: "username",
+                    oninput: move |evt| username_input.set(evt.value()),
+                }
+                FieldError { errors: field_errors, field: Field::Username }
+                input {
+                    r#type: "password",
+                    value: "{password_input}",
+                    placeholder: t!(locale, "password"),
+                    autocomplete: "current-password",
+                    oninput: move |evt| password_input.set(evt.value()),
+                }
+                FieldError { errors: field_errors, field: Field::Password }
+                div {
+                    button { class: "post-btn",
+                        r#type: "submit",
+                        prevent_default: "onclick",
+                        onclick: move |_| handle_submit(false),
+                        {t!(locale, "log-in")}
+                    }
+                    button { class: "post-btn",
+                        r#type: "button",
+                        onclick: move |_| handle_submit(true),
+                        {t!(locale, "register")}
+                    }
+                }
+                if let Some(error_msg) = message().1 {
+                    div { class: "error",
+                        color: "{message().0}",
+                        "{error_msg}"
+                    }
+                }
+            }
+            Link { to: Route::HomePage, {t!(locale, "back-to-blog")} }
+        }
+    }
+}
+
+/// The text fields of a blog post which is being written, kept in local storage so that it survives reloading the page.
+/// The image and avatar files are not kept, since local storage only holds a few megabytes.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+struct PostDraft {
+    text: String,
+    tags: String,
+    image_alt: String,
+    avatar_url: String,
+}
+
+#[component]
+fn BlogPostForm() -> Element {
+    let mut current_user = use_context::<Signal<Option<User>>>();
+    let changes = use_context::<FeedChanges>();
+    let mut text_input = use_signal(String::new);
+    let mut image_input = use_signal(|| None);
+    let mut image_alt_input = use_signal(String::new);
+    let mut avatar_input = use_signal(String::new);
+    // An avatar file replaces the avatar URL, and vice versa
+    let mut avatar_file_input = use_signal(|| None::<Vec<u8>>);
+    let avatar_preview = use_memo(move || avatar_file_input().map(|bytes| image_data_url(&bytes)));
+    let avatar_dimensions = use_resource(move || async move {
+        match avatar_file_input() {
+            Some(bytes) => image_dimensions(&bytes).await,
+            None => None,
+        }
+    });
+    let mut avatar_crop = use_signal(AvatarCrop::default);
+    let mut tags_input = use_signal(String::new);
+    let mut previewing = use_signal(|| false);
+    let toasts = use_toasts();
+    let mut message = use_signal(|| ("red", None));
+    let mut field_errors = use_signal(ValidationErrors::default);
+    // The fraction of the image which was uploaded so far, and how to abort the upload, while it is in flight
+    let upload_progress = use_signal(|| None::<f64>);
+    let mut abort_upload = use_signal(|| None::<Rc<dyn Fn()>>);
+    // Images are uploaded as picked until the server says how far to downscale them
+    let max_image_dimension = use_resource(fetch_max_image_dimension);
+    // The server may allow fewer characters than the absolute limit, which is assumed until it says so
+    let max_post_length = use_resource(fetch_max_post_length);
+    let max_post_length = max_post_length.read().as_ref().and_then(|result| result.as_ref().ok().copied());
+    let max_post_length = max_post_length.unwrap_or(MAX_POST_LENGTH);
+    let text_length = use_memo(move || post_length(&text_input()));
+    let remaining_length = max_post_length as i64 - text_length() as i64;
+    let locale = use_locale();
+    // The draft is only known in the browser, so it is restored once the form is mounted
+    use_effect(move || {
+        if let Some(draft) = load_draft() {
+            text_input.set(draft.text);
+            tags_input.set(draft.tags);
+            image_alt_input.set(draft.image_alt);
+            avatar_input.set(draft.avatar_url);
+        }
+    });
+    // Kept on every change, and cleared along with the form once the post is submitted
+    use_effect(move || {
+        store_draft(&PostDraft {
+            text: text_input(),
+            tags: tags_input(),
+            image_alt: image_alt_input(),
+            avatar_url: avatar_input(),
+        });
+    });
+
+    let handle_submit = move |_| async move {
+        message.set(("yellow", Some(Cow::from(t!(locale, "posting")))));
+
+        // The server attributes the post to the logged-in account
+        let mut params = CreateBlogPostParams {
+            text: text_input().clone(),
+            image_upload: None,
+            avatar_upload: None,
+            avatar_crop: avatar_file_input().is_some().then_some(avatar_crop()),
+            image_alt: if image_input().is_some() && !image_alt_input().trim().is_empty() {
+                Some(image_alt_input().trim().to_string())
+            } else {
+                None
+            },
+            avatar_url: if avatar_input().is_empty() {
+                None
+            } else {
+                Some(avatar_input().clone())
+            },
+            tags: parse_tags(&tags_input()),
+            challenge: ChallengeSolution::default(),
+        };
+
+        if let Err(errors) = params.validate() {
+            message.set(("red", None));
+            field_errors.set(errors);
+            return;
+        }
+        field_errors.set(ValidationErrors::default());
+
+        let max_dimension = max_image_dimension.read().as_ref().and_then(|result| result.as_ref().ok().copied().flatten());
+
+        // Upload the image on its own, so that the server can stream it to disk instead of deserializing it
+        if let Some(mut bytes) = image_input() {
+            if let Some(max_dimension) = max_dimension {
+                message.set(("yellow", Some(Cow::from(t!(locale, "resizing-image")))));
+                bytes = downscale_image(bytes, max_dimension).await;
+            }
+            message.set(("yellow", Some(Cow::from(t!(locale, "uploading-image")))));
+            match upload_image(bytes, upload_progress, abort_upload, locale).await {
+                Ok(token) => params.image_upload = Some(token),
+                Err(msg) => {
+                    error!("Failed to upload image: {}", msg);
+                    message.set(("red", Some(Cow::from(msg))));
+                    return;
+                }
+            }
+            message.set(("yellow", Some(Cow::from(t!(locale, "posting")))));
+        }
+        if let Some(mut bytes) = avatar_file_input() {
+            if let Some(max_dimension) = max_dimension {
+                bytes = downscale_image(bytes, max_dimension).await;
+            }
+            message.set(("yellow", Some(Cow::from(t!(locale, "uploading-avatar")))));
+            match upload_image(bytes, upload_progress, abort_upload, locale).await {
+                Ok(token) => params.avatar_upload = Some(token),
+                Err(msg) => {
+                    error!("Failed to upload avatar: {}", msg);
+                    message.set(("red", Some(Cow::from(msg))));
+                    return;
+                }
+            }
+            message.set(("yellow", Some(Cow::from(t!(locale, "posting")))));
+        }
+
+        // Prove to the server that this is not a bot by spending some CPU time
+        match fetch_challenge().await {
+            Ok(challenge) => params.challenge = solve_challenge(challenge).await,
+            Err(err) => {
+                error!("Failed to fetch challenge: {:?}", err);
+                message.set(("red", Some(Cow::from(error_message(err, locale)))));
+                return;
+            }
+        }
+
+        // Show the post in the feed while the server processes its images and checks its text
+        let username = current_user().map(|user| user.username).unwrap_or_default();
+        let pending = changes.add_pending(params.text.clone(), username);
+        let tags = params.tags.clone();
+        let submission = create_blog_post(params).await;
+        // Posts which were not published are rolled back, whether they were queued, rejected or failed
+        let published = match &submission {
+            Ok(PostSubmission::Published(post)) => Some((post.clone(), tags)),
+            _ => None,
+        };
+        changes.resolve_pending(pending, published);
+        if let Ok(PostSubmission::Published(_) | PostSubmission::Queued(_)) = &submission {
+            text_input.set(String::new());
+            previewing.set(false);
+            image_input.set(None);
+            image_alt_input.set(String::new());
+            avatar_input.set(String::new());
+            avatar_file_input.set(None);
+            tags_input.set(String::new());
+        }
+        match submission {
+            Ok(PostSubmission::Published(post)) => {
+                info!("Created post: {:?}", post);
+                message.set(("green", None));
+                toasts.success(t!(locale, "post-created"));
+            }
+            Ok(PostSubmission::Queued(violation)) => {
+                info!("Post was queued for review: {:?}", violation);
+                message.set(("yellow", Some(Cow::from(t!(locale, "post-queued", reason = violation.to_string())))));
+            }
+            Ok(PostSubmission::Rejected(violation)) => {
+                info!("Post was rejected: {:?}", violation);
+                message.set(("red", Some(Cow::from(violation.to_string()))));
+            }
+            Err(err) => {
+                error!("Failed to create post: {:?}", err);
+                // The session expired, so the form is replaced by the link to the login page
+                if AppError::from(err.clone()) == AppError::NotLoggedIn {
+                    current_user.set(None);
+                }
+                show_error(err, locale, message, field_errors);
+            }
+        }
+    };
+
+    let upload_percent = upload_progress().map(|fraction| (fraction * 100.0).round());
+
+    let Some(user) = current_user() else {
+        return rsx! {
+            div { class: "blog-post-form",
+                p {
+                    Link { to: Route::LoginPage, {t!(locale, "log-in")} }
+                    {t!(locale, "log-in-to-post")}
+                }
+            }
+        };
+    };
+
+    rsx! {
+        form { class: "blog-post-form",
+
+            // Labels
+            div {
+                label { {t!(locale, "posting-as", username = user.username.as_str())} }
+                label { {t!(locale, "whats-on-your-mind")} }
+            }
+
+            // Text Area for the Post, or its rendered Markdown while previewing
+            div {
+                if previewing() {
+                    div { class: "blog-post-text markdown-preview",
+                        dangerous_inner_html: markdown::render(&text_input()),
+                    }
+                } else {
+                    textarea {
+                        value: "{text_input}",
+                        placeholder: t!(locale, "post-text-placeholder"),
+                        oninput: move |evt| text_input.set(evt.value()),
+                    }
+                }
+                p { class: if remaining_length < 0 { "character-counter over-limit" } else { "character-counter" },
+                    if remaining_length < 0 {
+                        {t!(locale, "characters-over", count = -remaining_length)}
+                    } else {
+                        {t!(locale, "characters-remaining", count = remaining_length)}
+                    }
+                }
+                FieldError { errors: field_errors, field: Field::Text }
+                button { class: "preview-btn",
+                    r#type: "button",
+                    onclick: move |_| previewing.set(!previewing()),
+                    if previewing() { {t!(locale, "edit")} } else { {t!(locale, "preview")} }
+                }
+                input {
+                    r#type: "text",
+                    value: "{tags_input}",
+                    placeholder: t!(locale, "tags-placeholder"),
+                    oninput: move |evt| tags_input.set(evt.value()),
+                }
+                FieldError { errors: field_errors, field: Field::Tags }
+            }
+
+            div {
+                // Image File Upload
+                if IMAGE_UPLOADS {
+                    input {
+                        r#type: "file",
+                        accept: ACCEPTED_IMAGE_TYPES,
+                        onchange: move |evt| {
+                            async move {
+                                if let Some(file_engine) = evt.files() {
+                                    let files = file_engine.files();
+                                    for file_name in &files {
+                                        debug!("User picked file: {:?}", file_name);
+                                        match file_engine.read_file(file_name).await {
+                                            Some(bytes) => {
+                                                debug!("Uploaded {}B", bytes.len());
+                                                image_input.set(Some(bytes));
+                                            }
+                                            None => toasts.error(t!(locale, "file-read-failed")),
+                                        }
+                                    }
+                                }
+                            }
+                        },
+                    }
+                    if image_input().is_some() {
+                        input {
+                            r#type: "text",
+                            value: "{image_alt_input}",
+                            maxlength: "{MAX_IMAGE_ALT_LENGTH}",
+                            placeholder: t!(locale, "image-alt-placeholder"),
+                            oninput: move |evt| image_alt_input.set(evt.value()),
+                        }
+                        FieldError { errors: field_errors, field: Field::ImageAlt }
+                    }
+                }
+
+                // Avatar URL
+                input {
+                    r#type: "url",
+                    value: "{avatar_input}",
+                    placeholder: t!(locale, "avatar-url-placeholder"),
+                    oninput: move |evt| {
+                        avatar_input.set(evt.value());
+                        avatar_file_input.set(None);
+                    },
+                }
+
+                // Avatar File Upload, as an alternative to the URL
+                if IMAGE_UPLOADS {
+                    label { {t!(locale, "avatar-upload")} }
+                    input {
+                        r#type: "file",
+                        accept: ACCEPTED_IMAGE_TYPES,
+                        onchange: move |evt| {
+                            async move {
+                                if let Some(file_engine) = evt.files() {
+                                    for file_name in &file_engine.files() {
+                                        debug!("User picked avatar file: {:?}", file_name);
+                                        match file_engine.read_file(file_name).await {
+                                            Some(bytes) => {
+                                                avatar_file_input.set(Some(bytes));
+                                                avatar_crop.set(AvatarCrop::default());
+                                                avatar_input.set(String::new());
+                                            }
+                                            None => toasts.error(t!(locale, "file-read-failed")),
+                                        }
+                                    }
+                                }
+                            }
+                        },
+                    }
+                }
+                if let Some(preview) = avatar_preview() {
+                    AvatarCropper {
+                        preview,
+                        dimensions: (*avatar_dimensions.read()).flatten(),
+                        crop: avatar_crop,
+                    }
+                } else if !avatar_input().is_empty() {
+                    div {
+                        img {
+                            src: "{avatar_input}",
+                            alt: t!(locale, "avatar"),
+                            width: "50",
+                        }
+                    }
+                }
+                FieldError { errors: field_errors, field: Field::Avatar }
+            }
+
+            // Submit Button
+            div {
+                button { class: "post-btn",
+                    r#type: "submit",
+                    prevent_default: "onclick",
+                    onclick: handle_submit,
+                    disabled: upload_progress().is_some() || remaining_length < 0,
+                    {t!(locale, "submit-post")}
+                }
+                if let Some(percent) = upload_percent {
+                    div { class: "upload-progress",
+                        progress { max: "100", value: "{percent}" }
+                        button {
+                            r#type: "button",
+                            onclick: move |_| {
+                                if let Some(abort) = abort_upload.take() {
+                                    abort();
+                                }
+                            },
+                            {t!(locale, "cancel")}
+                        }
+                    }
+                }
+                if let Some(error_msg) = message().1 {
+                    div { class: "error",
+                        color: "{message().0}",
+                        "{error_msg}"
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Lets the author choose the square of their avatar file which is kept, with a preview of the result.
+/// Until the dimensions of the image are known, it is previewed as a whole and cropped to its largest centered square.
+#[component]
+fn AvatarCropper(
+    preview: String,
+    dimensions: Option<(u32, u32)>,
+    crop: Signal<AvatarCrop>,
+) -> Element {
+    let mut crop = crop;
+    let locale = use_locale();
+    let Some((width, height)) = dimensions else {
+        return rsx! {
+            div {
+                img {
+                    src: "{preview}",
+                    alt: t!(locale, "avatar"),
+                    width: "50",
+                }
+            }
+        };
+    };
+    // The preview box shows exactly the square, scaled to its size
+    let AvatarCrop { size, left, top } = crop();
+    let scale = AVATAR_CROP_PREVIEW_SIZE / (f64::from(width.min(height)) * size);
+    // Percentages position the square within the free space around it, just like the crop does
+    let style = format!(
+        "width: {AVATAR_CROP_PREVIEW_SIZE}px; height: {AVATAR_CROP_PREVIEW_SIZE}px; \
+        background-image: url('{preview}'); background-size: {}px {}px; background-position: {}% {}%;",
+        f64::from(width) * scale,
+        f64::from(height) * scale,
+        left * 100.0,
+        top * 100.0,
+    );
+    let min_size = AvatarCrop::MIN_SIZE * 100.0;
+    let size_percent = size * 100.0;
+    let left_percent = left * 100.0;
+    let top_percent = top * 100.0;
+
+    rsx! {
+        div { class: "avatar-cropper",
+            div { class: "avatar-crop-preview",
+                role: "img",
+                aria_label: t!(locale, "avatar-cropped"),
+                style: "{style}",
+            }
+            div { class: "avatar-crop-controls",
+                label {
+                    {t!(locale, "avatar-crop-size")}
+                    input {
+                        r#type: "range",
+                        min: "{min_size}",
+                        max: "100",
+                        value: "{size_percent}",
+                        oninput: move |evt| {
+                            if let Ok(percent) = evt.value().parse::<f64>() {
+                                crop.write().size = (percent / 100.0).clamp(AvatarCrop::MIN_SIZE, 1.0);
+                            }
+                        },
+                    }
+                }
+                label {
+                    {t!(locale, "avatar-crop-horizontal")}
+                    input {
+                        r#type: "range",
+                        min: "0",
+                        max: "100",
+                        value: "{left_percent}",
+                        oninput: move |evt| {
+                            if let Ok(percent) = evt.value().parse::<f64>() {
+                                crop.write().left = (percent / 100.0).clamp(0.0, 1.0);
+                            }
+                        },
+                    }
+                }
+                label {
+                    {t!(locale, "avatar-crop-vertical")}
+                    input {
+                        r#type: "range",
+                        min: "0",
+                        max: "100",
+                        value: "{top_percent}",
+                        oninput: move |evt| {
+                            if let Ok(percent) = evt.value().parse::<f64>() {
+                                crop.write().top = (percent / 100.0).clamp(0.0, 1.0);
+                            }
+                        },
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The changes made to a feed since it was fetched, which are shown on top of the fetched pages.
+/// Provided via context by the page showing the feed, so that the blog post form, the blog posts themselves
+/// and the changes pushed by the server can update the feed right away instead of fetching it again.
+#[derive(Debug, Clone, Copy)]
+struct FeedChanges {
+    /// The blog posts added to the top of the feed, newest first.
+    added: Signal<Vec<AddedPost>>,
+    /// The blog posts which were deleted, or are being deleted.
+    removed: Signal<HashSet<BlogPostId>>,
+    /// The key of the next blog post created from this client.
+    next_pending_key: Signal<u32>,
+}
+
+impl FeedChanges {
+    fn new() -> Self {
+        Self {
+            added: Signal::new(Vec::new()),
+            removed: Signal::new(HashSet::new()),
+            next_pending_key: Signal::new(0),
+        }
+    }
+
+    /// Show a blog post which the server is still creating at the top of the feed.
+    /// Returns the key to resolve it with once the server answers.
+    fn add_pending(mut self, text: String, username: String) -> u32 {
+        let key = *self.next_pending_key.peek();
+        *self.next_pending_key.write() += 1;
+        self.added.write().insert(0, AddedPost::Pending { key, text, username });
+        key
+    }
+
+    /// Replace the pending blog post with the published one, or roll it back if it was not published.
+    fn resolve_pending(mut self, key: u32, published: Option<(BlogPost, Vec<String>)>) {
+        let mut added = self.added.write();
+        let Some(index) = added.iter().position(|entry| entry.is_pending(key)) else {
+            return;
+        };
+        match published {
+            // The server may have pushed the blog post to the feed before answering
+            Some((post, tags)) if !added.iter().any(|entry| entry.is_published(post.id)) => {
+                added[index] = AddedPost::Published { post, tags };
+            }
+            _ => {
+                added.remove(index);
+            }
+        }
+    }
+
+    /// Show a published blog post at the top of the feed, unless it is already there.
+    fn publish(mut self, post: BlogPost, tags: Vec<String>) {
+        let mut added = self.added.write();
+        if !added.iter().any(|entry| entry.is_published(post.id)) {
+            added.insert(0, AddedPost::Published { post, tags });
+        }
+    }
+
+    /// Hide the blog post from the feed.
+    fn remove(mut self, post_id: BlogPostId) {
+        self.removed.write().insert(post_id);
+    }
+
+    /// Show the blog post again, e.g. because deleting it failed or was undone.
+    fn unremove(mut self, post_id: BlogPostId) {
+        // The feed may be gone by then, e.g. if deleting was undone after navigating to another page
+        if let Ok(mut removed) = self.removed.try_write() {
+            removed.remove(&post_id);
+        }
+    }
+
+    /// Whether the blog post is hidden from the feed.
+    fn is_removed(&self, post_id: BlogPostId) -> bool {
+        self.removed.read().contains(&post_id)
+    }
+}
+
+/// A blog post added to the top of a feed since it was fetched.
+#[derive(Debug, Clone, PartialEq)]
+enum AddedPost {
+    /// A blog post which the server is still creating, identified by a key local to the client.
+    Pending { key: u32, text: String, username: String },
+    /// A published blog post, along with its tags.
+    Published { post: BlogPost, tags: Vec<String> },
+}
+
+impl AddedPost {
+    fn is_pending(&self, key: u32) -> bool {
+        matches!(self, AddedPost::Pending { key: pending, .. } if *pending == key)
+    }
+
+    fn is_published(&self, post_id: BlogPostId) -> bool {
+        matches!(self, AddedPost::Published { post, .. } if post.id == post_id)
+    }
+
+    /// Identifies the entry among the rendered list items.
+    fn render_key(&self) -> String {
+        match self {
+            AddedPost::Pending { key, .. } => format!("pending-{key}"),
+            AddedPost::Published { post, .. } => post.id.to_string(),
+        }
+    }
+}
+
+/// Renders the fetched blog posts of the feed along with the changes made since, including those pushed by the server.
+/// Blog posts added since the feed was fetched are shown above it, and deleted blog posts are hidden.
+#[component]
+fn BlogPostFeed(
+    feed: Feed,
+    page: Option<Result<BlogPostPage, ServerFnError>>,
+) -> Element {
+    let changes = use_context::<FeedChanges>();
+    // Unsubscribes when the feed is dropped
+    let _subscription = use_hook({
+        let feed = feed.clone();
+        move || {
+            Rc::new(subscribe_post_events(move |event| match event {
+                PostEvent::Created { post, tags } if feed.includes(&post, &tags) => {
+                    debug!("Post {} was published", post.id);
+                    changes.publish(post, tags);
+                }
+                PostEvent::Created { .. } => {}
+                PostEvent::Deleted(post_id) => {
+                    debug!("Post {} was deleted", post_id);
+                    changes.remove(post_id);
+                }
+            }))
+        }
+    });
+    let locale = use_locale();
+    match page {
+        Some(Ok(page)) => {
+            // The feed may have been fetched after they were added, in which case it already contains them
+            let added = changes
+                .added
+                .read()
+                .iter()
+                .filter(|entry| !page.posts.iter().any(|fetched| entry.is_published(fetched.id)))
+                .map(|entry| (entry.render_key(), entry.clone()))
+                .collect::<Vec<_>>();
+            rsx! {
+                div {
+                    h2 { {t!(locale, "recent-posts")} }
+                    ul {
+                        for (key, entry) in added {
+                            AddedPostItem { key: "{key}", entry }
+                        }
+                    }
+                    BlogPostList { feed, page }
+                }
+            }
+        },
+        Some(Err(_)) => rsx! {
+            div {
+                h2 { color: "red",
+                    {t!(locale, "posts-error")}
+                }
+            }
+        },
+        None => rsx! {
+            div {
+                h2 { {t!(locale, "recent-posts")} }
+                FeedSkeleton { count: FEED_SKELETON_POSTS }
+            }
+        },
+    }
+}
+
+/// The number of placeholder blog posts shown while the first page of a feed is loading.
+const FEED_SKELETON_POSTS: usize = 3;
+
+/// Placeholders in the shape of blog posts, shown while the blog posts of a feed are loading.
+/// Screen readers announce the list as loading instead of reading the placeholders.
+#[component]
+fn FeedSkeleton(count: usize) -> Element {
+    let locale = use_locale();
+    rsx! {
+        ul { class: "feed-skeleton", aria_busy: "true", aria_label: t!(locale, "loading-posts"),
+            for index in 0..count {
+                li { key: "{index}", class: "skeleton-post", aria_hidden: "true",
+                    div { class: "skeleton-line skeleton-heading" }
+                    div { class: "skeleton-author",
+                        div { class: "skeleton-avatar" }
+                        div { class: "skeleton-line skeleton-short" }
+                    }
+                    div { class: "skeleton-line" }
+                    div { class: "skeleton-line" }
+                    div { class: "skeleton-line skeleton-short" }
+                }
+            }
+        }
+    }
+}
+
+/// Renders a page of blog posts, followed by a button to load the next page if there is one.
+#[component]
+fn BlogPostList(
+    feed: Feed,
+    page: BlogPostPage,
+) -> Element {
+    let changes = use_context::<FeedChanges>();
+    let posts = page.posts.into_iter().map(|post| {
+        let likes = page.likes.get(&post.id).copied().unwrap_or_default();
+        let tags = page.tags.get(&post.id).cloned().unwrap_or_default();
+        let last_edited = page.last_edited.get(&post.id).copied();
+        let hidden = changes.is_removed(post.id);
+        (post, likes, tags, last_edited, hidden)
+    });
+    rsx! {
+        ul {
+            for (post, likes, tags, last_edited, hidden) in posts {
+                li { key: "{post.id.clone()}", hidden: hidden,
+                    Post { post, likes, tags, last_edited }
+                }
+            }
+        }
+        if let Some(cursor) = page.next_cursor {
+            LoadMorePosts { key: "{cursor}", feed, cursor }
+        }
+    }
+}
+
+/// A blog post added to the top of the feed since it was fetched.
+/// Nobody has liked or edited a new blog post yet as far as the feed knows.
+#[component]
+fn AddedPostItem(entry: AddedPost) -> Element {
+    let changes = use_context::<FeedChanges>();
+    let locale = use_locale();
+    match entry {
+        AddedPost::Pending { text, username, .. } => rsx! {
+            li { class: "pending-post",
+                div {
+                    h3 { {t!(locale, "posting")} }
+                    p { {t!(locale, "posted-by-user", username = username.as_str())} }
+                    div { class: "blog-post-text",
+                        dangerous_inner_html: markdown::render(&text),
+                    }
+                }
+            }
+        },
+        AddedPost::Published { post, tags } => rsx! {
+            li { hidden: changes.is_removed(post.id),
+                Post { post, likes: PostLikes::default(), tags, last_edited: None }
+            }
+        },
+    }
+}
+
+/// A "Load more" button which fetches and renders the page of blog posts in the feed after the cursor.
+/// The page is also fetched as soon as the button scrolls into view, so that the feed scrolls endlessly.
+#[component]
+fn LoadMorePosts(
+    feed: Feed,
+    cursor: BlogPostId,
+) -> Element {
+    let mut requested = use_signal(|| false);
+    // Dropped along with the button, which stops observing it
+    let mut visibility_observer = use_signal(|| None::<VisibilityObserver>);
+    let fetch_next_page = use_resource({
+        let feed = feed.clone();
+        move || {
+            let feed = feed.clone();
+            async move {
+                if requested() {
+                    feed.fetch_page(Some(cursor)).await.map(Some)
+                } else {
+                    Ok(None)
+                }
+            }
+        }
+    });
+    let locale = use_locale();
+    match &*fetch_next_page.read_unchecked() {
+        Some(Ok(Some(page))) => rsx! {
+            BlogPostList { feed, page: page.clone() }
+        },
+        Some(Err(err)) => {
+            error!("Failed to fetch posts after {}: {:?}", cursor, err);
+            rsx! {
+                p { color: "red",
+                    {t!(locale, "more-posts-error")}
+                }
+            }
+        },
+        _ if requested() => rsx! {
+            FeedSkeleton { count: 1 }
+        },
+        _ => rsx! {
+            div { class: "load-more",
+                onmounted: move |evt| {
+                    let observer = observe_visibility(&evt.data(), move || {
+                        // The observer keeps firing while the button is visible
+                        if !*requested.peek() {
+                            requested.set(true);
+                        }
+                    });
+                    visibility_observer.set(observer);
+                },
+                button { class: "load-more-btn",
+                    onclick: move |_| requested.set(true),
+                    {t!(locale, "load-more")}
+                }
+            }
+        },
+    }
+}
+
+/// Receives the changes to the blog post feed pushed by the server until it is dropped.
+#[cfg(feature = "web")]
+struct PostEventSubscription {
+    source: web_sys::EventSource,
+    // The browser calls this for every event until the source is closed, so it must live as long as the source
+    _onmessage: wasm_bindgen::closure::Closure<dyn FnMut(web_sys::MessageEvent)>,
+}
+
+#[cfg(feature = "web")]
+impl Drop for PostEventSubscription {
+    fn drop(&mut self) {
+        self.source.close();
+    }
+}
+
+/// Call `onevent` for every change to the blog post feed pushed by the server, until the returned subscription is dropped.
+/// The browser reconnects by itself if the connection drops, but the changes made in between are missed.
+/// Returns `None` if the browser fails to connect.
+#[cfg(feature = "web")]
+fn subscribe_post_events(mut onevent: impl FnMut(PostEvent) + 'static) -> Option<PostEventSubscription> {
+    use wasm_bindgen::closure::Closure;
+    use wasm_bindgen::JsCast;
+
+    let source = web_sys::EventSource::new(POST_EVENTS_URL)
+        .inspect_err(|err| error!("Failed to subscribe to post events: {:?}", err))
+        .ok()?;
+    let onmessage = Closure::<dyn FnMut(web_sys::MessageEvent)>::new(move |message: web_sys::MessageEvent| {
+        let Some(data) = message.data().as_string() else {
+            return;
+        };
+        match serde_json::from_str::<PostEvent>(&data) {
+            Ok(event) => onevent(event),
+            Err(err) => error!("Failed to parse post event {}: {}", data, err),
+        }
+    });
+    source.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+    Some(PostEventSubscription { source, _onmessage: onmessage })
+}
+
+/// Changes are only pushed to the browser.
+#[cfg(not(feature = "web"))]
+type PostEventSubscription = ();
+
+/// Changes are only pushed to the browser.
+#[cfg(not(feature = "web"))]
+fn subscribe_post_events(_onevent: impl FnMut(PostEvent) + 'static) -> Option<PostEventSubscription> {
+    None
+}
+
+/// Observes whether an element is scrolled into view until it is dropped.
+#[cfg(feature = "web")]
+struct VisibilityObserver {
+    observer: web_sys::IntersectionObserver,
+    // The browser calls this until the observer is disconnected, so it must live as long as the observer
+    _callback: wasm_bindgen::closure::Closure<dyn FnMut(js_sys::Array)>,
+}
+
+#[cfg(feature = "web")]
+impl Drop for VisibilityObserver {
+    fn drop(&mut self) {
+        self.observer.disconnect();
+    }
+}
+
+/// Call `onvisible` whenever the mounted element scrolls into view, or comes close to it,
+/// until the returned observer is dropped.
+/// Returns `None` if the browser does not support observing the element.
+#[cfg(feature = "web")]
+fn observe_visibility(mounted: &MountedData, mut onvisible: impl FnMut() + 'static) -> Option<VisibilityObserver> {
+    use wasm_bindgen::closure::Closure;
+    use wasm_bindgen::JsCast;
+
+    let element = mounted.downcast::<web_sys::Element>()?;
+    let callback = Closure::<dyn FnMut(js_sys::Array)>::new(move |entries: js_sys::Array| {
+        let visible = entries.iter().any(|entry| {
+            entry.dyn_into::<web_sys::IntersectionObserverEntry>().is_ok_and(|entry| entry.is_intersecting())
+        });
+        if visible {
+            onvisible();
+        }
+    });
+    let options = web_sys::IntersectionObserverInit::new();
+    // Start loading before the element is actually visible, so that scrolling does not stall
+    options.set_root_margin(VISIBILITY_MARGIN);
+    let observer = web_sys::IntersectionObserver::new_with_options(callback.as_ref().unchecked_ref(), &options)
+        .inspect_err(|err| error!("Failed to observe visibility: {:?}", err))
+        .ok()?;
+    observer.observe(element);
+    Some(VisibilityObserver { observer, _callback: callback })
+}
+
+/// The feed is only scrolled in the browser.
+#[cfg(not(feature = "web"))]
+type VisibilityObserver = ();
+
+/// The feed is only scrolled in the browser.
+#[cfg(not(feature = "web"))]
+fn observe_visibility(_mounted: &MountedData, _onvisible: impl FnMut() + 'static) -> Option<VisibilityObserver> {
+    None
+}
+
+/// The width in pixels at which the thumbnails of images are shown in feeds.
+const THUMBNAIL_DISPLAY_WIDTH: u32 = 200;
+
+#[component]
+fn Post(
+    post: BlogPost,
+    likes: PostLikes,
+    tags: Vec<String>,
+    last_edited: Option<time::PrimitiveDateTime>,
+    // Show the image at full size instead of its thumbnail, e.g. on the page of the blog post itself
+    #[props(default)]
+    full_image: bool,
+) -> Element {
+    let current_user = use_context::<Signal<Option<User>>>();
+    let changes = use_context::<FeedChanges>();
+    let mut likes = use_signal(|| likes);
+    // The client does not know who the administrators are, so it only offers the authors to manage their posts
+    let editable = current_user().is_some_and(|user| post.is_editable_by(&user, false));
+    // The editable text is kept in a signal so that edits are reflected without refetching the feed
+    let mut text = use_signal(|| post.text.clone());
+    let rendered_text = use_memo(move || markdown::render(&text()));
+    let mut editing = use_signal(|| false);
+    // Edits made here are reflected immediately, although their exact time is only known to the server
+    let mut edited = use_signal(|| last_edited.is_some());
+    let mut showing_history = use_signal(|| false);
+    let mut reporting = use_signal(|| false);
+    // The images are shown as shimmering placeholders until they are loaded, which only happens once they scroll into view
+    let mut image_loaded = use_signal(|| false);
+    let mut avatar_loaded = use_signal(|| false);
+    // The placeholders take the size of the images if it is known, so that nothing moves once they are loaded
+    let image_size = post.image_size();
+    let thumbnail_height = image_size.map(|(width, height)| (THUMBNAIL_DISPLAY_WIDTH * height / width).to_string());
+    // Users have no reason to report their own posts, they can edit or delete them instead
+    let reportable = current_user().is_some() && !editable;
+    let selection = try_use_context::<PostSelection>().filter(|_| editable);
+    let toasts = use_toasts();
+    let locale = use_locale();
+    let image_alt = post.image_alt.clone().unwrap_or_else(|| t!(locale, "post-image"));
+    let path = post.path();
+    rsx! {
+        div {
+            h3 {
+                if let Some(selection) = selection {
+                    input {
+                        r#type: "checkbox",
+                        aria_label: t!(locale, "select-post"),
+                        checked: selection.contains(post.id),
+                        onchange: move |_| selection.toggle(post.id),
+                    }
+                }
+                Link { to: post_route(&post), {t!(locale, "post-heading", id = post.id)} }
+            }
+            if editing() {
+                BlogPostEditForm {
+                    post_id: post.id,
+                    text: text(),
+                    onsave: move |updated: BlogPost| {
+                        if updated.text != text() {
+                            edited.set(true);
+                        }
+                        text.set(updated.text);
+                        editing.set(false);
+                    },
+                    oncancel: move |_| editing.set(false),
+                }
+            } else {
+                p {
+                    {t!(locale, "posted-by")}
+                    " "
+                    Link { to: Route::UserPage { username: post.username.clone() }, "{post.username}" }
+                    " "
+                    if let Some(posted_at) = post.posted_at {
+                        RelativeTime { timestamp: posted_at }
+                    } else {
+                        {t!(locale, "posted-on", date = locale.format_date(post.posted_on))}
+                    }
+                    if edited() {
+                        button { class: "edited-indicator",
+                            title: last_edited.map(|time| t!(locale, "post-last-edited", time = locale.format_timestamp(time))),
+                            onclick: move |_| showing_history.set(true),
+                            {t!(locale, "edited")}
+                        }
+                    }
+                }
+            }
+            if showing_history() {
+                PostHistory {
+                    post_id: post.id,
+                    onclose: move |_| showing_history.set(false),
+                }
+            }
+            if let Some(avatar) = &post.avatar_uuid {
+                img {
+                    class: if avatar_loaded() { "avatar" } else { "avatar loading" },
+                    src: avatar.url(),
+                    alt: t!(locale, "avatar-of", username = post.username.as_str()),
+                    width: "50",
+                    height: "50",
+                    loading: "lazy",
+                    onload: move |_| avatar_loaded.set(true),
+                    onerror: move |_| avatar_loaded.set(true),
+                }
+            }
+            if !editing() {
+                div { class: "blog-post-text",
+                    dangerous_inner_html: rendered_text(),
+                }
+            }
+            if !tags.is_empty() {
+                div { class: "blog-post-tags",
+                    for tag in tags {
+                        Link { key: "{tag}", to: Route::TagPage { name: tag.clone() }, "#{tag}" }
+                    }
+                }
+            }
+            if let Some(image) = &post.image_uuid {
+                if full_image {
+                    img {
+                        class: if image_loaded() { "post-image-full" } else { "post-image-full loading" },
+                        src: image.url(),
+                        alt: image_alt.clone(),
+                        width: image_size.map(|(width, _)| width.to_string()),
+                        height: image_size.map(|(_, height)| height.to_string()),
+                        onload: move |_| image_loaded.set(true),
+                        onerror: move |_| {
+                            image_loaded.set(true);
+                            toasts.error(t!(locale, "image-load-failed"));
+                        },
+                    }
+                } else {
+                    a {
+                        href: image.url(),
+                        target: "_blank",
+                        img {
+                            class: if image_loaded() { "post-image" } else { "post-image loading" },
+                            src: image.thumbnail_url(),
+                            alt: image_alt.clone(),
+                            width: "{THUMBNAIL_DISPLAY_WIDTH}",
+                            height: thumbnail_height,
+                            loading: "lazy",
+                            onload: move |_| image_loaded.set(true),
+                            // A broken image should not look like one which is still loading
+                            onerror: move |_| image_loaded.set(true),
+                        }
+                    }
+                }
+            }
+            div { class: "blog-post-actions",
+                button {
+                    class: if likes().liked { "liked" } else { "" },
+                    disabled: current_user().is_none(),
+                    onclick: move |_| async move {
+                        // Toggle optimistically, and reconcile with the server's count once it answers
+                        let previous = likes();
+                        likes.set(PostLikes {
+                            count: previous.count + if previous.liked { -1 } else { 1 },
+                            liked: !previous.liked,
+                        });
+                        match toggle_like(post.id).await {
+                            Ok(updated) => likes.set(updated),
+                            Err(err) => {
+                                error!("Failed to toggle like on post with id {}: {:?}", post.id, err);
+                                likes.set(previous);
+                                toasts.error(error_message(err, locale));
+                            }
+                        }
+                    },
+                    if likes().liked { "♥ {likes().count}" } else { "♡ {likes().count}" }
+                }
+                button {
+                    onclick: move |_| {
+                        let path = path.clone();
+                        async move {
+                            match share_post(path).await {
+                                Ok(Some(confirmation)) => toasts.info(t!(locale, confirmation)),
+                                Ok(None) => {}
+                                Err(err) => {
+                                    error!("Failed to share post with id {}: {}", post.id, err);
+                                    toasts.error(t!(locale, "share-failed"));
+                                }
+                            }
+                        }
+                    },
+                    {t!(locale, "share")}
+                }
+                if reportable {
+                    button {
+                        disabled: reporting(),
+                        onclick: move |_| reporting.set(true),
+                        {t!(locale, "report")}
+                    }
+                }
+            }
+            if reporting() {
+                ReportForm {
+                    post_id: post.id,
+                    onclose: move |_| reporting.set(false),
+                }
+            }
+            if editable {
+                div { class: "blog-post-actions",
+                    button {
+                        disabled: editing(),
+                        onclick: move |_| editing.set(true),
+                        {t!(locale, "edit")}
+                    }
+                    button {
+                        onclick: move |_| async move {
+                            // Hide the post right away, and show it again if the server fails to delete it
+                            changes.remove(post.id);
+                            match delete_blog_post(post.id).await {
+                                Ok(()) => {
+                                    info!("Moved post with id {} to the trash", post.id);
+                                    let undo = ToastAction::UndoDelete { post_id: post.id, changes };
+                                    toasts.success_with_action(t!(locale, "post-moved-to-trash"), undo);
+                                }
+                                Err(err) => {
+                                    error!("Failed to delete post with id {}: {:?}", post.id, err);
+                                    changes.unremove(post.id);
+                                    toasts.error(error_message(err, locale));
+                                }
+                            }
+                        },
+                        {t!(locale, "delete")}
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A dialog listing the previous texts of an edited blog post, most recent first.
+#[component]
+fn PostHistory(
+    post_id: BlogPostId,
+    onclose: EventHandler<()>,
+) -> Element {
+    let history = use_resource(move || fetch_post_history(post_id));
+    let locale = use_locale();
+    let revisions = match &*history.read_unchecked() {
+        Some(Ok(revisions)) => rsx! {
+            ul {
+                for revision in revisions.iter().cloned() {
+                    PostRevisionItem { revision }
+                }
+            }
+        },
+        Some(Err(err)) => {
+            error!("Failed to fetch history of post with id {}: {:?}", post_id, err);
+            rsx! {
+                p { color: "red",
+                    {t!(locale, "history-error")}
+                }
+            }
+        },
+        None => rsx! {
+            p { color: "gray",
+                {t!(locale, "history-loading")}
+            }
+        },
+    };
+    rsx! {
+        dialog { class: "post-history",
+            open: true,
+            h3 { {t!(locale, "history-title", id = post_id)} }
+            {revisions}
+            button {
+                onclick: move |_| onclose(()),
+                {t!(locale, "close")}
+            }
+        }
+    }
+}
+
+/// The pages on other sites which link to the blog post, as announced by Webmentions.
+/// Nothing is shown while they load, or if there are none.
+#[component]
+fn PostMentions(post_id: BlogPostId) -> Element {
+    let mentions = use_resource(move || fetch_webmentions(post_id));
+    let locale = use_locale();
+    match &*mentions.read_unchecked() {
+        Some(Ok(mentions)) if !mentions.is_empty() => rsx! {
+            div { class: "post-mentions",
+                h3 { {t!(locale, "mentions-title")} }
+                ul {
+                    for mention in mentions.iter().cloned() {
+                        MentionItem { mention }
+                    }
+                }
+            }
+        },
+        Some(Err(err)) => {
+            error!("Failed to fetch mentions of post with id {}: {:?}", post_id, err);
+            None
+        },
+        _ => None,
+    }
+}
+
+/// A page which links to a blog post, shown by its title if it has one.
+#[component]
+fn MentionItem(mention: Webmention) -> Element {
+    let locale = use_locale();
+    let received = t!(locale, "mention-received", time = locale.format_timestamp(mention.received_at));
+    let title = mention.title.clone().unwrap_or_else(|| mention.source.clone());
+    rsx! {
+        li {
+            a {
+                href: mention.source.clone(),
+                rel: "nofollow ugc",
+                target: "_blank",
+                "{title}"
+            }
+            span { color: "gray", " {received}" }
+        }
+    }
+}
+
+/// A previous text of a blog post, with the time at which it was replaced.
+#[component]
+fn PostRevisionItem(revision: PostRevision) -> Element {
+    let locale = use_locale();
+    let replaced = t!(locale, "revision-replaced", time = locale.format_timestamp(revision.edited_at));
+    rsx! {
+        li {
+            p { color: "gray", "{replaced}" }
+            div { class: "blog-post-text",
+                dangerous_inner_html: markdown::render(&revision.text),
+            }
+        }
+    }
+}
+
+/// Inline form for reporting a blog post to the administrators.
+/// After the report is sent, the form shows a confirmation instead of the input.
+#[component]
+fn ReportForm(
+    post_id: BlogPostId,
+    onclose: EventHandler<()>,
+) -> Element {
+    let mut reason = use_signal(String::new);
+    let mut sent = use_signal(|| false);
+    let mut already_reported = use_signal(|| false);
+    let mut message = use_signal(|| None::<String>);
+    let mut field_errors = use_signal(ValidationErrors::default);
+    let locale = use_locale();
+    rsx! {
+        div { class: "report-form",
+            if sent() {
+                if already_reported() {
+                    p { color: "gray", {t!(locale, "report-already-sent")} }
+                } else {
+                    p { color: "green", {t!(locale, "report-thanks")} }
+                }
+                button {
+                    onclick: move |_| onclose(()),
+                    {t!(locale, "close")}
+                }
+            } else {
+                textarea {
+                    placeholder: t!(locale, "report-placeholder"),
+                    maxlength: "{MAX_REPORT_REASON_LENGTH}",
+                    value: reason,
+                    oninput: move |event| reason.set(event.value()),
+                }
+                FieldError { errors: field_errors, field: Field::Reason }
+                div { class: "blog-post-actions",
+                    button {
+                        onclick: move |_| async move {
+                            if let Err(errors) = validate_report_reason(&reason()) {
+                                field_errors.set(errors);
+                                return;
+                            }
+                            field_errors.set(ValidationErrors::default());
+                            match report_post(post_id, reason()).await {
+                                Ok(()) => {
+                                    info!("Reported post with id: {}", post_id);
+                                    sent.set(true);
+                                }
+                                Err(err) => {
+                                    error!("Failed to report post with id {}: {:?}", post_id, err);
+                                    match AppError::from(err) {
+                                        AppError::Validation(errors) => field_errors.set(errors),
+                                        // The earlier report still stands, so this one is as good as sent
+                                        AppError::AlreadyReported => {
+                                            already_reported.set(true);
+                                            sent.set(true);
+                                        }
+                                        err => message.set(Some(error_message(err, locale))),
+                                    }
+                                }
+                            }
+                        },
+                        {t!(locale, "report-send")}
+                    }
+                    button {
+                        onclick: move |_| onclose(()),
+                        {t!(locale, "cancel")}
+                    }
+                }
+                if let Some(error_msg) = message() {
+                    div { class: "error",
+                        color: "red",
+                        "{error_msg}"
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Inline form for editing the text of an existing blog post.
+/// Pre-filled with the current text of the post.
+#[component]
+fn BlogPostEditForm(
+    post_id: BlogPostId,
+    text: String,
+    onsave: EventHandler<BlogPost>,
+    oncancel: EventHandler<()>,
+) -> Element {
+    let mut text_input = use_signal(|| text);
+    let mut message = use_signal(|| ("red", None));
+    let mut field_errors = use_signal(ValidationErrors::default);
+    let locale = use_locale();
+
+    let handle_save = move |_| async move {
+        let params = UpdateBlogPostParams {
+            text: text_input().clone(),
+        };
+
+        if let Err(errors) = params.validate() {
+            message.set(("red", None));
+            field_errors.set(errors);
+            return;
+        }
+        field_errors.set(ValidationErrors::default());
+
+        message.set(("yellow", Some(Cow::from(t!(locale, "saving")))));
+        match update_blog_post(post_id, params).await {
+            Ok(post) => {
+                info!("Updated post: {:?}", post);
+                onsave(post);
+            }
+            Err(err) => {
+                error!("Failed to update post with id {}: {:?}", post_id, err);
+                show_error(err, locale, message, field_errors);
+            }
+        }
+    };
+
+    rsx! {
+        form { class: "blog-post-edit-form",
+            textarea {
+                value: "{text_input}",
+                placeholder: t!(locale, "edit-placeholder"),
+                oninput: move |evt| text_input.set(evt.value()),
+            }
+            FieldError { errors: field_errors, field: Field::Text }
+            div { class: "blog-post-actions",
+                button {
+                    r#type: "submit",
+                    prevent_default: "onclick",
+                    onclick: handle_save,
+                    {t!(locale, "save")}
+                }
+                button {
+                    r#type: "button",
+                    onclick: move |_| oncancel(()),
+                    {t!(locale, "cancel")}
+                }
+            }
+            if let Some(error_msg) = message().1 {
+                div { class: "error",
+                    color: "{message().0}",
+                    "{error_msg}"
+                }
+            }
+        }
+    }
+}
+
+/// Where the server pushes the changes to the blog post feed as server-sent events.
+#[cfg(feature = "web")]
+const POST_EVENTS_URL: &str = "/events/posts";
+
+/// How far below the bottom of the screen elements count as visible for `observe_visibility`.
+#[cfg(feature = "web")]
+const VISIBILITY_MARGIN: &str = "0px 0px 400px 0px";
+
+/// The width and height in pixels of the preview of a cropped avatar.
+const AVATAR_CROP_PREVIEW_SIZE: f64 = 100.0;
+
+/// The quality from 0 to 1 at which downscaled images are encoded as JPEG or WebP before they are uploaded.
+#[cfg(feature = "web")]
+const DOWNSCALED_QUALITY: f64 = 0.9;
+
+/// Share the permalink of the blog post at the path with the Web Share API where the browser supports it,
+/// or copy it to the clipboard otherwise.
+/// Returns the ID of the message confirming the share, or `None` if the user cancelled sharing.
+async fn share_post(path: String) -> Result<Option<&'static str>, String> {
+    let mut share = eval(&SHARE_SCRIPT.replace("{path}", &path));
+    let outcome = share.recv().await.map_err(|err| format!("{err:?}"))?;
+    match outcome.as_str() {
+        Some("shared") => Ok(Some("share-shared")),
+        Some("copied") => Ok(Some("share-copied")),
+        Some("cancelled") => Ok(None),
+        _ => Err(format!("Sharing failed: {outcome}")),
+    }
+}
+
+/// Shares or copies the URL of the path in `{path}`, and sends back how it went.
+const SHARE_SCRIPT: &str = r#"
+    const url = window.location.origin + "{path}";
+    try {
+        if (navigator.share) {
+            try {
+                await navigator.share({ url });
+                dioxus.send("shared");
+                return;
+            } catch (err) {
+                if (err.name === "AbortError") {
+                    dioxus.send("cancelled");
+                    return;
+                }
+            }
+        }
+        await navigator.clipboard.writeText(url);
+        dioxus.send("copied");
+    } catch (err) {
+        dioxus.send(String(err));
+    }
+"#;
+
+/// Whether images and avatars can be chosen from files, which are uploaded by the browser.
+/// The desktop app has no browser to upload them with, so it only takes avatars by URL.
+const IMAGE_UPLOADS: bool = !cfg!(feature = "desktop");
+
+/// Upload the image to `/uploads` as multipart form data.
+/// While the upload is in flight, `progress` holds the fraction of the image which was sent so far,
+/// and `abort` holds a function which aborts the upload. Both are reset to `None` once it is over.
+/// Returns the token which refers to the upload when creating the blog post,
+/// or the message of the server if the upload was rejected, or a message in the locale if it failed otherwise.
+#[cfg(feature = "web")]
+async fn upload_image(
+    bytes: Vec<u8>,
+    mut progress: Signal<Option<f64>>,
+    mut abort: Signal<Option<Rc<dyn Fn()>>>,
+    locale: Locale,
+) -> Result<UploadToken, String> {
+    use std::cell::Cell;
+    use wasm_bindgen::closure::Closure;
+    use wasm_bindgen::{JsCast, JsValue};
+
+    let failed = |err: JsValue| {
+        error!("Failed to send upload request: {:?}", err);
+        t!(locale, "upload-failed")
+    };
+    let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(bytes.as_slice()));
+    let blob = web_sys::Blob::new_with_u8_array_sequence(&parts).map_err(failed)?;
+    let form = web_sys::FormData::new().map_err(failed)?;
+    form.append_with_blob("image", &blob).map_err(failed)?;
+    let request = web_sys::XmlHttpRequest::new().map_err(failed)?;
+    request.open("POST", "/uploads").map_err(failed)?;
+    // Resolves once the request is over, whether it succeeded, failed or was aborted
+    let finished = js_sys::Promise::new(&mut |resolve, _reject| request.set_onloadend(Some(&resolve)));
+    // The closure must stay alive until the request is over, since the browser keeps calling it until then
+    let on_progress = Closure::<dyn FnMut(web_sys::ProgressEvent)>::new(move |event: web_sys::ProgressEvent| {
+        if event.length_computable() && event.total() > 0.0 {
+            progress.set(Some(event.loaded() / event.total()));
+        }
+    });
+    request.upload().map_err(failed)?.set_onprogress(Some(on_progress.as_ref().unchecked_ref()));
+    let aborted = Rc::new(Cell::new(false));
+    abort.set(Some(Rc::new({
+        let request = request.clone();
+        let aborted = aborted.clone();
+        move || {
+            aborted.set(true);
+            let _ = request.abort();
+        }
+    })));
+    progress.set(Some(0.0));
+    let sent = request.send_with_opt_form_data(Some(&form));
+    if sent.is_ok() {
+        let _ = wasm_bindgen_futures::JsFuture::from(finished).await;
+    }
+    progress.set(None);
+    abort.set(None);
+    drop(on_progress);
+    sent.map_err(failed)?;
+    if aborted.get() {
+        return Err(t!(locale, "upload-cancelled"));
+    }
+    let body = request.response_text().ok().flatten().unwrap_or_default();
+    match request.status().unwrap_or(0) {
+        201 => Ok(body),
+        // The request did not reach the server at all
+        0 => Err(t!(locale, "upload-offline")),
+        _ if body.is_empty() => Err(t!(locale, "upload-failed")),
+        _ => Err(body),
+    }
+}
+
+/// Images are only uploaded from the browser: event handlers never run while rendering on the server,
+/// and the desktop app leaves out the controls for choosing images, see `IMAGE_UPLOADS`.
+#[cfg(not(feature = "web"))]
+async fn upload_image(
+    _bytes: Vec<u8>,
+    _progress: Signal<Option<f64>>,
+    _abort: Signal<Option<Rc<dyn Fn()>>>,
+    _locale: Locale,
+) -> Result<UploadToken, String> {
+    Err("Images can only be uploaded from the browser".to_string())
+}
+
+/// Downscale the image so that its longest side is at most `max_dimension` pixels by drawing it onto a canvas,
+/// so that large photos are not uploaded in full only to be processed by the server.
+/// Returns the bytes unchanged if the image is small enough, is a GIF whose animation the canvas would flatten,
+/// fails to be downscaled, or would not get any smaller.
+#[cfg(feature = "web")]
+async fn downscale_image(bytes: Vec<u8>, max_dimension: u32) -> Vec<u8> {
+    if bytes.starts_with(b"GIF8") {
+        return bytes;
+    }
+    match draw_downscaled(&bytes, max_dimension).await {
+        Ok(Some(downscaled)) if downscaled.len() < bytes.len() => {
+            debug!("Downscaled image from {}B to {}B", bytes.len(), downscaled.len());
+            downscaled
+        }
+        Ok(_) => bytes,
+        Err(err) => {
+            error!("Failed to downscale image: {:?}", err);
+            bytes
+        }
+    }
+}
+
+/// Decode the image with the browser.
+/// Browsers apply the EXIF orientation while decoding, just like the server does.
+#[cfg(feature = "web")]
+async fn decode_bitmap(bytes: &[u8]) -> Result<web_sys::ImageBitmap, wasm_bindgen::JsValue> {
+    use wasm_bindgen::JsCast;
+    use wasm_bindgen_futures::JsFuture;
+
+    let window = web_sys::window().ok_or("no window")?;
+    let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(bytes));
+    let blob = web_sys::Blob::new_with_u8_array_sequence(&parts)?;
+    JsFuture::from(window.create_image_bitmap_with_blob(&blob)?).await?.dyn_into()
+}
+
+/// Returns the width and height in pixels of the image, or `None` if the browser fails to decode it.
+#[cfg(feature = "web")]
+async fn image_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    match decode_bitmap(bytes).await {
+        Ok(bitmap) => {
+            let dimensions = (bitmap.width(), bitmap.height());
+            bitmap.close();
+            Some(dimensions)
+        }
+        Err(err) => {
+            error!("Failed to decode image: {:?}", err);
+            None
+        }
+    }
+}
+
+/// Images are only decoded in the browser.
+#[cfg(not(feature = "web"))]
+async fn image_dimensions(_bytes: &[u8]) -> Option<(u32, u32)> {
+    None
+}
+
+/// Draw the image onto a canvas whose longest side is `max_dimension` pixels and encode the canvas.
+/// Returns `None` if the image is small enough already.
+#[cfg(feature = "web")]
+async fn draw_downscaled(bytes: &[u8], max_dimension: u32) -> Result<Option<Vec<u8>>, wasm_bindgen::JsValue> {
+    use wasm_bindgen::{JsCast, JsValue};
+    use wasm_bindgen_futures::JsFuture;
+
+    let window = web_sys::window().ok_or("no window")?;
+    let bitmap = decode_bitmap(bytes).await?;
+    let longest_side = bitmap.width().max(bitmap.height());
+    if longest_side <= max_dimension {
+        bitmap.close();
+        return Ok(None);
+    }
+    let scale = f64::from(max_dimension) / f64::from(longest_side);
+    let width = (f64::from(bitmap.width()) * scale).round().max(1.0);
+    let height = (f64::from(bitmap.height()) * scale).round().max(1.0);
+    let canvas: web_sys::HtmlCanvasElement = window.document().ok_or("no document")?.create_element("canvas")?.dyn_into()?;
+    canvas.set_width(width as u32);
+    canvas.set_height(height as u32);
+    let context: web_sys::CanvasRenderingContext2d = canvas.get_context("2d")?.ok_or("no 2d context")?.dyn_into()?;
+    context.draw_image_with_image_bitmap_and_dw_and_dh(&bitmap, 0.0, 0.0, width, height)?;
+    bitmap.close();
+    // PNG and WebP may be transparent, which JPEG cannot represent
+    let mime = match bytes {
+        [0x89, b'P', b'N', b'G', ..] => "image/png",
+        [b'R', b'I', b'F', b'F', ..] => "image/webp",
+        _ => "image/jpeg",
+    };
+    let encoded = js_sys::Promise::new(&mut |resolve, reject| {
+        let quality = JsValue::from_f64(DOWNSCALED_QUALITY);
+        if let Err(err) = canvas.to_blob_with_type_and_encoder_options(&resolve, mime, &quality) {
+            let _ = reject.call1(&JsValue::NULL, &err);
+        }
+    });
+    // The canvas calls back with `null` if it fails to encode the image
+    let encoded: web_sys::Blob = JsFuture::from(encoded).await?.dyn_into()?;
+    let buffer = JsFuture::from(encoded.array_buffer()).await?;
+    Ok(Some(js_sys::Uint8Array::new(&buffer).to_vec()))
+}
+
+/// Images are only downscaled from event handlers, which never run while rendering on the server.
+#[cfg(not(feature = "web"))]
+async fn downscale_image(bytes: Vec<u8>, _max_dimension: u32) -> Vec<u8> {
+    bytes
+}
+
+/// The key in local storage under which the theme chosen by the user is kept.
+#[cfg(feature = "web")]
+const THEME_STORAGE_KEY: &str = "theme";
+
+/// The key in local storage under which the locale chosen by the user is kept.
+#[cfg(feature = "web")]
+const LOCALE_STORAGE_KEY: &str = "locale";
+
+/// The key in local storage under which the blog post being written is kept.
+#[cfg(feature = "web")]
+const DRAFT_STORAGE_KEY: &str = "post-draft";
+
+/// Returns the preference kept in local storage under the key, if the user made a choice before.
+#[cfg(feature = "web")]
+fn load_preference(key: &str) -> Option<String> {
+    let storage = web_sys::window()?.local_storage().ok().flatten()?;
+    storage.get_item(key).ok().flatten()
+}
+
+/// Keep the preference chosen by the user in local storage under the key, so that it is used on their next visits.
+#[cfg(feature = "web")]
+fn store_preference(key: &str, value: &str) {
+    let storage = web_sys::window().and_then(|window| window.local_storage().ok().flatten());
+    if let Some(storage) = storage {
+        if let Err(err) = storage.set_item(key, value) {
+            error!("Failed to store {} as the {} preference: {:?}", value, key, err);
+        }
+    }
+}
+
+/// Returns the blog post which the user was writing when they last left the page, if any.
+#[cfg(feature = "web")]
+fn load_draft() -> Option<PostDraft> {
+    let draft = load_preference(DRAFT_STORAGE_KEY)?;
+    serde_json::from_str(&draft)
+        .inspect_err(|err| error!("Failed to parse the stored draft: {}", err))
+        .ok()
+}
+
+/// The draft is only known in the browser.
+#[cfg(not(feature = "web"))]
+fn load_draft() -> Option<PostDraft> {
+    None
+}
+
+/// Keep the blog post being written in local storage, or delete it from there once nothing is written anymore.
+#[cfg(feature = "web")]
+fn store_draft(draft: &PostDraft) {
+    let Some(storage) = web_sys::window().and_then(|window| window.local_storage().ok().flatten()) else {
+        return;
+    };
+    // Nothing was written yet, so there is no draft to keep
+    let stored = if *draft == PostDraft::default() {
+        storage.remove_item(DRAFT_STORAGE_KEY)
+    } else {
+        let json = serde_json::to_string(draft).expect("a draft should always be serializable");
+        storage.set_item(DRAFT_STORAGE_KEY, &json)
+    };
+    if let Err(err) = stored {
+        error!("Failed to store the draft: {:?}", err);
+    }
+}
+
+/// Effects never run while rendering on the server.
+#[cfg(not(feature = "web"))]
+fn store_draft(_draft: &PostDraft) {}
+
+/// Set the attribute of the root element of the page, e.g. `lang`.
+#[cfg(feature = "web")]
+fn set_root_attribute(name: &str, value: &str) {
+    let root = web_sys::window()
+        .and_then(|window| window.document())
+        .and_then(|document| document.document_element());
+    if let Some(root) = root {
+        if let Err(err) = root.set_attribute(name, value) {
+            error!("Failed to set the {} attribute to {}: {:?}", name, value, err);
+        }
+    }
+}
+
+/// Returns the theme the user chose before, or the one preferred by their system if they never chose one.
+#[cfg(feature = "web")]
+fn initial_theme() -> Option<Theme> {
+    let theme = match load_preference(THEME_STORAGE_KEY).as_deref() {
+        Some("dark") => Theme::Dark,
+        Some("light") => Theme::Light,
+        _ => {
+            let prefers_dark = web_sys::window()?
+                .match_media("(prefers-color-scheme: dark)")
+                .ok()
+                .flatten()
+                .is_some_and(|query| query.matches());
+            if prefers_dark { Theme::Dark } else { Theme::Light }
+        }
+    };
+    Some(theme)
+}
+
+/// The theme is only known in the browser.
+#[cfg(not(feature = "web"))]
+fn initial_theme() -> Option<Theme> {
+    None
+}
+
+/// Set the `data-theme` attribute of the root element, which the stylesheet takes the colors of the theme from.
+#[cfg(feature = "web")]
+fn apply_theme(theme: Theme) {
+    set_root_attribute("data-theme", theme.name());
+}
+
+/// Effects never run while rendering on the server.
+#[cfg(not(feature = "web"))]
+fn apply_theme(_theme: Theme) {}
+
+/// Remember the theme chosen by the user, so that it is used on their next visits.
+#[cfg(feature = "web")]
+fn store_theme(theme: Theme) {
+    store_preference(THEME_STORAGE_KEY, theme.name());
+}
+
+/// Event handlers never run while rendering on the server.
+#[cfg(not(feature = "web"))]
+fn store_theme(_theme: Theme) {}
+
+/// Returns the locale the user chose before, or the one of the language of their browser if they never chose one,
+/// or `None` if that language is not supported.
+#[cfg(feature = "web")]
+fn initial_locale() -> Option<Locale> {
+    match load_preference(LOCALE_STORAGE_KEY).and_then(|code| Locale::from_code(&code)) {
+        Some(locale) => Some(locale),
+        None => Locale::from_code(&web_sys::window()?.navigator().language()?),
+    }
+}
+
+/// The locale is only known in the browser.
+#[cfg(not(feature = "web"))]
+fn initial_locale() -> Option<Locale> {
+    None
+}
+
+/// Set the `lang` attribute of the root element, so that the browser, e.g. its screen reader, knows the language.
+#[cfg(feature = "web")]
+fn apply_locale(locale: Locale) {
+    set_root_attribute("lang", locale.code());
+}
+
+/// Effects never run while rendering on the server.
+#[cfg(not(feature = "web"))]
+fn apply_locale(_locale: Locale) {}
+
+/// Remember the locale chosen by the user, so that it is used on their next visits.
+#[cfg(feature = "web")]
+fn store_locale(locale: Locale) {
+    store_preference(LOCALE_STORAGE_KEY, locale.code());
+}
+
+/// Event handlers never run while rendering on the server.
+#[cfg(not(feature = "web"))]
+fn store_locale(_locale: Locale) {}
+
+/// The URL of the service worker, which must be served from the root to control every page.
+#[cfg(feature = "web")]
+const SERVICE_WORKER_URL: &str = "/sw.js";
+
+/// The message which asks the service worker to forget what it cached, as handled in `assets/sw.js`.
+#[cfg(feature = "web")]
+const SERVICE_WORKER_LOGOUT_MESSAGE: &str = "logout";
+
+/// Register the service worker, which caches the app and the first page of the feed for reading offline.
+/// Browsers without service workers, or pages not served over HTTPS, are left without.
+#[cfg(feature = "web")]
+async fn register_service_worker() {
+    let Some(window) = web_sys::window() else {
+        return;
+    };
+    let navigator = window.navigator();
+    if !js_sys::Reflect::has(&navigator, &"serviceWorker".into()).unwrap_or(false) {
+        info!("Service workers are not supported, so the app is not available offline");
+        return;
+    }
+    let registration = navigator.service_worker().register(SERVICE_WORKER_URL);
+    if let Err(err) = wasm_bindgen_futures::JsFuture::from(registration).await {
+        error!("Failed to register the service worker: {:?}", err);
+    }
+}
+
+/// Service workers are only registered in the browser.
+#[cfg(not(feature = "web"))]
+async fn register_service_worker() {}
+
+/// Ask the service worker to forget what it cached for reading offline, once the user logged out.
+/// Does nothing if no service worker controls the page.
+#[cfg(feature = "web")]
+fn forget_offline_data() {
+    let Some(window) = web_sys::window() else {
+        return;
+    };
+    let navigator = window.navigator();
+    if !js_sys::Reflect::has(&navigator, &"serviceWorker".into()).unwrap_or(false) {
+        return;
+    }
+    if let Some(controller) = navigator.service_worker().controller() {
+        if let Err(err) = controller.post_message(&SERVICE_WORKER_LOGOUT_MESSAGE.into()) {
+            error!("Failed to clear the offline cache: {:?}", err);
+        }
+    }
+}
+
+/// Event handlers never run while rendering on the server.
+#[cfg(not(feature = "web"))]
+fn forget_offline_data() {}
+
+/// How many nonces are tried between giving the browser a chance to handle events while solving a challenge.
+/// Trying them takes a few milliseconds, so that the page stays responsive while the challenge is solved.
+const CHALLENGE_BATCH: u64 = 5_000;
+
+/// Solve the challenge in batches, yielding to the browser in between, instead of blocking its only thread.
+async fn solve_challenge(challenge: Challenge) -> ChallengeSolution {
+    let mut start = 0;
+    loop {
+        if let Some(nonce) = challenge.solve_batch(start, CHALLENGE_BATCH) {
+            return ChallengeSolution { token: challenge.token, nonce };
+        }
+        start += CHALLENGE_BATCH;
+        // Timeouts only exist in the browser, and the desktop app may as well solve the challenge in one go
+        if cfg!(feature = "web") {
+            sleep(std::time::Duration::ZERO).await;
+        }
+    }
+}
+
+/// Wait for the duration using a timeout of the browser.
+/// Never finishes if the timeout cannot be set.
+#[cfg(feature = "web")]
+async fn sleep(duration: std::time::Duration) {
+    let elapsed = js_sys::Promise::new(&mut |resolve, _reject| {
+        let timeout = web_sys::window().ok_or("no window".into()).and_then(|window| {
+            window.set_timeout_with_callback_and_timeout_and_arguments_0(&resolve, duration.as_millis() as i32)
+        });
+        if let Err(err) = timeout {
+            error!("Failed to set a timeout: {:?}", err);
+        }
+    });
+    let _ = wasm_bindgen_futures::JsFuture::from(elapsed).await;
+}
+
+/// Futures never make progress while rendering on the server, so there is nothing to wait for.
+#[cfg(not(feature = "web"))]
+async fn sleep(_duration: std::time::Duration) {
+    std::future::pending().await
+}
+
+/// Returns a `data:` URL of the image, so that a picked file can be previewed before it is uploaded.
+/// The MIME type is detected from the magic bytes of the accepted formats.
+fn image_data_url(bytes: &[u8]) -> String {
+    use base64::Engine;
+
+    let mime = match bytes {
+        [0x89, b'P', b'N', b'G', ..] => "image/png",
+        [0xFF, 0xD8, 0xFF, ..] => "image/jpeg",
+        [b'G', b'I', b'F', b'8', ..] => "image/gif",
+        _ => "image/webp",
+    };
+    format!("data:{mime};base64,{}", base64::engine::general_purpose::STANDARD.encode(bytes))
+}
+
+/// Returns the message of an error returned by a server function, for display in a form.
+/// Errors whose reason is given by the server, e.g. why an image was rejected, are shown as sent,
+/// while all others are translated from their code.
+fn error_message(err: impl Into<AppError>, locale: Locale) -> String {
+    let err = err.into();
+    match err {
+        AppError::Validation(_)
+        | AppError::ContentRejected(_)
+        | AppError::ImageRejected(_)
+        | AppError::ImageTooLarge(_) => err.to_string(),
+        AppError::NotLoggedIn => t!(locale, "error-not-logged-in"),
+        AppError::NotAdmin => t!(locale, "error-not-admin"),
+        AppError::PostNotFound => t!(locale, "error-post-not-found"),
+        AppError::NotPostOwner => t!(locale, "error-not-post-owner"),
+        AppError::InvalidCredentials => t!(locale, "error-invalid-credentials"),
+        AppError::InvalidApiKey => t!(locale, "error-invalid-api-key"),
+        AppError::AlreadyReported => t!(locale, "error-already-reported"),
+        AppError::RateLimited { retry_after } => t!(locale, "error-rate-limited", seconds = retry_after),
+        AppError::InvalidChallenge => t!(locale, "error-invalid-challenge"),
+        AppError::Unavailable => t!(locale, "error-unavailable"),
+        AppError::TimedOut => t!(locale, "error-timed-out"),
+        AppError::Internal => t!(locale, "error-internal"),
+        AppError::Network => t!(locale, "error-network"),
+    }
+}
+
+/// Show the error returned by a server function next to the offending inputs if the form failed validation,
+/// or else as the message of the whole form.
+fn show_error(
+    err: ServerFnError,
+    locale: Locale,
+    mut message: Signal<(&'static str, Option<Cow<'static, str>>)>,
+    mut field_errors: Signal<ValidationErrors>,
+) {
+    match AppError::from(err) {
+        AppError::Validation(errors) => {
+            message.set(("red", None));
+            field_errors.set(errors);
+        }
+        err => message.set(("red", Some(Cow::from(error_message(err, locale))))),
+    }
+}
+
+/// The error of a single input of a form, shown right below it.
+#[component]
+fn FieldError(errors: Signal<ValidationErrors>, field: Field) -> Element {
+    let errors = errors.read();
+    rsx! {
+        if let Some(error) = errors.get(field) {
+            p { class: "field-error", "{error}" }
         }
     }
 }
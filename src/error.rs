@@ -0,0 +1,339 @@
+//! The errors which server functions return to the client.
+//! Internal errors, e.g. of the database or image store, are logged on the server and only reach the client
+//! as a generic error, so that their details, such as SQL statements, are never shown to users.
+//! Every error has a stable code, which the client matches on to react to it, e.g. by asking the user to log in.
+
+use crate::validation::ValidationErrors;
+use dioxus::prelude::ServerFnError;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// An error returned by a server function, as seen by the client.
+/// This deliberately does not implement `std::error::Error`, which would make `?` turn it into a plain
+/// `ServerFnError` with only its message, so that it is converted with its code instead.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AppError {
+    /// The params failed validation, with the errors of the offending fields.
+    Validation(ValidationErrors),
+    /// Nobody is logged in, or the session expired.
+    NotLoggedIn,
+    /// The logged-in user is not an administrator.
+    NotAdmin,
+    /// The blog post does not exist, or is hidden or in the trash.
+    PostNotFound,
+    /// The blog post was written by someone else.
+    NotPostOwner,
+    /// The username or password is wrong.
+    InvalidCredentials,
+    /// The API key sent to the REST API does not exist or was revoked.
+    InvalidApiKey,
+    /// The logged-in user already reported the blog post.
+    AlreadyReported,
+    /// The client exceeded the rate limit and may retry after this many seconds.
+    RateLimited { retry_after: u64 },
+    /// The proof-of-work challenge is invalid, expired or was already used.
+    InvalidChallenge,
+    /// The content filter rejected the text, for the given reason.
+    ContentRejected(String),
+    /// The image or avatar was rejected, for the given reason.
+    ImageRejected(String),
+    /// The image or avatar is larger than allowed, as described by the message.
+    ImageTooLarge(String),
+    /// The server is too busy to handle the request right now, e.g. because the database is locked.
+    Unavailable,
+    /// The server did not handle the request in time, e.g. because an upload was too slow.
+    TimedOut,
+    /// The server failed for a reason which is not shown to the client.
+    Internal,
+    /// The request did not reach the server or the response could not be read.
+    /// This is only ever created on the client.
+    Network,
+}
+
+impl AppError {
+    /// The stable code of the error, which is sent to the client, e.g. `not_logged_in`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::Validation(_) => "validation",
+            AppError::NotLoggedIn => "not_logged_in",
+            AppError::NotAdmin => "not_admin",
+            AppError::PostNotFound => "post_not_found",
+            AppError::NotPostOwner => "not_post_owner",
+            AppError::InvalidCredentials => "invalid_credentials",
+            AppError::InvalidApiKey => "invalid_api_key",
+            AppError::AlreadyReported => "already_reported",
+            AppError::RateLimited { .. } => "rate_limited",
+            AppError::InvalidChallenge => "invalid_challenge",
+            AppError::ContentRejected(_) => "content_rejected",
+            AppError::ImageRejected(_) => "image_rejected",
+            AppError::ImageTooLarge(_) => "image_too_large",
+            AppError::Unavailable => "unavailable",
+            AppError::TimedOut => "timed_out",
+            AppError::Internal => "internal",
+            AppError::Network => "network",
+        }
+    }
+
+    /// The details which are sent along with the code, if any.
+    fn payload(&self) -> Option<String> {
+        match self {
+            AppError::Validation(errors) => Some(errors.to_string()),
+            AppError::RateLimited { retry_after } => Some(retry_after.to_string()),
+            AppError::ContentRejected(reason) | AppError::ImageRejected(reason) | AppError::ImageTooLarge(reason) => {
+                Some(reason.clone())
+            }
+            _ => None,
+        }
+    }
+
+    /// Encodes the error as sent to the client: the code on the first line, followed by the payload, if any.
+    fn encode(&self) -> String {
+        match self.payload() {
+            Some(payload) => format!("{}\n{}", self.code(), payload),
+            None => self.code().to_string(),
+        }
+    }
+}
+
+/// The message shown to users who see the error, in English.
+/// The client translates the errors whose message does not come from the server.
+impl Display for AppError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AppError::Validation(errors) => {
+                write!(f, "{}", errors.messages().collect::<Vec<_>>().join(". "))
+            }
+            AppError::NotLoggedIn => write!(f, "You must be logged in"),
+            AppError::NotAdmin => write!(f, "Only administrators may do that"),
+            AppError::PostNotFound => write!(f, "This post does not exist"),
+            AppError::NotPostOwner => write!(f, "You can only change your own posts"),
+            AppError::InvalidCredentials => write!(f, "Invalid username or password"),
+            AppError::InvalidApiKey => write!(f, "The API key is invalid or was revoked"),
+            AppError::AlreadyReported => write!(f, "You already reported this post"),
+            AppError::RateLimited { retry_after } => {
+                write!(f, "You're doing that too often, please try again in {retry_after} seconds")
+            }
+            AppError::InvalidChallenge => write!(f, "The anti-spam check failed, please try again"),
+            AppError::ContentRejected(reason) | AppError::ImageRejected(reason) | AppError::ImageTooLarge(reason) => {
+                write!(f, "{reason}")
+            }
+            AppError::Unavailable => write!(f, "The server is busy, please try again in a moment"),
+            AppError::TimedOut => write!(f, "The request took too long, please try again"),
+            AppError::Internal => write!(f, "Something went wrong on the server, please try again later"),
+            AppError::Network => write!(f, "The server could not be reached, please check your connection"),
+        }
+    }
+}
+
+/// Parses an error encoded by the server.
+/// Fails if the code is unknown or its payload is missing or malformed.
+impl FromStr for AppError {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        let (code, payload) = match s.split_once('\n') {
+            Some((code, payload)) => (code, Some(payload)),
+            None => (s, None),
+        };
+        let error = match (code, payload) {
+            ("validation", Some(errors)) => AppError::Validation(errors.parse()?),
+            ("not_logged_in", None) => AppError::NotLoggedIn,
+            ("not_admin", None) => AppError::NotAdmin,
+            ("post_not_found", None) => AppError::PostNotFound,
+            ("not_post_owner", None) => AppError::NotPostOwner,
+            ("invalid_credentials", None) => AppError::InvalidCredentials,
+            ("invalid_api_key", None) => AppError::InvalidApiKey,
+            ("already_reported", None) => AppError::AlreadyReported,
+            ("rate_limited", Some(seconds)) => AppError::RateLimited { retry_after: seconds.parse().map_err(|_| ())? },
+            ("invalid_challenge", None) => AppError::InvalidChallenge,
+            ("content_rejected", Some(reason)) => AppError::ContentRejected(reason.to_string()),
+            ("image_rejected", Some(reason)) => AppError::ImageRejected(reason.to_string()),
+            ("image_too_large", Some(reason)) => AppError::ImageTooLarge(reason.to_string()),
+            ("unavailable", None) => AppError::Unavailable,
+            ("timed_out", None) => AppError::TimedOut,
+            ("internal", None) => AppError::Internal,
+            _ => return Err(()),
+        };
+        Ok(error)
+    }
+}
+
+impl From<ValidationErrors> for AppError {
+    fn from(errors: ValidationErrors) -> Self {
+        AppError::Validation(errors)
+    }
+}
+
+/// Server functions return the error as a `ServerFnError`, which carries it to the client encoded with its code.
+impl From<AppError> for ServerFnError {
+    fn from(error: AppError) -> Self {
+        ServerFnError::ServerError(error.encode())
+    }
+}
+
+/// Recovers the error returned by a server function on the client.
+/// Errors which did not come from a server function, e.g. because the request failed, are network errors.
+impl From<ServerFnError> for AppError {
+    fn from(error: ServerFnError) -> Self {
+        match error {
+            ServerFnError::ServerError(message) => message.parse().unwrap_or(AppError::Internal),
+            _ => AppError::Network,
+        }
+    }
+}
+
+/// Conversions of the internal errors of the server, which are logged and hidden from the client.
+#[cfg(feature = "server")]
+mod internal {
+    use super::AppError;
+    use crate::server::challenge::ChallengeError;
+    use crate::server::gc::GcError;
+    use crate::server::images::AppImageError;
+    use crate::server::persistence::database::DatabaseError;
+    use crate::server::storage::StorageError;
+    use crate::server::uploads::UploadError;
+    use crate::server::CurrentUserRejection;
+    use axum::response::{IntoResponse, Response};
+    use http::header::RETRY_AFTER;
+    use http::StatusCode;
+    use std::convert::Infallible;
+    use tracing::{error, warn};
+
+    impl AppError {
+        /// The HTTP status of responses which fail with the error.
+        pub fn status(&self) -> StatusCode {
+            match self {
+                AppError::Validation(_) | AppError::ContentRejected(_) | AppError::ImageRejected(_) => {
+                    StatusCode::UNPROCESSABLE_ENTITY
+                }
+                AppError::NotLoggedIn | AppError::InvalidCredentials | AppError::InvalidApiKey => {
+                    StatusCode::UNAUTHORIZED
+                }
+                AppError::NotAdmin | AppError::NotPostOwner => StatusCode::FORBIDDEN,
+                AppError::PostNotFound => StatusCode::NOT_FOUND,
+                AppError::AlreadyReported => StatusCode::CONFLICT,
+                AppError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+                AppError::InvalidChallenge => StatusCode::BAD_REQUEST,
+                AppError::ImageTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+                AppError::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+                AppError::TimedOut => StatusCode::REQUEST_TIMEOUT,
+                AppError::Internal | AppError::Network => StatusCode::INTERNAL_SERVER_ERROR,
+            }
+        }
+    }
+
+    /// Plain HTTP routes, e.g. uploads, respond with the status of the error and its message, which clients show
+    /// as is. Rate limited clients are told when to retry in the `Retry-After` header.
+    impl IntoResponse for AppError {
+        fn into_response(self) -> Response {
+            let mut response = (self.status(), self.to_string()).into_response();
+            if let AppError::RateLimited { retry_after } = self {
+                response.headers_mut().insert(RETRY_AFTER, retry_after.into());
+            }
+            response
+        }
+    }
+
+    impl From<Infallible> for AppError {
+        fn from(never: Infallible) -> Self {
+            match never {}
+        }
+    }
+
+    impl From<DatabaseError> for AppError {
+        fn from(err: DatabaseError) -> Self {
+            if err.is_transient() {
+                warn!("Database is unavailable: {}", err);
+                return AppError::Unavailable;
+            }
+            error!("Database error: {}", err);
+            AppError::Internal
+        }
+    }
+
+    impl From<StorageError> for AppError {
+        fn from(err: StorageError) -> Self {
+            error!("Image store error: {}", err);
+            AppError::Internal
+        }
+    }
+
+    impl From<GcError> for AppError {
+        fn from(err: GcError) -> Self {
+            match err {
+                GcError::Database(err) => err.into(),
+                GcError::Storage(err) => err.into(),
+            }
+        }
+    }
+
+    /// Errors caused by the image itself are shown to the user, while failures of the server are not.
+    impl From<AppImageError> for AppError {
+        fn from(err: AppImageError) -> Self {
+            match err {
+                AppImageError::Download(err) => {
+                    warn!("Failed to download avatar: {}", err);
+                    AppError::ImageRejected("The avatar could not be downloaded".to_string())
+                }
+                AppImageError::Decode(err) => {
+                    warn!("Failed to decode image: {}", err);
+                    AppError::ImageRejected("The image could not be read".to_string())
+                }
+                AppImageError::Storage(err) => err.into(),
+                AppImageError::Io(err) => {
+                    error!("Failed to read uploaded image: {}", err);
+                    AppError::Internal
+                }
+                err @ AppImageError::TooLarge { .. } => AppError::ImageTooLarge(err.to_string()),
+                err @ (AppImageError::UnsupportedFormat
+                | AppImageError::DownloadTimeout { .. }
+                | AppImageError::NotAnImage { .. }
+                | AppImageError::Blocked(_)) => AppError::ImageRejected(err.to_string()),
+            }
+        }
+    }
+
+    impl From<UploadError> for AppError {
+        fn from(err: UploadError) -> Self {
+            match err {
+                UploadError::Multipart(_) | UploadError::Io(_) => {
+                    error!("Upload error: {}", err);
+                    AppError::Internal
+                }
+                err @ UploadError::TooLarge { .. } => AppError::ImageTooLarge(err.to_string()),
+                err @ (UploadError::Missing | UploadError::Unknown) => AppError::ImageRejected(err.to_string()),
+            }
+        }
+    }
+
+    impl From<CurrentUserRejection> for AppError {
+        fn from(rejection: CurrentUserRejection) -> Self {
+            match rejection {
+                CurrentUserRejection::NotLoggedIn => AppError::NotLoggedIn,
+                CurrentUserRejection::NotAdmin => AppError::NotAdmin,
+                CurrentUserRejection::Session => AppError::Internal,
+            }
+        }
+    }
+
+    impl From<ChallengeError> for AppError {
+        fn from(err: ChallengeError) -> Self {
+            warn!("Rejected challenge: {}", err);
+            AppError::InvalidChallenge
+        }
+    }
+
+    impl From<tower_sessions::session::Error> for AppError {
+        fn from(err: tower_sessions::session::Error) -> Self {
+            error!("Session error: {}", err);
+            AppError::Internal
+        }
+    }
+
+    impl From<argon2::password_hash::Error> for AppError {
+        fn from(err: argon2::password_hash::Error) -> Self {
+            error!("Failed to hash password: {}", err);
+            AppError::Internal
+        }
+    }
+}
@@ -0,0 +1,164 @@
+//! Translations of the user interface into the supported locales.
+//! The messages of every locale are kept in a Fluent catalog in `locales/`, which is compiled into the binary.
+//! Messages missing from a catalog fall back to English, so that a new message only has to be added to `en.ftl` at first.
+
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::{FluentArgs, FluentResource};
+use std::sync::OnceLock;
+use time::macros::format_description;
+use time::{Date, PrimitiveDateTime};
+use tracing::{error, warn};
+use unic_langid::{langid, LanguageIdentifier};
+
+/// A language which the user interface is translated into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Locale {
+    #[default]
+    English,
+    German,
+}
+
+impl Locale {
+    /// All supported locales, in the order in which they are offered to the user.
+    pub const ALL: [Locale; 2] = [Locale::English, Locale::German];
+
+    /// The language tag of the locale, e.g. `en`.
+    pub fn code(self) -> &'static str {
+        match self {
+            Locale::English => "en",
+            Locale::German => "de",
+        }
+    }
+
+    /// The name of the language in the language itself, e.g. `Deutsch`.
+    pub fn native_name(self) -> &'static str {
+        match self {
+            Locale::English => "English",
+            Locale::German => "Deutsch",
+        }
+    }
+
+    /// Returns the locale of the language tag, ignoring its region, e.g. German for `de-AT`,
+    /// or `None` if the language is not supported.
+    pub fn from_code(code: &str) -> Option<Self> {
+        let language = code.split(['-', '_']).next()?.to_ascii_lowercase();
+        Self::ALL.into_iter().find(|locale| locale.code() == language)
+    }
+
+    fn language(self) -> LanguageIdentifier {
+        match self {
+            Locale::English => langid!("en"),
+            Locale::German => langid!("de"),
+        }
+    }
+
+    fn catalog(self) -> &'static str {
+        match self {
+            Locale::English => include_str!("../locales/en.ftl"),
+            Locale::German => include_str!("../locales/de.ftl"),
+        }
+    }
+
+    /// The messages of the locale, which are parsed the first time they are needed.
+    fn bundle(self) -> &'static FluentBundle<FluentResource> {
+        static ENGLISH: OnceLock<FluentBundle<FluentResource>> = OnceLock::new();
+        static GERMAN: OnceLock<FluentBundle<FluentResource>> = OnceLock::new();
+        let bundle = match self {
+            Locale::English => &ENGLISH,
+            Locale::German => &GERMAN,
+        };
+        bundle.get_or_init(|| self.load_bundle())
+    }
+
+    /// Parse the catalog of the locale. Messages which fail to parse are skipped and logged.
+    fn load_bundle(self) -> FluentBundle<FluentResource> {
+        let resource = FluentResource::try_new(self.catalog().to_string()).unwrap_or_else(|(resource, errors)| {
+            error!("Failed to parse the {} catalog: {:?}", self.code(), errors);
+            resource
+        });
+        let mut bundle = FluentBundle::new_concurrent(vec![self.language()]);
+        // Isolation marks around arguments would end up in attributes and the titles of buttons
+        bundle.set_use_isolating(false);
+        if let Err(errors) = bundle.add_resource(resource) {
+            error!("Failed to add the {} catalog: {:?}", self.code(), errors);
+        }
+        bundle
+    }
+
+    /// Translate the message with the ID into the locale, filling in the arguments.
+    /// Falls back to the English message if the catalog of the locale lacks it, and to the ID if both lack it.
+    /// Use the `t!` macro rather than calling this directly.
+    pub fn translate(self, id: &str, args: Option<&FluentArgs>) -> String {
+        for bundle in [self.bundle(), Locale::English.bundle()] {
+            let Some(pattern) = bundle.get_message(id).and_then(|message| message.value()) else {
+                continue;
+            };
+            let mut errors = Vec::new();
+            let translated = bundle.format_pattern(pattern, args, &mut errors);
+            if !errors.is_empty() {
+                warn!("Failed to format the message {} in {}: {:?}", id, self.code(), errors);
+            }
+            return translated.into_owned();
+        }
+        error!("Missing message: {}", id);
+        id.to_string()
+    }
+
+    /// Format the date the way it is written in the locale.
+    pub fn format_date(self, date: Date) -> String {
+        let formatted = match self {
+            Locale::English => date.format(format_description!("[month]/[day]/[year]")),
+            Locale::German => date.format(format_description!("[day].[month].[year]")),
+        };
+        formatted.unwrap_or_else(|_| date.to_string())
+    }
+
+    /// Format a timestamp from the server, which is always in UTC, down to the minute the way it is written in the locale.
+    pub fn format_timestamp(self, timestamp: PrimitiveDateTime) -> String {
+        let formatted = match self {
+            Locale::English => timestamp.format(format_description!(
+                "[month]/[day]/[year] [hour repr:12 padding:none]:[minute] [period] UTC"
+            )),
+            Locale::German => timestamp.format(format_description!("[day].[month].[year], [hour]:[minute] UTC")),
+        };
+        formatted.unwrap_or_else(|_| timestamp.to_string())
+    }
+
+    /// Describe how long before `now` the timestamp was, e.g. "3 hours ago", down to the minute.
+    /// Timestamps older than a week are given as their date instead,
+    /// and timestamps after `now`, e.g. because the clock of the browser is behind, count as "just now".
+    pub fn format_relative(self, timestamp: PrimitiveDateTime, now: PrimitiveDateTime) -> String {
+        let elapsed = now - timestamp;
+        let (id, count) = if elapsed.whole_minutes() < 1 {
+            return self.translate("relative-just-now", None);
+        } else if elapsed.whole_hours() < 1 {
+            ("relative-minutes", elapsed.whole_minutes())
+        } else if elapsed.whole_days() < 1 {
+            ("relative-hours", elapsed.whole_hours())
+        } else if elapsed.whole_weeks() < 1 {
+            ("relative-days", elapsed.whole_days())
+        } else {
+            let mut args = FluentArgs::new();
+            args.set("date", self.format_date(timestamp.date()));
+            return self.translate("relative-date", Some(&args));
+        };
+        let mut args = FluentArgs::new();
+        args.set("count", count);
+        self.translate(id, Some(&args))
+    }
+}
+
+/// Translate the message with the ID into the locale, with the arguments of the message given as `name = value`,
+/// e.g. `t!(locale, "logged-in-as", username = user.username.as_str())`.
+macro_rules! t {
+    ($locale:expr, $id:expr) => {
+        $locale.translate($id, None)
+    };
+    ($locale:expr, $id:expr, $($name:ident = $value:expr),+ $(,)?) => {{
+        let mut args = fluent_bundle::FluentArgs::new();
+        $(args.set(stringify!($name), $value);)+
+        $locale.translate($id, Some(&args))
+    }};
+}
+
+pub(crate) use t;
@@ -3,9 +3,13 @@ use tracing::info;
 
 mod api;
 mod client;
+mod error;
+mod i18n;
+mod markdown;
 mod model;
 #[cfg(feature = "server")]
 mod server;
+mod validation;
 
 /// Run the webapp.
 /// This function will start the client-side webapp.
@@ -17,70 +21,395 @@ fn main() {
     dioxus::launch(Webapp);
 }
 
+/// Run the webapp in a native window, as a client of a server running elsewhere.
+/// The server is given by the `--url` argument or the `BLOG_URL` environment variable,
+/// and defaults to `http://localhost:8080`, like for the command-line client.
+/// Server functions are called on that server, and the stylesheet and images are loaded from it.
+#[cfg(all(feature = "desktop", not(feature = "server"), not(feature = "web")))]
+fn main() {
+    use dioxus::desktop::{Config, WindowBuilder};
+    use dioxus::prelude::server_fn::client::set_server_url;
+    use dioxus::prelude::LaunchBuilder;
+
+    dioxus_logger::init(tracing::Level::INFO).ok();
+    let mut args = std::env::args().skip(1);
+    let url = match (args.next().as_deref(), args.next()) {
+        (Some("--url"), Some(url)) => url,
+        _ => std::env::var("BLOG_URL").unwrap_or_else(|_| "http://localhost:8080".to_string()),
+    };
+    let url = url.trim_end_matches('/').to_string();
+    info!("Starting desktop app for {}", url);
+
+    // The server functions keep calling the server for as long as the app runs
+    set_server_url(Box::leak(url.clone().into_boxed_str()));
+    // Relative URLs, such as those of images, resolve against the server instead of the app itself
+    let head = format!(r#"<base href="{url}/"><link rel="stylesheet" href="{url}/main.css">"#);
+    let config = Config::new()
+        .with_window(WindowBuilder::new().with_title("blogposts"))
+        .with_custom_head(head);
+    LaunchBuilder::desktop().with_cfg(config).launch(Webapp);
+}
+
 /// Run the server.
-/// This function will connect to the database and start the server.
-/// The DATABASE_URL environment variable must be set.
-/// The LOG_LEVEL environment variable is optional and defaults to INFO.
-/// The HOST_ADDR environment variable is optional and defaults to "0.0.0.0:8080".
-/// The server will listen on the specified host address.
+/// This function will load the configuration, connect to the database and start the server.
+/// The configuration is read from the file given by the `--config` argument or the CONFIG_PATH
+/// environment variable, if any, with environment variables overriding its values (see `server::config`).
+/// The database URL must be set, while all other configuration values are optional.
+/// The server will listen on the configured host address, over HTTPS if a TLS certificate and key
+/// are configured, until it receives SIGINT or SIGTERM,
+/// after which it finishes in-flight requests, background jobs and pending image writes and closes the database.
+/// With the `--reencode-images` flag, it instead re-encodes the stored images into the configured format and exits.
+/// With the `--export-static <dir>` flag, it instead writes the public pages as static HTML files to the directory
+/// and exits.
 ///
 /// # Panics
 /// This function panics for the following reasons, all of which are considered fatal errors:
-/// - If the LOG_LEVEL environment variable is set but fails to parse.
-/// - If the DATABASE_URL environment variable is not set.
-/// - If the server fails to connect to the database with the specified URL.
-/// - If the server fails to open a TCP listener on the specified host address.
+/// - If the configuration fails to load, e.g. because a value is missing or fails to parse.
+/// - If the server fails to connect to the database with the configured URL.
+/// - If the configured image store cannot be set up, e.g. because S3 credentials are missing.
+/// - If the static site is exported and the blog posts cannot be loaded or the pages cannot be written.
+/// - If the configured Redis server cannot be reached, or Redis is configured without the `redis` feature.
+/// - If the HTTP client for outgoing requests cannot be built, e.g. because the proxy URL is invalid.
+/// - If the configured backup target cannot be set up, e.g. because S3 credentials are missing.
+/// - If the configured ActivityPub key cannot be read, or generated and saved.
+/// - If the server fails to open a TCP listener on the configured host or HTTP redirect address.
+/// - If the configured TLS certificate or key fails to load.
 /// - If the axum server fails to start.
 #[cfg(all(feature = "server", not(feature = "web")))]
 #[tokio::main]
 async fn main() {
-    use std::env::var as env;
+    use axum::extract::DefaultBodyLimit;
     use axum::{Extension, Router};
     use dioxus::prelude::*;
+    use server::activitypub::Federation;
+    use server::assets::{self, StaticAssets};
+    use server::audit::AuditLog;
+    use server::auth::Admins;
+    use server::avatar_cache::AvatarCache;
+    use server::backup::Backups;
+    use server::challenge::ChallengeIssuer;
+    use server::client_ip::TrustedProxies;
+    use server::config::Config;
+    use server::events::PostEvents;
+    use server::feed_cache::FeedCache;
+    use server::filter::ContentFilter;
+    use server::gc::ImageCollector;
+    use server::images::ImagePolicy;
+    use server::jobs::JobRunner;
+    use server::opengraph::LinkPreviews;
+    use server::outbound::HttpClient;
+    use server::rate_limit::RateLimiter;
+    use server::storage::ImageStorage;
+    use server::trash::{self, Trash};
+    use server::uploads::{self, Uploads};
+    use server::usage::{self, StorageUsage};
+    use server::webhooks::{self, Webhooks};
+    use server::webmention::{self, Webmentions};
     use server::{Database, ServerState};
+    use std::net::SocketAddr;
+    use tower_sessions::cookie::{time::Duration, SameSite};
+    use tokio_util::sync::CancellationToken;
+    use tower_http::compression::CompressionLayer;
+    use tower_http::limit::RequestBodyLimitLayer;
+    use tower_sessions::{Expiry, MemoryStore, SessionManagerLayer};
+    use tracing::warn;
     
     // Load environment variables
     dotenvy::dotenv().ok();
     
-    // Load the log level from the environment variable or use the default
-    let log_level = match env("LOG_LEVEL") {
-        Ok(level) => level.parse().unwrap_or_else(|err| {
-            panic!("Failed to parse log level from environment variable `LOG_LEVEL`: {}", err)
-        }),
-        Err(_) => tracing::Level::INFO,
-    };
+    // Load the configuration from the config file and the environment variables
+    let config = Config::load().unwrap_or_else(|err| panic!("Failed to load configuration: {}", err));
     
-    // If the logger fails to initialize, we'll just continue without logging.
-    dioxus_logger::init(log_level).ok();
+    // Log to stdout and export traces if configured
+    server::telemetry::init(&config.log_filter, config.otlp_endpoint.as_deref());
     info!("Starting server");
 
-    // Load the database URL from the environment variable
-    let database_url = env("DATABASE_URL")
-        .expect("DATABASE_URL environment variable must be set");
-    // Connect to the database with the specified URL
-    let database = Database::try_connect(&database_url)
+    // Connect to the database with the configured URL
+    let database_url = &config.database_url;
+    let database = Database::try_connect(database_url, config.db_pool, config.db_retry, config.sqlite)
         .await
         .inspect(|_| info!("Connected to database at {database_url}"))
         .unwrap_or_else(|err| panic!("Failed to connect to database at '{database_url}': {err}"));
 
-    // Load the host address from the environment variable or use the default
-    let host_addr = env("HOST_ADDR").unwrap_or_else(|_| "0.0.0.0:8080".to_string());
-    // Open a TCP listener on the specified host address
-    let listener = tokio::net::TcpListener::bind(&host_addr)
+    // Store images in the configured backend
+    let images = ImageStorage::from_config(&config.image_store)
+        .map(|images| images.with_cache(config.image_cache_bytes))
+        .inspect(|_| info!("Storing images in {:?}", config.image_store))
+        .unwrap_or_else(|err| panic!("Failed to set up image store: {err}"));
+    // Images whose writes were interrupted by a crash were never referenced, so what is left of them can go
+    match images.remove_partial_writes().await {
+        Ok(0) => {}
+        Ok(removed) => info!("Removed {} partially written images", removed),
+        Err(err) => warn!("Failed to remove partially written images: {}", err),
+    }
+
+    // Cache the feed in Redis for all servers, if configured
+    let feed_cache = FeedCache::connect(config.redis_url.as_deref())
+        .await
+        .inspect(|cache| if cache.is_enabled() { info!("Caching the feed in Redis") })
+        .unwrap_or_else(|err| panic!("Failed to connect to Redis: {err}"));
+
+    // Send all outgoing requests, e.g. avatar downloads, with a single client which pools connections
+    let http_client = HttpClient::new(config.https_proxy.as_deref())
+        .unwrap_or_else(|err| panic!("Failed to build HTTP client: {err}"));
+
+    // Reuse avatars downloaded from the same URL for a while instead of downloading them for every post
+    let avatar_cache = AvatarCache::new(database.clone());
+
+    // Process and store new images according to the configured rules
+    let image_policy = ImagePolicy {
+        max_bytes: config.max_image_bytes,
+        require_alt_text: config.require_alt_text,
+        gravatar_fallback: config.gravatar_fallback,
+        encoding: config.image_encoding,
+        max_dimension: config.max_image_dimension,
+        download_timeout: config.avatar_download_timeout,
+    };
+    // Decode and encode only so many images at once, so that a burst of uploads cannot starve other work
+    server::images::set_parallelism(config.image_workers);
+
+    // Export the public pages to static HTML files instead of serving, if asked to
+    if let Some(dir) = server::static_site::export_dir() {
+        server::static_site::export(&database, &dir)
+            .await
+            .unwrap_or_else(|err| panic!("Failed to export the static site to '{}': {err}", dir.display()));
+        if let Err(err) = database.close().await {
+            warn!("Failed to close database cleanly: {}", err);
+        }
+        return;
+    }
+
+    // Re-encode the stored images into the configured format instead of serving, if asked to
+    if std::env::args().any(|arg| arg == "--reencode-images") {
+        server::reencode::reencode_images(&database, &images, image_policy)
+            .await
+            .unwrap_or_else(|err| panic!("Failed to list the images to re-encode: {err}"));
+        server::storage::flush().await;
+        if let Err(err) = database.close().await {
+            warn!("Failed to close database cleanly: {}", err);
+        }
+        return;
+    }
+
+    // Keep uploaded images in their own directory until the blog post they belong to is created
+    let upload_dir = &config.upload_dir;
+    let uploads = Uploads::new(upload_dir.clone(), config.max_image_bytes)
+        .await
+        .inspect(|_| info!("Keeping uploads in {}", upload_dir.display()))
+        .unwrap_or_else(|err| panic!("Failed to create upload directory '{}': {err}", upload_dir.display()));
+
+    // Open a TCP listener on the configured host address
+    let host_addr = &config.host_addr;
+    let listener = tokio::net::TcpListener::bind(host_addr)
         .await
         .unwrap_or_else(|err| panic!("Failed to bind to address '{}': {}", host_addr, err));
     info!("Listening on {}", host_addr);
 
-    // Create the router service using the Dioxus application router
-    let router_service = Router::new()
-        .serve_dioxus_application(ServeConfig::builder().build(), || VirtualDom::new(Webapp))
+    // Sessions are kept in memory, so everyone is logged out when the server restarts.
+    // Disabling secure cookies is only useful when serving over plain HTTP, e.g. during local development.
+    let session_layer = SessionManagerLayer::new(MemoryStore::default())
+        .with_secure(config.secure_cookies)
+        .with_http_only(true)
+        .with_same_site(SameSite::Lax)
+        .with_expiry(Expiry::OnInactivity(Duration::days(7)));
+
+    // Limit how often each client may call the server functions which change data
+    let rate_limiter = RateLimiter::new(config.rate_limit_requests, config.rate_limit_window);
+
+    // Cancelled once the server is asked to shut down, which stops all listeners and background jobs
+    let shutdown = CancellationToken::new();
+    tokio::spawn({
+        let shutdown = shutdown.clone();
+        async move {
+            server::shutdown_signal().await;
+            shutdown.cancel();
+        }
+    });
+
+    // Run scheduled work in the background
+    let jobs = JobRunner::new(shutdown.clone());
+
+    // Record who changes which blog posts, for administrators to review
+    let audit = AuditLog::new(database.clone());
+
+    // Take the IP address of the client from the headers of the configured reverse proxies
+    let trusted_proxies = TrustedProxies::new(config.trusted_proxies.clone());
+
+    // Only the configured users may perform administrative tasks
+    let admins = Admins::new(config.admin_usernames.iter().cloned());
+
+    // Delete images which are not referenced by any blog post, periodically and on demand
+    let image_collector = ImageCollector::new(database.clone(), images.clone(), config.image_gc_grace_period);
+    jobs.register(image_collector.clone(), config.image_gc_interval);
+
+    // Keep deleted blog posts in the trash for the retention window before purging them
+    let trash = Trash::new(database.clone(), images.clone(), audit.clone(), config.trash_retention);
+    jobs.register(trash.clone(), trash::PURGE_INTERVAL);
+
+    // Delete uploads which were never used to create a blog post
+    jobs.register(uploads.clone(), uploads::PURGE_INTERVAL);
+
+    // Measure the size of the stored images now and then, since it takes listing all of them
+    let storage_usage = StorageUsage::new(images.clone());
+    jobs.register(storage_usage.clone(), usage::MEASURE_INTERVAL);
+    tokio::spawn({
+        let storage_usage = storage_usage.clone();
+        async move {
+            if let Err(err) = storage_usage.measure().await {
+                warn!("Failed to measure the size of the stored images: {}", err);
+            }
+        }
+    });
+
+    // Check the text of new blog posts against the configured rules
+    let content_filter = ContentFilter::new(config.content_filter.clone(), http_client.clone());
+
+    // Make bots solve a proof-of-work challenge for every blog post they create
+    let challenges = ChallengeIssuer::new(config.challenge_difficulty);
+
+    // Notify the configured webhooks of changes to blog posts, retrying failed deliveries in the background
+    let webhooks = Webhooks::new(config.webhooks.clone(), database.clone(), http_client.clone());
+    jobs.register(webhooks.clone(), webhooks::RETRY_INTERVAL);
+
+    // Back up the database and the images to the configured target, if any
+    if let Some(backup) = &config.backup {
+        let backups = Backups::new(database.clone(), images.clone(), backup)
+            .unwrap_or_else(|err| panic!("Failed to set up backups: {err}"));
+        jobs.register(backups, backup.interval);
+        info!("Backing up every {:?}", backup.interval);
+    }
+
+    // Let users of Mastodon and other servers of the fediverse follow the blog, if a key is configured
+    let federation = Federation::new(
+        config.public_url.as_deref(),
+        config.activitypub_key_path.as_deref(),
+        database.clone(),
+        http_client.clone(),
+    )
+    .await
+    .unwrap_or_else(|err| panic!("Failed to set up ActivityPub federation: {err}"));
+    if federation.is_enabled() {
+        info!("Federating the blog over ActivityPub");
+    }
+
+    // Tell the sites which posts link to about the links, and verify the links which other sites report
+    let webmentions = Webmentions::new(config.public_url.clone(), database.clone(), http_client.clone());
+
+    // Push new and deleted blog posts to open feeds, until the server is asked to shut down
+    let events = PostEvents::new(shutdown.clone());
+
+    // Build the URLs in the link previews of blog posts from the configured public URL
+    let link_previews = LinkPreviews::new(config.public_url.clone(), config.tls.is_some());
+
+    // Serve the assets of the client under names which include the hash of their content
+    let assets = StaticAssets::load(assets::ASSET_DIR)
         .await
-        // This allows us to extract the database from the request extensions
-        .layer(Extension(ServerState { database }))
-        .into_make_service();
+        .inspect(|assets| info!("Serving {} assets by their hashed names", assets.len()))
+        .unwrap_or_else(|err| {
+            warn!("Failed to hash assets, serving them by their original names: {}", err);
+            StaticAssets::default()
+        });
+
+    // Collect metrics from here on, to be served at /metrics
+    let metrics = server::metrics::install_recorder();
 
-    // Start the server
-    axum::serve(listener, router_service)
+    // Create the router using the Dioxus application router
+    let router = Router::new()
+        // Plain HTTP routes, such as images, take precedence over the Dioxus application
+        .merge(server::routes::router())
+        .merge(server::metrics::router(metrics, config.metrics_token.clone()))
+        .serve_dioxus_application(ServeConfig::builder().build(), || VirtualDom::new(Webapp))
         .await
-        .unwrap_or_else(|err| panic!("Failed to start server: {}", err));
+        // This announces the Webmention endpoint on the pages of blog posts
+        .layer(axum::middleware::from_fn(webmention::advertise_endpoint))
+        // This adds link previews to the server-rendered pages of blog posts
+        .layer(axum::middleware::from_fn(server::opengraph::inject_meta_tags))
+        // This redirects the pages of blog posts to their canonical paths, which include their slugs
+        .layer(axum::middleware::from_fn(server::permalinks::redirect_to_canonical))
+        // This links the pages to the assets by their hashed names, which browsers may cache indefinitely
+        .layer(axum::middleware::from_fn(server::assets::link_hashed_assets))
+        // This counts every request and records its latency
+        .layer(axum::middleware::from_fn(server::metrics::track_requests))
+        // This allows us to extract the database, rate limiter, image store and more from the request extensions
+        .layer(Extension(ServerState {
+            database: database.clone(),
+            rate_limiter,
+            images,
+            image_policy,
+            uploads,
+            admins,
+            image_collector,
+            jobs: jobs.clone(),
+            trash,
+            content_filter,
+            challenges,
+            events,
+            link_previews,
+            http_client,
+            avatar_cache,
+            assets,
+            feed_cache,
+            webhooks,
+            federation,
+            webmentions,
+            audit,
+            trusted_proxies: trusted_proxies.clone(),
+            storage_usage,
+        }))
+        // This allows us to extract the session and the logged-in user from the request
+        .layer(session_layer)
+        // These reject request bodies, e.g. uploaded images, larger than the configured limit with `413 Payload Too Large`.
+        // Server functions read the body themselves, which only the second layer limits
+        .layer(DefaultBodyLimit::max(config.max_upload_bytes))
+        .layer(RequestBodyLimitLayer::new(config.max_upload_bytes))
+        // This compresses pages, JSON and server function responses with brotli or gzip, as the client accepts.
+        // The default predicate skips images, which are already compressed, server-sent events and tiny responses
+        .layer(CompressionLayer::new());
+    // Time out slow requests and shed requests beyond the concurrency limit, which are still traced
+    let router = server::middleware::limit_requests(router, config.request_timeout, config.max_concurrent_requests);
+    // Assign every request an ID and log it in a span, wrapping all other layers
+    let router_service = server::middleware::trace_requests(router, trusted_proxies)
+        // This allows us to extract the IP address of the client, e.g. for rate limiting
+        .into_make_service_with_connect_info::<SocketAddr>();
+
+    // Redirect plain HTTP to HTTPS on the secondary address, if configured
+    if let Some(redirect_addr) = &config.http_redirect_addr {
+        let redirect_listener = tokio::net::TcpListener::bind(redirect_addr)
+            .await
+            .unwrap_or_else(|err| panic!("Failed to bind to address '{}': {}", redirect_addr, err));
+        let https_port = listener
+            .local_addr()
+            .unwrap_or_else(|err| panic!("Failed to read local address of listener: {}", err))
+            .port();
+        info!("Redirecting HTTP requests on {} to HTTPS", redirect_addr);
+        let shutdown = shutdown.clone();
+        tokio::spawn(async move {
+            if let Err(err) = server::tls::redirect_to_https(redirect_listener, https_port, shutdown).await {
+                warn!("HTTP to HTTPS redirect server failed: {}", err);
+            }
+        });
+    }
+
+    // Start the server and stop accepting connections once asked to shut down,
+    // waiting for in-flight requests to complete
+    let result = match &config.tls {
+        Some(tls) => server::tls::serve(listener, router_service, tls, shutdown).await,
+        None => {
+            axum::serve(listener, router_service)
+                .with_graceful_shutdown(shutdown.cancelled_owned())
+                .await
+        }
+    };
+    result.unwrap_or_else(|err| panic!("Failed to start server: {}", err));
+
+    // Let jobs which are still running finish, since they may be writing to the database or image store
+    jobs.wait().await;
+    // Requests whose clients disconnected may have left image writes running in the background
+    server::storage::flush().await;
+    // The server has dropped its clones of the database, so the connections can be closed
+    if let Err(err) = database.close().await {
+        warn!("Failed to close database cleanly: {}", err);
+    }
+    info!("Server stopped");
+    server::telemetry::shutdown();
 }
@@ -0,0 +1,48 @@
+//! Markdown rendering for blog post text.
+//! This is used by both the server and the client, since posts are rendered on both.
+
+use pulldown_cmark::{html, Options, Parser};
+
+/// Render Markdown to HTML which is safe to inject into the page.
+/// The generated HTML is sanitized to strip anything which could execute scripts,
+/// since raw HTML in the Markdown is passed through by the parser.
+pub fn render(text: &str) -> String {
+    let options = Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TABLES | Options::ENABLE_TASKLISTS;
+    let mut unsafe_html = String::new();
+    html::push_html(&mut unsafe_html, Parser::new_ext(text, options));
+    ammonia::clean(&unsafe_html)
+}
+
+/// Strip the formatting from Markdown, leaving only its text on a single line, e.g. for link previews.
+/// Raw HTML in the Markdown is dropped.
+#[cfg(feature = "server")]
+pub fn plain_text(text: &str) -> String {
+    use pulldown_cmark::Event;
+
+    let mut plain = String::new();
+    for event in Parser::new(text) {
+        match event {
+            Event::Text(text) | Event::Code(text) => plain.push_str(&text),
+            Event::SoftBreak | Event::HardBreak | Event::End(_) => plain.push(' '),
+            _ => {}
+        }
+    }
+    plain.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// The destinations of the links in the Markdown, in order of appearance and without duplicates,
+/// e.g. to send Webmentions to the linked pages.
+#[cfg(feature = "server")]
+pub fn links(text: &str) -> Vec<String> {
+    use pulldown_cmark::{Event, Tag};
+
+    let mut links: Vec<String> = Vec::new();
+    for event in Parser::new(text) {
+        if let Event::Start(Tag::Link { dest_url, .. }) = event {
+            if !links.iter().any(|link| **link == *dest_url) {
+                links.push(dest_url.to_string());
+            }
+        }
+    }
+    links
+}
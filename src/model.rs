@@ -1,34 +1,315 @@
 //! Data models for the blog post application.
 
+use crate::validation::{Field, Rule, Validate, ValidationErrors};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 /// Blog post creation data sent by the client.
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct CreateBlogPostParams {
     /// The text of the blog post.
+    /// The author is not part of the params, since it is always the logged-in user.
     pub text: String,
-    /// The username of the author of the blog post.
-    pub username: String,
-    /// The bytes of the file attached to the blog post, if any.
-    /// These bytes have not yet been validated to ensure they are an image.
-    pub image: Option<Vec<u8>>,
+    /// The token of the file uploaded to `/uploads` to be attached to the blog post, if any.
+    /// The file has not yet been validated to ensure it is an image.
+    pub image_upload: Option<UploadToken>,
+    /// The description of the attached image for people who cannot see it, if any.
+    pub image_alt: Option<String>,
     /// The URL of the author's avatar, if any.
     /// This URL has not yet been validated to ensure it is an image.
     pub avatar_url: Option<String>,
+    /// The token of the file uploaded to `/uploads` to be used as the author's avatar, if any.
+    /// This is an alternative to `avatar_url`, so at most one of them may be set.
+    pub avatar_upload: Option<UploadToken>,
+    /// The square of the uploaded avatar which is kept, if any.
+    pub avatar_crop: Option<AvatarCrop>,
+    /// The tags of the blog post, normalized with `parse_tags`.
+    pub tags: Vec<String>,
+    /// The solved proof-of-work challenge, which makes creating blog posts in bulk expensive.
+    pub challenge: ChallengeSolution,
 }
 
-impl CreateBlogPostParams {
-    pub fn validate(&self) -> Result<(), &'static str> {
-        if self.text.is_empty() {
-            return Err("The blog post text cannot be empty");
+impl Validate for CreateBlogPostParams {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.check(Field::Text, &self.text, TEXT_RULES);
+        validate_tags(&self.tags, &mut errors);
+        if let Some(alt) = &self.image_alt {
+            errors.check(Field::ImageAlt, alt, IMAGE_ALT_RULES);
+        }
+        if let Some(url) = &self.avatar_url {
+            errors.check(Field::Avatar, url, AVATAR_URL_RULES);
+        }
+        if self.avatar_url.is_some() && self.avatar_upload.is_some() {
+            errors.add(Field::Avatar, "Choose either an avatar URL or an avatar file, not both");
+        }
+        if let Some(crop) = &self.avatar_crop {
+            if self.avatar_upload.is_none() {
+                errors.add(Field::Avatar, "Only uploaded avatars can be cropped");
+            }
+            if let Err(msg) = crop.validate() {
+                errors.add(Field::Avatar, msg);
+            }
+        }
+        errors.into_result()
+    }
+}
+
+/// The rules for the URL of an avatar, which the server downloads the avatar from.
+const AVATAR_URL_RULES: &[Rule] = &[Rule::HttpUrl];
+
+/// A square region of an avatar image which is kept when the avatar is cropped.
+/// It is relative to the size of the image, so that it still applies after the browser downscales the image.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct AvatarCrop {
+    /// The side length of the square, as a fraction of the shorter side of the image.
+    pub size: f64,
+    /// The position of the square, from the left edge (0) to the right edge (1) of the image.
+    pub left: f64,
+    /// The position of the square, from the top edge (0) to the bottom edge (1) of the image.
+    pub top: f64,
+}
+
+/// The largest square in the center of the image.
+impl Default for AvatarCrop {
+    fn default() -> Self {
+        Self { size: 1.0, left: 0.5, top: 0.5 }
+    }
+}
+
+impl AvatarCrop {
+    /// The smallest side length of the square, so that avatars are not cropped down to a few pixels.
+    pub const MIN_SIZE: f64 = 0.1;
+
+    fn validate(&self) -> Result<(), &'static str> {
+        if !(Self::MIN_SIZE..=1.0).contains(&self.size) {
+            return Err("The avatar cannot be cropped to less than a tenth of its size");
         }
-        if self.username.is_empty() {
-            return Err("The username cannot be empty");
+        if !(0.0..=1.0).contains(&self.left) || !(0.0..=1.0).contains(&self.top) {
+            return Err("The avatar cannot be cropped outside of its edges");
         }
         Ok(())
     }
+
+    /// Returns the left edge, top edge and side length in pixels of the square within an image of the given size.
+    pub fn pixels(&self, width: u32, height: u32) -> (u32, u32, u32) {
+        let shorter_side = width.min(height);
+        let side = ((f64::from(shorter_side) * self.size).round() as u32).clamp(1, shorter_side.max(1));
+        let left = (f64::from(width.saturating_sub(side)) * self.left).round() as u32;
+        let top = (f64::from(height.saturating_sub(side)) * self.top).round() as u32;
+        (left, top, side)
+    }
+}
+
+/// The maximum length of the description of an image.
+pub const MAX_IMAGE_ALT_LENGTH: usize = 300;
+
+/// The rules for the description of an image, which may be left empty unless the server requires it.
+const IMAGE_ALT_RULES: &[Rule] = &[Rule::MaxChars(MAX_IMAGE_ALT_LENGTH)];
+
+/// The token which refers to a file uploaded ahead of creating a blog post.
+pub type UploadToken = String;
+
+/// A proof-of-work challenge issued by the server, which the client must solve before creating a blog post.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct Challenge {
+    /// The signed token which identifies the challenge.
+    pub token: String,
+    /// The number of leading zero bits which the hash of the token and the nonce must have.
+    pub difficulty: u32,
+}
+
+impl Challenge {
+    /// Find the smallest nonce which solves the challenge.
+    /// This takes about `2^difficulty` hashes on average.
+    pub fn solve(self) -> ChallengeSolution {
+        let nonce = self.solve_batch(0, u64::MAX).expect("some nonce should solve the challenge");
+        ChallengeSolution { token: self.token, nonce }
+    }
+
+    /// Find the smallest nonce which solves the challenge among the `count` nonces from `start` on, if any,
+    /// so that the challenge can be solved in batches with other work in between.
+    pub fn solve_batch(&self, start: u64, count: u64) -> Option<u64> {
+        (start..start.saturating_add(count)).find(|&nonce| challenge_hash_bits(&self.token, nonce) >= self.difficulty)
+    }
+}
+
+/// A solution to a proof-of-work challenge.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct ChallengeSolution {
+    /// The token of the solved challenge.
+    pub token: String,
+    /// The nonce which, hashed together with the token, has enough leading zero bits.
+    pub nonce: u64,
+}
+
+/// Returns the number of leading zero bits of the hash of the challenge token and the nonce.
+pub fn challenge_hash_bits(token: &str, nonce: u64) -> u32 {
+    let hash = blake3::Hasher::new()
+        .update(token.as_bytes())
+        .update(&nonce.to_le_bytes())
+        .finalize();
+    let mut bits = 0;
+    for byte in hash.as_bytes() {
+        bits += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    bits
+}
+
+/// The maximum number of tags a blog post can have.
+pub const MAX_TAGS: usize = 10;
+
+/// The maximum length of a tag, as constrained by the database schema.
+pub const MAX_TAG_LENGTH: usize = 30;
+
+/// Parse tags from user input, e.g. `"#rust, web  #Rust"` into `["rust", "web"]`.
+/// Tags may be separated by commas or whitespace and may start with a `#`.
+/// They are lowercased and deduplicated, keeping the order in which they first appear.
+pub fn parse_tags(input: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in input.split(|c: char| c == ',' || c.is_whitespace()) {
+        let tag = tag.trim_start_matches('#').to_lowercase();
+        if !tag.is_empty() && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags
+}
+
+/// The rules for every single tag.
+const TAG_RULES: &[Rule] = &[
+    Rule::Required,
+    Rule::MaxChars(MAX_TAG_LENGTH),
+    Rule::Chars {
+        allowed: |c| c.is_alphanumeric() || c == '-' || c == '_',
+        description: "letters, digits, '-' and '_'",
+    },
+];
+
+/// Validate that there are not too many tags and that each follows the `TAG_RULES`.
+fn validate_tags(tags: &[String], errors: &mut ValidationErrors) {
+    if tags.len() > MAX_TAGS {
+        errors.add(Field::Tags, "A blog post cannot have more than 10 tags");
+    }
+    for tag in tags {
+        errors.check(Field::Tags, tag, TAG_RULES);
+    }
+}
+
+/// The MIME types of the image formats accepted for uploads.
+/// Used as the `accept` attribute of the image file input.
+pub const ACCEPTED_IMAGE_TYPES: &str = "image/png,image/jpeg,image/webp,image/gif";
+
+/// Blog post update data sent by the client.
+/// Only the text of a blog post can be edited after it has been created.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UpdateBlogPostParams {
+    /// The new text of the blog post.
+    pub text: String,
+}
+
+impl Validate for UpdateBlogPostParams {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.check(Field::Text, &self.text, TEXT_RULES);
+        errors.into_result()
+    }
+}
+
+/// Account credentials sent by the client to register or log in.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct Credentials {
+    /// The username of the account.
+    pub username: String,
+    /// The plaintext password of the account.
+    /// This is only ever hashed on the server and never persisted as-is.
+    pub password: String,
+}
+
+/// The maximum length of a username, as constrained by the database schema.
+pub const MAX_USERNAME_LENGTH: usize = 15;
+
+/// The minimum length of a password.
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+
+impl Validate for Credentials {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors
+            .check(Field::Username, &self.username, &[Rule::Required, Rule::MaxChars(MAX_USERNAME_LENGTH)])
+            .check(Field::Password, &self.password, &[Rule::MinChars(MIN_PASSWORD_LENGTH)]);
+        errors.into_result()
+    }
+}
+
+/// Validate the text and tags of a blog post which was not written in the form, e.g. one which is imported.
+pub fn validate_post(text: &str, tags: &[String]) -> Result<(), ValidationErrors> {
+    let mut errors = ValidationErrors::default();
+    errors.check(Field::Text, text, TEXT_RULES);
+    validate_tags(tags, &mut errors);
+    errors.into_result()
 }
 
+/// The maximum length of the reason given when reporting a blog post.
+pub const MAX_REPORT_REASON_LENGTH: usize = 500;
+
+/// Validate the reason given when reporting a blog post.
+pub fn validate_report_reason(reason: &str) -> Result<(), ValidationErrors> {
+    let mut errors = ValidationErrors::default();
+    errors.check(Field::Reason, reason, &[Rule::Required, Rule::MaxChars(MAX_REPORT_REASON_LENGTH)]);
+    errors.into_result()
+}
+
+/// Why the content filter flagged the text of a blog post.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, derive_more::Display)]
+pub enum ContentViolation {
+    /// The text contains a word which is not allowed.
+    #[display("The post contains the banned word \"{}\"", word)]
+    BannedWord { word: String },
+    /// The text contains more links than allowed.
+    #[display("The post contains {} links, but at most {} are allowed", count, max)]
+    TooManyLinks { count: usize, max: usize },
+    /// The text is longer than allowed.
+    #[display("The post is {} characters long, but at most {} are allowed", length, max)]
+    TooLong { length: usize, max: usize },
+    /// The external moderation service refused the text.
+    #[display("The post was flagged by moderation: {}", reason)]
+    Flagged { reason: String },
+}
+
+/// The outcome of submitting a new blog post.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum PostSubmission {
+    /// The blog post passed the content filter and is visible to everyone.
+    Published(BlogPost),
+    /// The blog post was flagged by the content filter and saved hidden until an administrator unhides it.
+    Queued(ContentViolation),
+    /// The blog post was flagged by the content filter and not saved.
+    Rejected(ContentViolation),
+}
+
+/// The maximum length of the text of a blog post.
+/// The server can be configured to allow fewer characters, but never more.
+pub const MAX_POST_LENGTH: usize = 10_000;
+
+/// Returns the number of characters in the text as the user perceives them,
+/// i.e. grapheme clusters, so that e.g. an emoji with a skin tone counts as a single character.
+pub fn post_length(text: &str) -> usize {
+    use unicode_segmentation::UnicodeSegmentation;
+
+    text.graphemes(true).count()
+}
+
+/// The rules for the text field shared by the blog post params.
+const TEXT_RULES: &[Rule] = &[Rule::Required, Rule::MaxGraphemes(MAX_POST_LENGTH)];
+
 /// The file system path of a blog post image.
 /// This is a newtype around a `String`, which is the UUID of the image.
 /// The UUID is persisted to the database, and is used to load the image from the file system later.
@@ -51,13 +332,33 @@ pub struct PostImagePath(pub String);
 )]
 pub struct AvatarImagePath(pub String);
 
+impl PostImagePath {
+    /// The URL at which the server serves this image.
+    pub fn url(&self) -> String {
+        format!("/images/posts/{}", self.0)
+    }
+
+    /// The URL at which the server serves the downscaled thumbnail of this image.
+    /// The feed uses thumbnails, while the original image is only loaded when viewed directly.
+    pub fn thumbnail_url(&self) -> String {
+        format!("/images/posts/{}/thumbnail", self.0)
+    }
+}
+
+impl AvatarImagePath {
+    /// The URL at which the server serves this image.
+    pub fn url(&self) -> String {
+        format!("/images/avatars/{}", self.0)
+    }
+}
+
 #[cfg(feature = "server")]
 pub use server::*;
 
 /// Server-specific models and functionality.
 #[cfg(feature = "server")]
 mod server {
-    use super::{AvatarImagePath, PostImagePath};
+    use super::{AvatarImagePath, PostImagePath, User, UserId};
     use diesel::{backend::Backend, deserialize, serialize, sql_types::Text};
 
     /// Implement the necessary Diesel traits for an image UUID newtype.
@@ -91,7 +392,7 @@ mod server {
     impl_image!(AvatarImagePath);
 
     /// Insertable data for a blog post.
-    #[derive(Debug, diesel::Insertable)]
+    #[derive(Debug, Clone, diesel::Insertable)]
     #[diesel(table_name = crate::server::persistence::schema::blog_post)]
     pub struct InsertBlogPost {
         pub posted_on: time::Date,
@@ -99,24 +400,209 @@ mod server {
         pub username: String,
         pub image_uuid: Option<PostImagePath>,
         pub avatar_uuid: Option<AvatarImagePath>,
+        pub user_id: Option<UserId>,
+        pub hidden: bool,
+        pub image_alt: Option<String>,
+        /// Generated when the blog post is saved.
+        pub slug: Option<String>,
+        pub posted_at: Option<time::PrimitiveDateTime>,
+        pub image_width: Option<i32>,
+        pub image_height: Option<i32>,
     }
 
     impl InsertBlogPost {
+        /// Create a blog post written by the provided user.
         pub fn new(
             text: String,
-            username: String,
+            author: User,
             image_uuid: Option<PostImagePath>,
+            image_alt: Option<String>,
             avatar_uuid: Option<AvatarImagePath>,
         ) -> Self {
+            let now = time::OffsetDateTime::now_utc();
             Self {
-                posted_on: time::OffsetDateTime::now_utc().date(),
-                text,
-                username,
+                posted_on: now.date(),
+                text: super::strip_highlight_markers(text),
+                username: author.username,
                 image_uuid,
                 avatar_uuid,
+                user_id: Some(author.id),
+                hidden: false,
+                image_alt,
+                slug: None,
+                posted_at: Some(time::PrimitiveDateTime::new(now.date(), now.time())),
+                image_width: None,
+                image_height: None,
+            }
+        }
+    }
+
+    /// Insertable data for a user account.
+    #[derive(Debug, diesel::Insertable)]
+    #[diesel(table_name = crate::server::persistence::schema::users)]
+    pub struct InsertUser {
+        pub username: String,
+        pub password_hash: String,
+        pub created_on: time::Date,
+    }
+
+    impl InsertUser {
+        pub fn new(username: String, password_hash: String) -> Self {
+            Self {
+                username,
+                password_hash,
+                created_on: time::OffsetDateTime::now_utc().date(),
             }
         }
     }
+
+    /// Changeset for updating the editable fields of a blog post.
+    #[derive(Debug, diesel::AsChangeset)]
+    #[diesel(table_name = crate::server::persistence::schema::blog_post)]
+    pub struct UpdateBlogPost {
+        pub text: String,
+    }
+
+    impl From<super::UpdateBlogPostParams> for UpdateBlogPost {
+        fn from(params: super::UpdateBlogPostParams) -> Self {
+            Self {
+                text: super::strip_highlight_markers(params.text),
+            }
+        }
+    }
+
+    /// The state of a delivery of a post event to a webhook.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum WebhookStatus {
+        /// The delivery has not succeeded yet, and is attempted again at its `next_attempt_at`.
+        Pending,
+        /// The webhook accepted the delivery.
+        Delivered,
+        /// Every attempt failed, so the delivery was given up.
+        Failed,
+    }
+
+    impl WebhookStatus {
+        /// The name under which the status is stored in the database.
+        pub fn as_str(self) -> &'static str {
+            match self {
+                WebhookStatus::Pending => "pending",
+                WebhookStatus::Delivered => "delivered",
+                WebhookStatus::Failed => "failed",
+            }
+        }
+    }
+
+    /// A delivery of a post event to a webhook, as recorded in the delivery log.
+    #[derive(Debug, Clone, diesel::Queryable, diesel::Selectable)]
+    #[diesel(table_name = crate::server::persistence::schema::webhook_delivery)]
+    pub struct WebhookDelivery {
+        pub id: i32,
+        pub webhook_url: String,
+        /// The name of the event, e.g. `post.created`.
+        pub event: String,
+        pub post_id: super::BlogPostId,
+        /// The JSON body which is sent to the webhook.
+        pub payload: String,
+        /// `pending`, `delivered` or `failed`.
+        pub status: String,
+        /// The number of attempts made so far.
+        pub attempts: i32,
+        /// When the delivery is attempted next, or `None` once it is no longer pending.
+        pub next_attempt_at: Option<time::PrimitiveDateTime>,
+        /// The HTTP status of the response to the last attempt, if there was one.
+        pub response_status: Option<i32>,
+        /// Why the last attempt failed, if it did.
+        pub last_error: Option<String>,
+        pub created_at: time::PrimitiveDateTime,
+        pub delivered_at: Option<time::PrimitiveDateTime>,
+    }
+
+    /// Insertable data for a pending delivery of a post event to a webhook.
+    #[derive(Debug, diesel::Insertable)]
+    #[diesel(table_name = crate::server::persistence::schema::webhook_delivery)]
+    pub struct InsertWebhookDelivery {
+        pub webhook_url: String,
+        pub event: String,
+        pub post_id: super::BlogPostId,
+        pub payload: String,
+        pub status: String,
+        pub attempts: i32,
+        pub next_attempt_at: Option<time::PrimitiveDateTime>,
+        pub created_at: time::PrimitiveDateTime,
+    }
+
+    /// Insertable data for an API key, whose user is looked up by username when it is created.
+    #[derive(Debug, diesel::Insertable)]
+    #[diesel(table_name = crate::server::persistence::schema::api_key)]
+    pub struct InsertApiKey {
+        pub name: String,
+        pub key_hash: String,
+        pub prefix: String,
+        pub created_at: time::PrimitiveDateTime,
+    }
+
+    /// Insertable data for a remote ActivityPub actor who follows the blog.
+    #[derive(Debug, diesel::Insertable)]
+    #[diesel(table_name = crate::server::persistence::schema::follower)]
+    pub struct InsertFollower {
+        /// The URL of the actor document of the follower.
+        pub actor_id: String,
+        /// The inbox to which new blog posts are delivered.
+        pub inbox: String,
+        pub followed_at: time::PrimitiveDateTime,
+    }
+
+    /// Insertable data for a verified Webmention of a blog post by another site.
+    #[derive(Debug, diesel::Insertable)]
+    #[diesel(table_name = crate::server::persistence::schema::webmention)]
+    pub struct InsertWebmention {
+        pub post_id: BlogPostId,
+        /// The URL of the page which mentions the blog post.
+        pub source: String,
+        /// The title of the page, if it has one.
+        pub title: Option<String>,
+        pub received_at: time::PrimitiveDateTime,
+    }
+
+    /// Insertable record of the erasure of the data of a user, which is kept after the data is gone.
+    #[derive(Debug, diesel::Insertable)]
+    #[diesel(table_name = crate::server::persistence::schema::user_erasure)]
+    pub struct InsertUserErasure {
+        /// The username of the erased user.
+        pub username: String,
+        /// The username of the administrator who erased the data.
+        pub erased_by: String,
+        pub erased_at: time::PrimitiveDateTime,
+        /// The number of erased blog posts.
+        pub posts: i32,
+    }
+
+    /// Insertable entry of the audit log.
+    #[derive(Debug, Clone, diesel::Insertable)]
+    #[diesel(table_name = crate::server::persistence::schema::audit_log)]
+    pub struct InsertAuditEntry {
+        /// The name of the `AuditAction`.
+        pub action: String,
+        pub actor: Option<String>,
+        pub ip: Option<String>,
+        pub post_id: Option<super::BlogPostId>,
+        pub summary: String,
+        pub occurred_at: time::PrimitiveDateTime,
+    }
+
+    /// Changeset recording the outcome of an attempt to deliver a post event to a webhook.
+    /// Unset fields are cleared, e.g. the error of a previous attempt once an attempt succeeds.
+    #[derive(Debug, diesel::AsChangeset)]
+    #[diesel(table_name = crate::server::persistence::schema::webhook_delivery, treat_none_as_null = true)]
+    pub struct WebhookAttempt {
+        pub status: String,
+        pub attempts: i32,
+        pub next_attempt_at: Option<time::PrimitiveDateTime>,
+        pub response_status: Option<i32>,
+        pub last_error: Option<String>,
+        pub delivered_at: Option<time::PrimitiveDateTime>,
+    }
 }
 
 /// The ID of a blog post.
@@ -125,7 +611,7 @@ pub type BlogPostId = i32;
 /// A blog post that has been saved to the database.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[cfg_attr(feature = "server",
-    derive(diesel::Queryable, diesel::Selectable),
+    derive(diesel::Queryable, diesel::QueryableByName, diesel::Selectable),
     diesel(table_name = crate::server::persistence::schema::blog_post),
     diesel(check_for_backend(diesel::sqlite::Sqlite))
 )]
@@ -136,4 +622,509 @@ pub struct BlogPost {
     pub username: String,
     pub image_uuid: Option<PostImagePath>,
     pub avatar_uuid: Option<AvatarImagePath>,
+    /// The account which wrote the blog post, or `None` if it was written before accounts existed.
+    pub user_id: Option<UserId>,
+    /// The description of the image for people who cannot see it, if the author gave one.
+    pub image_alt: Option<String>,
+    /// The human-readable part of the permalink, or `None` if the blog post was written before slugs existed.
+    pub slug: Option<String>,
+    /// The exact time in UTC at which the blog post was published,
+    /// or `None` if it was published before the time was recorded, in which case only `posted_on` is known.
+    pub posted_at: Option<time::PrimitiveDateTime>,
+    /// The width in pixels of the image, or `None` if it has none or it was saved before its size was recorded.
+    pub image_width: Option<i32>,
+    /// The height in pixels of the image, likewise.
+    pub image_height: Option<i32>,
+}
+
+impl BlogPost {
+    /// The canonical path of the page of this blog post, which includes its slug if it has one.
+    pub fn path(&self) -> String {
+        match &self.slug {
+            Some(slug) => format!("/post/{}/{}", self.id, slug),
+            None => format!("/post/{}", self.id),
+        }
+    }
+
+    /// Whether the user, who is an administrator if `is_admin`, may edit and delete this blog post.
+    /// Posts written before accounts existed belong to nobody, so only administrators may manage them.
+    pub fn is_editable_by(&self, user: &User, is_admin: bool) -> bool {
+        match self.user_id {
+            Some(author) => author == user.id,
+            None => is_admin,
+        }
+    }
+
+    /// The width and height in pixels of the image, if it has one whose size is known.
+    pub fn image_size(&self) -> Option<(u32, u32)> {
+        let width = u32::try_from(self.image_width?).ok()?;
+        let height = u32::try_from(self.image_height?).ok()?;
+        (width > 0 && height > 0).then_some((width, height))
+    }
+}
+
+/// The number of blog posts the client requests per page.
+pub const DEFAULT_PAGE_SIZE: i64 = 10;
+
+/// The maximum number of blog posts the server returns per page.
+pub const MAX_PAGE_SIZE: i64 = 50;
+
+/// The order in which the blog posts of the feed are sorted.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub enum FeedSort {
+    /// The most recently published blog posts first.
+    #[default]
+    Newest,
+    /// The earliest published blog posts first.
+    Oldest,
+}
+
+/// The sorting and filters of the feed of all blog posts chosen by the visitor.
+/// The default shows every blog post, newest first.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct FeedOptions {
+    pub sort: FeedSort,
+    /// Only show blog posts by the author with this username.
+    pub author: Option<String>,
+    /// Only show blog posts with an image.
+    pub with_image: bool,
+    /// Only show blog posts published on or after this day.
+    pub posted_from: Option<time::Date>,
+    /// Only show blog posts published on or before this day.
+    pub posted_until: Option<time::Date>,
+}
+
+impl FeedOptions {
+    /// Whether a newly published blog post belongs at the top of the feed with these options.
+    /// New blog posts are never shown at the top of a feed sorted oldest first, since they belong at its end.
+    pub fn includes(&self, post: &BlogPost) -> bool {
+        self.sort == FeedSort::Newest
+            && self.author.as_ref().map_or(true, |author| post.username == *author)
+            && (!self.with_image || post.image_uuid.is_some())
+            && self.posted_from.map_or(true, |from| post.posted_on >= from)
+            && self.posted_until.map_or(true, |until| post.posted_on <= until)
+    }
+}
+
+/// A page of blog posts, sorted by ID in descending order, or ascending if the feed is sorted oldest first.
+/// Pages are addressed by a cursor, which is the ID of the last post on the previous page.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct BlogPostPage {
+    /// The blog posts on this page.
+    pub posts: Vec<BlogPost>,
+    /// The cursor to pass to the server to fetch the next page, or `None` if this is the last page.
+    pub next_cursor: Option<BlogPostId>,
+    /// The likes of the blog posts on this page.
+    /// Posts which nobody has liked are omitted.
+    pub likes: HashMap<BlogPostId, PostLikes>,
+    /// The tags of the blog posts on this page.
+    /// Posts without tags are omitted.
+    pub tags: HashMap<BlogPostId, Vec<String>>,
+    /// When the blog posts on this page were last edited, in UTC.
+    /// Posts which were never edited are omitted.
+    pub last_edited: HashMap<BlogPostId, time::PrimitiveDateTime>,
+}
+
+/// A single blog post as shown on its own page.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BlogPostDetail {
+    pub post: BlogPost,
+    /// The likes of the blog post.
+    pub likes: PostLikes,
+    /// The tags of the blog post, sorted by name.
+    pub tags: Vec<String>,
+    /// When the blog post was last edited, in UTC, or `None` if it was never edited.
+    pub last_edited: Option<time::PrimitiveDateTime>,
+}
+
+/// The public profile of an author, as shown on their page.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UserProfile {
+    pub username: String,
+    /// The number of visible blog posts by the author.
+    pub post_count: i64,
+    /// The avatar of the most recent blog post by the author which has one.
+    pub avatar_uuid: Option<AvatarImagePath>,
+}
+
+/// A change to the blog post feed, which is pushed to every open feed as it happens.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum PostEvent {
+    /// A blog post was published with the tags.
+    Created { post: BlogPost, tags: Vec<String> },
+    /// The blog post with the ID was deleted or hidden.
+    Deleted(BlogPostId),
+}
+
+/// The likes of a blog post, as seen by the user who requested them.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct PostLikes {
+    /// The number of users who liked the blog post.
+    pub count: i64,
+    /// Whether the requesting user liked the blog post.
+    /// Always `false` if nobody is logged in.
+    pub liked: bool,
+}
+
+/// A previous text of an edited blog post.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "server",
+    derive(diesel::Queryable, diesel::Selectable),
+    diesel(table_name = crate::server::persistence::schema::blog_post_revision),
+    diesel(check_for_backend(diesel::sqlite::Sqlite))
+)]
+pub struct PostRevision {
+    /// The text of the blog post before the edit.
+    pub text: String,
+    /// When the text was replaced by the edit, in UTC.
+    pub edited_at: time::PrimitiveDateTime,
+}
+
+/// A page on another site which links to a blog post, as announced by a Webmention.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "server",
+    derive(diesel::Queryable, diesel::Selectable),
+    diesel(table_name = crate::server::persistence::schema::webmention),
+    diesel(check_for_backend(diesel::sqlite::Sqlite))
+)]
+pub struct Webmention {
+    /// The URL of the page.
+    pub source: String,
+    /// The title of the page, if it has one.
+    pub title: Option<String>,
+    /// When the mention was last verified, in UTC.
+    pub received_at: time::PrimitiveDateTime,
+}
+
+/// A blog post in the trash, which can be restored until it is purged.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TrashedPost {
+    /// The deleted blog post.
+    pub post: BlogPost,
+    /// When the blog post was moved to the trash, in UTC.
+    pub deleted_at: time::PrimitiveDateTime,
+    /// When the blog post will be permanently deleted, in UTC.
+    pub purge_after: time::PrimitiveDateTime,
+}
+
+/// A blog post as seen by administrators, who also see hidden posts and posts in the trash.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ModeratedPost {
+    pub post: BlogPost,
+    /// Whether the blog post is withheld from everyone but administrators.
+    pub hidden: bool,
+    /// When the blog post was moved to the trash, in UTC, or `None` if it is not in the trash.
+    pub deleted_at: Option<time::PrimitiveDateTime>,
+}
+
+/// A page of blog posts as seen by administrators, sorted by ID in descending order.
+/// Pagination works the same as for `BlogPostPage`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ModerationPage {
+    /// The blog posts on this page.
+    pub posts: Vec<ModeratedPost>,
+    /// The cursor to pass to the server to fetch the next page, or `None` if this is the last page.
+    pub next_cursor: Option<BlogPostId>,
+}
+
+/// An action which administrators can apply to many blog posts at once.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum ModerationAction {
+    /// Withhold the blog posts from everyone but administrators.
+    Hide,
+    /// Show hidden blog posts to everyone again.
+    Unhide,
+    /// Move the blog posts to the trash.
+    Delete,
+    /// Restore the blog posts from the trash.
+    Restore,
+}
+
+/// The ID of a report.
+pub type ReportId = i32;
+
+/// A report of a blog post which no administrator has handled yet.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OpenReport {
+    pub id: ReportId,
+    /// The reported blog post.
+    pub post: BlogPost,
+    /// The username of the user who reported the blog post.
+    pub reporter: String,
+    /// Why the blog post was reported.
+    pub reason: String,
+    /// When the blog post was reported, in UTC.
+    pub reported_at: time::PrimitiveDateTime,
+}
+
+/// How an administrator handles a report.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum ReportResolution {
+    /// The report is valid: the blog post is hidden, and all open reports of it are closed.
+    Resolve,
+    /// The report is not valid: only this report is closed.
+    Dismiss,
+}
+
+impl ReportResolution {
+    /// The name under which the resolution is stored in the database.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ReportResolution::Resolve => "resolved",
+            ReportResolution::Dismiss => "dismissed",
+        }
+    }
+}
+
+/// Statistics about the database and image store, as shown to administrators.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct AdminStatistics {
+    /// The number of blog posts which are neither hidden nor in the trash.
+    pub visible_posts: i64,
+    /// The number of hidden blog posts, including those in the trash.
+    pub hidden_posts: i64,
+    /// The number of blog posts in the trash.
+    pub trashed_posts: i64,
+    /// The number of revisions of edited blog posts.
+    pub revisions: i64,
+    /// The number of registered users.
+    pub users: i64,
+    /// The number of likes on all blog posts.
+    pub likes: i64,
+    /// The number of distinct tags.
+    pub tags: i64,
+    /// The number of reports which no administrator has handled yet.
+    pub open_reports: i64,
+    /// The number of open connections in the database connection pool.
+    pub db_connections: u32,
+    /// The number of idle connections in the database connection pool.
+    pub db_idle_connections: u32,
+    /// The number of stored images, counting thumbnails separately.
+    pub stored_images: usize,
+    /// The total size in bytes of all stored images.
+    pub stored_bytes: u64,
+}
+
+/// The number of days, including today, for which the blog statistics count the published blog posts.
+pub const STATS_DAYS: i64 = 30;
+
+/// Statistics about the blog, as shown to everyone on the home page.
+/// Only blog posts which are neither hidden nor in the trash are counted.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct BlogStats {
+    /// The number of blog posts.
+    pub total_posts: i64,
+    /// The number of blog posts published on each of the last `STATS_DAYS` days in UTC, oldest first.
+    /// Days without blog posts are included with a count of zero.
+    pub posts_per_day: Vec<(time::Date, i64)>,
+    /// The number of distinct usernames under which blog posts were published.
+    pub authors: i64,
+    /// The total size in bytes of all stored images, including avatars and thumbnails,
+    /// as last measured by the server, which does so every few minutes.
+    pub image_bytes: u64,
+}
+
+/// The maximum number of entries of the audit log returned at once.
+pub const MAX_AUDIT_ENTRIES: i64 = 200;
+
+/// A kind of change which is recorded in the audit log.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum AuditAction {
+    /// A blog post was created.
+    Create,
+    /// The text of a blog post was edited.
+    Update,
+    /// A blog post was moved to the trash.
+    Delete,
+    /// A blog post was restored from the trash.
+    Restore,
+    /// A blog post was purged from the trash after the retention window.
+    Purge,
+    /// A blog post was hidden by an administrator.
+    Hide,
+    /// A hidden blog post was shown to everyone again.
+    Unhide,
+    /// A blog post was imported from an archive.
+    Import,
+    /// All data of a user was erased.
+    EraseUser,
+    /// An API key was created.
+    CreateApiKey,
+    /// An API key was revoked.
+    RevokeApiKey,
+}
+
+impl AuditAction {
+    /// All actions, in the order in which they are offered as filters.
+    pub const ALL: [AuditAction; 11] = [
+        AuditAction::Create,
+        AuditAction::Update,
+        AuditAction::Delete,
+        AuditAction::Restore,
+        AuditAction::Purge,
+        AuditAction::Hide,
+        AuditAction::Unhide,
+        AuditAction::Import,
+        AuditAction::EraseUser,
+        AuditAction::CreateApiKey,
+        AuditAction::RevokeApiKey,
+    ];
+
+    /// The name under which the action is stored in the database.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AuditAction::Create => "create",
+            AuditAction::Update => "update",
+            AuditAction::Delete => "delete",
+            AuditAction::Restore => "restore",
+            AuditAction::Purge => "purge",
+            AuditAction::Hide => "hide",
+            AuditAction::Unhide => "unhide",
+            AuditAction::Import => "import",
+            AuditAction::EraseUser => "erase_user",
+            AuditAction::CreateApiKey => "create_api_key",
+            AuditAction::RevokeApiKey => "revoke_api_key",
+        }
+    }
+
+    /// Returns the action stored under the name, or `None` if there is no such action.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|action| action.as_str() == name)
+    }
+}
+
+/// An entry of the audit log, recording who changed what and when.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AuditEntry {
+    pub id: i32,
+    pub action: AuditAction,
+    /// The username of whoever made the change, or `None` if the server made it on its own, e.g. purging the trash.
+    pub actor: Option<String>,
+    /// The IP address from which the change was requested, or `None` if the server made it on its own.
+    pub ip: Option<String>,
+    /// The changed blog post, if the change was to a single blog post.
+    pub post_id: Option<BlogPostId>,
+    /// What was changed, e.g. how long the text of an edited blog post was before and after the edit.
+    pub summary: String,
+    /// When the change was made, in UTC.
+    pub occurred_at: time::PrimitiveDateTime,
+}
+
+/// The maximum number of results returned by a search.
+pub const MAX_SEARCH_RESULTS: i64 = 20;
+
+/// The maximum number of usernames suggested while typing a username.
+pub const MAX_USERNAME_SUGGESTIONS: i64 = 10;
+
+/// Marks the start of a search term match in `SearchResult::highlighted_text`.
+/// Control characters are used as markers, which are removed from the text of blog posts before they are saved
+/// with `strip_highlight_markers`, so that no text can pass for a match.
+pub const HIGHLIGHT_START: char = '\u{2}';
+
+/// Marks the end of a search term match in `SearchResult::highlighted_text`.
+pub const HIGHLIGHT_END: char = '\u{3}';
+
+/// Remove the characters which mark search term matches from the text of a blog post.
+pub fn strip_highlight_markers(text: String) -> String {
+    if text.contains([HIGHLIGHT_START, HIGHLIGHT_END]) {
+        text.replace([HIGHLIGHT_START, HIGHLIGHT_END], "")
+    } else {
+        text
+    }
+}
+
+/// A blog post matching a full-text search.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SearchResult {
+    /// The matching blog post.
+    pub post: BlogPost,
+    /// The text of the blog post with matches enclosed in `HIGHLIGHT_START` and `HIGHLIGHT_END`.
+    pub highlighted_text: String,
+}
+
+impl SearchResult {
+    /// Split the highlighted text into segments, each flagged with whether it is a match.
+    pub fn segments(&self) -> Vec<(&str, bool)> {
+        let mut segments = Vec::new();
+        for (i, part) in self.highlighted_text.split(HIGHLIGHT_START).enumerate() {
+            // Every part except the first starts with a match, which is terminated by the end marker
+            match part.split_once(HIGHLIGHT_END) {
+                Some((matched, rest)) if i > 0 => {
+                    segments.push((matched, true));
+                    segments.push((rest, false));
+                }
+                _ => segments.push((part, false)),
+            }
+        }
+        segments.retain(|(segment, _)| !segment.is_empty());
+        segments
+    }
+}
+
+/// The ID of an API key.
+pub type ApiKeyId = i32;
+
+/// The maximum length of the name of an API key.
+pub const MAX_API_KEY_NAME_LENGTH: usize = 50;
+
+/// Params for creating an API key, which authenticates requests to the REST API on behalf of a user.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct CreateApiKeyParams {
+    /// The username of the user on whose behalf the key authenticates.
+    pub username: String,
+    /// What the key is used for, e.g. the name of the bot.
+    pub name: String,
+}
+
+impl Validate for CreateApiKeyParams {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors
+            .check(Field::Username, &self.username, &[Rule::Required, Rule::MaxChars(MAX_USERNAME_LENGTH)])
+            .check(Field::KeyName, &self.name, &[Rule::Required, Rule::MaxChars(MAX_API_KEY_NAME_LENGTH)]);
+        errors.into_result()
+    }
+}
+
+/// An API key as shown to administrators.
+/// The key itself is not part of this model, since only its hash is stored.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "server", derive(diesel::Queryable))]
+pub struct ApiKeyInfo {
+    pub id: ApiKeyId,
+    /// What the key is used for.
+    pub name: String,
+    /// The username of the user on whose behalf the key authenticates.
+    pub username: String,
+    /// The first characters of the key, by which its owner can recognize it.
+    pub prefix: String,
+    /// When the key was created, in UTC.
+    pub created_at: time::PrimitiveDateTime,
+    /// When the key last authenticated a request, in UTC, or `None` if it never did.
+    pub last_used_at: Option<time::PrimitiveDateTime>,
+}
+
+/// An API key which was just created.
+/// This is the only time the key is shown, so that it has to be copied right away.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct NewApiKey {
+    /// The key, which is sent as `Authorization: Bearer <key>`.
+    pub key: String,
+    pub info: ApiKeyInfo,
+}
+
+/// The ID of a user account.
+pub type UserId = i32;
+
+/// A registered user account.
+/// The password hash is deliberately not part of this model, so it never leaves the server.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "server",
+    derive(diesel::Queryable, diesel::Selectable),
+    diesel(table_name = crate::server::persistence::schema::users),
+    diesel(check_for_backend(diesel::sqlite::Sqlite))
+)]
+pub struct User {
+    pub id: UserId,
+    pub username: String,
 }
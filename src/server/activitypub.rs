@@ -0,0 +1,646 @@
+//! ActivityPub federation, so that users of Mastodon and other servers of the fediverse can follow the blog.
+//! The blog is a single actor, `@blog@<host>`, which is found through WebFinger and whose outbox holds the newest
+//! blog posts as `Note`s. Remote actors follow it by sending a `Follow` activity to its inbox, which is accepted
+//! right away, and every blog post published from then on is delivered to their inboxes.
+//! Requests between servers are authenticated with HTTP Signatures as Mastodon implements them:
+//! the blog signs its requests with its RSA key, and only accepts activities signed by the key of their actor.
+//! Federation is enabled by configuring `activitypub_key_path`, and its routes respond with `404 Not Found` otherwise.
+
+use crate::markdown;
+use crate::model::{BlogPostId, InsertFollower};
+use crate::server::outbound::{self, BlockedUrl, HttpClient};
+use crate::server::persistence::database::PostFilter;
+use crate::server::{posts, rest, Database, ServerState};
+use axum::body::Bytes;
+use axum::extract::{Path, Query};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::Router;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use futures_util::StreamExt;
+use http::header::{ACCEPT, CONTENT_TYPE};
+use http::{HeaderMap, Method, StatusCode, Uri};
+use rsa::pkcs1v15::{Signature, SigningKey, VerifyingKey};
+use rsa::pkcs8::{DecodePrivateKey, DecodePublicKey, EncodePrivateKey, EncodePublicKey, LineEnding};
+use rsa::rand_core::OsRng;
+use rsa::signature::{SignatureEncoding, Signer, Verifier};
+use rsa::{RsaPrivateKey, RsaPublicKey};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+use std::time::Duration;
+use time::format_description::FormatItem;
+use time::macros::format_description;
+use time::{OffsetDateTime, PrimitiveDateTime};
+use tokio::io::AsyncWriteExt;
+use tracing::{debug, info, instrument, warn};
+use uuid::Uuid;
+
+/// The preferred username of the blog, by which it is followed as `@blog@<host>`.
+const ACTOR_NAME: &str = "blog";
+
+/// The media type of ActivityPub documents.
+const ACTIVITY_JSON: &str = "application/activity+json";
+
+/// The JSON-LD context of ActivityStreams, which all documents refer to.
+const ACTIVITY_STREAMS: &str = "https://www.w3.org/ns/activitystreams";
+
+/// The JSON-LD context which defines the public key of actors.
+const SECURITY: &str = "https://w3id.org/security/v1";
+
+/// The collection which addresses an object to everyone.
+const PUBLIC: &str = "https://www.w3.org/ns/activitystreams#Public";
+
+/// The size in bits of the RSA key which is generated for the blog.
+const KEY_BITS: usize = 2048;
+
+/// How many of the newest blog posts the outbox holds.
+const OUTBOX_SIZE: i64 = 20;
+
+/// How long a remote server may take to answer a request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How many inboxes a new blog post is delivered to at once.
+const MAX_CONCURRENT_DELIVERIES: usize = 8;
+
+/// How far the `Date` of a signed request may be from the current time, so that signatures cannot be replayed later.
+const MAX_SIGNATURE_AGE: time::Duration = time::Duration::hours(12);
+
+/// The format of the `Date` header, e.g. `Wed, 16 Oct 2024 12:00:00 GMT`.
+const HTTP_DATE: &[FormatItem<'_>] =
+    format_description!("[weekday repr:short], [day] [month repr:short] [year] [hour]:[minute]:[second] GMT");
+
+/// The reasons why federation could not be set up, or a request to a remote server failed.
+#[derive(Debug, derive_more::Display, derive_more::From)]
+pub enum FederationError {
+    #[display("Failed to read or write the key: {}", _0)]
+    Io(std::io::Error),
+    #[display("Invalid key: {}", _0)]
+    Key(rsa::pkcs8::Error),
+    #[display("Failed to generate the key: {}", _0)]
+    Generate(rsa::Error),
+    #[display("Invalid URL: {}", _0)]
+    #[from(ignore)]
+    InvalidUrl(String),
+    #[display("Refusing to request the URL: {}", _0)]
+    Blocked(BlockedUrl),
+    #[display("Request failed: {}", _0)]
+    Request(reqwest::Error),
+    #[display("The remote server answered with {}", _0)]
+    Status(StatusCode),
+}
+
+/// The blog as an ActivityPub actor, with the key it signs its requests with.
+struct Actor {
+    /// The public URL of the server, without a trailing slash.
+    base_url: String,
+    signing_key: SigningKey<Sha256>,
+    public_key_pem: String,
+}
+
+impl Debug for Actor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Actor").field("base_url", &self.base_url).finish_non_exhaustive()
+    }
+}
+
+impl Actor {
+    /// Load the key of the blog from the file, or generate one and save it there if the file does not exist.
+    async fn load(base_url: &str, key_path: &std::path::Path) -> Result<Self, FederationError> {
+        let private_key = match tokio::fs::read_to_string(key_path).await {
+            Ok(pem) => RsaPrivateKey::from_pkcs8_pem(&pem)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                info!("Generating the ActivityPub key of the blog in {}", key_path.display());
+                let key = tokio::task::spawn_blocking(|| RsaPrivateKey::new(&mut OsRng, KEY_BITS))
+                    .await
+                    .expect("generating a key should never panic")?;
+                let pem = key.to_pkcs8_pem(LineEnding::LF)?;
+                write_private(key_path, pem.as_bytes()).await?;
+                key
+            }
+            Err(err) => return Err(err.into()),
+        };
+        let public_key_pem = private_key
+            .to_public_key()
+            .to_public_key_pem(LineEnding::LF)
+            .expect("an RSA public key should be encodable as PEM");
+        Ok(Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            signing_key: SigningKey::new(private_key),
+            public_key_pem,
+        })
+    }
+
+    /// The absolute URL of the path on this server.
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    /// The ID of the blog, which is the URL of its actor document.
+    fn id(&self) -> String {
+        self.url("/activitypub/actor")
+    }
+
+    /// The ID of the public key of the blog, which remote servers look up to verify its signatures.
+    fn key_id(&self) -> String {
+        format!("{}#main-key", self.id())
+    }
+
+    /// The host of the server, e.g. `blog.example.com`, which is the domain of the handle of the blog.
+    fn host(&self) -> &str {
+        let rest = self.base_url.split_once("://").map_or(self.base_url.as_str(), |(_, rest)| rest);
+        rest.split('/').next().unwrap_or(rest)
+    }
+
+    /// The actor document of the blog.
+    fn document(&self) -> Value {
+        json!({
+            "@context": [ACTIVITY_STREAMS, SECURITY],
+            "id": self.id(),
+            "type": "Service",
+            "preferredUsername": ACTOR_NAME,
+            "name": "Blog posts",
+            "summary": "<p>The newest blog posts</p>",
+            "url": self.url("/"),
+            "inbox": self.url("/activitypub/inbox"),
+            "outbox": self.url("/activitypub/outbox"),
+            "followers": self.url("/activitypub/followers"),
+            "manuallyApprovesFollowers": false,
+            "discoverable": true,
+            "publicKey": {
+                "id": self.key_id(),
+                "owner": self.id(),
+                "publicKeyPem": self.public_key_pem,
+            },
+        })
+    }
+
+    /// The blog post as a `Note` attributed to the blog.
+    /// The blog is the only actor, so the author of the blog post is credited at the end of the content.
+    fn note(&self, post: &rest::Post) -> Value {
+        let author_url = self.url(&format!("/user/{}", post.author));
+        let content = format!(
+            "{}<p>— <a href=\"{}\">{}</a></p>",
+            markdown::render(&post.text),
+            ammonia::clean_text(&author_url),
+            ammonia::clean_text(&post.author),
+        );
+        let tags: Vec<Value> = post
+            .tags
+            .iter()
+            .map(|tag| json!({ "type": "Hashtag", "name": format!("#{tag}"), "href": self.url(&format!("/tag/{tag}")) }))
+            .collect();
+        let attachments: Vec<Value> = post
+            .image
+            .iter()
+            .map(|image| {
+                json!({
+                    "type": "Image",
+                    "url": self.url(&image.url),
+                    "name": image.alt,
+                    "width": image.width,
+                    "height": image.height,
+                })
+            })
+            .collect();
+        json!({
+            "id": self.url(&format!("/activitypub/posts/{}", post.id)),
+            "type": "Note",
+            "attributedTo": self.id(),
+            "content": content,
+            "published": published(post),
+            "updated": post.last_edited,
+            "url": self.url(&post.path),
+            "to": [PUBLIC],
+            "cc": [self.url("/activitypub/followers")],
+            "tag": tags,
+            "attachment": attachments,
+        })
+    }
+
+    /// The activity which announces that the blog post was published.
+    fn create(&self, post: &rest::Post) -> Value {
+        json!({
+            "@context": ACTIVITY_STREAMS,
+            "id": self.url(&format!("/activitypub/posts/{}/activity", post.id)),
+            "type": "Create",
+            "actor": self.id(),
+            "published": published(post),
+            "to": [PUBLIC],
+            "cc": [self.url("/activitypub/followers")],
+            "object": self.note(post),
+        })
+    }
+
+    /// Sign a request to the URL with the key of the blog.
+    /// The signature covers the method and path, the host, the date and, if there is a body, its digest.
+    /// Returns the headers to send along with the request, besides `Host`, which the client sets.
+    fn sign(&self, method: &Method, url: &reqwest::Url, body: Option<&[u8]>) -> Vec<(&'static str, String)> {
+        let date = OffsetDateTime::now_utc().format(HTTP_DATE).expect("the current time should be formattable as an HTTP date");
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+            None => url.host_str().unwrap_or_default().to_string(),
+        };
+        let target = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        };
+        let mut headers = vec![
+            ("(request-target)", format!("{} {}", method.as_str().to_lowercase(), target)),
+            ("host", host),
+            ("date", date),
+        ];
+        if let Some(body) = body {
+            headers.push(("digest", digest(body)));
+        }
+        let names = headers.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(" ");
+        let signed = headers.iter().map(|(name, value)| format!("{name}: {value}")).collect::<Vec<_>>().join("\n");
+        let signature = BASE64.encode(self.signing_key.sign(signed.as_bytes()).to_bytes());
+        let mut headers: Vec<_> = headers.into_iter().filter(|(name, _)| !matches!(*name, "(request-target)" | "host")).collect();
+        headers.push((
+            "signature",
+            format!("keyId=\"{}\",algorithm=\"rsa-sha256\",headers=\"{}\",signature=\"{}\"", self.key_id(), names, signature),
+        ));
+        headers
+    }
+}
+
+/// The time at which the blog post was published in RFC 3339 format.
+/// Blog posts from before publication times were recorded fall back to midnight of their date.
+fn published(post: &rest::Post) -> String {
+    post.posted_at.clone().unwrap_or_else(|| format!("{}T00:00:00Z", post.posted_on))
+}
+
+/// The `Digest` header of the body, e.g. `SHA-256=...`.
+fn digest(body: &[u8]) -> String {
+    format!("SHA-256={}", BASE64.encode(Sha256::digest(body)))
+}
+
+/// Write the contents to a new file which only the owner may read, since it holds a private key.
+async fn write_private(path: &std::path::Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut options = tokio::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(path).await?;
+    file.write_all(contents).await?;
+    file.sync_all().await
+}
+
+/// Whether both URLs are `https://` URLs of the same host and port.
+fn same_https_host(a: &str, b: &str) -> bool {
+    let (Ok(a), Ok(b)) = (reqwest::Url::parse(a), reqwest::Url::parse(b)) else {
+        return false;
+    };
+    [&a, &b].iter().all(|url| url.scheme() == "https")
+        && a.host_str().is_some()
+        && (a.host_str(), a.port()) == (b.host_str(), b.port())
+}
+
+/// Parse the parameters of a `Signature` header, e.g. `keyId="...",headers="...",signature="..."`.
+fn parse_signature(header: &str) -> HashMap<&str, &str> {
+    header
+        .split(',')
+        .filter_map(|param| param.split_once('='))
+        .map(|(name, value)| (name.trim(), value.trim().trim_matches('"')))
+        .collect()
+}
+
+/// Serve the document as ActivityPub JSON.
+fn activity_json(document: Value) -> Response {
+    ([(CONTENT_TYPE, ACTIVITY_JSON)], document.to_string()).into_response()
+}
+
+/// Federates the blog over ActivityPub, if it is enabled.
+#[derive(Debug, Clone)]
+pub struct Federation {
+    /// The blog as an actor, or `None` if federation is disabled.
+    actor: Option<Arc<Actor>>,
+    database: Database,
+    client: HttpClient,
+}
+
+impl Federation {
+    /// Set up federation if both the public URL of the server and the path of the key are configured.
+    /// The key is generated on first start, and must be kept afterwards, since followers know the blog by it.
+    pub async fn new(
+        public_url: Option<&str>,
+        key_path: Option<&std::path::Path>,
+        database: Database,
+        client: HttpClient,
+    ) -> Result<Self, FederationError> {
+        let actor = match (public_url, key_path) {
+            (Some(public_url), Some(key_path)) => Some(Arc::new(Actor::load(public_url, key_path).await?)),
+            _ => None,
+        };
+        Ok(Self { actor, database, client })
+    }
+
+    /// Whether the blog is federated.
+    pub fn is_enabled(&self) -> bool {
+        self.actor.is_some()
+    }
+
+    /// The blog as an actor, or `404 Not Found` if federation is disabled.
+    fn actor(&self) -> Result<Arc<Actor>, StatusCode> {
+        self.actor.clone().ok_or(StatusCode::NOT_FOUND)
+    }
+
+    /// Deliver the newly published blog post to the inboxes of all followers in the background.
+    /// Does nothing if federation is disabled.
+    pub fn publish(&self, post: &rest::Post) {
+        let Some(actor) = self.actor.clone() else {
+            return;
+        };
+        let activity = actor.create(post);
+        let federation = self.clone();
+        tokio::spawn(async move {
+            let inboxes = match federation.database.fetch_follower_inboxes().await {
+                Ok(inboxes) => inboxes,
+                Err(err) => {
+                    warn!("Failed to fetch the inboxes of followers: {}", err);
+                    return;
+                }
+            };
+            let (federation, actor, activity) = (&federation, &actor, &activity);
+            let mut deliveries = futures_util::stream::iter(inboxes)
+                .map(|inbox| async move {
+                    let result = federation.deliver(actor, &inbox, activity).await;
+                    (inbox, result)
+                })
+                .buffer_unordered(MAX_CONCURRENT_DELIVERIES);
+            while let Some((inbox, result)) = deliveries.next().await {
+                let outcome = match result {
+                    Ok(()) => "delivered",
+                    Err(err) => {
+                        warn!("Failed to deliver a blog post to {}: {}", inbox, err);
+                        "failed"
+                    }
+                };
+                metrics::counter!("activitypub_deliveries_total", "outcome" => outcome).increment(1);
+            }
+        });
+    }
+
+    /// Post the activity to the inbox, signed by the blog.
+    #[instrument(level = "debug", skip(self, actor, activity))]
+    async fn deliver(&self, actor: &Actor, inbox: &str, activity: &Value) -> Result<(), FederationError> {
+        let url = reqwest::Url::parse(inbox).map_err(|err| FederationError::InvalidUrl(err.to_string()))?;
+        outbound::check_https_url(&url)?;
+        let body = activity.to_string().into_bytes();
+        let mut request = self.client.post(url.clone()).timeout(REQUEST_TIMEOUT).header(CONTENT_TYPE, ACTIVITY_JSON);
+        for (name, value) in actor.sign(&Method::POST, &url, Some(&body)) {
+            request = request.header(name, value);
+        }
+        let response = request.body(body).send().await?;
+        if !response.status().is_success() {
+            return Err(FederationError::Status(response.status()));
+        }
+        Ok(())
+    }
+
+    /// Fetch the ActivityPub document at the URL with a request signed by the blog,
+    /// since servers in secure mode refuse to answer anonymous requests.
+    async fn fetch(&self, actor: &Actor, url: &str) -> Result<Value, FederationError> {
+        let url = reqwest::Url::parse(url).map_err(|err| FederationError::InvalidUrl(err.to_string()))?;
+        outbound::check_https_url(&url)?;
+        let mut request = self.client.get(url.clone()).timeout(REQUEST_TIMEOUT).header(ACCEPT, ACTIVITY_JSON);
+        for (name, value) in actor.sign(&Method::GET, &url, None) {
+            request = request.header(name, value);
+        }
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            return Err(FederationError::Status(response.status()));
+        }
+        Ok(response.json().await?)
+    }
+
+    /// Verify the HTTP signature of a request to the inbox.
+    /// The signature must cover the path, the date and the digest of the body, so that it cannot be replayed
+    /// for other requests or much later, and must be made with the public key published by `remote_id`, the actor of
+    /// the activity. The key is only fetched once everything else checks out, and only from the server of that actor.
+    /// Returns the document of the remote actor who signed the request, or `401 Unauthorized` if the signature is
+    /// missing, stale or invalid.
+    async fn verify(
+        &self,
+        actor: &Actor,
+        remote_id: &str,
+        uri: &Uri,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> Result<Value, StatusCode> {
+        let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+        let params = parse_signature(header("signature").ok_or(StatusCode::UNAUTHORIZED)?);
+        let (Some(&key_id), Some(&signature)) = (params.get("keyId"), params.get("signature")) else {
+            return Err(StatusCode::UNAUTHORIZED);
+        };
+        let signed_headers: Vec<&str> = params.get("headers").copied().unwrap_or("date").split_whitespace().collect();
+        if !["(request-target)", "date", "digest"].iter().all(|name| signed_headers.contains(name)) {
+            debug!("Rejecting an activity whose signature does not cover its target, date and digest");
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+        if header("digest") != Some(digest(body).as_str()) {
+            debug!("Rejecting an activity whose digest does not match its body");
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+        let date = header("date")
+            .and_then(|date| PrimitiveDateTime::parse(date, HTTP_DATE).ok())
+            .ok_or(StatusCode::UNAUTHORIZED)?;
+        let now = OffsetDateTime::now_utc();
+        if (PrimitiveDateTime::new(now.date(), now.time()) - date).abs() > MAX_SIGNATURE_AGE {
+            debug!("Rejecting an activity whose signature is too old");
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+        let target = uri.path_and_query().map_or(uri.path(), |target| target.as_str());
+        let signed = signed_headers
+            .iter()
+            .map(|name| match *name {
+                "(request-target)" => Some(format!("(request-target): post {target}")),
+                name => header(name).map(|value| format!("{name}: {value}")),
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or(StatusCode::UNAUTHORIZED)?
+            .join("\n");
+        let signature = BASE64
+            .decode(signature)
+            .ok()
+            .and_then(|bytes| Signature::try_from(bytes.as_slice()).ok())
+            .ok_or(StatusCode::UNAUTHORIZED)?;
+        // The key is published in the actor document, whose URL is the ID of the key without its fragment.
+        // Only keys on the server of the actor are fetched, so that anyone cannot make the blog request any URL
+        let actor_url = key_id.split('#').next().unwrap_or(key_id);
+        if !same_https_host(actor_url, remote_id) {
+            debug!("Rejecting an activity of {} signed with the key {} of another server", remote_id, key_id);
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+        let remote = self.fetch(actor, actor_url).await.map_err(|err| {
+            debug!("Failed to fetch the signer {} of an activity: {}", actor_url, err);
+            StatusCode::UNAUTHORIZED
+        })?;
+        let public_key = &remote["publicKey"];
+        let pem = public_key["publicKeyPem"]
+            .as_str()
+            .filter(|_| public_key["id"].as_str() == Some(key_id))
+            .ok_or(StatusCode::UNAUTHORIZED)?;
+        let key = RsaPublicKey::from_public_key_pem(pem).map_err(|_| StatusCode::UNAUTHORIZED)?;
+        VerifyingKey::<Sha256>::new(key).verify(signed.as_bytes(), &signature).map_err(|_| {
+            debug!("Rejecting an activity with an invalid signature by {}", key_id);
+            StatusCode::UNAUTHORIZED
+        })?;
+        Ok(remote)
+    }
+
+    /// Save the remote actor as a follower, and accept their follow in the background.
+    /// New blog posts are delivered to the shared inbox of their server if it has one, so that every server
+    /// receives each blog post only once, however many of its users follow the blog.
+    async fn accept_follow(&self, actor: Arc<Actor>, remote: &Value, follow: Value) -> Result<(), StatusCode> {
+        let remote_id = remote["id"].as_str().ok_or(StatusCode::BAD_REQUEST)?;
+        let personal_inbox = remote["inbox"].as_str().ok_or(StatusCode::BAD_REQUEST)?;
+        let inbox = remote["endpoints"]["sharedInbox"].as_str().unwrap_or(personal_inbox);
+        if ![personal_inbox, inbox].iter().all(|inbox| inbox.starts_with("https://")) {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        let now = OffsetDateTime::now_utc();
+        let follower = InsertFollower {
+            actor_id: remote_id.to_string(),
+            inbox: inbox.to_string(),
+            followed_at: PrimitiveDateTime::new(now.date(), now.time()),
+        };
+        self.database.add_follower(follower).await.map_err(|err| {
+            warn!("Failed to save follower {}: {}", remote_id, err);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        info!("{} follows the blog", remote_id);
+        let accept = json!({
+            "@context": ACTIVITY_STREAMS,
+            "id": format!("{}#accepts/{}", actor.id(), Uuid::new_v4()),
+            "type": "Accept",
+            "actor": actor.id(),
+            "object": follow,
+        });
+        let federation = self.clone();
+        let personal_inbox = personal_inbox.to_string();
+        tokio::spawn(async move {
+            if let Err(err) = federation.deliver(&actor, &personal_inbox, &accept).await {
+                warn!("Failed to accept the follow of {}: {}", personal_inbox, err);
+            }
+        });
+        Ok(())
+    }
+}
+
+/// Create the router for the ActivityPub endpoints of the blog.
+pub fn router() -> Router {
+    Router::new()
+        .route("/.well-known/webfinger", get(webfinger))
+        .route("/activitypub/actor", get(actor))
+        .route("/activitypub/inbox", post(inbox))
+        .route("/activitypub/outbox", get(outbox))
+        .route("/activitypub/followers", get(followers))
+        .route("/activitypub/posts/:id", get(note))
+}
+
+/// The query of a WebFinger lookup, e.g. `?resource=acct:blog@blog.example.com`.
+#[derive(Debug, Deserialize)]
+struct WebFingerQuery {
+    resource: String,
+}
+
+/// Resolve the handle of the blog to its actor document, which is how remote servers find it.
+async fn webfinger(federation: Federation, Query(query): Query<WebFingerQuery>) -> Result<Response, StatusCode> {
+    let actor = federation.actor()?;
+    let account = format!("acct:{}@{}", ACTOR_NAME, actor.host());
+    if query.resource != account && query.resource != actor.id() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let document = json!({
+        "subject": account,
+        "aliases": [actor.id()],
+        "links": [
+            { "rel": "self", "type": ACTIVITY_JSON, "href": actor.id() },
+            { "rel": "http://webfinger.net/rel/profile-page", "type": "text/html", "href": actor.url("/") },
+        ],
+    });
+    Ok(([(CONTENT_TYPE, "application/jrd+json")], document.to_string()).into_response())
+}
+
+/// The actor document of the blog.
+async fn actor(federation: Federation) -> Result<Response, StatusCode> {
+    Ok(activity_json(federation.actor()?.document()))
+}
+
+/// The newest blog posts as `Create` activities, newest first.
+async fn outbox(state: ServerState, federation: Federation) -> Result<Response, StatusCode> {
+    let actor = federation.actor()?;
+    let page = posts::fetch_page(&state, None, OUTBOX_SIZE, PostFilter::default(), None)
+        .await
+        .map_err(|err| err.status())?;
+    let total = state.database.count_posts().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let items: Vec<Value> = rest::PostList::from(page).posts.iter().map(|post| actor.create(post)).collect();
+    Ok(activity_json(json!({
+        "@context": ACTIVITY_STREAMS,
+        "id": actor.url("/activitypub/outbox"),
+        "type": "OrderedCollection",
+        "totalItems": total,
+        "orderedItems": items,
+    })))
+}
+
+/// The number of followers of the blog, who are not listed to respect their privacy.
+async fn followers(federation: Federation) -> Result<Response, StatusCode> {
+    let actor = federation.actor()?;
+    let total = federation.database.count_followers().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(activity_json(json!({
+        "@context": ACTIVITY_STREAMS,
+        "id": actor.url("/activitypub/followers"),
+        "type": "OrderedCollection",
+        "totalItems": total,
+    })))
+}
+
+/// A single blog post as a `Note`, which remote servers fetch to resolve links to it.
+async fn note(state: ServerState, federation: Federation, Path(id): Path<BlogPostId>) -> Result<Response, StatusCode> {
+    let actor = federation.actor()?;
+    let detail = posts::fetch_post(&state, id, None)
+        .await
+        .map_err(|err| err.status())?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let mut note = actor.note(&rest::Post::from(detail));
+    note["@context"] = json!(ACTIVITY_STREAMS);
+    Ok(activity_json(note))
+}
+
+/// Receive an activity from a remote server, whose signature must be made by the actor of the activity.
+/// Follows of the blog are accepted, and undoing a follow removes the follower; other activities are ignored.
+async fn inbox(federation: Federation, uri: Uri, headers: HeaderMap, body: Bytes) -> Result<StatusCode, StatusCode> {
+    let actor = federation.actor()?;
+    let activity: Value = serde_json::from_slice(&body).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let claimed_actor = activity["actor"].as_str().ok_or(StatusCode::BAD_REQUEST)?;
+    let remote = federation.verify(&actor, claimed_actor, &uri, &headers, &body).await?;
+    let remote_id = remote["id"].as_str().ok_or(StatusCode::BAD_REQUEST)?;
+    if claimed_actor != remote_id {
+        debug!("Rejecting an activity of {:?} signed by {}", activity["actor"], remote_id);
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let kind = activity["type"].as_str().unwrap_or_default().to_string();
+    match kind.as_str() {
+        "Follow" if activity["object"].as_str() == Some(actor.id().as_str()) => {
+            federation.accept_follow(actor, &remote, activity).await?;
+        }
+        "Undo" if activity["object"]["type"].as_str() == Some("Follow") => {
+            let removed = federation.database.remove_follower(remote_id.to_string()).await.map_err(|err| {
+                warn!("Failed to remove follower {}: {}", remote_id, err);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+            if removed {
+                info!("{} no longer follows the blog", remote_id);
+            }
+        }
+        _ => debug!("Ignoring {} activity of {}", kind, remote_id),
+    }
+    Ok(StatusCode::ACCEPTED)
+}
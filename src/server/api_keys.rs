@@ -0,0 +1,44 @@
+//! API keys, with which bots and command-line tools authenticate to the REST API without logging in to a session.
+//! Administrators create keys on behalf of a user, and the tools send them as `Authorization: Bearer <key>`.
+//! Only the SHA-256 hash of a key is stored, so a key cannot be recovered from the database
+//! and is only shown once, when it is created.
+
+use crate::model::{InsertApiKey, User};
+use crate::server::persistence::database::DatabaseError;
+use crate::server::Database;
+use sha2::{Digest, Sha256};
+use time::{OffsetDateTime, PrimitiveDateTime};
+use uuid::Uuid;
+
+/// The prefix of every key, which makes keys recognizable, e.g. to secret scanners.
+const KEY_PREFIX: &str = "bp_";
+
+/// How many of the first characters of a key are stored in plain text, so that administrators can tell keys apart.
+const DISPLAYED_PREFIX_LENGTH: usize = 11;
+
+/// Generate a new random key with the name.
+/// Returns the key, which must be shown to the administrator, along with the data to store in its place.
+pub fn generate(name: String) -> (String, InsertApiKey) {
+    let key = format!("{KEY_PREFIX}{}", Uuid::new_v4().simple());
+    let now = OffsetDateTime::now_utc();
+    let to_persist = InsertApiKey {
+        name,
+        key_hash: hash(&key),
+        prefix: key[..DISPLAYED_PREFIX_LENGTH].to_string(),
+        created_at: PrimitiveDateTime::new(now.date(), now.time()),
+    };
+    (key, to_persist)
+}
+
+/// Look up the user on whose behalf the key authenticates, and record that the key was used.
+/// Returns `None` if the key does not exist or was revoked.
+pub async fn authenticate(database: &Database, key: &str) -> Result<Option<User>, DatabaseError> {
+    let now = OffsetDateTime::now_utc();
+    database.authenticate_api_key(hash(key), PrimitiveDateTime::new(now.date(), now.time())).await
+}
+
+/// Returns the hex-encoded SHA-256 hash of the key, under which it is stored.
+/// Keys are random enough that they need no salt or slow hash, unlike passwords.
+fn hash(key: &str) -> String {
+    format!("{:x}", Sha256::digest(key.as_bytes()))
+}
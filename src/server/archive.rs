@@ -0,0 +1,743 @@
+//! Archives of all blog posts with their images, for backups and for moving the blog to another server.
+//! An archive is a tar file which holds, in this order:
+//! - `posts.json`: the format version and every blog post which is not in the trash, oldest first, see `Archive`;
+//! - `posts/<id>.md`: the text of every blog post as Markdown, with its metadata as front matter, for reading;
+//! - `images/<key>`: the original images and avatars of the blog posts, under their keys in the image store.
+//!
+//! The archive of the data of a single user, which they may request, holds their blog posts, including those in the
+//! trash, and also `account.json`: their account, their likes and reports, and their API keys, see `ArchivedAccount`.
+//!
+//! The archive is streamed to the client while it is built, so that the images never have to fit into memory at once.
+//!
+//! Archives can be imported again, as can `posts.json` on its own, which leaves out the images,
+//! and the JSON exports of Ghost and of the WordPress REST API, whose blog posts are attributed to the importer.
+//! Every blog post is validated and saved on its own, so that an invalid one is skipped and reported
+//! rather than failing the whole import.
+
+use crate::error::AppError;
+use crate::model::{strip_highlight_markers, validate_post, BlogPost, BlogPostId, InsertBlogPost, User, UserId};
+use crate::server::images::{self, AvatarSource, ImagePath, ImagePolicy};
+use crate::server::persistence::database::{AccountData, DatabaseError};
+use crate::server::storage::{ImageStorage, StorageError};
+use crate::server::uploads::{self, Uploads};
+use crate::server::{Database, ServerState};
+use axum::body::Bytes;
+use futures_util::Stream;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::PathBuf;
+use time::format_description::well_known::Rfc3339;
+use time::macros::format_description;
+use time::{Date, OffsetDateTime, PrimitiveDateTime, UtcOffset};
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+/// The version of the archive format, which is increased whenever `Archive` changes incompatibly.
+pub const VERSION: u32 = 1;
+
+/// The path of the index of the blog posts in the archive.
+pub const INDEX_PATH: &str = "posts.json";
+
+/// The path of the account of the user in the archive of their data.
+pub const ACCOUNT_PATH: &str = "account.json";
+
+/// The directory of the images in the archive, under which they keep their keys in the image store.
+pub const IMAGES_DIR: &str = "images";
+
+/// The size of the blocks of a tar file.
+const BLOCK_SIZE: usize = 512;
+
+/// How many chunks of the archive are built ahead of the client downloading them.
+const BUFFERED_CHUNKS: usize = 16;
+
+/// The index of an archive, which is stored as `posts.json`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Archive {
+    /// The version of the archive format, see `VERSION`.
+    pub version: u32,
+    /// When the archive was exported, in RFC 3339 format.
+    pub exported_at: String,
+    pub posts: Vec<ArchivedPost>,
+}
+
+/// A blog post in an archive.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchivedPost {
+    /// The ID of the blog post on the server it was exported from.
+    pub id: BlogPostId,
+    /// The username of the author.
+    pub author: String,
+    /// The text of the blog post, in Markdown.
+    pub text: String,
+    /// The date on which the blog post was published, e.g. `2024-10-16`.
+    pub posted_on: String,
+    /// The time at which the blog post was published in RFC 3339 format, if it is known.
+    pub posted_at: Option<String>,
+    /// The time at which the blog post was last edited in RFC 3339 format, if it was edited.
+    pub last_edited: Option<String>,
+    /// Whether the blog post is withheld from everyone but administrators.
+    pub hidden: bool,
+    /// The time at which the blog post was moved to the trash in RFC 3339 format, which only the archives of the
+    /// data of a user contain, since the archive of the blog leaves the trash out.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<String>,
+    pub tags: Vec<String>,
+    pub image: Option<ArchivedImage>,
+    /// The path of the avatar of the author in the archive, if any.
+    pub avatar: Option<String>,
+}
+
+/// The image of a blog post in an archive.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchivedImage {
+    /// The path of the image in the archive.
+    pub path: String,
+    /// The description of the image for people who cannot see it, if the author gave one.
+    pub alt: Option<String>,
+}
+
+/// The account of a user in the archive of their data, which is stored as `account.json`.
+/// The password hash and the API keys themselves are left out.
+#[derive(Debug, Serialize)]
+pub struct ArchivedAccount {
+    pub id: UserId,
+    pub username: String,
+    /// The date on which the account was created, e.g. `2024-10-16`.
+    pub created_on: String,
+    /// The IDs of the blog posts which the user liked.
+    pub likes: Vec<BlogPostId>,
+    pub reports: Vec<ArchivedReport>,
+    pub api_keys: Vec<ArchivedApiKey>,
+}
+
+/// A report of a blog post by the user in the archive of their data.
+#[derive(Debug, Serialize)]
+pub struct ArchivedReport {
+    pub post_id: BlogPostId,
+    pub reason: String,
+    /// The time at which the blog post was reported in RFC 3339 format.
+    pub reported_at: Option<String>,
+}
+
+/// An API key of the user in the archive of their data.
+#[derive(Debug, Serialize)]
+pub struct ArchivedApiKey {
+    pub name: String,
+    /// The time at which the key was created in RFC 3339 format.
+    pub created_at: Option<String>,
+    /// The time at which the key was last used in RFC 3339 format, if it was used.
+    pub last_used_at: Option<String>,
+}
+
+impl From<AccountData> for ArchivedAccount {
+    fn from(account: AccountData) -> Self {
+        Self {
+            id: account.user.id,
+            username: account.user.username,
+            created_on: account.created_on.to_string(),
+            likes: account.likes,
+            reports: account
+                .reports
+                .into_iter()
+                .map(|(post_id, reason, reported_at)| ArchivedReport {
+                    post_id,
+                    reason,
+                    reported_at: rfc3339(reported_at),
+                })
+                .collect(),
+            api_keys: account
+                .api_keys
+                .into_iter()
+                .map(|(name, created_at, last_used_at)| ArchivedApiKey {
+                    name,
+                    created_at: rfc3339(created_at),
+                    last_used_at: last_used_at.and_then(rfc3339),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Why building an archive failed.
+#[derive(Debug, derive_more::Display, derive_more::From)]
+enum ExportError {
+    #[display("Failed to load the blog posts: {}", _0)]
+    Database(DatabaseError),
+    #[display("Failed to read an image: {}", _0)]
+    Storage(StorageError),
+    #[display("Failed to write the archive: {}", _0)]
+    Io(std::io::Error),
+    #[display("The client stopped downloading the archive")]
+    Disconnected,
+}
+
+/// Stream an archive of all blog posts which are not in the trash, with their images,
+/// or of the data of the user with the username, including their blog posts in the trash.
+/// The archive is built in the background, and an error ends the stream early, which aborts the download.
+pub fn export(
+    database: Database,
+    images: ImageStorage,
+    policy: ImagePolicy,
+    user: Option<String>,
+) -> impl Stream<Item = Result<Bytes, std::io::Error>> {
+    let (sender, receiver) = mpsc::channel(BUFFERED_CHUNKS);
+    tokio::spawn(async move {
+        let mut writer = TarWriter { sender };
+        match write_archive(&database, &images, policy, user, &mut writer).await {
+            Ok(count) => info!("Exported {} blog posts", count),
+            Err(ExportError::Disconnected) => warn!("The client stopped downloading the export"),
+            Err(err) => {
+                warn!("Failed to export the blog posts: {}", err);
+                let _ = writer.sender.send(Err(std::io::Error::other(err.to_string()))).await;
+            }
+        }
+    });
+    futures_util::stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|chunk| (chunk, receiver))
+    })
+}
+
+/// Write the index, the Markdown files and the images of all blog posts, or of those of the user along with
+/// their account, to the archive.
+/// Returns the number of exported blog posts.
+async fn write_archive(
+    database: &Database,
+    images: &ImageStorage,
+    policy: ImagePolicy,
+    user: Option<String>,
+    writer: &mut TarWriter,
+) -> Result<usize, ExportError> {
+    let account = match &user {
+        Some(user) => database.fetch_account_data(user.clone()).await?,
+        None => None,
+    };
+    let export = database.fetch_export(user).await?;
+    let now = OffsetDateTime::now_utc();
+    // The images are looked up first, so that the index can be written before them
+    let mut posts = Vec::with_capacity(export.posts.len());
+    let mut image_keys = Vec::new();
+    for (post, is_hidden, trashed_at) in &export.posts {
+        let image_key = match &post.image_uuid {
+            Some(image) => find_stored(images, policy, image).await?,
+            None => None,
+        };
+        let avatar_key = match &post.avatar_uuid {
+            Some(avatar) => find_stored(images, policy, avatar).await?,
+            None => None,
+        };
+        let image = image_key.as_ref().map(|key| ArchivedImage { path: image_path(key), alt: post.image_alt.clone() });
+        posts.push(ArchivedPost {
+            id: post.id,
+            author: post.username.clone(),
+            text: post.text.clone(),
+            posted_on: post.posted_on.to_string(),
+            posted_at: post.posted_at.and_then(rfc3339),
+            last_edited: export.last_edited.get(&post.id).copied().and_then(rfc3339),
+            hidden: *is_hidden,
+            deleted_at: trashed_at.and_then(rfc3339),
+            tags: export.tags.get(&post.id).cloned().unwrap_or_default(),
+            image,
+            avatar: avatar_key.as_ref().map(|key| image_path(key)),
+        });
+        image_keys.extend(image_key.into_iter().chain(avatar_key));
+    }
+    let mtime = now.unix_timestamp().max(0) as u64;
+    let archive = Archive {
+        version: VERSION,
+        exported_at: now.format(&Rfc3339).expect("the current time should be formattable as RFC 3339"),
+        posts,
+    };
+    let index = serde_json::to_vec_pretty(&archive).expect("archives should be serializable");
+    writer.write_file(INDEX_PATH, index.into(), mtime).await?;
+    if let Some(account) = account {
+        let account = ArchivedAccount::from(account);
+        let account = serde_json::to_vec_pretty(&account).expect("accounts should be serializable");
+        writer.write_file(ACCOUNT_PATH, account.into(), mtime).await?;
+    }
+    for (post, archived) in export.posts.iter().map(|(post, ..)| post).zip(&archive.posts) {
+        writer.write_file(&format!("posts/{}.md", post.id), markdown(post, archived).into(), mtime).await?;
+    }
+    // Several blog posts may share an avatar, which is only archived once
+    image_keys.sort();
+    image_keys.dedup();
+    for key in image_keys {
+        let Some(body) = images.get(&key).await? else {
+            // The image was deleted since it was found, which leaves the blog post without it
+            warn!("Image {} disappeared during the export", key);
+            continue;
+        };
+        let bytes = axum::body::to_bytes(body, usize::MAX).await.map_err(std::io::Error::other)?;
+        writer.write_file(&image_path(&key), bytes, mtime).await?;
+    }
+    writer.finish().await?;
+    Ok(archive.posts.len())
+}
+
+/// The key under which the image is stored, trying its animation and then every format it may be stored in,
+/// like the image routes do, or `None` if it no longer exists.
+async fn find_stored<I: ImagePath>(
+    images: &ImageStorage,
+    policy: ImagePolicy,
+    image: &I,
+) -> Result<Option<String>, StorageError> {
+    let keys = image
+        .animated_key()
+        .into_iter()
+        .chain(images::stored_formats(policy.format()).map(|format| image.key(format)));
+    for key in keys {
+        if images.exists(&key).await? {
+            return Ok(Some(key));
+        }
+    }
+    Ok(None)
+}
+
+/// The path in the archive of the image stored under the key.
+fn image_path(key: &str) -> String {
+    format!("{IMAGES_DIR}/{key}")
+}
+
+/// Format the time, which is in UTC, in RFC 3339 format.
+fn rfc3339(time: PrimitiveDateTime) -> Option<String> {
+    time.assume_utc().format(&Rfc3339).ok()
+}
+
+/// The blog post as a Markdown file, with its metadata as front matter.
+fn markdown(post: &BlogPost, archived: &ArchivedPost) -> String {
+    let mut front_matter = vec![
+        format!("id: {}", post.id),
+        format!("author: {}", post.username),
+        format!("posted_on: {}", archived.posted_on),
+    ];
+    if let Some(posted_at) = &archived.posted_at {
+        front_matter.push(format!("posted_at: {posted_at}"));
+    }
+    if let Some(last_edited) = &archived.last_edited {
+        front_matter.push(format!("last_edited: {last_edited}"));
+    }
+    if !archived.tags.is_empty() {
+        front_matter.push(format!("tags: [{}]", archived.tags.join(", ")));
+    }
+    if let Some(image) = &archived.image {
+        front_matter.push(format!("image: {}", image.path));
+    }
+    if archived.hidden {
+        front_matter.push("hidden: true".to_string());
+    }
+    if let Some(deleted_at) = &archived.deleted_at {
+        front_matter.push(format!("deleted_at: {deleted_at}"));
+    }
+    format!("---\n{}\n---\n\n{}\n", front_matter.join("\n"), post.text)
+}
+
+/// The outcome of an import.
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    /// The IDs of the imported blog posts, in the order of the export.
+    pub imported: Vec<BlogPostId>,
+    /// The blog posts which were not imported, by their position in the export starting at 0, with the reason.
+    pub skipped: Vec<(usize, String)>,
+}
+
+/// Why an upload could not be imported at all.
+#[derive(Debug, derive_more::Display)]
+pub enum ImportError {
+    #[display("The file is neither an archive nor a JSON export: {}", _0)]
+    Unreadable(String),
+    #[display("The archive does not contain {}", INDEX_PATH)]
+    MissingIndex,
+    #[display("The archive has version {}, but only version {} can be imported", _0, VERSION)]
+    UnsupportedVersion(u32),
+}
+
+/// A blog post to import, from an archive or from another blogging platform.
+#[derive(Debug)]
+struct Entry {
+    /// The username of the author, or `None` to attribute the blog post to the importer.
+    author: Option<String>,
+    text: String,
+    /// The date on which the blog post was published, or `None` to take it from `posted_at`.
+    posted_on: Option<String>,
+    /// The time at which the blog post was published in RFC 3339 format, if it is known.
+    posted_at: Option<String>,
+    hidden: bool,
+    /// Whether the blog post was in the trash, which is not imported.
+    trashed: bool,
+    tags: Vec<String>,
+    image: Option<ArchivedImage>,
+    /// The path of the avatar of the author in the archive, if any.
+    avatar: Option<String>,
+}
+
+/// The JSON exports which can be imported, told apart by their shape.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonExport {
+    /// The index of an archive, without its images.
+    Archive(Archive),
+    Ghost(GhostExport),
+    /// The blog posts as returned by `/wp-json/wp/v2/posts`.
+    WordPress(Vec<WordPressPost>),
+}
+
+/// The JSON export of Ghost, of which only the blog posts and their tags are imported.
+#[derive(Deserialize)]
+struct GhostExport {
+    db: Vec<GhostDatabase>,
+}
+
+#[derive(Deserialize)]
+struct GhostDatabase {
+    data: GhostData,
+}
+
+#[derive(Deserialize)]
+struct GhostData {
+    posts: Vec<GhostPost>,
+    #[serde(default)]
+    tags: Vec<GhostTag>,
+    #[serde(default)]
+    posts_tags: Vec<GhostPostTag>,
+}
+
+#[derive(Deserialize)]
+struct GhostPost {
+    id: String,
+    title: Option<String>,
+    html: Option<String>,
+    plaintext: Option<String>,
+    /// `published`, `draft` or `scheduled`.
+    status: String,
+    /// `post` or `page`, of which pages are not imported.
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    created_at: String,
+    published_at: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct GhostTag {
+    id: String,
+    slug: String,
+}
+
+#[derive(Deserialize)]
+struct GhostPostTag {
+    post_id: String,
+    tag_id: String,
+}
+
+#[derive(Deserialize)]
+struct WordPressPost {
+    /// The time at which the blog post was published in UTC, without an offset, e.g. `2024-10-16T12:00:00`.
+    date_gmt: String,
+    /// `publish`, `draft`, `pending`, `future` or `private`.
+    status: String,
+    title: WordPressText,
+    content: WordPressText,
+}
+
+#[derive(Deserialize)]
+struct WordPressText {
+    rendered: String,
+}
+
+impl Archive {
+    fn into_entries(self) -> Result<Vec<Entry>, ImportError> {
+        if self.version != VERSION {
+            return Err(ImportError::UnsupportedVersion(self.version));
+        }
+        let entries = self
+            .posts
+            .into_iter()
+            .map(|post| Entry {
+                author: Some(post.author),
+                text: post.text,
+                posted_on: Some(post.posted_on),
+                posted_at: post.posted_at,
+                hidden: post.hidden,
+                trashed: post.deleted_at.is_some(),
+                tags: post.tags,
+                image: post.image,
+                avatar: post.avatar,
+            })
+            .collect();
+        Ok(entries)
+    }
+}
+
+impl JsonExport {
+    fn into_entries(self) -> Result<Vec<Entry>, ImportError> {
+        match self {
+            JsonExport::Archive(archive) => archive.into_entries(),
+            JsonExport::Ghost(export) => Ok(export.db.into_iter().flat_map(|db| db.data.into_entries()).collect()),
+            JsonExport::WordPress(posts) => Ok(posts.into_iter().map(WordPressPost::into_entry).collect()),
+        }
+    }
+}
+
+impl GhostData {
+    fn into_entries(self) -> Vec<Entry> {
+        let slugs: HashMap<String, String> = self.tags.into_iter().map(|tag| (tag.id, tag.slug)).collect();
+        let mut tags: HashMap<String, Vec<String>> = HashMap::new();
+        for post_tag in self.posts_tags {
+            if let Some(slug) = slugs.get(&post_tag.tag_id) {
+                tags.entry(post_tag.post_id).or_default().push(slug.clone());
+            }
+        }
+        self.posts
+            .into_iter()
+            .filter(|post| post.kind.as_deref() != Some("page"))
+            .map(|post| Entry {
+                author: None,
+                text: with_title(post.title.as_deref(), post.html.or(post.plaintext).unwrap_or_default()),
+                posted_on: None,
+                // Drafts were never published, so they keep the time they were written
+                posted_at: Some(post.published_at.unwrap_or(post.created_at)),
+                hidden: post.status != "published",
+                trashed: false,
+                tags: tags.remove(&post.id).unwrap_or_default(),
+                image: None,
+                avatar: None,
+            })
+            .collect()
+    }
+}
+
+impl WordPressPost {
+    fn into_entry(self) -> Entry {
+        Entry {
+            author: None,
+            text: with_title(Some(&self.title.rendered), self.content.rendered),
+            posted_on: None,
+            posted_at: Some(format!("{}Z", self.date_gmt)),
+            hidden: self.status != "publish",
+            trashed: false,
+            tags: Vec::new(),
+            image: None,
+            avatar: None,
+        }
+    }
+}
+
+/// The body of a blog post from a platform with titles, with the title as its heading.
+/// The body may be HTML, which the Markdown of blog posts allows.
+fn with_title(title: Option<&str>, body: String) -> String {
+    match title.map(str::trim).filter(|title| !title.is_empty()) {
+        Some(title) => format!("# {title}\n\n{}", body.trim()),
+        None => body.trim().to_string(),
+    }
+}
+
+/// Import the blog posts of an archive, of the index of an archive, or of a JSON export of Ghost or WordPress.
+/// Every blog post is validated like new ones, and its image and the avatar of its author are processed again,
+/// but it keeps its author, the time at which it was published and whether it is hidden.
+/// Blog posts which are invalid or were already imported are skipped and reported.
+/// Imported blog posts are not announced to followers, webhooks or the live feed, since they are not new.
+pub async fn import(state: &ServerState, importer: &User, upload: Bytes) -> Result<ImportReport, ImportError> {
+    let (entries, files) = tokio::task::spawn_blocking(move || read(&upload))
+        .await
+        .expect("reading an archive should never panic")?;
+    let mut report = ImportReport::default();
+    for (index, entry) in entries.into_iter().enumerate() {
+        match import_entry(state, importer, entry, &files).await {
+            Ok(post_id) => report.imported.push(post_id),
+            Err(reason) => report.skipped.push((index, reason)),
+        }
+    }
+    if !report.imported.is_empty() {
+        state.feed_cache.invalidate(&report.imported).await;
+    }
+    info!("Imported {} blog posts and skipped {}", report.imported.len(), report.skipped.len());
+    Ok(report)
+}
+
+/// Read the blog posts of the upload, which is a tar archive unless it starts like JSON.
+/// Returns them with the other files of the archive by their path, of which JSON exports have none.
+fn read(upload: &[u8]) -> Result<(Vec<Entry>, HashMap<String, Vec<u8>>), ImportError> {
+    let is_json = upload
+        .iter()
+        .find(|byte| !byte.is_ascii_whitespace())
+        .is_some_and(|byte| matches!(byte, b'{' | b'['));
+    if is_json {
+        let export: JsonExport = serde_json::from_slice(upload).map_err(unreadable)?;
+        return Ok((export.into_entries()?, HashMap::new()));
+    }
+    let mut files = HashMap::new();
+    let mut archive = tar::Archive::new(upload);
+    for file in archive.entries().map_err(unreadable)? {
+        let mut file = file.map_err(unreadable)?;
+        if file.header().entry_type() != tar::EntryType::Regular {
+            continue;
+        }
+        let path = file.path().map_err(unreadable)?.to_string_lossy().into_owned();
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents).map_err(unreadable)?;
+        files.insert(path, contents);
+    }
+    let index = files.remove(INDEX_PATH).ok_or(ImportError::MissingIndex)?;
+    let archive: Archive = serde_json::from_slice(&index).map_err(unreadable)?;
+    Ok((archive.into_entries()?, files))
+}
+
+fn unreadable(err: impl std::fmt::Display) -> ImportError {
+    ImportError::Unreadable(err.to_string())
+}
+
+/// Validate the blog post, process its images and save it.
+/// Returns the ID of the saved blog post, or why it was skipped.
+async fn import_entry(
+    state: &ServerState,
+    importer: &User,
+    mut entry: Entry,
+    files: &HashMap<String, Vec<u8>>,
+) -> Result<BlogPostId, String> {
+    if entry.trashed {
+        return Err("The blog post is in the trash".to_string());
+    }
+    // Like the text of new blog posts, so that importing the same archive again finds the imported blog posts
+    entry.text = strip_highlight_markers(entry.text);
+    validate_post(&entry.text, &entry.tags).map_err(|errors| AppError::from(errors).to_string())?;
+    state.content_filter.check_length(&entry.text).map_err(|violation| violation.to_string())?;
+    let posted_at = entry.posted_at.as_deref().map(parse_time).transpose()?;
+    let posted_on = match (entry.posted_on.as_deref(), posted_at) {
+        (Some(date), _) => {
+            Date::parse(date, format_description!("[year]-[month]-[day]")).map_err(|_| format!("Invalid date {date}"))?
+        }
+        (None, Some(time)) => time.date(),
+        (None, None) => return Err("The blog post has no date".to_string()),
+    };
+    let database = &state.database;
+    let (username, user_id) = match entry.author {
+        // Authors who have no account on this server keep their name, like the blog posts from before accounts
+        Some(author) => {
+            let user = database.fetch_user(author.clone()).await.map_err(internal)?;
+            (author, user.map(|user| user.id))
+        }
+        None => (importer.username.clone(), Some(importer.id)),
+    };
+    if database.contains_post(username.clone(), entry.text.clone(), posted_on).await.map_err(internal)? {
+        return Err("The blog post was already imported".to_string());
+    }
+    // Images which are missing from the archive are left out, e.g. when only the index is imported
+    let image = entry.image.and_then(|image| Some((archived_file(files, &image.path)?, image.alt)));
+    let avatar = entry.avatar.and_then(|path| archived_file(files, &path));
+    let uploads = &state.uploads;
+    let image_path = stage(uploads, image.as_ref().map(|(bytes, _)| *bytes)).await.map_err(internal)?;
+    let avatar_path = match stage(uploads, avatar).await {
+        Ok(path) => path,
+        Err(err) => {
+            if let Some(path) = &image_path {
+                uploads::discard(path).await;
+            }
+            return Err(internal(err));
+        }
+    };
+    let storage = &state.images;
+    let client = &state.http_client;
+    let avatar_source = avatar_path.clone().map(|path| AvatarSource::Upload { path, crop: None });
+    let processed = images::process_images(
+        &**storage,
+        database,
+        client,
+        &state.avatar_cache,
+        state.image_policy,
+        image_path.clone(),
+        avatar_source,
+    )
+    .await;
+    for path in image_path.iter().chain(&avatar_path) {
+        uploads::discard(path).await;
+    }
+    let (saved_image, avatar_uuid, reservation) = processed.map_err(|err| AppError::from(err).to_string())?;
+    let image_uuid = saved_image.as_ref().map(|image| image.path.clone());
+    let to_persist = InsertBlogPost {
+        posted_on,
+        text: entry.text,
+        username,
+        image_uuid: image_uuid.clone(),
+        avatar_uuid: avatar_uuid.clone(),
+        user_id,
+        hidden: entry.hidden,
+        image_alt: image.and_then(|(_, alt)| alt).filter(|_| saved_image.is_some()),
+        slug: None,
+        posted_at,
+        image_width: saved_image.as_ref().map(|image| image.width as i32),
+        image_height: saved_image.as_ref().map(|image| image.height as i32),
+    };
+    let saved = database.save(to_persist, entry.tags).await;
+    // Deleting the images below waits for the reservation to be released
+    drop(reservation);
+    match saved {
+        Ok(post) => Ok(post.id),
+        Err(err) => {
+            images::delete_unreferenced(&**storage, database, image_uuid, avatar_uuid).await;
+            Err(internal(err))
+        }
+    }
+}
+
+/// Parse the time in RFC 3339 format into UTC, in which times are stored.
+fn parse_time(time: &str) -> Result<PrimitiveDateTime, String> {
+    let time = OffsetDateTime::parse(time, &Rfc3339).map_err(|_| format!("Invalid time {time}"))?;
+    let time = time.to_offset(UtcOffset::UTC);
+    Ok(PrimitiveDateTime::new(time.date(), time.time()))
+}
+
+/// The contents of the file at the path in the archive, or `None` if it is missing.
+fn archived_file<'a>(files: &'a HashMap<String, Vec<u8>>, path: &str) -> Option<&'a [u8]> {
+    let file = files.get(path).map(Vec::as_slice);
+    if file.is_none() {
+        warn!("The archive does not contain the image {}", path);
+    }
+    file
+}
+
+/// Write the image to a file for the image pipeline, which only processes files.
+async fn stage(uploads: &Uploads, image: Option<&[u8]>) -> std::io::Result<Option<PathBuf>> {
+    match image {
+        Some(bytes) => uploads.write(bytes).await.map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Log the error, which is only shown to the importer as an internal error.
+fn internal(err: impl std::fmt::Display) -> String {
+    warn!("Failed to import a blog post: {}", err);
+    AppError::Internal.to_string()
+}
+
+/// Writes files in the tar format to the channel which the archive is streamed from.
+struct TarWriter {
+    sender: mpsc::Sender<Result<Bytes, std::io::Error>>,
+}
+
+impl TarWriter {
+    /// Write a regular file with the contents to the archive.
+    async fn write_file(&mut self, path: &str, contents: Bytes, mtime: u64) -> Result<(), ExportError> {
+        let mut header = tar::Header::new_ustar();
+        header.set_path(path)?;
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(mtime);
+        header.set_entry_type(tar::EntryType::Regular);
+        header.set_cksum();
+        let padding = (BLOCK_SIZE - contents.len() % BLOCK_SIZE) % BLOCK_SIZE;
+        self.send(Bytes::copy_from_slice(header.as_bytes())).await?;
+        self.send(contents).await?;
+        self.send(Bytes::from(vec![0; padding])).await
+    }
+
+    /// End the archive with the two empty blocks which mark the end of a tar file.
+    async fn finish(&mut self) -> Result<(), ExportError> {
+        self.send(Bytes::from(vec![0; 2 * BLOCK_SIZE])).await
+    }
+
+    async fn send(&mut self, chunk: Bytes) -> Result<(), ExportError> {
+        if chunk.is_empty() {
+            return Ok(());
+        }
+        self.sender.send(Ok(chunk)).await.map_err(|_| ExportError::Disconnected)
+    }
+}
@@ -0,0 +1,170 @@
+//! The stylesheet, script and WebAssembly bundle of the client, served under names which include the hash of their content.
+//! Browsers may cache assets under such names indefinitely, and still load a new version as soon as it is deployed,
+//! since the pages then link to a different name. The pages are rewritten to link to the hashed names as they are served.
+
+use crate::server::opengraph::{is_html_page, MAX_PAGE_BYTES};
+use axum::body::Body;
+use axum::extract::{Path, Request};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use http::header::{CACHE_CONTROL, CONTENT_LENGTH, CONTENT_TYPE};
+use http::StatusCode;
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path as FilePath, PathBuf};
+use std::sync::Arc;
+use tokio_util::io::ReaderStream;
+use tracing::warn;
+
+/// The directory which the client is built into, as configured by `out_dir` in `Dioxus.toml`.
+pub const ASSET_DIR: &str = "dist";
+
+/// The path under which the assets are served by their hashed names.
+const PREFIX: &str = "/static/";
+
+/// The extensions of the files which are served by their hashed names.
+/// Other files, such as the favicon, are linked from places which cannot be rewritten and are served as they are.
+const EXTENSIONS: [&str; 3] = ["css", "js", "wasm"];
+
+/// The content of an asset never changes under its hashed name, so browsers may cache it indefinitely.
+const ASSET_CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+
+/// The assets of the client by their hashed names, which are built once on startup.
+#[derive(Debug, Clone, Default)]
+pub struct StaticAssets(Arc<Manifest>);
+
+#[derive(Debug, Default)]
+struct Manifest {
+    /// The URL of each asset as linked by the pages and the URL of its hashed name, e.g. `/main.css`
+    /// and `/static/main.1a2b3c4d5e6f7a8b.css`.
+    urls: Vec<(String, String)>,
+    /// The file of each asset by its hashed name.
+    files: HashMap<String, PathBuf>,
+}
+
+impl StaticAssets {
+    /// Hash the assets in the directory.
+    /// If the directory does not exist, e.g. because the client is served by the Dioxus CLI, there are no assets.
+    /// Returns an error if the directory or one of its assets cannot be read.
+    pub async fn load(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        tokio::task::spawn_blocking(move || {
+            let mut manifest = Manifest::default();
+            for path in asset_files(&dir)? {
+                let Ok(relative) = path.strip_prefix(&dir) else {
+                    continue;
+                };
+                let relative = relative.to_string_lossy().replace('\\', "/");
+                let hash = blake3::hash(&std::fs::read(&path)?).to_hex();
+                let hashed = hashed_name(&relative, &hash[..16]);
+                let hashed_url = format!("{PREFIX}{hashed}");
+                // The Dioxus CLI links the bundle as `/./assets/...`, and the stylesheet as `/main.css`
+                manifest.urls.push((format!("/./{relative}"), hashed_url.clone()));
+                manifest.urls.push((format!("/{relative}"), hashed_url));
+                manifest.files.insert(hashed, path);
+            }
+            Ok(Self(Arc::new(manifest)))
+        })
+        .await
+        .expect("hashing assets should not panic")
+    }
+
+    /// The number of assets which are served by their hashed names.
+    pub fn len(&self) -> usize {
+        self.0.files.len()
+    }
+
+    /// Whether there are no assets, in which case pages are served as they are.
+    pub fn is_empty(&self) -> bool {
+        self.0.files.is_empty()
+    }
+
+    /// Replace the links to the assets in the page with links to their hashed names.
+    /// Only quoted links are replaced, so that the URL of an asset which is the prefix of another is not mistaken for it.
+    fn link_hashed(&self, page: &str) -> String {
+        self.0.urls.iter().fold(page.to_string(), |page, (url, hashed_url)| {
+            page.replace(&format!("\"{url}\""), &format!("\"{hashed_url}\""))
+        })
+    }
+}
+
+/// The files in the directory and its subdirectories with one of the hashed extensions.
+/// Returns no files if the directory does not exist.
+fn asset_files(dir: &FilePath) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(err),
+        };
+        for entry in entries {
+            let path = entry?.path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if path
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .is_some_and(|extension| EXTENSIONS.contains(&extension))
+            {
+                files.push(path);
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Insert the hash into the name of the file before its extension, e.g. `main.css` becomes `main.1a2b3c4d5e6f7a8b.css`.
+fn hashed_name(name: &str, hash: &str) -> String {
+    match name.rsplit_once('.') {
+        Some((stem, extension)) => format!("{stem}.{hash}.{extension}"),
+        None => format!("{name}.{hash}"),
+    }
+}
+
+/// The MIME type of the asset, based on its extension.
+fn content_type(name: &str) -> &'static str {
+    match name.rsplit_once('.').map(|(_, extension)| extension) {
+        Some("css") => "text/css; charset=utf-8",
+        Some("js") => "text/javascript; charset=utf-8",
+        Some("wasm") => "application/wasm",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Stream the asset with the hashed name with a long-lived cache header.
+/// Responds with `404 Not Found` if there is no asset with the name, e.g. because it is from a previous deployment.
+pub async fn serve_asset(assets: StaticAssets, Path(name): Path<String>) -> Result<Response, StatusCode> {
+    let path = assets.0.files.get(&name).ok_or(StatusCode::NOT_FOUND)?;
+    let file = tokio::fs::File::open(path).await.map_err(|err| {
+        warn!("Failed to open asset {}: {}", path.display(), err);
+        StatusCode::NOT_FOUND
+    })?;
+    let headers = [(CONTENT_TYPE, content_type(&name)), (CACHE_CONTROL, ASSET_CACHE_CONTROL)];
+    Ok((headers, Body::from_stream(ReaderStream::new(file))).into_response())
+}
+
+/// Middleware which rewrites the links to the assets in every page to link to their hashed names.
+/// All other responses are passed through unchanged.
+pub async fn link_hashed_assets(assets: StaticAssets, request: Request, next: Next) -> Response {
+    let response = next.run(request).await;
+    if assets.is_empty() || !is_html_page(&response) {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    let page = match axum::body::to_bytes(body, MAX_PAGE_BYTES).await {
+        Ok(page) => page,
+        Err(err) => {
+            // The body is consumed, so there is nothing left to pass through
+            warn!("Failed to buffer page to link hashed assets: {}", err);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let page = match std::str::from_utf8(&page) {
+        Ok(text) => assets.link_hashed(text),
+        Err(_) => return Response::from_parts(parts, Body::from(page)),
+    };
+    parts.headers.remove(CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(page))
+}
@@ -0,0 +1,62 @@
+//! The audit log, which records who created, changed or deleted which blog posts, when and from where,
+//! as well as the administrative changes of user data and API keys.
+//! Entries are written once a change has been made, and failing to write one does not undo or fail the change.
+//! Summaries describe changes without the text of blog posts, so that the log keeps no content which was deleted.
+
+use crate::model::{AuditAction, BlogPostId, InsertAuditEntry, User};
+use crate::server::Database;
+use std::net::IpAddr;
+use time::{OffsetDateTime, PrimitiveDateTime};
+use tracing::error;
+
+/// Writes entries to the audit log.
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+    database: Database,
+}
+
+impl AuditLog {
+    /// Create an audit log which writes its entries to the database.
+    pub fn new(database: Database) -> Self {
+        Self { database }
+    }
+
+    /// Record that the user made the change from the IP address, with one entry for each of the blog posts,
+    /// or a single entry if the change was not to blog posts.
+    pub async fn record(&self, actor: &User, ip: IpAddr, action: AuditAction, post_ids: &[BlogPostId], summary: &str) {
+        self.write(Some(actor.username.clone()), Some(ip.to_string()), action, post_ids, summary).await;
+    }
+
+    /// Record that the server made the change on its own, e.g. when purging the trash.
+    pub async fn record_server(&self, action: AuditAction, post_ids: &[BlogPostId], summary: &str) {
+        self.write(None, None, action, post_ids, summary).await;
+    }
+
+    async fn write(
+        &self,
+        actor: Option<String>,
+        ip: Option<String>,
+        action: AuditAction,
+        post_ids: &[BlogPostId],
+        summary: &str,
+    ) {
+        let now = OffsetDateTime::now_utc();
+        let occurred_at = PrimitiveDateTime::new(now.date(), now.time());
+        let entry = |post_id| InsertAuditEntry {
+            action: action.as_str().to_string(),
+            actor: actor.clone(),
+            ip: ip.clone(),
+            post_id,
+            summary: summary.to_string(),
+            occurred_at,
+        };
+        let entries = match post_ids {
+            [] => vec![entry(None)],
+            post_ids => post_ids.iter().map(|&post_id| entry(Some(post_id))).collect(),
+        };
+        // The change has already been made, so it is only logged if it cannot be recorded
+        if let Err(err) = self.database.record_audit(entries).await {
+            error!("Failed to record {} of {:?} by {:?} in the audit log: {}", action.as_str(), post_ids, actor, err);
+        }
+    }
+}
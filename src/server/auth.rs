@@ -0,0 +1,71 @@
+//! Authentication utilities for the server.
+
+use crate::error::AppError;
+use crate::model::{Credentials, User};
+use crate::server::Database;
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// Hash the password with Argon2 and a freshly generated salt.
+/// Returns the hash in PHC string format, which also encodes the salt and the Argon2 parameters.
+pub async fn hash_password(password: String) -> Result<String, argon2::password_hash::Error> {
+    // Hashing is deliberately expensive, so keep it off the async runtime
+    tokio::task::spawn_blocking(move || {
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+    })
+    .await
+    .expect("hashing should not panic")
+}
+
+/// Verify the password against a hash in PHC string format.
+/// Returns `false` if the password does not match or the hash is malformed.
+pub async fn verify_password(password: String, hash: String) -> bool {
+    tokio::task::spawn_blocking(move || {
+        PasswordHash::new(&hash)
+            .map(|hash| Argon2::default().verify_password(password.as_bytes(), &hash).is_ok())
+            .unwrap_or(false)
+    })
+    .await
+    .expect("verifying should not panic")
+}
+
+/// A hash which no password matches, with the parameters of `Argon2::default` which all passwords are hashed with,
+/// so that verifying a password against it takes as long as against the hash of an account.
+const DUMMY_PASSWORD_HASH: &str =
+    "$argon2id$v=19$m=19456,t=2,p=1$ZHVtbXktc2FsdC0xNi1ieQ$AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8";
+
+/// Find the user with the credentials, which the caller then logs in to their session.
+/// Returns `AppError::InvalidCredentials` both for unknown usernames and wrong passwords,
+/// so that it cannot be told which accounts exist, neither by the error nor by how long it takes.
+pub async fn authenticate(database: &Database, credentials: Credentials) -> Result<User, AppError> {
+    let Some((user, password_hash)) = database.fetch_credentials(credentials.username).await? else {
+        // Unknown usernames are verified all the same, so that they are not answered faster than wrong passwords
+        verify_password(credentials.password, DUMMY_PASSWORD_HASH.to_string()).await;
+        return Err(AppError::InvalidCredentials);
+    };
+    if !verify_password(credentials.password, password_hash).await {
+        return Err(AppError::InvalidCredentials);
+    }
+    Ok(user)
+}
+
+/// The usernames of the users who may perform administrative tasks.
+#[derive(Debug, Clone, Default)]
+pub struct Admins(Arc<HashSet<String>>);
+
+impl Admins {
+    pub fn new(usernames: impl IntoIterator<Item = String>) -> Self {
+        Self(Arc::new(usernames.into_iter().collect()))
+    }
+
+    /// Returns whether the user may perform administrative tasks.
+    pub fn contains(&self, user: &User) -> bool {
+        self.0.contains(&user.username)
+    }
+}
@@ -0,0 +1,74 @@
+//! Cache of the avatars downloaded from URLs, so that an author who gives the same avatar URL for every blog post
+//! does not cause it to be downloaded and processed again each time.
+//! The cache only remembers which stored image the URL led to, since identical images are stored only once anyway.
+
+use crate::model::AvatarImagePath;
+use crate::server::images::{self, ImagePolicy};
+use crate::server::storage::ImageStore;
+use crate::server::Database;
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+use time::{OffsetDateTime, PrimitiveDateTime};
+use tracing::{debug, instrument, warn};
+
+/// How long the avatar downloaded from a URL is reused, after which the URL is downloaded again,
+/// in case the image behind it was changed.
+pub const AVATAR_CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Remembers the avatars downloaded from URLs.
+/// Failures to read or write the cache are logged and otherwise ignored, since the avatar can always be downloaded.
+#[derive(Debug, Clone)]
+pub struct AvatarCache {
+    database: Database,
+}
+
+impl AvatarCache {
+    pub fn new(database: Database) -> Self {
+        Self { database }
+    }
+
+    /// Returns the avatar downloaded from the URL within the `AVATAR_CACHE_TTL`, if it is still stored,
+    /// in which case it is renewed like any other reused image.
+    /// Cached avatars which were deleted in the meantime, e.g. by the garbage collector, are downloaded again.
+    #[instrument(level = "info", skip(self, store, policy))]
+    pub async fn get(&self, store: &dyn ImageStore, policy: ImagePolicy, url: &str) -> Option<AvatarImagePath> {
+        let cached = self.database.fetch_cached_avatar(url_hash(url), cutoff()).await;
+        let avatar = match cached {
+            Ok(avatar) => avatar?,
+            Err(err) => {
+                warn!("Failed to look up the cached avatar of {}: {}", url, err);
+                return None;
+            }
+        };
+        match images::renew(store, policy, &avatar).await {
+            Ok(true) => {
+                debug!("Reusing cached avatar {:?}", avatar);
+                Some(avatar)
+            }
+            Ok(false) => None,
+            Err(err) => {
+                warn!("Failed to check whether the cached avatar {:?} is stored: {}", avatar, err);
+                None
+            }
+        }
+    }
+
+    /// Remember the avatar downloaded from the URL, so that it is reused for the `AVATAR_CACHE_TTL`.
+    #[instrument(level = "info", skip(self))]
+    pub async fn put(&self, url: &str, avatar: &AvatarImagePath) {
+        if let Err(err) = self.database.cache_avatar(url_hash(url), avatar.clone(), cutoff()).await {
+            warn!("Failed to cache the avatar of {}: {}", url, err);
+        }
+    }
+}
+
+/// Returns the hex-encoded SHA-256 hash of the URL, which keeps the keys of the cache short however long the URL is.
+fn url_hash(url: &str) -> String {
+    format!("{:x}", Sha256::digest(url.as_bytes()))
+}
+
+/// Returns the time before which cached avatars are no longer reused.
+fn cutoff() -> PrimitiveDateTime {
+    let now = OffsetDateTime::now_utc();
+    PrimitiveDateTime::new(now.date(), now.time()) - AVATAR_CACHE_TTL
+}
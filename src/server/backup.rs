@@ -0,0 +1,142 @@
+//! Scheduled backups of the database and the images to a local directory or an S3 bucket.
+//! Every backup writes a snapshot of the SQLite database to `database/<time>.sqlite`, of which only the most recent
+//! are kept, and copies the images which the target does not have yet to `images/<key>`.
+//! Images are never deleted from the target, so that every kept snapshot can be restored along with its images.
+//! PostgreSQL databases are not snapshotted, since they are backed up with their own tools, e.g. `pg_dump`.
+
+use crate::server::config::BackupConfig;
+use crate::server::jobs::Job;
+use crate::server::persistence::database::DatabaseError;
+use crate::server::storage::{ImageStorage, StorageError};
+use crate::server::Database;
+use axum::async_trait;
+use std::collections::HashSet;
+use time::macros::format_description;
+use time::OffsetDateTime;
+use tracing::{info, instrument, warn};
+use uuid::Uuid;
+
+/// The directory of the database snapshots in the target.
+const DATABASE_DIR: &str = "database";
+
+/// The directory of the images in the target, under which they keep their keys in the image store.
+const IMAGES_DIR: &str = "images";
+
+/// The extension of the database snapshots.
+const SNAPSHOT_EXTENSION: &str = "sqlite";
+
+/// Why a backup failed.
+#[derive(Debug, derive_more::Display, derive_more::From)]
+pub enum BackupError {
+    #[display("Failed to snapshot the database: {}", _0)]
+    Database(DatabaseError),
+    #[display("Failed to access the images or the backup target: {}", _0)]
+    Storage(StorageError),
+    #[display("Failed to read the database snapshot: {}", _0)]
+    Io(std::io::Error),
+}
+
+/// Backs up the database and the images to the configured target.
+#[derive(Debug, Clone)]
+pub struct Backups {
+    database: Database,
+    images: ImageStorage,
+    target: ImageStorage,
+    prefix: String,
+    retention: usize,
+}
+
+impl Backups {
+    /// Create the backups described by the configuration.
+    /// Returns `StorageError::S3` or `StorageError::Credentials` if the S3 bucket or its credentials are misconfigured.
+    pub fn new(database: Database, images: ImageStorage, config: &BackupConfig) -> Result<Self, StorageError> {
+        let target = ImageStorage::from_config(&config.target)?;
+        Ok(Self { database, images, target, prefix: config.prefix.clone(), retention: config.retention })
+    }
+
+    /// Snapshot the database, delete the snapshots beyond the retention and copy the new images to the target.
+    /// Returns the number of copied images.
+    #[instrument(level = "info", skip(self))]
+    pub async fn back_up(&self) -> Result<usize, BackupError> {
+        let existing: HashSet<String> = self.target.list().await?.into_iter().map(|object| object.key).collect();
+        if self.snapshot_database().await? {
+            self.delete_old_snapshots(&existing).await?;
+        } else {
+            info!("Not snapshotting the database, which is not SQLite");
+        }
+        let mut copied = 0;
+        for image in self.images.list().await? {
+            let key = format!("{}{IMAGES_DIR}/{}", self.prefix, image.key);
+            if existing.contains(&key) {
+                continue;
+            }
+            // The image was deleted since it was listed, which leaves nothing to back up
+            let Some(body) = self.images.get(&image.key).await? else {
+                continue;
+            };
+            let bytes = axum::body::to_bytes(body, usize::MAX).await.map_err(std::io::Error::other)?;
+            self.target.put(&key, bytes.to_vec()).await?;
+            copied += 1;
+        }
+        info!("Backed up the database and {} new images", copied);
+        Ok(copied)
+    }
+
+    /// Write a snapshot of the database to the target, by way of a temporary file.
+    /// Returns `false` if the database cannot be snapshotted because it is not SQLite.
+    async fn snapshot_database(&self) -> Result<bool, BackupError> {
+        let path = std::env::temp_dir().join(format!("blog-backup-{}.{SNAPSHOT_EXTENSION}", Uuid::new_v4()));
+        let snapshot = async {
+            if !self.database.snapshot(path.clone()).await? {
+                return Ok(false);
+            }
+            let bytes = tokio::fs::read(&path).await?;
+            let now = OffsetDateTime::now_utc();
+            // The times sort like the snapshots were made, so that the oldest can be found by name
+            let time = now
+                .format(format_description!("[year][month][day]T[hour][minute][second]Z"))
+                .expect("the current time should be formattable");
+            let key = format!("{}{DATABASE_DIR}/{time}.{SNAPSHOT_EXTENSION}", self.prefix);
+            self.target.put(&key, bytes).await?;
+            info!("Wrote database snapshot {}", key);
+            Ok(true)
+        }
+        .await;
+        match tokio::fs::remove_file(&path).await {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                warn!("Failed to delete the temporary database snapshot {}: {}", path.display(), err);
+            }
+            _ => {}
+        }
+        snapshot
+    }
+
+    /// Delete the oldest snapshots in the target, so that only the retained number is left besides the new one.
+    async fn delete_old_snapshots(&self, existing: &HashSet<String>) -> Result<(), StorageError> {
+        let dir = format!("{}{DATABASE_DIR}/", self.prefix);
+        let extension = format!(".{SNAPSHOT_EXTENSION}");
+        let mut snapshots: Vec<&String> =
+            existing.iter().filter(|key| key.starts_with(&dir) && key.ends_with(&extension)).collect();
+        snapshots.sort();
+        let excess = (snapshots.len() + 1).saturating_sub(self.retention);
+        for key in snapshots.into_iter().take(excess) {
+            self.target.delete(key).await?;
+            info!("Deleted database snapshot {}", key);
+        }
+        Ok(())
+    }
+}
+
+/// Backups are scheduled as a background job.
+#[async_trait]
+impl Job for Backups {
+    type Error = BackupError;
+
+    fn name(&self) -> &'static str {
+        "backup"
+    }
+
+    async fn run(&self) -> Result<(), BackupError> {
+        self.back_up().await.map(|_| ())
+    }
+}
@@ -0,0 +1,105 @@
+//! Proof-of-work challenges which make it expensive for bots to create blog posts in bulk.
+//! Challenges are stateless tokens signed by the server, so issuing them stores nothing;
+//! only the tokens of solved challenges are remembered until they expire, so that each can be used once.
+
+use crate::model::{challenge_hash_bits, Challenge, ChallengeSolution};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// How long a challenge can be solved and used after it was issued.
+const CHALLENGE_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Errors that can occur when verifying the solution to a challenge.
+#[derive(Debug, derive_more::Display, derive_more::Error)]
+pub enum ChallengeError {
+    #[display("The challenge is malformed")]
+    Malformed,
+    #[display("The challenge was not issued by this server")]
+    Forged,
+    #[display("The challenge expired, please try again")]
+    Expired,
+    #[display("The challenge was not solved")]
+    Unsolved,
+    #[display("The challenge was already used")]
+    Reused,
+}
+
+/// Issues proof-of-work challenges and verifies their solutions.
+/// Clones share the same signing key and remember the same used challenges.
+#[derive(Clone)]
+pub struct ChallengeIssuer {
+    key: [u8; 32],
+    difficulty: u32,
+    used: Arc<Mutex<HashMap<String, Instant>>>,
+}
+
+/// The signing key is deliberately left out.
+impl std::fmt::Debug for ChallengeIssuer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChallengeIssuer")
+            .field("difficulty", &self.difficulty)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ChallengeIssuer {
+    /// Create an issuer with a random signing key, so that challenges are only valid until the server restarts.
+    pub fn new(difficulty: u32) -> Self {
+        let mut key = [0; 32];
+        key[..16].copy_from_slice(uuid::Uuid::new_v4().as_bytes());
+        key[16..].copy_from_slice(uuid::Uuid::new_v4().as_bytes());
+        Self { key, difficulty, used: Arc::default() }
+    }
+
+    /// Issue a new challenge.
+    /// The token consists of the time of issue, a random value and the signature of both.
+    pub fn issue(&self) -> Challenge {
+        let issued_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("system time should be after the epoch")
+            .as_secs();
+        let payload = format!("{issued_at}.{}", uuid::Uuid::new_v4().simple());
+        let signature = self.sign(&payload);
+        Challenge {
+            token: format!("{payload}.{}", signature.to_hex()),
+            difficulty: self.difficulty,
+        }
+    }
+
+    /// Verify that the challenge was issued by this server, has not expired, is solved and was not used before.
+    /// Marks the challenge as used if it is valid.
+    pub fn verify(&self, solution: &ChallengeSolution) -> Result<(), ChallengeError> {
+        let (payload, signature) = solution.token.rsplit_once('.').ok_or(ChallengeError::Malformed)?;
+        let signature = blake3::Hash::from_hex(signature).map_err(|_| ChallengeError::Malformed)?;
+        // Comparing hashes takes constant time, so the signature cannot be guessed byte by byte
+        if self.sign(payload) != signature {
+            return Err(ChallengeError::Forged);
+        }
+        let issued_at: u64 = payload
+            .split_once('.')
+            .and_then(|(issued_at, _)| issued_at.parse().ok())
+            .ok_or(ChallengeError::Malformed)?;
+        let age = SystemTime::now()
+            .duration_since(UNIX_EPOCH + Duration::from_secs(issued_at))
+            .unwrap_or_default();
+        if age > CHALLENGE_TTL {
+            return Err(ChallengeError::Expired);
+        }
+        if challenge_hash_bits(&solution.token, solution.nonce) < self.difficulty {
+            return Err(ChallengeError::Unsolved);
+        }
+        let now = Instant::now();
+        let mut used = self.used.lock().expect("challenge lock should not be poisoned");
+        // Tokens which have expired are rejected before getting here, so they no longer need to be remembered
+        used.retain(|_, used_at| now - *used_at < CHALLENGE_TTL);
+        if used.insert(solution.token.clone(), now).is_some() {
+            return Err(ChallengeError::Reused);
+        }
+        Ok(())
+    }
+
+    fn sign(&self, payload: &str) -> blake3::Hash {
+        blake3::keyed_hash(&self.key, payload.as_bytes())
+    }
+}
@@ -0,0 +1,159 @@
+//! The IP address of the client of a request, which reverse proxies in front of the server pass along in a header.
+//! Behind a proxy, every request comes from the address of the proxy, so the address in the `Forwarded` or
+//! `X-Forwarded-For` header is used instead, but only if the request comes from one of the configured trusted proxies.
+//! Anyone can send these headers, so the addresses in them are read from the right, where the trusted proxies
+//! appended them, and the first address which is not a trusted proxy is taken as the client.
+
+use axum::async_trait;
+use axum::extract::{ConnectInfo, FromRequestParts};
+use http::header::FORWARDED;
+use http::request::Parts;
+use http::{HeaderMap, StatusCode};
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::sync::Arc;
+use tracing::error;
+
+/// The non-standard but more common header in which proxies pass along the addresses of the client and proxies.
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+
+/// A range of IP addresses in CIDR notation, e.g. `10.0.0.0/8`, or a single address, e.g. `127.0.0.1`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IpNetwork {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpNetwork {
+    /// Whether the address is in this range.
+    /// IPv4 addresses mapped to IPv6, as dual-stack listeners report them, are matched as IPv4 addresses.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                prefix_matches(&network.octets(), &ip.octets(), self.prefix_len)
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                prefix_matches(&network.octets(), &ip.octets(), self.prefix_len)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for IpNetwork {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, String> {
+        let (addr, prefix_len) = match value.split_once('/') {
+            Some((addr, prefix_len)) => (addr, Some(prefix_len)),
+            None => (value, None),
+        };
+        let addr: IpAddr = addr.parse().map_err(|err| format!("'{value}' is not an IP address: {err}"))?;
+        let max_len = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match prefix_len {
+            Some(prefix_len) => prefix_len
+                .parse()
+                .ok()
+                .filter(|&prefix_len| prefix_len <= max_len)
+                .ok_or_else(|| format!("'{value}' has a prefix length which is not between 0 and {max_len}"))?,
+            None => max_len,
+        };
+        Ok(Self { addr, prefix_len })
+    }
+}
+
+/// Whether the first `prefix_len` bits of both addresses are equal.
+fn prefix_matches(network: &[u8], ip: &[u8], prefix_len: u8) -> bool {
+    let whole_bytes = usize::from(prefix_len / 8);
+    let remaining_bits = prefix_len % 8;
+    if network[..whole_bytes] != ip[..whole_bytes] {
+        return false;
+    }
+    if remaining_bits == 0 {
+        return true;
+    }
+    let mask = 0xFFu8 << (8 - remaining_bits);
+    network[whole_bytes] & mask == ip[whole_bytes] & mask
+}
+
+/// The reverse proxies whose headers are trusted to tell the address of the client.
+/// Without any, the address of the peer is always the address of the client.
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies(Arc<Vec<IpNetwork>>);
+
+impl TrustedProxies {
+    pub fn new(networks: Vec<IpNetwork>) -> Self {
+        Self(Arc::new(networks))
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        self.0.iter().any(|network| network.contains(ip))
+    }
+
+    /// The address of the client of a request from the peer with the headers.
+    /// Returns the peer if it is not a trusted proxy, or the rightmost forwarded address which is not one.
+    /// If every forwarded address is a trusted proxy, the leftmost is the client.
+    pub fn client_ip(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        if !self.contains(peer) {
+            return peer.to_canonical();
+        }
+        let forwarded = forwarded_for(headers);
+        let client = forwarded.iter().rev().find(|ip| !self.contains(**ip)).or(forwarded.first());
+        client.copied().unwrap_or(peer).to_canonical()
+    }
+}
+
+/// The addresses which the proxies passed along, from the client to the last proxy.
+/// The standard `Forwarded` header takes precedence over `X-Forwarded-For`.
+/// Obfuscated identifiers, unknown clients and values which fail to parse are skipped.
+fn forwarded_for(headers: &HeaderMap) -> Vec<IpAddr> {
+    let (values, parse): (_, fn(&str) -> Option<&str>) = if headers.contains_key(FORWARDED) {
+        (headers.get_all(FORWARDED), forwarded_element_for)
+    } else {
+        (headers.get_all(X_FORWARDED_FOR), |element| Some(element.trim()))
+    };
+    values
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(parse)
+        .filter_map(parse_forwarded_addr)
+        .collect()
+}
+
+/// The node of the `for` parameter of an element of the `Forwarded` header, e.g. `192.0.2.60` of
+/// `for=192.0.2.60;proto=http`, or `None` if the element has none.
+fn forwarded_element_for(element: &str) -> Option<&str> {
+    element.split(';').find_map(|pair| {
+        let (name, value) = pair.split_once('=')?;
+        name.trim().eq_ignore_ascii_case("for").then(|| value.trim().trim_matches('"'))
+    })
+}
+
+/// Parse a forwarded address, which may carry a port, e.g. `192.0.2.60:4711` or `[2001:db8::1]:4711`.
+fn parse_forwarded_addr(addr: &str) -> Option<IpAddr> {
+    addr.parse::<IpAddr>()
+        .or_else(|_| addr.parse::<SocketAddr>().map(|addr| addr.ip()))
+        .ok()
+        .or_else(|| addr.strip_prefix('[')?.strip_suffix(']')?.parse().ok())
+}
+
+/// The IP address of the client of the current request, as told by the trusted proxies, if any.
+/// Extracting this fails with `500 Internal Server Error` if the server is not served with connection info.
+#[derive(Debug, Clone, Copy)]
+pub struct ClientIp(pub IpAddr);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ClientIp {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, StatusCode> {
+        let Some(ConnectInfo(peer)) = parts.extensions.get::<ConnectInfo<SocketAddr>>() else {
+            error!("Failed to extract client address: the server is not served with connection info");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        };
+        let peer = peer.ip();
+        let proxies = TrustedProxies::from_request_parts(parts, state).await.unwrap_or_else(|never| match never {});
+        Ok(ClientIp(proxies.client_ip(peer, &parts.headers)))
+    }
+}
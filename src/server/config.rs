@@ -0,0 +1,740 @@
+//! Server configuration.
+//! The configuration is read from an optional TOML file, whose path is given by the `--config`
+//! command line argument or the `CONFIG_PATH` environment variable.
+//! Every field can be overridden by an environment variable, which takes precedence over the file.
+
+use crate::model::MAX_POST_LENGTH;
+use crate::server::client_ip::IpNetwork;
+use serde::Deserialize;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+use tracing_subscriber::EnvFilter;
+
+/// Errors that can occur when loading the configuration.
+#[derive(Debug, derive_more::Display, derive_more::Error)]
+pub enum ConfigError {
+    #[display("Failed to read config file '{}': {}", path.display(), source)]
+    Read { path: PathBuf, source: std::io::Error },
+    #[display("Failed to parse config file '{}': {}", path.display(), source)]
+    Parse { path: PathBuf, source: toml::de::Error },
+    #[display("Missing required config field `{}` (or environment variable `{}`)", field, var)]
+    Missing { field: &'static str, var: &'static str },
+    #[display("Invalid value for config field `{}`: {}", field, reason)]
+    Invalid { field: &'static str, reason: String },
+}
+
+/// The validated configuration of the server.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// The address the server listens on, e.g. `0.0.0.0:8080`.
+    pub host_addr: String,
+    /// The URL of the database, see `Database::try_connect`.
+    pub database_url: String,
+    /// The size of the database connection pool and how long requests wait for a connection.
+    pub db_pool: PoolConfig,
+    /// How often database writes are retried when they fail because of contention.
+    pub db_retry: RetryConfig,
+    /// How connections to an SQLite database are set up.
+    pub sqlite: SqliteConfig,
+    /// Where uploaded and downloaded images are stored.
+    pub image_store: ImageStoreConfig,
+    /// The directory where uploaded images are kept until the blog post they belong to is created.
+    /// Its contents are deleted when the server starts.
+    pub upload_dir: PathBuf,
+    /// The maximum size in bytes of a request body, which bounds the size of uploaded images.
+    pub max_upload_bytes: usize,
+    /// The maximum size in bytes of a single uploaded or downloaded image file, checked before it is decoded.
+    pub max_image_bytes: usize,
+    /// Whether blog posts with an image must describe it for people who cannot see it.
+    pub require_alt_text: bool,
+    /// Whether blog posts without an avatar get the Gravatar of their author, downloaded from gravatar.com.
+    pub gravatar_fallback: bool,
+    /// The format in which new images are stored.
+    pub image_encoding: ImageEncoding,
+    /// The length in pixels of the longest side to which browsers downscale images before uploading them,
+    /// or `None` if they are uploaded as picked.
+    pub max_image_dimension: Option<u32>,
+    /// How long downloading an avatar from its URL may take in total before it is given up.
+    pub avatar_download_timeout: Duration,
+    /// How many images may be decoded, hashed or encoded at once, beyond which image work waits its turn.
+    pub image_workers: usize,
+    /// The maximum total size in bytes of the images which are kept in memory to serve them faster, or zero to disable the cache.
+    pub image_cache_bytes: usize,
+    /// The directives which select the log messages that are emitted, in the syntax of `RUST_LOG`,
+    /// e.g. `info` for one level everywhere or `info,blogposts::server::images=trace,diesel=warn` per module.
+    pub log_filter: String,
+    /// Whether session cookies are marked `Secure`, i.e. only sent over HTTPS.
+    pub secure_cookies: bool,
+    /// The maximum number of requests to server functions which change data
+    /// that a single IP address may make within `rate_limit_window`.
+    pub rate_limit_requests: usize,
+    /// The length of the sliding window in which requests count towards `rate_limit_requests`.
+    pub rate_limit_window: Duration,
+    /// The reverse proxies whose `Forwarded` and `X-Forwarded-For` headers tell the IP address of the client,
+    /// which is used for rate limiting and logging instead of the address of the proxy.
+    pub trusted_proxies: Vec<IpNetwork>,
+    /// How long the server may take to respond to a request, including reading its body, before it gives up.
+    pub request_timeout: Duration,
+    /// The maximum number of requests which are handled at once, beyond which requests are rejected.
+    pub max_concurrent_requests: usize,
+    /// The endpoint of the OpenTelemetry collector to export spans to over OTLP, if any.
+    pub otlp_endpoint: Option<String>,
+    /// The token which scrapers must send as `Authorization: Bearer <token>` to read `/metrics`,
+    /// or `None` to serve the metrics to anyone who can reach the server.
+    pub metrics_token: Option<String>,
+    /// The proxy which outgoing requests to `https://` URLs, e.g. avatar downloads, are sent through, if any.
+    pub https_proxy: Option<String>,
+    /// The certificate and key to serve HTTPS with, or `None` to serve plain HTTP.
+    pub tls: Option<TlsConfig>,
+    /// The address of a secondary plain HTTP listener which redirects all requests to HTTPS, if any.
+    /// This is only allowed together with `tls`.
+    pub http_redirect_addr: Option<String>,
+    /// The URL under which the server is reachable from the outside, e.g. `https://blog.example.com`,
+    /// or `None` to tell it from the `Host` header of each request.
+    pub public_url: Option<String>,
+    /// The URL of the Redis server in which the feed is cached for all servers, if any.
+    /// Requires the `redis` feature.
+    pub redis_url: Option<String>,
+    /// The usernames of the users who may perform administrative tasks.
+    pub admin_usernames: Vec<String>,
+    /// How often stored images which are not referenced by any blog post are deleted.
+    pub image_gc_interval: Duration,
+    /// How long an unreferenced image is kept before it is deleted,
+    /// so that images of blog posts which are still being created are not deleted.
+    pub image_gc_grace_period: Duration,
+    /// How long deleted blog posts are kept in the trash, where they can be restored, before they are purged.
+    pub trash_retention: Duration,
+    /// The checks which the text of new and edited blog posts must pass.
+    pub content_filter: ContentFilterConfig,
+    /// The number of leading zero bits required by the proof-of-work challenge for creating blog posts.
+    /// Every additional bit doubles the work of the client, and zero disables the challenge.
+    pub challenge_difficulty: u32,
+    /// The webhooks which are notified when blog posts are created, updated or deleted.
+    pub webhooks: Vec<WebhookConfig>,
+    /// The file holding the private key with which the blog signs its ActivityPub requests,
+    /// which is created if it does not exist, or `None` to disable federation.
+    /// This requires `public_url`, since the IDs of the blog and its posts must not change.
+    pub activitypub_key_path: Option<PathBuf>,
+    /// Where and how often the database and the images are backed up, or `None` to disable backups.
+    pub backup: Option<BackupConfig>,
+}
+
+/// The settings of the database connection pool.
+#[derive(Debug, Clone, Copy)]
+pub struct PoolConfig {
+    /// The maximum number of open connections.
+    pub max_size: u32,
+    /// The number of idle connections which the pool keeps open, or `None` to keep up to `max_size` open.
+    pub min_idle: Option<u32>,
+    /// How long a query waits for a connection to become available before it fails.
+    pub connection_timeout: Duration,
+}
+
+/// How database writes which fail because of contention are retried.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryConfig {
+    /// How many times a write is retried after it failed, zero to never retry.
+    pub retries: u32,
+    /// The delay before the first retry, which doubles with every further one.
+    /// The actual delays are picked at random up to this, so that writes which collided don't collide again.
+    pub backoff: Duration,
+}
+
+/// The settings applied to every connection to an SQLite database when it is opened.
+/// They have no effect on PostgreSQL.
+#[derive(Debug, Clone, Copy)]
+pub struct SqliteConfig {
+    /// Whether the database keeps a write-ahead log, which lets readers proceed while a write is in progress.
+    pub wal: bool,
+    /// How long a connection waits for a lock held by another connection before failing with `database is locked`.
+    pub busy_timeout: Duration,
+    /// How often writes wait until they reach the disk.
+    pub synchronous: SqliteSynchronous,
+}
+
+/// The `synchronous` setting of SQLite, from the fastest to the most durable.
+/// With a write-ahead log, `Normal` can only lose the most recent transactions on power loss, never corrupt the database.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SqliteSynchronous {
+    Off,
+    Normal,
+    Full,
+    Extra,
+}
+
+impl SqliteSynchronous {
+    /// The value of the setting in a `PRAGMA synchronous` statement.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SqliteSynchronous::Off => "OFF",
+            SqliteSynchronous::Normal => "NORMAL",
+            SqliteSynchronous::Full => "FULL",
+            SqliteSynchronous::Extra => "EXTRA",
+        }
+    }
+}
+
+/// The backend in which images are stored.
+#[derive(Debug, Clone)]
+pub enum ImageStoreConfig {
+    /// Store images as files in the directory.
+    Filesystem { dir: PathBuf },
+    /// Store images as objects in the S3 bucket.
+    /// The endpoint is only needed for S3-compatible services other than AWS.
+    S3 {
+        bucket: String,
+        region: String,
+        endpoint: Option<String>,
+    },
+}
+
+/// Where and how often the database and the images are backed up, see `server::backup`.
+#[derive(Debug, Clone)]
+pub struct BackupConfig {
+    /// The directory or S3 bucket to which the backups are written.
+    pub target: ImageStoreConfig,
+    /// The prefix of the keys of the backups in the target, e.g. `blog/`, which is empty for directories.
+    pub prefix: String,
+    /// How often a backup is made.
+    pub interval: Duration,
+    /// How many snapshots of the database are kept, beyond which the oldest are deleted.
+    pub retention: usize,
+}
+
+/// The format in which images are stored after they are processed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageEncoding {
+    /// Lossless PNG, which makes photos large.
+    Png,
+    /// Lossless WebP, which is smaller than PNG.
+    WebP,
+    /// Lossy AVIF at a quality from 1 to 100, which is the smallest but the slowest to encode.
+    Avif { quality: u8 },
+}
+
+/// The paths to the PEM encoded files which are used to serve HTTPS.
+#[derive(Debug, Clone)]
+pub struct TlsConfig {
+    /// The certificate chain of the server.
+    pub cert_path: PathBuf,
+    /// The private key of the server.
+    pub key_path: PathBuf,
+}
+
+/// The checks of the content filter, which are all disabled by default except for the length limit.
+#[derive(Debug, Clone)]
+pub struct ContentFilterConfig {
+    /// The maximum number of characters of a blog post, at most `MAX_POST_LENGTH`.
+    /// Longer posts are always rejected, regardless of `action`.
+    pub max_length: usize,
+    /// Words which blog posts must not contain, matched as whole words regardless of case.
+    pub banned_words: Vec<String>,
+    /// The maximum number of links a blog post may contain, if limited.
+    pub max_links: Option<usize>,
+    /// The URL of an external moderation service which is asked about every blog post, if any.
+    pub webhook_url: Option<String>,
+    /// What happens to blog posts which fail a check.
+    pub action: FilterAction,
+}
+
+/// A webhook which is notified of post events.
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookConfig {
+    /// The URL to which the events are posted.
+    pub url: String,
+    /// The secret with which the events are signed, so that the receiver can tell they were sent by this server.
+    pub secret: String,
+}
+
+/// Keeps the secret out of the logs.
+impl std::fmt::Debug for WebhookConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WebhookConfig").field("url", &self.url).finish_non_exhaustive()
+    }
+}
+
+/// What happens to a blog post which fails the content filter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FilterAction {
+    /// The blog post is not saved.
+    Reject,
+    /// The blog post is saved hidden, so that an administrator can review it.
+    Queue,
+}
+
+/// The configuration as it appears in the config file, where every field is optional.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    host_addr: Option<String>,
+    database_url: Option<String>,
+    db_pool_max_size: Option<u32>,
+    db_pool_min_idle: Option<u32>,
+    db_pool_timeout_secs: Option<u64>,
+    db_retries: Option<u32>,
+    db_retry_backoff_ms: Option<u64>,
+    sqlite_wal: Option<bool>,
+    sqlite_busy_timeout_ms: Option<u64>,
+    sqlite_synchronous: Option<String>,
+    image_store: Option<String>,
+    image_dir: Option<PathBuf>,
+    upload_dir: Option<PathBuf>,
+    s3_bucket: Option<String>,
+    s3_region: Option<String>,
+    s3_endpoint: Option<String>,
+    max_upload_bytes: Option<usize>,
+    max_image_bytes: Option<usize>,
+    require_alt_text: Option<bool>,
+    gravatar_fallback: Option<bool>,
+    image_format: Option<String>,
+    image_quality: Option<u8>,
+    max_image_dimension: Option<u32>,
+    avatar_download_timeout_secs: Option<u64>,
+    image_workers: Option<usize>,
+    image_cache_bytes: Option<usize>,
+    log_level: Option<String>,
+    secure_cookies: Option<bool>,
+    rate_limit_requests: Option<usize>,
+    rate_limit_window_secs: Option<u64>,
+    trusted_proxies: Option<Vec<String>>,
+    request_timeout_secs: Option<u64>,
+    max_concurrent_requests: Option<usize>,
+    otlp_endpoint: Option<String>,
+    metrics_token: Option<String>,
+    https_proxy: Option<String>,
+    tls_cert_path: Option<PathBuf>,
+    tls_key_path: Option<PathBuf>,
+    http_redirect_addr: Option<String>,
+    public_url: Option<String>,
+    redis_url: Option<String>,
+    admin_usernames: Option<Vec<String>>,
+    image_gc_interval_secs: Option<u64>,
+    image_gc_grace_secs: Option<u64>,
+    trash_retention_days: Option<u64>,
+    banned_words: Option<Vec<String>>,
+    max_links_per_post: Option<usize>,
+    max_post_length: Option<usize>,
+    content_filter_webhook_url: Option<String>,
+    content_filter_action: Option<String>,
+    challenge_difficulty: Option<u32>,
+    webhooks: Option<Vec<WebhookConfig>>,
+    activitypub_key_path: Option<PathBuf>,
+    backup_target: Option<String>,
+    backup_interval_secs: Option<u64>,
+    backup_retention: Option<usize>,
+}
+
+impl Config {
+    /// Load the configuration from the config file, if any, and the environment variables.
+    /// Returns an error naming the offending field if a value is missing or invalid.
+    pub fn load() -> Result<Self, ConfigError> {
+        let file = match config_path() {
+            Some(path) => read_file(&path)?,
+            None => ConfigFile::default(),
+        };
+        let host_addr = env_override(file.host_addr, "host_addr", "HOST_ADDR")?
+            .unwrap_or_else(|| "0.0.0.0:8080".to_string());
+        let database_url = env_override(file.database_url, "database_url", "DATABASE_URL")?
+            .ok_or(ConfigError::Missing { field: "database_url", var: "DATABASE_URL" })?;
+        let db_pool_max_size = env_override(file.db_pool_max_size, "db_pool_max_size", "DB_POOL_MAX_SIZE")?
+            .unwrap_or(5);
+        let db_pool_min_idle = env_override(file.db_pool_min_idle, "db_pool_min_idle", "DB_POOL_MIN_IDLE")?;
+        let db_pool_timeout_secs = env_override(file.db_pool_timeout_secs, "db_pool_timeout_secs", "DB_POOL_TIMEOUT_SECS")?
+            .unwrap_or(30);
+        let db_retries = env_override(file.db_retries, "db_retries", "DB_RETRIES")?
+            .unwrap_or(3);
+        let db_retry_backoff_ms = env_override(file.db_retry_backoff_ms, "db_retry_backoff_ms", "DB_RETRY_BACKOFF_MS")?
+            .unwrap_or(25);
+        let sqlite_wal = env_override(file.sqlite_wal, "sqlite_wal", "SQLITE_WAL")?
+            .unwrap_or(true);
+        let sqlite_busy_timeout_ms = env_override(file.sqlite_busy_timeout_ms, "sqlite_busy_timeout_ms", "SQLITE_BUSY_TIMEOUT_MS")?
+            .unwrap_or(5000);
+        let sqlite_synchronous = env_override(file.sqlite_synchronous, "sqlite_synchronous", "SQLITE_SYNCHRONOUS")?;
+        let image_store = env_override(file.image_store, "image_store", "IMAGE_STORE")?;
+        let image_dir = env_override(file.image_dir, "image_dir", "IMAGE_DIR")?
+            .unwrap_or_else(|| PathBuf::from("./images"));
+        let upload_dir = env_override(file.upload_dir, "upload_dir", "UPLOAD_DIR")?
+            .unwrap_or_else(|| std::env::temp_dir().join("blogposts-uploads"));
+        let s3_bucket = env_override(file.s3_bucket, "s3_bucket", "S3_BUCKET")?;
+        let s3_region = env_override(file.s3_region, "s3_region", "S3_REGION")?
+            .unwrap_or_else(|| "us-east-1".to_string());
+        let s3_endpoint = env_override(file.s3_endpoint, "s3_endpoint", "S3_ENDPOINT")?;
+        let max_upload_bytes = env_override(file.max_upload_bytes, "max_upload_bytes", "MAX_UPLOAD_BYTES")?
+            .unwrap_or(10 * 1024 * 1024);
+        let max_image_bytes = env_override(file.max_image_bytes, "max_image_bytes", "MAX_IMAGE_BYTES")?
+            .unwrap_or(5 * 1024 * 1024);
+        let require_alt_text = env_override(file.require_alt_text, "require_alt_text", "REQUIRE_ALT_TEXT")?
+            .unwrap_or(true);
+        let gravatar_fallback = env_override(file.gravatar_fallback, "gravatar_fallback", "GRAVATAR_FALLBACK")?
+            .unwrap_or(false);
+        let image_format = env_override(file.image_format, "image_format", "IMAGE_FORMAT")?;
+        let image_quality = env_override(file.image_quality, "image_quality", "IMAGE_QUALITY")?
+            .unwrap_or(80);
+        let max_image_dimension = env_override(file.max_image_dimension, "max_image_dimension", "MAX_IMAGE_DIMENSION")?
+            .unwrap_or(2048);
+        let avatar_download_timeout_secs = env_override(file.avatar_download_timeout_secs, "avatar_download_timeout_secs", "AVATAR_DOWNLOAD_TIMEOUT_SECS")?
+            .unwrap_or(10);
+        let image_workers = env_override(file.image_workers, "image_workers", "IMAGE_WORKERS")?
+            .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |parallelism| parallelism.get()));
+        let image_cache_bytes = env_override(file.image_cache_bytes, "image_cache_bytes", "IMAGE_CACHE_BYTES")?
+            .unwrap_or(64 * 1024 * 1024);
+        // The directives are those of `RUST_LOG`, which is where they are usually set
+        let log_level = env_override(file.log_level, "log_level", "LOG_LEVEL")?
+            .or_else(|| std::env::var("RUST_LOG").ok());
+        let secure_cookies = env_override(file.secure_cookies, "secure_cookies", "SECURE_COOKIES")?
+            .unwrap_or(true);
+        let rate_limit_requests = env_override(file.rate_limit_requests, "rate_limit_requests", "RATE_LIMIT_REQUESTS")?
+            .unwrap_or(30);
+        let rate_limit_window_secs = env_override(file.rate_limit_window_secs, "rate_limit_window_secs", "RATE_LIMIT_WINDOW_SECS")?
+            .unwrap_or(60);
+        let trusted_proxies = env_override_list(file.trusted_proxies, "TRUSTED_PROXIES")
+            .unwrap_or_default()
+            .iter()
+            .map(|proxy| proxy.parse())
+            .collect::<Result<Vec<IpNetwork>, String>>()
+            .map_err(|reason| invalid("trusted_proxies", reason))?;
+        let request_timeout_secs = env_override(file.request_timeout_secs, "request_timeout_secs", "REQUEST_TIMEOUT_SECS")?
+            .unwrap_or(60);
+        let max_concurrent_requests = env_override(file.max_concurrent_requests, "max_concurrent_requests", "MAX_CONCURRENT_REQUESTS")?
+            .unwrap_or(512);
+        let otlp_endpoint = env_override(file.otlp_endpoint, "otlp_endpoint", "OTEL_EXPORTER_OTLP_ENDPOINT")?;
+        let metrics_token = env_override(file.metrics_token, "metrics_token", "METRICS_TOKEN")?;
+        let https_proxy = env_override(file.https_proxy, "https_proxy", "HTTPS_PROXY")?;
+        let tls_cert_path = env_override(file.tls_cert_path, "tls_cert_path", "TLS_CERT_PATH")?;
+        let tls_key_path = env_override(file.tls_key_path, "tls_key_path", "TLS_KEY_PATH")?;
+        let http_redirect_addr = env_override(file.http_redirect_addr, "http_redirect_addr", "HTTP_REDIRECT_ADDR")?;
+        let public_url = env_override(file.public_url, "public_url", "PUBLIC_URL")?;
+        let redis_url = env_override(file.redis_url, "redis_url", "REDIS_URL")?;
+        let activitypub_key_path = env_override(file.activitypub_key_path, "activitypub_key_path", "ACTIVITYPUB_KEY_PATH")?;
+        let backup_target = env_override(file.backup_target, "backup_target", "BACKUP_TARGET")?;
+        let backup_interval_secs = env_override(file.backup_interval_secs, "backup_interval_secs", "BACKUP_INTERVAL_SECS")?
+            .unwrap_or(24 * 60 * 60);
+        let backup_retention = env_override(file.backup_retention, "backup_retention", "BACKUP_RETENTION")?
+            .unwrap_or(7);
+        let admin_usernames = env_override_list(file.admin_usernames, "ADMIN_USERNAMES").unwrap_or_default();
+        let image_gc_interval_secs = env_override(file.image_gc_interval_secs, "image_gc_interval_secs", "IMAGE_GC_INTERVAL_SECS")?
+            .unwrap_or(60 * 60);
+        let image_gc_grace_secs = env_override(file.image_gc_grace_secs, "image_gc_grace_secs", "IMAGE_GC_GRACE_SECS")?
+            .unwrap_or(24 * 60 * 60);
+        let trash_retention_days = env_override(file.trash_retention_days, "trash_retention_days", "TRASH_RETENTION_DAYS")?
+            .unwrap_or(30);
+        let banned_words = env_override_list(file.banned_words, "BANNED_WORDS").unwrap_or_default();
+        let max_links = env_override(file.max_links_per_post, "max_links_per_post", "MAX_LINKS_PER_POST")?;
+        let max_post_length = env_override(file.max_post_length, "max_post_length", "MAX_POST_LENGTH")?
+            .unwrap_or(MAX_POST_LENGTH);
+        let webhook_url = env_override(file.content_filter_webhook_url, "content_filter_webhook_url", "CONTENT_FILTER_WEBHOOK_URL")?;
+        let filter_action = env_override(file.content_filter_action, "content_filter_action", "CONTENT_FILTER_ACTION")?;
+        let challenge_difficulty = env_override(file.challenge_difficulty, "challenge_difficulty", "CHALLENGE_DIFFICULTY")?
+            .unwrap_or(18);
+        // A webhook given by the environment replaces those in the file, since lists of tables don't fit in a variable
+        let outbound_webhook_url = env_override::<String>(None, "webhooks", "WEBHOOK_URL")?;
+        let outbound_webhook_secret = env_override::<String>(None, "webhooks", "WEBHOOK_SECRET")?;
+
+        if host_addr.trim().is_empty() {
+            return Err(invalid("host_addr", "must not be empty"));
+        }
+        if database_url.trim().is_empty() {
+            return Err(invalid("database_url", "must not be empty"));
+        }
+        if metrics_token.as_deref().is_some_and(|token| token.trim().is_empty()) {
+            return Err(invalid("metrics_token", "must not be empty"));
+        }
+        if db_pool_max_size == 0 {
+            return Err(invalid("db_pool_max_size", "must be greater than zero"));
+        }
+        if db_pool_min_idle.is_some_and(|min_idle| min_idle > db_pool_max_size) {
+            return Err(invalid("db_pool_min_idle", "must be at most `db_pool_max_size`"));
+        }
+        if db_pool_timeout_secs == 0 {
+            return Err(invalid("db_pool_timeout_secs", "must be greater than zero"));
+        }
+        if db_retries > 10 {
+            return Err(invalid("db_retries", "must be at most 10, since the backoff doubles with every retry"));
+        }
+        let sqlite_synchronous = match sqlite_synchronous.as_deref().unwrap_or("normal") {
+            "off" => SqliteSynchronous::Off,
+            "normal" => SqliteSynchronous::Normal,
+            "full" => SqliteSynchronous::Full,
+            "extra" => SqliteSynchronous::Extra,
+            other => {
+                let reason = format!("expected `off`, `normal`, `full` or `extra`, got `{other}`");
+                return Err(invalid("sqlite_synchronous", reason));
+            }
+        };
+        if backup_interval_secs == 0 {
+            return Err(invalid("backup_interval_secs", "must be greater than zero"));
+        }
+        if backup_retention == 0 {
+            return Err(invalid("backup_retention", "must be greater than zero, or every snapshot would be deleted"));
+        }
+        // Backups to S3 use the same region and endpoint as images stored in S3
+        let backup = match backup_target.as_deref().map(str::trim) {
+            None | Some("") => None,
+            Some(target) => {
+                let (target, prefix) = match target.strip_prefix("s3://") {
+                    Some(location) => {
+                        let (bucket, prefix) = location.split_once('/').unwrap_or((location, ""));
+                        if bucket.is_empty() {
+                            return Err(invalid("backup_target", "must name a bucket after `s3://`"));
+                        }
+                        let prefix = match prefix.trim_matches('/') {
+                            "" => String::new(),
+                            prefix => format!("{prefix}/"),
+                        };
+                        let target = ImageStoreConfig::S3 {
+                            bucket: bucket.to_string(),
+                            region: s3_region.clone(),
+                            endpoint: s3_endpoint.clone(),
+                        };
+                        (target, prefix)
+                    }
+                    None => (ImageStoreConfig::Filesystem { dir: PathBuf::from(target) }, String::new()),
+                };
+                Some(BackupConfig {
+                    target,
+                    prefix,
+                    interval: Duration::from_secs(backup_interval_secs),
+                    retention: backup_retention,
+                })
+            }
+        };
+        let image_store = match image_store.as_deref().unwrap_or("filesystem") {
+            "filesystem" => {
+                if image_dir.as_os_str().is_empty() {
+                    return Err(invalid("image_dir", "must not be empty"));
+                }
+                ImageStoreConfig::Filesystem { dir: image_dir }
+            }
+            "s3" => ImageStoreConfig::S3 {
+                bucket: s3_bucket.ok_or(ConfigError::Missing { field: "s3_bucket", var: "S3_BUCKET" })?,
+                region: s3_region,
+                endpoint: s3_endpoint,
+            },
+            other => return Err(invalid("image_store", format!("expected `filesystem` or `s3`, got `{other}`"))),
+        };
+        if upload_dir.as_os_str().is_empty() {
+            return Err(invalid("upload_dir", "must not be empty"));
+        }
+        if max_upload_bytes == 0 {
+            return Err(invalid("max_upload_bytes", "must be greater than zero"));
+        }
+        if max_image_bytes == 0 {
+            return Err(invalid("max_image_bytes", "must be greater than zero"));
+        }
+        if max_image_bytes > max_upload_bytes {
+            return Err(invalid("max_image_bytes", "must not be greater than `max_upload_bytes`, or such images could never be uploaded"));
+        }
+        if !(1..=100).contains(&image_quality) {
+            return Err(invalid("image_quality", "must be between 1 and 100"));
+        }
+        let image_encoding = match image_format.as_deref().unwrap_or("webp") {
+            "png" => ImageEncoding::Png,
+            "webp" => ImageEncoding::WebP,
+            "avif" => ImageEncoding::Avif { quality: image_quality },
+            other => return Err(invalid("image_format", format!("expected `webp`, `avif` or `png`, got `{other}`"))),
+        };
+        if avatar_download_timeout_secs == 0 {
+            return Err(invalid("avatar_download_timeout_secs", "must be greater than zero"));
+        }
+        if image_workers == 0 {
+            return Err(invalid("image_workers", "must be greater than zero"));
+        }
+        if rate_limit_requests == 0 {
+            return Err(invalid("rate_limit_requests", "must be greater than zero"));
+        }
+        if rate_limit_window_secs == 0 {
+            return Err(invalid("rate_limit_window_secs", "must be greater than zero"));
+        }
+        if request_timeout_secs == 0 {
+            return Err(invalid("request_timeout_secs", "must be greater than zero"));
+        }
+        if max_concurrent_requests == 0 {
+            return Err(invalid("max_concurrent_requests", "must be greater than zero"));
+        }
+        if image_gc_interval_secs == 0 {
+            return Err(invalid("image_gc_interval_secs", "must be greater than zero"));
+        }
+        if banned_words.iter().any(|word| word.trim().is_empty()) {
+            return Err(invalid("banned_words", "must not contain empty words"));
+        }
+        if !(1..=MAX_POST_LENGTH).contains(&max_post_length) {
+            return Err(invalid("max_post_length", format!("must be between 1 and {MAX_POST_LENGTH}")));
+        }
+        let filter_action = match filter_action.as_deref().unwrap_or("reject") {
+            "reject" => FilterAction::Reject,
+            "queue" => FilterAction::Queue,
+            other => return Err(invalid("content_filter_action", format!("expected `reject` or `queue`, got `{other}`"))),
+        };
+        let webhooks = match (outbound_webhook_url, outbound_webhook_secret) {
+            (Some(url), Some(secret)) => vec![WebhookConfig { url, secret }],
+            (None, None) => file.webhooks.unwrap_or_default(),
+            (Some(_), None) => return Err(ConfigError::Missing { field: "webhooks", var: "WEBHOOK_SECRET" }),
+            (None, Some(_)) => return Err(ConfigError::Missing { field: "webhooks", var: "WEBHOOK_URL" }),
+        };
+        for webhook in &webhooks {
+            if !webhook.url.starts_with("http://") && !webhook.url.starts_with("https://") {
+                return Err(invalid("webhooks", format!("expected an `http://` or `https://` URL, got `{}`", webhook.url)));
+            }
+            if webhook.secret.trim().is_empty() {
+                return Err(invalid("webhooks", format!("the secret of `{}` must not be empty", webhook.url)));
+            }
+        }
+        if challenge_difficulty > 32 {
+            return Err(invalid("challenge_difficulty", "must be at most 32, or clients would take hours to post"));
+        }
+        let log_filter = log_level.unwrap_or_else(|| "info".to_string());
+        // The filter is only built once logging is set up, but typos in it should fail right away
+        if let Err(err) = EnvFilter::try_new(&log_filter) {
+            return Err(invalid("log_level", err));
+        }
+
+        let tls = match (tls_cert_path, tls_key_path) {
+            (Some(cert_path), Some(key_path)) => Some(TlsConfig { cert_path, key_path }),
+            (None, None) => None,
+            (Some(_), None) => return Err(invalid("tls_key_path", "must be set together with `tls_cert_path`")),
+            (None, Some(_)) => return Err(invalid("tls_cert_path", "must be set together with `tls_key_path`")),
+        };
+        if http_redirect_addr.is_some() && tls.is_none() {
+            return Err(invalid("http_redirect_addr", "requires `tls_cert_path` and `tls_key_path` to be set"));
+        }
+        if let Some(url) = &public_url {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                return Err(invalid("public_url", format!("expected an `http://` or `https://` URL, got `{url}`")));
+            }
+        }
+        if activitypub_key_path.is_some() && public_url.is_none() {
+            return Err(invalid("activitypub_key_path", "requires `public_url` to be set"));
+        }
+        if let Some(url) = &redis_url {
+            if !url.starts_with("redis://") && !url.starts_with("rediss://") {
+                return Err(invalid("redis_url", format!("expected a `redis://` or `rediss://` URL, got `{url}`")));
+            }
+        }
+
+        Ok(Self {
+            host_addr,
+            database_url,
+            db_pool: PoolConfig {
+                max_size: db_pool_max_size,
+                min_idle: db_pool_min_idle,
+                connection_timeout: Duration::from_secs(db_pool_timeout_secs),
+            },
+            db_retry: RetryConfig {
+                retries: db_retries,
+                backoff: Duration::from_millis(db_retry_backoff_ms),
+            },
+            sqlite: SqliteConfig {
+                wal: sqlite_wal,
+                busy_timeout: Duration::from_millis(sqlite_busy_timeout_ms),
+                synchronous: sqlite_synchronous,
+            },
+            image_store,
+            upload_dir,
+            max_upload_bytes,
+            max_image_bytes,
+            require_alt_text,
+            gravatar_fallback,
+            image_encoding,
+            // Zero disables downscaling
+            max_image_dimension: Some(max_image_dimension).filter(|dimension| *dimension > 0),
+            avatar_download_timeout: Duration::from_secs(avatar_download_timeout_secs),
+            image_workers,
+            image_cache_bytes,
+            log_filter,
+            secure_cookies,
+            rate_limit_requests,
+            rate_limit_window: Duration::from_secs(rate_limit_window_secs),
+            trusted_proxies,
+            request_timeout: Duration::from_secs(request_timeout_secs),
+            max_concurrent_requests,
+            otlp_endpoint,
+            metrics_token,
+            https_proxy,
+            tls,
+            http_redirect_addr,
+            public_url,
+            redis_url,
+            admin_usernames,
+            image_gc_interval: Duration::from_secs(image_gc_interval_secs),
+            image_gc_grace_period: Duration::from_secs(image_gc_grace_secs),
+            trash_retention: Duration::from_secs(trash_retention_days * 24 * 60 * 60),
+            content_filter: ContentFilterConfig {
+                max_length: max_post_length,
+                banned_words,
+                max_links,
+                webhook_url,
+                action: filter_action,
+            },
+            challenge_difficulty,
+            webhooks,
+            activitypub_key_path,
+            backup,
+        })
+    }
+}
+
+/// Returns the path of the config file from the `--config` command line argument
+/// (as `--config <path>` or `--config=<path>`), or else the `CONFIG_PATH` environment variable.
+/// Returns `None` if neither is set, in which case only environment variables are used.
+fn config_path() -> Option<PathBuf> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.strip_prefix("--config=") {
+            return Some(PathBuf::from(path));
+        }
+    }
+    std::env::var_os("CONFIG_PATH").map(PathBuf::from)
+}
+
+/// Read and parse the config file at the path.
+fn read_file(path: &Path) -> Result<ConfigFile, ConfigError> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|source| ConfigError::Read { path: path.to_owned(), source })?;
+    toml::from_str(&contents).map_err(|source| ConfigError::Parse { path: path.to_owned(), source })
+}
+
+/// Returns the value of the environment variable `var` parsed into the type of the field,
+/// or the value from the config file if the environment variable is not set.
+fn env_override<T>(
+    value: Option<T>,
+    field: &'static str,
+    var: &'static str,
+) -> Result<Option<T>, ConfigError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    match std::env::var(var) {
+        Ok(raw) => raw
+            .parse()
+            .map(Some)
+            .map_err(|err| invalid(field, format!("{err} (from environment variable `{var}`)"))),
+        Err(_) => Ok(value),
+    }
+}
+
+/// Returns the comma-separated values of the environment variable `var`,
+/// or the list from the config file if the environment variable is not set.
+/// Surrounding whitespace and empty values are ignored.
+fn env_override_list(value: Option<Vec<String>>, var: &'static str) -> Option<Vec<String>> {
+    match std::env::var(var) {
+        Ok(raw) => Some(
+            raw.split(',')
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+                .collect(),
+        ),
+        Err(_) => value,
+    }
+}
+
+fn invalid(field: &'static str, reason: impl Display) -> ConfigError {
+    ConfigError::Invalid { field, reason: reason.to_string() }
+}
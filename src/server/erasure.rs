@@ -0,0 +1,42 @@
+//! Erasure of all data of a user when they ask for it, e.g. under the GDPR.
+//! Unlike deleting blog posts, which moves them to the trash, erasure is irreversible: the account and every
+//! blog post by its username are deleted at once, along with their images unless other blog posts share them.
+//! Only a record of whose data was erased, by whom and when is kept, see `Database::erase_user`.
+//! The data can be exported beforehand from `/api/v1/users/{username}/export`, see `server::archive`.
+
+use crate::error::AppError;
+use crate::model::{AuditAction, BlogPostId, PostEvent, User};
+use crate::server::images;
+use crate::server::webhooks::WebhookEvent;
+use crate::server::ServerState;
+use crate::validation::{Field, ValidationErrors};
+use std::net::IpAddr;
+use time::{OffsetDateTime, PrimitiveDateTime};
+use tracing::info;
+
+/// Erase the account with the username and every blog post by that name on behalf of the administrator,
+/// who asked for it from the IP address.
+/// Sessions in which the user is logged in are not ended, but the requests in them which change data fail,
+/// since the account is gone.
+/// Returns the number of erased blog posts, or a validation error if there is no user or blog post by that name.
+pub async fn erase_user(state: &ServerState, admin: &User, ip: IpAddr, username: String) -> Result<usize, AppError> {
+    let now = OffsetDateTime::now_utc();
+    let erased_at = PrimitiveDateTime::new(now.date(), now.time());
+    let Some(posts) = state.database.erase_user(username.clone(), admin.username.clone(), erased_at).await? else {
+        return Err(ValidationErrors::of(Field::Username, "There is no user or blog post by that name").into());
+    };
+    for post in &posts {
+        let (image, avatar) = (post.image_uuid.clone(), post.avatar_uuid.clone());
+        images::delete_unreferenced(&*state.images, &state.database, image, avatar).await;
+    }
+    let post_ids: Vec<BlogPostId> = posts.iter().map(|post| post.id).collect();
+    state.feed_cache.invalidate(&post_ids).await;
+    for post_id in post_ids {
+        state.webhooks.publish(WebhookEvent::Deleted { post_id });
+        state.events.publish(PostEvent::Deleted(post_id));
+    }
+    let summary = format!("Erased the user {} with {} blog posts", username, posts.len());
+    state.audit.record(admin, ip, AuditAction::EraseUser, &[], &summary).await;
+    info!("Erased the data of a user with {} blog posts on behalf of {}", posts.len(), admin.username);
+    Ok(posts.len())
+}
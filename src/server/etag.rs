@@ -0,0 +1,44 @@
+//! Conditional requests, which let browsers reuse responses of server functions which did not change.
+
+use dioxus::prelude::server_context;
+use http::header::{CACHE_CONTROL, ETAG, IF_NONE_MATCH, VARY};
+use http::{HeaderValue, StatusCode};
+use tracing::debug;
+
+/// Tag the response of the current server function with the entity tag, which browsers must revalidate on every use.
+/// Returns whether the browser already has the response with this tag, in which case the status is set to
+/// `304 Not Modified`, and the body which the server function returns is not sent, so it may as well be empty.
+/// Browsers revalidate the response when the session cookie changes. Responses which are `personal`, i.e. rendered
+/// for a logged-in user, are kept private, while those for anonymous visitors may be kept for reading offline.
+/// This is only meaningful for server functions which are called with `GET`, since browsers do not cache other methods.
+pub fn not_modified(etag: &str, personal: bool) -> bool {
+    let context = server_context();
+    let cached = context
+        .request_parts()
+        .headers
+        .get(IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| matches(value, etag));
+    let mut response = context.response_parts_mut();
+    if let Ok(value) = HeaderValue::from_str(etag) {
+        response.headers.insert(ETAG, value);
+    }
+    let cache_control = if personal { "private, no-cache" } else { "public, no-cache" };
+    response.headers.insert(CACHE_CONTROL, HeaderValue::from_static(cache_control));
+    response.headers.insert(VARY, HeaderValue::from_static("Cookie"));
+    if cached {
+        debug!("Response with ETag {} is not modified", etag);
+        response.status = StatusCode::NOT_MODIFIED;
+    }
+    cached
+}
+
+/// Whether the value of an `If-None-Match` header, which lists entity tags or is `*`, matches the entity tag.
+/// Weak tags match as well, since the comparison for `If-None-Match` is weak.
+pub(crate) fn matches(if_none_match: &str, etag: &str) -> bool {
+    let etag = etag.trim_start_matches("W/");
+    if_none_match
+        .split(',')
+        .map(str::trim)
+        .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag)
+}
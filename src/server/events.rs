@@ -0,0 +1,66 @@
+//! Live updates of the blog post feed, which are pushed to browsers as server-sent events.
+//! Events are only delivered to clients which are connected when they happen;
+//! clients which fall behind or reconnect catch up by fetching the feed again.
+
+use crate::model::PostEvent;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use futures_util::Stream;
+use std::convert::Infallible;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+
+/// How many events are kept for subscribers which have not received them yet.
+/// Subscribers which fall further behind skip the events they missed.
+const EVENT_BUFFER: usize = 64;
+
+/// Broadcasts changes to the blog post feed to all subscribed clients.
+/// Clones share the same subscribers.
+#[derive(Debug, Clone)]
+pub struct PostEvents {
+    sender: broadcast::Sender<PostEvent>,
+    shutdown: CancellationToken,
+}
+
+impl PostEvents {
+    /// Create a broadcaster whose event streams end once the server is asked to shut down,
+    /// so that they do not keep the server waiting for in-flight requests.
+    pub fn new(shutdown: CancellationToken) -> Self {
+        let (sender, _) = broadcast::channel(EVENT_BUFFER);
+        Self { sender, shutdown }
+    }
+
+    /// Send the event to all subscribed clients.
+    /// Events are dropped if nobody is subscribed.
+    pub fn publish(&self, event: PostEvent) {
+        debug!("Publishing {:?}", event);
+        // Sending only fails if there are no subscribers, who would not have received the event anyway
+        let _ = self.sender.send(event);
+    }
+
+    /// Subscribe to the events published from now on, as a stream of server-sent events.
+    /// Every event is sent as JSON in the data of a `message` event.
+    pub fn subscribe(&self) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+        let receiver = self.sender.subscribe();
+        let stream = futures_util::stream::unfold(
+            (receiver, self.shutdown.clone()),
+            |(mut receiver, shutdown)| async move {
+                loop {
+                    let received = tokio::select! {
+                        _ = shutdown.cancelled() => return None,
+                        received = receiver.recv() => received,
+                    };
+                    match received {
+                        Ok(event) => match Event::default().json_data(&event) {
+                            Ok(event) => return Some((Ok(event), (receiver, shutdown))),
+                            Err(err) => warn!("Failed to serialize {:?}: {}", event, err),
+                        },
+                        Err(RecvError::Lagged(skipped)) => warn!("Subscriber skipped {} post events", skipped),
+                        Err(RecvError::Closed) => return None,
+                    }
+                }
+            },
+        );
+        Sse::new(stream).keep_alive(KeepAlive::default())
+    }
+}
@@ -0,0 +1,189 @@
+//! Cache of the first page of the feed and of single blog posts, shared by all servers through Redis,
+//! so that a deployment with several servers answers the most frequent requests without querying the database.
+//! Only what anonymous visitors see is cached, since logged-in users see which posts they liked.
+//! Entries are removed whenever a blog post is created, changed or deleted, and expire after `CACHE_TTL` in any case,
+//! which bounds how long changes the servers do not know of, such as purges of the trash, take to show.
+//! The cache requires the `redis` feature and `REDIS_URL` to be set; otherwise, every lookup misses.
+
+use crate::model::{BlogPostDetail, BlogPostId, BlogPostPage};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt::{Debug, Formatter};
+use std::time::Duration;
+#[cfg(feature = "redis")]
+use tracing::{debug, warn};
+
+/// How long entries are kept at most.
+#[cfg_attr(not(feature = "redis"), allow(dead_code))]
+const CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// The key of the first page of the feed.
+const FIRST_PAGE_KEY: &str = "blogposts:feed:first";
+
+/// The key of the blog post with the ID.
+fn post_key(post_id: BlogPostId) -> String {
+    format!("blogposts:post:{post_id}")
+}
+
+/// Errors that can occur when connecting to the cache.
+#[derive(Debug, derive_more::Display, derive_more::Error)]
+pub enum FeedCacheError {
+    #[cfg(feature = "redis")]
+    #[display("Redis error: {}", _0)]
+    Redis(redis::RedisError),
+    #[display("REDIS_URL is set, but the server was built without the `redis` feature")]
+    Unsupported,
+}
+
+/// A handle to the cache, which does nothing if no Redis server is configured.
+/// Failures to read or write the cache are logged and otherwise ignored, since everything can be loaded from the database.
+#[derive(Clone, Default)]
+pub struct FeedCache {
+    #[cfg(feature = "redis")]
+    connection: Option<redis::aio::ConnectionManager>,
+}
+
+impl Debug for FeedCache {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FeedCache").field("enabled", &self.is_enabled()).finish()
+    }
+}
+
+impl FeedCache {
+    /// Connect to the Redis server at the URL, or create a cache which does nothing if there is none.
+    /// Returns `FeedCacheError::Redis` if the server cannot be reached,
+    /// or `FeedCacheError::Unsupported` if a URL is given without the `redis` feature.
+    pub async fn connect(url: Option<&str>) -> Result<Self, FeedCacheError> {
+        match url {
+            Some(url) => Self::open(url).await,
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Returns the first page of the feed as seen by anonymous visitors, if it is cached.
+    pub async fn first_page(&self) -> Option<BlogPostPage> {
+        self.get(FIRST_PAGE_KEY).await
+    }
+
+    /// Cache the first page of the feed as seen by anonymous visitors.
+    pub async fn put_first_page(&self, page: &BlogPostPage) {
+        self.put(FIRST_PAGE_KEY, page).await
+    }
+
+    /// Returns the blog post with the ID as seen by anonymous visitors, if it is cached.
+    pub async fn post(&self, post_id: BlogPostId) -> Option<BlogPostDetail> {
+        self.get(&post_key(post_id)).await
+    }
+
+    /// Cache the blog post as seen by anonymous visitors.
+    pub async fn put_post(&self, detail: &BlogPostDetail) {
+        self.put(&post_key(detail.post.id), detail).await
+    }
+
+    /// Remove the first page of the feed and the blog posts with the IDs from the cache,
+    /// after the blog posts were created, changed or deleted.
+    pub async fn invalidate(&self, post_ids: &[BlogPostId]) {
+        let keys = std::iter::once(FIRST_PAGE_KEY.to_string())
+            .chain(post_ids.iter().copied().map(post_key))
+            .collect();
+        self.delete(keys).await
+    }
+
+    #[cfg(feature = "redis")]
+    async fn open(url: &str) -> Result<Self, FeedCacheError> {
+        let client = redis::Client::open(url).map_err(FeedCacheError::Redis)?;
+        let connection = redis::aio::ConnectionManager::new(client).await.map_err(FeedCacheError::Redis)?;
+        Ok(Self { connection: Some(connection) })
+    }
+
+    /// Whether a Redis server is configured.
+    #[cfg(feature = "redis")]
+    pub fn is_enabled(&self) -> bool {
+        self.connection.is_some()
+    }
+
+    /// Returns the value cached under the key, if any.
+    #[cfg(feature = "redis")]
+    async fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        use redis::AsyncCommands;
+
+        let mut connection = self.connection.clone()?;
+        let json = match connection.get::<_, Option<String>>(key).await {
+            Ok(Some(json)) => json,
+            Ok(None) => {
+                metrics::counter!("feed_cache_misses_total").increment(1);
+                return None;
+            }
+            Err(err) => {
+                warn!("Failed to read {} from the cache: {}", key, err);
+                return None;
+            }
+        };
+        match serde_json::from_str(&json) {
+            Ok(value) => {
+                metrics::counter!("feed_cache_hits_total").increment(1);
+                Some(value)
+            }
+            Err(err) => {
+                // Entries written by an older version of the server may no longer parse
+                debug!("Ignoring unreadable cache entry {}: {}", key, err);
+                None
+            }
+        }
+    }
+
+    /// Cache the value under the key for the `CACHE_TTL`.
+    #[cfg(feature = "redis")]
+    async fn put<T: Serialize>(&self, key: &str, value: &T) {
+        use redis::AsyncCommands;
+
+        let Some(mut connection) = self.connection.clone() else {
+            return;
+        };
+        let json = match serde_json::to_string(value) {
+            Ok(json) => json,
+            Err(err) => {
+                warn!("Failed to serialize {} for the cache: {}", key, err);
+                return;
+            }
+        };
+        if let Err(err) = connection.set_ex::<_, _, ()>(key, json, CACHE_TTL.as_secs()).await {
+            warn!("Failed to write {} to the cache: {}", key, err);
+        }
+    }
+
+    /// Remove the values cached under the keys.
+    #[cfg(feature = "redis")]
+    async fn delete(&self, keys: Vec<String>) {
+        use redis::AsyncCommands;
+
+        let Some(mut connection) = self.connection.clone() else {
+            return;
+        };
+        debug!("Invalidating cached {:?}", keys);
+        if let Err(err) = connection.del::<_, ()>(&keys).await {
+            warn!("Failed to invalidate cached {:?}: {}", keys, err);
+        }
+    }
+
+    #[cfg(not(feature = "redis"))]
+    async fn open(_url: &str) -> Result<Self, FeedCacheError> {
+        Err(FeedCacheError::Unsupported)
+    }
+
+    #[cfg(not(feature = "redis"))]
+    pub fn is_enabled(&self) -> bool {
+        false
+    }
+
+    #[cfg(not(feature = "redis"))]
+    async fn get<T: DeserializeOwned>(&self, _key: &str) -> Option<T> {
+        None
+    }
+
+    #[cfg(not(feature = "redis"))]
+    async fn put<T: Serialize>(&self, _key: &str, _value: &T) {}
+
+    #[cfg(not(feature = "redis"))]
+    async fn delete(&self, _keys: Vec<String>) {}
+}
@@ -0,0 +1,136 @@
+//! Content filter which the text of blog posts is run through before they are saved.
+//! The checks run in order from cheapest to most expensive and stop at the first violation,
+//! so that the external moderation service is only asked about posts which pass the local checks.
+
+use crate::model::{post_length, ContentViolation};
+use crate::server::config::{ContentFilterConfig, FilterAction};
+use crate::server::outbound::HttpClient;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, instrument, warn};
+
+/// How long to wait for the external moderation service before letting a blog post through.
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The request sent to the external moderation service.
+#[derive(Debug, Serialize)]
+struct WebhookRequest<'a> {
+    text: &'a str,
+    username: &'a str,
+}
+
+/// The answer expected from the external moderation service.
+#[derive(Debug, Deserialize)]
+struct WebhookResponse {
+    allowed: bool,
+    reason: Option<String>,
+}
+
+/// Checks the text of blog posts against the configured rules.
+#[derive(Debug, Clone)]
+pub struct ContentFilter {
+    max_length: usize,
+    /// Lowercased, so that words can be matched regardless of case.
+    banned_words: Arc<HashSet<String>>,
+    max_links: Option<usize>,
+    webhook_url: Option<String>,
+    client: HttpClient,
+    action: FilterAction,
+}
+
+impl ContentFilter {
+    pub fn new(config: ContentFilterConfig, client: HttpClient) -> Self {
+        let banned_words = config
+            .banned_words
+            .iter()
+            .map(|word| word.trim().to_lowercase())
+            .collect();
+        Self {
+            max_length: config.max_length,
+            banned_words: Arc::new(banned_words),
+            max_links: config.max_links,
+            webhook_url: config.webhook_url,
+            client,
+            action: config.action,
+        }
+    }
+
+    /// What should happen to blog posts which fail the filter.
+    pub fn action(&self) -> FilterAction {
+        self.action
+    }
+
+    /// The maximum number of characters of a blog post, counted by `post_length`.
+    pub fn max_length(&self) -> usize {
+        self.max_length
+    }
+
+    /// Check that the text is not longer than allowed.
+    /// Unlike the other checks, this one always rejects the text, so it runs before `check` rather than in it.
+    pub fn check_length(&self, text: &str) -> Result<(), ContentViolation> {
+        let length = post_length(text);
+        if length > self.max_length {
+            return Err(ContentViolation::TooLong { length, max: self.max_length });
+        }
+        Ok(())
+    }
+
+    /// Run the text written by the user through all checks.
+    /// Returns the first violation, or `None` if the text passes.
+    /// If the external moderation service cannot be reached or answers with garbage,
+    /// the text passes that check, so that an outage of the service does not stop anyone from posting.
+    #[instrument(level = "info", skip(self, text))]
+    pub async fn check(&self, text: &str, username: &str) -> Option<ContentViolation> {
+        if let Some(violation) = self.check_banned_words(text).or_else(|| self.check_links(text)) {
+            debug!("Blog post failed the content filter: {}", violation);
+            return Some(violation);
+        }
+        let url = self.webhook_url.as_deref()?;
+        match self.ask_webhook(url, text, username).await {
+            Ok(WebhookResponse { allowed: true, .. }) => None,
+            Ok(WebhookResponse { allowed: false, reason }) => {
+                let reason = reason.unwrap_or_else(|| "no reason given".to_string());
+                debug!("Blog post was flagged by the moderation service: {}", reason);
+                Some(ContentViolation::Flagged { reason })
+            }
+            Err(err) => {
+                warn!("Failed to ask the moderation service, letting the blog post through: {}", err);
+                None
+            }
+        }
+    }
+
+    /// Returns the first banned word which appears in the text as a whole word.
+    fn check_banned_words(&self, text: &str) -> Option<ContentViolation> {
+        if self.banned_words.is_empty() {
+            return None;
+        }
+        text.split(|c: char| !c.is_alphanumeric())
+            .map(str::to_lowercase)
+            .find(|word| self.banned_words.contains(word))
+            .map(|word| ContentViolation::BannedWord { word })
+    }
+
+    /// Returns a violation if the text contains more links than allowed.
+    fn check_links(&self, text: &str) -> Option<ContentViolation> {
+        let max = self.max_links?;
+        let lowercase = text.to_lowercase();
+        let count = lowercase.matches("http://").count() + lowercase.matches("https://").count();
+        (count > max).then_some(ContentViolation::TooManyLinks { count, max })
+    }
+
+    async fn ask_webhook(&self, url: &str, text: &str, username: &str) -> Result<WebhookResponse, reqwest::Error> {
+        self.client
+            .trusted()
+            .post(url)
+            .timeout(WEBHOOK_TIMEOUT)
+            .json(&WebhookRequest { text, username })
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+    }
+}
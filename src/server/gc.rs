@@ -0,0 +1,111 @@
+//! Garbage collection of stored images which are not referenced by any blog post.
+//! Images are saved before the blog post referencing them is inserted into the database,
+//! so post creations and deletions which are interrupted, e.g. by a crash, can leave orphaned images behind.
+
+use crate::model::{AvatarImagePath, PostImagePath};
+use crate::server::images::{self, ImagePath};
+use crate::server::jobs::Job;
+use crate::server::persistence::database::DatabaseError;
+use crate::server::storage::{ImageStorage, StorageError};
+use crate::server::Database;
+use axum::async_trait;
+use std::collections::HashSet;
+use std::time::{Duration, SystemTime};
+use tracing::{debug, info, instrument, warn};
+use uuid::Uuid;
+
+/// Errors that can occur when collecting orphaned images.
+#[derive(Debug, derive_more::From, derive_more::Display, derive_more::Error)]
+pub enum GcError {
+    #[display("Database error: {}", _0)]
+    Database(DatabaseError),
+    #[display("Storage error: {}", _0)]
+    Storage(StorageError),
+}
+
+/// Deletes stored images which are not referenced by any blog post.
+#[derive(Debug, Clone)]
+pub struct ImageCollector {
+    database: Database,
+    images: ImageStorage,
+    grace_period: Duration,
+}
+
+impl ImageCollector {
+    /// Create a collector which only deletes unreferenced images older than the grace period,
+    /// so that the images of blog posts which are still being created are left alone.
+    pub fn new(database: Database, images: ImageStorage, grace_period: Duration) -> Self {
+        Self { database, images, grace_period }
+    }
+
+    /// Delete all unreferenced images older than the grace period.
+    /// Images are renewed whenever they are reused, so their age is the time since they were last saved or reused.
+    /// The references of every image are checked again right before it is deleted,
+    /// in case a blog post referencing it was inserted by another server sharing the store in the meantime.
+    /// Images which fail to be deleted are skipped and retried by the next collection.
+    /// Returns the number of deleted images, or an error if the images or their references cannot be listed.
+    #[instrument(level = "info", skip(self))]
+    pub async fn collect(&self) -> Result<usize, GcError> {
+        let cutoff = SystemTime::now() - self.grace_period;
+        // List the images before their references, so that images stored in between are not mistaken for orphans
+        let stored = self.images.list().await?;
+        // Blog posts which are being created with reused images are inserted before the references are fetched,
+        // while images which are reused afterward are found deleted and stored again
+        let _deletions = images::lock_deletions().await;
+        let (post_images, avatars) = self.database.referenced_images().await?;
+        let referenced: HashSet<String> = post_images
+            .iter()
+            .flat_map(ImagePath::all_keys)
+            .chain(avatars.iter().flat_map(ImagePath::all_keys))
+            .collect();
+        let mut deleted = 0;
+        for image in stored {
+            if image.modified > cutoff || referenced.contains(&image.key) {
+                continue;
+            }
+            if let Some((post_image, avatar)) = image_of(&image.key) {
+                if self.database.unreferenced_images(post_image, avatar).await? == (None, None) {
+                    debug!("Keeping image {}, which was referenced in the meantime", image.key);
+                    continue;
+                }
+            }
+            match self.images.delete(&image.key).await {
+                Ok(()) => {
+                    debug!("Deleted orphaned image {}", image.key);
+                    deleted += 1;
+                }
+                Err(err) => warn!("Failed to delete orphaned image {}: {}", image.key, err),
+            }
+        }
+        info!("Deleted {} orphaned images", deleted);
+        Ok(deleted)
+    }
+}
+
+/// The post image or avatar which the key belongs to, whichever format or variant of the image it stores,
+/// or `None` if the key belongs to neither.
+fn image_of(key: &str) -> Option<(Option<PostImagePath>, Option<AvatarImagePath>)> {
+    let (dir, path) = key.split_once('/')?;
+    // The file name is the UUID followed by the extension, e.g. `.webp` or `.thumb.webp`
+    let file_name = path.rsplit('/').next()?;
+    let uuid = Uuid::parse_str(file_name.split('.').next()?).ok()?;
+    match dir {
+        "posts" => Some((Some(PostImagePath::new(uuid)), None)),
+        "avatars" => Some((None, Some(AvatarImagePath::new(uuid)))),
+        _ => None,
+    }
+}
+
+/// Collecting orphaned images is scheduled as a background job.
+#[async_trait]
+impl Job for ImageCollector {
+    type Error = GcError;
+
+    fn name(&self) -> &'static str {
+        "collect_orphaned_images"
+    }
+
+    async fn run(&self) -> Result<(), GcError> {
+        self.collect().await.map(|_| ())
+    }
+}
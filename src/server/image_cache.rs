@@ -0,0 +1,182 @@
+//! In-memory cache of the most recently served images, in front of the configured image store,
+//! so that popular images, such as the avatars of frequent authors, are not read from disk or S3 on every request.
+//! Images are only ever deleted through the cache, which forgets them, so it never serves an image which was deleted
+//! by this server. Images deleted by another server sharing the store may be served until they are evicted.
+
+use crate::server::storage::{ImageStore, StorageError, StoredImage};
+use axum::async_trait;
+use axum::body::{Body, Bytes, HttpBody};
+use futures_util::StreamExt;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use tracing::debug;
+
+/// Images larger than this fraction of the capacity are not cached, so that one of them cannot evict all the others.
+const MAX_ENTRY_FRACTION: usize = 8;
+
+/// An image store which keeps the most recently read images in memory, up to a total size in bytes,
+/// evicting the least recently read images first.
+#[derive(Debug)]
+pub struct CachedStore {
+    inner: Arc<dyn ImageStore>,
+    cache: Mutex<Lru>,
+}
+
+impl CachedStore {
+    /// Cache the images of the store, up to the capacity in bytes.
+    pub fn new(inner: Arc<dyn ImageStore>, capacity: usize) -> Self {
+        Self { inner, cache: Mutex::new(Lru::new(capacity)) }
+    }
+
+    fn cache(&self) -> std::sync::MutexGuard<'_, Lru> {
+        self.cache.lock().expect("image cache lock should not be poisoned")
+    }
+}
+
+#[async_trait]
+impl ImageStore for CachedStore {
+    async fn put(&self, key: &str, bytes: Vec<u8>) -> Result<(), StorageError> {
+        let result = self.inner.put(key, bytes).await;
+        self.cache().remove(key);
+        result
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Body>, StorageError> {
+        if let Some(bytes) = self.cache().get(key) {
+            metrics::counter!("image_cache_hits_total").increment(1);
+            return Ok(Some(Body::from(bytes)));
+        }
+        metrics::counter!("image_cache_misses_total").increment(1);
+        let Some(body) = self.inner.get(key).await? else {
+            return Ok(None);
+        };
+        // Images which are too large to be cached are passed through as they are read, instead of buffered in memory
+        let max_entry_size = self.cache().max_entry_size();
+        if body.size_hint().lower() > max_entry_size as u64 {
+            return Ok(Some(body));
+        }
+        // Stores need not know the size of an image up front, so it is only buffered until it turns out too large
+        let mut chunks = Vec::new();
+        let mut size = 0;
+        let mut stream = body.into_data_stream();
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.map_err(std::io::Error::other)?;
+            size += chunk.len();
+            chunks.push(chunk);
+            if size > max_entry_size {
+                let read = futures_util::stream::iter(chunks.into_iter().map(Ok::<_, axum::Error>));
+                return Ok(Some(Body::from_stream(read.chain(stream))));
+            }
+        }
+        let bytes = Bytes::from(chunks.concat());
+        self.cache().insert(key, bytes.clone());
+        Ok(Some(Body::from(bytes)))
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, StorageError> {
+        if self.cache().contains(key) {
+            return Ok(true);
+        }
+        self.inner.exists(key).await
+    }
+
+    async fn touch(&self, key: &str) -> Result<bool, StorageError> {
+        self.inner.touch(key).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        self.cache().remove(key);
+        let result = self.inner.delete(key).await;
+        // A read which started before the deletion may have cached the image in the meantime
+        self.cache().remove(key);
+        result
+    }
+
+    async fn list(&self) -> Result<Vec<StoredImage>, StorageError> {
+        self.inner.list().await
+    }
+
+    async fn stored_bytes(&self) -> Result<u64, StorageError> {
+        self.inner.stored_bytes().await
+    }
+
+    async fn check_available(&self) -> Result<(), StorageError> {
+        self.inner.check_available().await
+    }
+
+    async fn remove_partial_writes(&self) -> Result<usize, StorageError> {
+        self.inner.remove_partial_writes().await
+    }
+}
+
+/// Images by key, with the order in which they were last read.
+#[derive(Debug)]
+struct Lru {
+    /// The maximum total size in bytes of the cached images.
+    capacity: usize,
+    /// The total size in bytes of the cached images.
+    size: usize,
+    /// Increases with every read, so that the entry with the lowest tick was read least recently.
+    tick: u64,
+    /// The cached images and the tick at which they were last read.
+    entries: HashMap<String, (Bytes, u64)>,
+    /// The keys of the cached images by the tick at which they were last read.
+    recency: BTreeMap<u64, String>,
+}
+
+impl Lru {
+    fn new(capacity: usize) -> Self {
+        Self { capacity, size: 0, tick: 0, entries: HashMap::new(), recency: BTreeMap::new() }
+    }
+
+    /// The size in bytes of the largest image which is cached.
+    fn max_entry_size(&self) -> usize {
+        self.capacity / MAX_ENTRY_FRACTION
+    }
+
+    fn contains(&self, key: &str) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    /// Returns the image under the key, if it is cached, and marks it as the most recently read.
+    fn get(&mut self, key: &str) -> Option<Bytes> {
+        self.tick += 1;
+        let (bytes, last_read) = self.entries.get_mut(key)?;
+        self.recency.remove(last_read);
+        *last_read = self.tick;
+        self.recency.insert(self.tick, key.to_string());
+        Some(bytes.clone())
+    }
+
+    /// Cache the image under the key, evicting the least recently read images until it fits.
+    /// Images which are too large for the cache are not cached.
+    fn insert(&mut self, key: &str, bytes: Bytes) {
+        if bytes.len() > self.max_entry_size() {
+            return;
+        }
+        self.remove(key);
+        while self.size + bytes.len() > self.capacity {
+            let Some((_, evicted)) = self.recency.pop_first() else {
+                break;
+            };
+            if let Some((evicted_bytes, _)) = self.entries.remove(&evicted) {
+                debug!("Evicting image {} from the cache", evicted);
+                self.size -= evicted_bytes.len();
+            }
+        }
+        self.tick += 1;
+        self.size += bytes.len();
+        self.entries.insert(key.to_string(), (bytes, self.tick));
+        self.recency.insert(self.tick, key.to_string());
+        metrics::gauge!("image_cache_bytes").set(self.size as f64);
+    }
+
+    /// Forget the image under the key, if it is cached.
+    fn remove(&mut self, key: &str) {
+        if let Some((bytes, last_read)) = self.entries.remove(key) {
+            self.recency.remove(&last_read);
+            self.size -= bytes.len();
+            metrics::gauge!("image_cache_bytes").set(self.size as f64);
+        }
+    }
+}
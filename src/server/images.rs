@@ -1,11 +1,29 @@
 //! Image processing utilities for the server.
 
-use crate::model::{AvatarImagePath, PostImagePath};
-use image::{DynamicImage, ImageError, ImageFormat, ImageReader};
+use crate::model::{AvatarCrop, AvatarImagePath, PostImagePath};
+use crate::server::avatar_cache::AvatarCache;
+use crate::server::config::ImageEncoding;
+use crate::server::outbound::{self, BlockedUrl, HttpClient};
+use crate::server::storage::{ImageStore, StorageError};
+use crate::server::Database;
+use image::codecs::avif::AvifEncoder;
+use image::codecs::gif::{GifDecoder, GifEncoder, Repeat};
+use image::codecs::webp::WebPEncoder;
+use image::metadata::Orientation;
+use image::{
+    AnimationDecoder, ColorType, DynamicImage, ImageDecoder, ImageError, ImageFormat, ImageReader, Rgb, RgbImage,
+};
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
 use std::fmt::Debug;
+use std::io::{BufRead, Cursor, Seek};
 use std::path::PathBuf;
-use tokio::try_join;
-use tracing::{debug, trace, warn};
+use std::sync::{LazyLock, OnceLock};
+use std::time::Duration;
+use tokio::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard, Semaphore};
+use tokio::task::JoinError;
+use tokio::join;
+use tracing::{debug, instrument, warn};
 use uuid::Uuid;
 
 /// Errors that can occur when processing images.
@@ -15,34 +33,199 @@ pub enum AppImageError {
     Download(reqwest::Error),
     #[display("Image error: {}", _0)]
     Decode(ImageError),
-    #[display("IO error: {}", _0)]
+    #[display("Storage error: {}", _0)]
+    Storage(StorageError),
+    #[display("Failed to read uploaded image: {}", _0)]
     Io(std::io::Error),
+    #[display("Unsupported image format: expected PNG, JPEG, WebP or GIF")]
+    #[from(skip)]
+    UnsupportedFormat,
+    #[display("The image is too large, it can be at most {} KiB", max_bytes / 1024)]
+    #[from(skip)]
+    TooLarge { max_bytes: usize },
+    #[display("The image could not be downloaded within {} seconds", timeout.as_secs())]
+    #[from(skip)]
+    DownloadTimeout { timeout: Duration },
+    #[display("The URL does not point to an image, but to {}", content_type)]
+    #[from(skip)]
+    NotAnImage { content_type: String },
+    #[display("The image cannot be downloaded from there: {}", _0)]
+    Blocked(BlockedUrl),
 }
 
-/// Returns the path to the image with the provided UUID on the file system.
+/// The rules for the images of new blog posts.
+#[derive(Debug, Clone, Copy)]
+pub struct ImagePolicy {
+    /// The maximum size in bytes of an image file, checked before it is decoded.
+    pub max_bytes: usize,
+    /// Whether blog posts with an image must describe it for people who cannot see it.
+    pub require_alt_text: bool,
+    /// Whether blog posts without an avatar get the Gravatar of their author instead.
+    pub gravatar_fallback: bool,
+    /// The format in which new images are stored.
+    pub encoding: ImageEncoding,
+    /// The length in pixels of the longest side to which browsers downscale images before uploading them, if any.
+    /// The server does not enforce this, since it only saves the bandwidth of the author.
+    pub max_dimension: Option<u32>,
+    /// How long downloading an avatar may take in total, from connecting to receiving its last byte.
+    pub download_timeout: Duration,
+}
+
+impl ImagePolicy {
+    /// The format in which new images are stored.
+    pub fn format(&self) -> ImageFormat {
+        match self.encoding {
+            ImageEncoding::Png => ImageFormat::Png,
+            ImageEncoding::WebP => ImageFormat::WebP,
+            ImageEncoding::Avif { .. } => ImageFormat::Avif,
+        }
+    }
+}
+
+/// Limits how many CPU-bound image tasks, e.g. decoding and encoding, run at once,
+/// so that a burst of uploads cannot occupy every thread of the blocking pool, which database queries need as well.
+static WORKERS: OnceLock<Semaphore> = OnceLock::new();
+
+/// Let at most `parallelism` images be decoded, hashed or encoded at once.
+/// This must be called before any image is processed, since the limit defaults to the number of CPUs otherwise.
+pub fn set_parallelism(parallelism: usize) {
+    if WORKERS.set(Semaphore::new(parallelism)).is_err() {
+        warn!("The number of image workers was already set, ignoring {}", parallelism);
+    }
+}
+
+/// Keeps stored images from being deleted as unreferenced while blog posts which reuse them are being created.
+/// Images are reused by the blog posts with identical images, so an image which no blog post references yet
+/// may be about to be referenced by one which is being inserted.
+/// Saving or reusing images holds it for reading until the blog post is inserted, see `ImageReservation`,
+/// while deleting unreferenced images holds it for writing from checking their references until they are deleted.
+static REFERENCES: LazyLock<RwLock<()>> = LazyLock::new(|| RwLock::new(()));
+
+/// Keeps the images which were saved for a blog post from being deleted as unreferenced until it is dropped.
+/// Hold it until the blog post which references them is inserted, and drop it before deleting any images,
+/// or the deletion waits for it forever.
+#[derive(Debug)]
+pub struct ImageReservation(#[allow(dead_code)] RwLockReadGuard<'static, ()>);
+
+/// Wait until no images are reserved for blog posts which are being created, and keep new ones from being reserved
+/// until the guard is dropped. Hold it from checking the references of images until they are deleted.
+pub async fn lock_deletions() -> RwLockWriteGuard<'static, ()> {
+    REFERENCES.write().await
+}
+
+/// Run the CPU-bound image work on the blocking thread pool as soon as one of the workers is free.
+/// The worker stays occupied until the work is done, even if the request which waits for it is cancelled.
+async fn run_blocking<T: Send + 'static>(work: impl FnOnce() -> T + Send + 'static) -> Result<T, JoinError> {
+    let workers = WORKERS.get_or_init(|| {
+        Semaphore::new(std::thread::available_parallelism().map_or(1, |parallelism| parallelism.get()))
+    });
+    let permit = workers.acquire().await.expect("the image workers are never closed");
+    tokio::task::spawn_blocking(move || {
+        let _permit = permit;
+        work()
+    })
+    .await
+}
+
+/// The image formats which are accepted for post images and avatars.
+/// Images are converted to the configured `ImageEncoding` before they are saved, regardless of their original format.
+const SUPPORTED_FORMATS: [ImageFormat; 4] = [
+    ImageFormat::Png,
+    ImageFormat::Jpeg,
+    ImageFormat::WebP,
+    ImageFormat::Gif,
+];
+
+/// The width and height in pixels of the Gravatars which are downloaded as fallback avatars.
+const GRAVATAR_SIZE: u32 = 128;
+
+/// The number of rows and columns of cells in generated identicons.
+const IDENTICON_GRID: u32 = 5;
+
+/// The width and height in pixels of a single cell of generated identicons.
+const IDENTICON_CELL_SIZE: u32 = 24;
+
+/// The formats in which images can be stored.
+/// Images stay in the format which was configured when they were saved until they are re-encoded.
+const STORED_FORMATS: [ImageFormat; 3] = [ImageFormat::WebP, ImageFormat::Avif, ImageFormat::Png];
+
+/// How much effort goes into encoding images as AVIF, from 1 (slowest, smallest) to 10 (fastest).
+/// Encoding happens while the author waits for their blog post to be created, so speed matters.
+const AVIF_SPEED: u8 = 8;
+
+/// How much effort goes into reducing the colors of the frames of animated GIFs when they are re-encoded,
+/// from 1 (slowest, best colors) to 30 (fastest).
+const GIF_SPEED: i32 = 10;
+
+/// The maximum width in pixels of the thumbnails generated for post images.
+const THUMBNAIL_WIDTH: u32 = 480;
+
+/// The bit of the last byte of the UUID of an image which is set if the image is an animated GIF,
+/// so that serving an image only looks for its animation if it has one.
+const ANIMATED_UUID_BIT: u8 = 1;
+
+/// Whether the UUID is that of an animated GIF, see `content_uuid`.
+fn is_animated(uuid: &str) -> bool {
+    Uuid::parse_str(uuid).is_ok_and(|uuid| uuid.as_bytes()[15] & ANIMATED_UUID_BIT != 0)
+}
+
+/// Returns the key under which the image with the provided UUID is stored.
 /// In order to prevent the file system from becoming overwhelmed,
-/// images are stored in directories based on their type and the first four characters of their UUID.
-/// Their file name is their UUID with a `.png` extension.
-/// For example, a post image with UUID `123e4567-e89b-12d3-a456-426614174000` would be stored at:
-/// `./images/posts/12/3e/123e4567-e89b-12d3-a456-426614174000.png`
+/// images are grouped by their type and the first four characters of their UUID.
+/// Their name is their UUID with the provided extension, e.g. `webp`.
+/// For example, a post image with UUID `123e4567-e89b-12d3-a456-426614174000` would be stored under:
+/// `posts/12/3e/123e4567-e89b-12d3-a456-426614174000.webp`
 ///
 /// Safety: Only call this function with valid UUIDs.
 /// It will panic if there are not enough characters in the UUID.
-fn image_path(dir: &str, uuid: &str) -> PathBuf {
+fn image_key(dir: &str, uuid: &str, extension: &str) -> String {
     format!(
-        "./images/{}/{}/{}/{}.png",
+        "{}/{}/{}/{}.{}",
         dir,
         &uuid[0..2],
         &uuid[2..4],
-        uuid
+        uuid,
+        extension
     )
-    .into()
+}
+
+/// Returns the file extension of images stored in the format.
+fn extension(format: ImageFormat) -> &'static str {
+    format.extensions_str()[0]
+}
+
+/// Returns the formats in which an image may be stored, starting with the preferred one.
+pub fn stored_formats(preferred: ImageFormat) -> impl Iterator<Item = ImageFormat> {
+    std::iter::once(preferred).chain(STORED_FORMATS.into_iter().filter(move |format| *format != preferred))
 }
 
 /// The `ImagePath` trait is used to abstract over the different locations where images are stored.
-pub trait ImagePath: Debug + Send + 'static {
+pub trait ImagePath: Debug + Send + Sync + 'static {
     fn new(uuid: Uuid) -> Self;
-    fn path(&self) -> PathBuf;
+    /// Returns the UUID of the image.
+    fn uuid(&self) -> &str;
+    /// Returns the key under which the image is stored in the `ImageStore` in the format.
+    fn key(&self, format: ImageFormat) -> String;
+    /// Returns the key under which the downscaled thumbnail of the image is stored in the format,
+    /// or `None` if no thumbnail is generated for this kind of image.
+    fn thumbnail_key(&self, _format: ImageFormat) -> Option<String> {
+        None
+    }
+    /// Returns the key under which the image is stored as a GIF if it is animated.
+    /// The still image under `key` is its first frame.
+    fn gif_key(&self) -> String;
+    /// Returns the key of the animation of the image, or `None` if its UUID says it is a still image.
+    fn animated_key(&self) -> Option<String> {
+        is_animated(self.uuid()).then(|| self.gif_key())
+    }
+    /// Returns every key under which the image, its thumbnail or its animation may be stored,
+    /// with the originals last, since they are stored last.
+    fn all_keys(&self) -> Vec<String> {
+        let mut keys = vec![self.gif_key()];
+        keys.extend(STORED_FORMATS.into_iter().filter_map(|format| self.thumbnail_key(format)));
+        keys.extend(STORED_FORMATS.into_iter().map(|format| self.key(format)));
+        keys
+    }
 }
 
 impl ImagePath for PostImagePath {
@@ -50,10 +233,23 @@ impl ImagePath for PostImagePath {
         PostImagePath(uuid.to_string())
     }
 
-    /// Post images are stored in the `images/posts` directory.
-    /// Returns the path to the image file on the file system.
-    fn path(&self) -> PathBuf {
-        image_path("posts", &self.0)
+    fn uuid(&self) -> &str {
+        &self.0
+    }
+
+    /// Post images are stored under the `posts` prefix.
+    fn key(&self, format: ImageFormat) -> String {
+        image_key("posts", &self.0, extension(format))
+    }
+
+    /// Post image thumbnails are stored next to the original with a `.thumb` infix, e.g. `.thumb.webp`.
+    fn thumbnail_key(&self, format: ImageFormat) -> Option<String> {
+        Some(image_key("posts", &self.0, &format!("thumb.{}", extension(format))))
+    }
+
+    /// Animated post images are stored next to the original with a `.gif` extension.
+    fn gif_key(&self) -> String {
+        image_key("posts", &self.0, "gif")
     }
 }
 
@@ -62,121 +258,713 @@ impl ImagePath for AvatarImagePath {
         AvatarImagePath(uuid.to_string())
     }
 
-    /// Avatars are stored in the `images/avatars` directory.
-    /// Returns the path to the image file on the file system.
-    fn path(&self) -> PathBuf {
-        image_path("avatars", &self.0)
+    fn uuid(&self) -> &str {
+        &self.0
+    }
+
+    /// Avatars are stored under the `avatars` prefix.
+    fn key(&self, format: ImageFormat) -> String {
+        image_key("avatars", &self.0, extension(format))
+    }
+
+    /// Animated avatars are stored next to the original with a `.gif` extension.
+    fn gif_key(&self) -> String {
+        image_key("avatars", &self.0, "gif")
     }
 }
 
-/// Preprocesses the post image bytes and avatar URL, if present.
-/// Returns the UUIDs of the saved images, if any.
+/// A validated image which is ready to be saved.
+enum ProcessedImage {
+    /// An image which is saved in the configured format.
+    Still(DynamicImage),
+    /// An animated GIF, which is re-encoded frame by frame so that it keeps its animation but nothing else,
+    /// along with its first frame in the configured format for thumbnails.
+    Animated { bytes: Vec<u8>, first_frame: DynamicImage },
+}
+
+impl ProcessedImage {
+    /// The width and height in pixels of the image, or of the first frame of an animation.
+    fn dimensions(&self) -> (u32, u32) {
+        let image = match self {
+            ProcessedImage::Still(image) => image,
+            ProcessedImage::Animated { first_frame, .. } => first_frame,
+        };
+        (image.width(), image.height())
+    }
+}
+
+/// A saved post image, along with its size, which clients reserve space for before the image loads.
+#[derive(Debug, Clone)]
+pub struct SavedPostImage {
+    pub path: PostImagePath,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Where the avatar of a blog post comes from.
+#[derive(Debug)]
+pub enum AvatarSource {
+    /// The avatar is downloaded from the URL.
+    Url(String),
+    /// The avatar was uploaded to the file, and is cropped to the square if one was chosen.
+    Upload { path: PathBuf, crop: Option<AvatarCrop> },
+}
+
+/// Preprocesses the uploaded post image and the avatar, if present.
+/// Returns the saved post image along with its size and the UUID of the saved avatar, if any,
+/// and the reservation which keeps them from being deleted until the blog post referencing them is inserted.
+/// If one of the images fails, the other one is deleted again unless other blog posts share it,
+/// so that no image is left behind for a blog post which is never created.
+#[instrument(level = "info", skip_all, fields(image = post_image.is_some(), avatar = avatar.is_some()))]
 pub async fn process_images(
-    post_image_bytes: Option<Vec<u8>>,
-    avatar_url: Option<String>,
-) -> Result<(Option<PostImagePath>, Option<AvatarImagePath>), AppImageError> {
-    match (post_image_bytes, avatar_url) {
+    store: &dyn ImageStore,
+    database: &Database,
+    client: &HttpClient,
+    cache: &AvatarCache,
+    policy: ImagePolicy,
+    post_image: Option<PathBuf>,
+    avatar: Option<AvatarSource>,
+) -> Result<(Option<SavedPostImage>, Option<AvatarImagePath>, ImageReservation), AppImageError> {
+    let reservation = ImageReservation(REFERENCES.read().await);
+    match (post_image, avatar) {
         (None, None) => {
             debug!("No images to process");
-            Ok((None, None))
+            Ok((None, None, reservation))
         }
         (Some(post_image), None) => {
             debug!("Processing post image");
-            let image = process_image(post_image).await?;
-            let image_path = save(image).await?;
-            Ok((Some(image_path), None))
+            let image = process_and_save_image(store, policy, post_image).await?;
+            Ok((Some(image), None, reservation))
         }
-        (None, Some(avatar_url)) => {
+        (None, Some(avatar)) => {
             debug!("Processing avatar image");
-            let avatar = process_avatar(avatar_url).await?;
-            let avatar_path = save(avatar).await?;
-            Ok((None, Some(avatar_path)))
+            let avatar_path = process_and_save_avatar(store, client, cache, policy, avatar).await?;
+            Ok((None, Some(avatar_path), reservation))
         }
-        (Some(post_image), Some(avatar_url)) => {
+        (Some(post_image), Some(avatar)) => {
             debug!("Processing post and avatar images");
-            let (image, avatar) = try_join!(process_image(post_image), process_avatar(avatar_url))?;
-            let (image_path, avatar_path) = try_join!(save(image), save(avatar))?;
-            Ok((Some(image_path), Some(avatar_path)))
+            let (image, avatar_path) = join!(
+                process_and_save_image(store, policy, post_image),
+                process_and_save_avatar(store, client, cache, policy, avatar),
+            );
+            // If only one of the images was saved, it would be left behind without a blog post
+            match (image, avatar_path) {
+                (Ok(image), Ok(avatar_path)) => Ok((Some(image), Some(avatar_path), reservation)),
+                (Ok(image), Err(err)) => {
+                    drop(reservation);
+                    delete_unreferenced(store, database, Some(image.path), None).await;
+                    Err(err)
+                }
+                (Err(err), Ok(avatar_path)) => {
+                    drop(reservation);
+                    delete_unreferenced(store, database, None, Some(avatar_path)).await;
+                    Err(err)
+                }
+                (Err(err), Err(_)) => Err(err),
+            }
         }
     }
 }
 
-/// Validate that the bytes are a PNG image, if present.
-async fn process_image(bytes: Vec<u8>) -> Result<DynamicImage, AppImageError> {
-    let image = decode(bytes).await?;
-    // Do more processing here if needed, e.g. resizing
-    Ok(image)
+/// Process the uploaded post image and save it.
+async fn process_and_save_image(
+    store: &dyn ImageStore,
+    policy: ImagePolicy,
+    path: PathBuf,
+) -> Result<SavedPostImage, AppImageError> {
+    let image = process_image(path, policy).await?;
+    let (width, height) = image.dimensions();
+    let path = save(store, policy, image).await?;
+    Ok(SavedPostImage { path, width, height })
+}
+
+/// Process the avatar and save it, unless it is given as a URL which an avatar was downloaded from recently,
+/// in which case that avatar is reused without downloading it again.
+async fn process_and_save_avatar(
+    store: &dyn ImageStore,
+    client: &HttpClient,
+    cache: &AvatarCache,
+    policy: ImagePolicy,
+    avatar: AvatarSource,
+) -> Result<AvatarImagePath, AppImageError> {
+    let url = match &avatar {
+        AvatarSource::Url(url) => Some(url.clone()),
+        AvatarSource::Upload { .. } => None,
+    };
+    if let Some(url) = &url {
+        if let Some(cached) = cache.get(store, policy, url).await {
+            return Ok(cached);
+        }
+    }
+    let processed = process_avatar(avatar, client, policy).await?;
+    let avatar_path = save(store, policy, processed).await?;
+    if let Some(url) = &url {
+        cache.put(url, &avatar_path).await;
+    }
+    Ok(avatar_path)
 }
 
-/// Download the file at the URL and validate that it is a PNG image, if present.
-async fn process_avatar(url: String) -> Result<DynamicImage, AppImageError> {
-    let bytes = download(url).await?;
-    let image = decode(bytes).await?;
+/// Validate that the uploaded file is an image in a supported format.
+/// The file is decoded straight from disk, so it is never held in memory as a whole besides its pixels,
+/// unless it is a GIF, whose bytes are kept in case it is animated.
+#[instrument(level = "info")]
+async fn process_image(path: PathBuf, policy: ImagePolicy) -> Result<ProcessedImage, AppImageError> {
+    let size = tokio::fs::metadata(&path).await?.len();
+    if size > policy.max_bytes as u64 {
+        debug!("Rejecting image of {} bytes", size);
+        return Err(AppImageError::TooLarge { max_bytes: policy.max_bytes });
+    }
+    // Decoding is CPU-bound and reads the file synchronously, so it must not block the async runtime
+    let image = run_blocking(move || {
+        let reader = ImageReader::open(&path)?.with_guessed_format()?;
+        match reader.format() {
+            Some(ImageFormat::Gif) => decode_gif(std::fs::read(&path)?),
+            _ => decode_with(reader).map(ProcessedImage::Still),
+        }
+    })
+    .await
+    .expect("decoding should not panic")?;
     // Do more processing here if needed, e.g. resizing
     Ok(image)
 }
 
-/// Downloads the bytes at the provided URL.
-async fn download(url: String) -> Result<Vec<u8>, reqwest::Error> {
-    debug!("Downloading image from {}", url);
-    reqwest::get(&url)
-        .await?
-        .bytes()
+/// Download the avatar if it is given as a URL, and validate that it is an image in a supported format.
+#[instrument(level = "info", skip(client))]
+async fn process_avatar(avatar: AvatarSource, client: &HttpClient, policy: ImagePolicy) -> Result<ProcessedImage, AppImageError> {
+    match avatar {
+        AvatarSource::Url(url) => {
+            let bytes = download(client, url, policy).await?;
+            let image = decode(bytes).await?;
+            // Do more processing here if needed, e.g. resizing
+            Ok(image)
+        }
+        AvatarSource::Upload { path, crop } => {
+            let avatar = process_image(path, policy).await?;
+            Ok(match crop {
+                Some(crop) => crop_avatar(avatar, crop),
+                None => avatar,
+            })
+        }
+    }
+}
+
+/// Crop the avatar to the square chosen by its author.
+/// Animated avatars lose their animation, since only their first frame is cropped.
+fn crop_avatar(avatar: ProcessedImage, crop: AvatarCrop) -> ProcessedImage {
+    let image = match avatar {
+        ProcessedImage::Still(image) => image,
+        ProcessedImage::Animated { first_frame, .. } => first_frame,
+    };
+    let (left, top, side) = crop.pixels(image.width(), image.height());
+    debug!("Cropping avatar to {}x{} pixels at ({}, {})", side, side, left, top);
+    ProcessedImage::Still(image.crop_imm(left, top, side, side))
+}
+
+/// Find an avatar for a blog post whose author did not provide one.
+/// This is the Gravatar of the user if `gravatar_fallback` is enabled and it can be downloaded,
+/// and otherwise an identicon generated from their username.
+/// Returns `None` if neither can be saved, since a missing avatar should not stop a blog post from being created.
+#[instrument(level = "info", skip(store, client, cache, policy))]
+pub async fn process_fallback_avatar(
+    store: &dyn ImageStore,
+    client: &HttpClient,
+    cache: &AvatarCache,
+    policy: ImagePolicy,
+    username: &str,
+) -> Option<AvatarImagePath> {
+    if policy.gravatar_fallback {
+        match process_gravatar(store, client, cache, policy, username).await {
+            Ok(avatar_path) => return Some(avatar_path),
+            Err(err) => warn!("Failed to fetch the Gravatar of {}, generating an identicon: {}", username, err),
+        }
+    }
+    let username = username.to_string();
+    let identicon = run_blocking(move || generate_identicon(&username))
+        .await
+        .expect("generating an identicon should not panic");
+    // Identicons of the same user are identical, so `save` stores each of them only once
+    save(store, policy, ProcessedImage::Still(identicon))
         .await
-        .map(|bytes| bytes.to_vec())
+        .inspect_err(|err| warn!("Failed to save identicon: {}", err))
+        .ok()
 }
 
-/// Validates that the provided bytes are a PNG image.
-/// Returns the decoded image if it is a PNG, otherwise returns an error.
-async fn decode(image_bytes: Vec<u8>) -> Result<DynamicImage, ImageError> {
-    ImageReader::with_format(std::io::Cursor::new(image_bytes), ImageFormat::Png).decode()
+/// Download the Gravatar of the user and save it like a downloaded avatar, which is cached like one as well.
+/// Users have no email address, so the hash of their username stands in for the hash of one,
+/// for which Gravatar generates an identicon unless someone claimed the hash.
+async fn process_gravatar(
+    store: &dyn ImageStore,
+    client: &HttpClient,
+    cache: &AvatarCache,
+    policy: ImagePolicy,
+    username: &str,
+) -> Result<AvatarImagePath, AppImageError> {
+    let hash = Sha256::digest(username.trim().to_lowercase().as_bytes());
+    let url = format!("https://gravatar.com/avatar/{hash:x}?d=identicon&s={GRAVATAR_SIZE}");
+    process_and_save_avatar(store, client, cache, policy, AvatarSource::Url(url)).await
 }
 
-/// Save the image to the file system.
-/// This creates a new UUID for the image, saves the image to the corresponding file path,
-/// and returns the UUID in the corresponding newtype.
-async fn save<Path: ImagePath>(image: DynamicImage) -> Result<Path, AppImageError> {
-    tokio::task::spawn_blocking(move || {
-        let image_path = Path::new(Uuid::new_v4());
-        let path = image_path.path();
-        // Create the directory if it doesn't exist
-        // Safety: We know the parent directory exists because we are creating the path from the UUID
-        std::fs::create_dir_all(path.parent().expect("parent dir should exist"))?;
-        image.save(path)?;
-        Ok(image_path)
+/// Generate a GitHub-style identicon from the hash of the username:
+/// a horizontally symmetric grid of `IDENTICON_GRID` by `IDENTICON_GRID` cells in a color derived from the hash,
+/// surrounded by a margin of half a cell.
+fn generate_identicon(username: &str) -> DynamicImage {
+    let hash = blake3::hash(username.trim().to_lowercase().as_bytes());
+    let hash = hash.as_bytes();
+    let color = Rgb([hash[0], hash[1], hash[2]]);
+    let background = Rgb([240, 240, 240]);
+    // Only the left half and the middle column are random, the right half mirrors the left
+    let columns = IDENTICON_GRID.div_ceil(2);
+    let filled = |row: u32, column: u32| {
+        let column = column.min(IDENTICON_GRID - 1 - column);
+        let bit = (row * columns + column) as usize;
+        hash[3 + bit / 8] & (1 << (bit % 8)) != 0
+    };
+    let margin = IDENTICON_CELL_SIZE / 2;
+    let size = IDENTICON_GRID * IDENTICON_CELL_SIZE + 2 * margin;
+    let image = RgbImage::from_fn(size, size, |x, y| {
+        let in_grid = (margin..size - margin).contains(&x) && (margin..size - margin).contains(&y);
+        if in_grid && filled((y - margin) / IDENTICON_CELL_SIZE, (x - margin) / IDENTICON_CELL_SIZE) {
+            color
+        } else {
+            background
+        }
+    });
+    DynamicImage::ImageRgb8(image)
+}
+
+/// Downloads the image at the provided URL.
+/// The download is aborted if it takes longer than the `download_timeout` of the policy,
+/// so that a slow server cannot hold up the creation of a blog post indefinitely.
+async fn download(client: &HttpClient, url: String, policy: ImagePolicy) -> Result<Vec<u8>, AppImageError> {
+    let timeout = policy.download_timeout;
+    tokio::time::timeout(timeout, download_bytes(client, url, policy.max_bytes))
+        .await
+        .map_err(|_| AppImageError::DownloadTimeout { timeout })?
+}
+
+/// Downloads the bytes at the provided URL, if the server says that they are an image.
+/// The download is aborted as soon as it exceeds `max_bytes`, even if the server did not announce its length.
+async fn download_bytes(client: &HttpClient, url: String, max_bytes: usize) -> Result<Vec<u8>, AppImageError> {
+    debug!("Downloading image from {}", url);
+    let url = reqwest::Url::parse(&url).map_err(|_| BlockedUrl::NoHost)?;
+    outbound::check_public_url(&url)?;
+    let mut response = client.get(url).send().await?.error_for_status()?;
+    // Servers which do not say what they serve get the benefit of the doubt, since the bytes are decoded anyway
+    if let Some(content_type) = response.headers().get(reqwest::header::CONTENT_TYPE) {
+        let content_type = content_type.to_str().unwrap_or_default().to_ascii_lowercase();
+        if !content_type.starts_with("image/") {
+            return Err(AppImageError::NotAnImage { content_type });
+        }
+    }
+    let too_large = AppImageError::TooLarge { max_bytes };
+    if response.content_length().is_some_and(|length| length > max_bytes as u64) {
+        return Err(too_large);
+    }
+    let mut bytes = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if bytes.len() + chunk.len() > max_bytes {
+            return Err(too_large);
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(bytes)
+}
+
+/// Detects the format of the provided bytes from their magic bytes and decodes them.
+/// Returns the decoded image if it is in one of the `SUPPORTED_FORMATS`, otherwise returns an error.
+async fn decode(image_bytes: Vec<u8>) -> Result<ProcessedImage, AppImageError> {
+    // Decoding is CPU-bound, so it must not block the async runtime
+    run_blocking(move || {
+        if image::guess_format(&image_bytes).ok() == Some(ImageFormat::Gif) {
+            return decode_gif(image_bytes);
+        }
+        decode_with(ImageReader::new(Cursor::new(image_bytes))).map(ProcessedImage::Still)
     })
     .await
-    .expect("saving should not panic")
-    .inspect(|save| debug!("Saved image to {}", save.path().display()))
-    .inspect_err(|e| warn!("Failed to save image: {}", e))
+    .expect("decoding should not panic")
+}
+
+/// Decode every frame of the GIF to validate it, holding only one frame in memory at a time.
+/// GIFs with a single frame are still images. GIFs with more frames are re-encoded frame by frame
+/// with the delays of their frames, looping forever, which drops the comments, XMP metadata
+/// and other extensions of the uploaded GIF, like decoding and encoding drops the metadata of still images.
+fn decode_gif(bytes: Vec<u8>) -> Result<ProcessedImage, AppImageError> {
+    let mut frames = GifDecoder::new(Cursor::new(bytes))?.into_frames();
+    // A GIF without frames is not an image
+    let first = frames.next().ok_or(AppImageError::UnsupportedFormat)??;
+    let first_frame = DynamicImage::ImageRgba8(first.buffer().clone());
+    let Some(second) = frames.next().transpose()? else {
+        return Ok(ProcessedImage::Still(first_frame));
+    };
+    let mut animation = Vec::new();
+    let mut frame_count = 2;
+    {
+        let mut encoder = GifEncoder::new_with_speed(&mut animation, GIF_SPEED);
+        encoder.set_repeat(Repeat::Infinite)?;
+        encoder.encode_frame(first)?;
+        encoder.encode_frame(second)?;
+        for frame in frames {
+            encoder.encode_frame(frame?)?;
+            frame_count += 1;
+        }
+    }
+    debug!("Re-encoded animated GIF with {} frames", frame_count);
+    Ok(ProcessedImage::Animated { bytes: animation, first_frame })
+}
+
+/// Detects the format of the image read by the reader from its magic bytes and decodes it.
+/// Only the pixels are kept, so metadata such as EXIF and XMP, which can contain the GPS coordinates
+/// of a photo and the device it was taken with, never reaches the image store.
+/// The EXIF orientation is applied to the pixels before it is discarded, so that photos stay upright.
+fn decode_with<R: BufRead + Seek>(reader: ImageReader<R>) -> Result<DynamicImage, AppImageError> {
+    let reader = reader.with_guessed_format()?;
+    match reader.format() {
+        Some(format) if SUPPORTED_FORMATS.contains(&format) => {}
+        format => {
+            debug!("Rejecting image in unsupported format {:?}", format);
+            return Err(AppImageError::UnsupportedFormat);
+        }
+    }
+    let mut decoder = reader.into_decoder()?;
+    let orientation = decoder.orientation()?;
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    if orientation != Orientation::NoTransforms {
+        debug!("Applying EXIF orientation {:?}", orientation);
+        image.apply_orientation(orientation);
+    }
+    Ok(image)
+}
+
+/// Save the image to the image store.
+/// The UUID of the image is derived from a hash of its content, so identical images share a UUID.
+/// If an image with the same UUID is already stored, it is reused and renewed instead of being stored again.
+/// Otherwise, the image is stored in the configured format under the key of the UUID,
+/// and if the image type has a thumbnail key, a thumbnail at most `THUMBNAIL_WIDTH` pixels wide
+/// is stored under it as well.
+/// Animated GIFs are additionally stored as re-encoded under their animated key,
+/// while their first frame takes the place of the image under the other keys.
+/// Returns the UUID in the corresponding newtype.
+#[instrument(level = "info", skip_all)]
+async fn save<Path: ImagePath>(
+    store: &dyn ImageStore,
+    policy: ImagePolicy,
+    image: ProcessedImage,
+) -> Result<Path, AppImageError> {
+    // Hashing and encoding are CPU-bound, so they must not block the async runtime
+    let (image, uuid) = run_blocking(move || {
+        let uuid = content_uuid(&image);
+        (image, uuid)
+    })
+    .await
+    .expect("hashing should not panic");
+    let image_path = Path::new(uuid);
+    let key = image_path.key(policy.format());
+    if renew(store, policy, &image_path).await? {
+        debug!("Reusing identical image {}", key);
+        return Ok(image_path);
+    }
+    let (image, animated_bytes) = match image {
+        ProcessedImage::Still(image) => (image, None),
+        ProcessedImage::Animated { bytes, first_frame } => (first_frame, Some(bytes)),
+    };
+    let result = async {
+        if let Some(animated_bytes) = animated_bytes {
+            store.put(&image_path.gif_key(), animated_bytes).await?;
+        }
+        store_encoded(store, policy, &image_path, image).await
+    }
+    .await;
+    match result {
+        Ok(()) => {
+            debug!("Saved image to {}", key);
+            Ok(image_path)
+        }
+        Err(err) => {
+            warn!("Failed to save image: {}", err);
+            Err(err)
+        }
+    }
+}
+
+/// Touch the image in the configured format, along with its thumbnail and animation, if it is stored,
+/// so that the garbage collector of any server sharing the store treats it as new while it is being reused.
+/// Returns whether the image is stored in the configured format.
+pub async fn renew<Path: ImagePath>(
+    store: &dyn ImageStore,
+    policy: ImagePolicy,
+    image_path: &Path,
+) -> Result<bool, StorageError> {
+    if !store.touch(&image_path.key(policy.format())).await? {
+        return Ok(false);
+    }
+    // The thumbnail and animation are stored before the original, so if the original exists, so do they
+    if let Some(thumbnail_key) = image_path.thumbnail_key(policy.format()) {
+        store.touch(&thumbnail_key).await?;
+    }
+    if let Some(animated_key) = image_path.animated_key() {
+        store.touch(&animated_key).await?;
+    }
+    Ok(true)
+}
+
+/// Encode the image and its thumbnail, if the image type has one, in the configured format
+/// and store them under the keys of the image, the thumbnail first.
+async fn store_encoded<Path: ImagePath>(
+    store: &dyn ImageStore,
+    policy: ImagePolicy,
+    image_path: &Path,
+    image: DynamicImage,
+) -> Result<(), AppImageError> {
+    let format = policy.format();
+    let thumbnail_key = image_path.thumbnail_key(format);
+    let has_thumbnail = thumbnail_key.is_some();
+    // Encoding is CPU-bound, so it must not block the async runtime
+    let (bytes, thumbnail_bytes) = run_blocking(move || {
+        let thumbnail_bytes = if !has_thumbnail {
+            None
+        } else if image.width() > THUMBNAIL_WIDTH {
+            Some(encode(&image.thumbnail(THUMBNAIL_WIDTH, u32::MAX), policy.encoding)?)
+        } else {
+            // Images which are already narrow enough are not upscaled
+            Some(encode(&image, policy.encoding)?)
+        };
+        Ok::<_, ImageError>((encode(&image, policy.encoding)?, thumbnail_bytes))
+    })
+    .await
+    .expect("encoding should not panic")?;
+    if let (Some(key), Some(bytes)) = (thumbnail_key, thumbnail_bytes) {
+        store.put(&key, bytes).await?;
+    }
+    store.put(&image_path.key(format), bytes).await?;
+    Ok(())
 }
 
-/// Loads the image from the file system with the provided UUID.
-#[rustfmt::skip]
-pub async fn load<I: ImagePath>(image_uuid: &I) -> Result<Vec<u8>, AppImageError> {
-    let path = image_uuid.path();
-    tokio::task::spawn_blocking(move || std::fs::read(path))
+/// Store the image in the configured format if it is only stored in another format,
+/// e.g. because it was saved before the configured encoding was changed.
+/// The copies in other formats are deleted once it is stored, so that it can be served throughout.
+/// The UUID stays the same, since blog posts refer to it, even if it no longer matches the pixels after lossy encoding.
+/// Returns whether the image was re-encoded.
+#[instrument(level = "info", skip(store, policy))]
+pub async fn reencode<Path: ImagePath>(
+    store: &dyn ImageStore,
+    policy: ImagePolicy,
+    image_path: &Path,
+) -> Result<bool, AppImageError> {
+    let format = policy.format();
+    if store.exists(&image_path.key(format)).await? {
+        return Ok(false);
+    }
+    let mut source = None;
+    for other in stored_formats(format).skip(1) {
+        if let Some(body) = store.get(&image_path.key(other)).await? {
+            source = Some((other, body));
+            break;
+        }
+    }
+    let Some((source_format, body)) = source else {
+        warn!("Image {:?} is not stored in any format", image_path);
+        return Ok(false);
+    };
+    let bytes = axum::body::to_bytes(body, usize::MAX)
         .await
-        .expect("loading should not panic")
-        .inspect(|_| trace!("Loaded image from {}", image_uuid.path().display()))
-        .inspect_err(|e| warn!("Failed to load image from {}: {}", image_uuid.path().display(), e))
-        .map_err(Into::into)
+        .map_err(|err| StorageError::Io(std::io::Error::other(err)))?;
+    let image = run_blocking(move || image::load_from_memory_with_format(&bytes, source_format))
+        .await
+        .expect("decoding should not panic")?;
+    store_encoded(store, policy, image_path, image).await?;
+    // The original is deleted before the thumbnail, so that a remaining original always has its thumbnail
+    store.delete(&image_path.key(source_format)).await?;
+    if let Some(thumbnail_key) = image_path.thumbnail_key(source_format) {
+        store.delete(&thumbnail_key).await?;
+    }
+    debug!("Re-encoded image {:?} from {:?} to {:?}", image_path, source_format, format);
+    Ok(true)
+}
+
+/// Derive a UUID from a BLAKE3 hash of the dimensions, color type and pixels of the image.
+/// The decoded pixels are hashed rather than the uploaded bytes,
+/// so that the same image is recognized even if it was uploaded in a different format.
+/// Animated GIFs are stored as re-encoded, so their re-encoded bytes are hashed instead,
+/// and their UUID is marked with the `ANIMATED_UUID_BIT`, which is clear for still images.
+fn content_uuid(image: &ProcessedImage) -> Uuid {
+    let mut hasher = blake3::Hasher::new();
+    match image {
+        ProcessedImage::Still(image) => {
+            hasher.update(&image.width().to_le_bytes());
+            hasher.update(&image.height().to_le_bytes());
+            hasher.update(format!("{:?}", image.color()).as_bytes());
+            hasher.update(image.as_bytes());
+        }
+        ProcessedImage::Animated { bytes, .. } => {
+            // Distinguishes the hash from that of a still image whose pixels happen to equal the bytes
+            hasher.update(b"animated gif");
+            hasher.update(bytes);
+        }
+    }
+    let hash = hasher.finalize();
+    let mut bytes: [u8; 16] = hash.as_bytes()[..16].try_into().expect("hash should be at least 16 bytes long");
+    match image {
+        ProcessedImage::Still(_) => bytes[15] &= !ANIMATED_UUID_BIT,
+        ProcessedImage::Animated { .. } => bytes[15] |= ANIMATED_UUID_BIT,
+    }
+    uuid::Builder::from_custom_bytes(bytes).into_uuid()
+}
+
+/// Encode the image in the format of the encoding.
+/// The encoders only write the pixels, so the saved file carries no metadata.
+fn encode(image: &DynamicImage, encoding: ImageEncoding) -> Result<Vec<u8>, ImageError> {
+    let mut bytes = Vec::new();
+    match encoding {
+        ImageEncoding::Png => image.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)?,
+        // The WebP encoder is lossless, so it takes no quality
+        ImageEncoding::WebP => to_rgb8_or_rgba8(image).write_with_encoder(WebPEncoder::new_lossless(&mut bytes))?,
+        ImageEncoding::Avif { quality } => {
+            let encoder = AvifEncoder::new_with_speed_quality(&mut bytes, AVIF_SPEED, quality);
+            to_rgb8_or_rgba8(image).write_with_encoder(encoder)?
+        }
+    }
+    Ok(bytes)
+}
+
+/// Convert the image to 8-bit RGB, or RGBA if it has an alpha channel, unless it already is,
+/// since the WebP and AVIF encoders do not accept other pixel types.
+fn to_rgb8_or_rgba8(image: &DynamicImage) -> Cow<'_, DynamicImage> {
+    match image.color() {
+        ColorType::Rgb8 | ColorType::Rgba8 => Cow::Borrowed(image),
+        color if color.has_alpha() => Cow::Owned(DynamicImage::ImageRgba8(image.to_rgba8())),
+        _ => Cow::Owned(DynamicImage::ImageRgb8(image.to_rgb8())),
+    }
+}
+
+/// Deletes those of the images which no blog post references, e.g. after the blog post they were saved for
+/// was purged or failed to be inserted, since identical images are shared between blog posts.
+/// If the references cannot be counted, the images are kept for the garbage collector to delete later.
+/// Waits for the blog posts which reserved images to be inserted first, see `ImageReservation`.
+#[instrument(level = "info", skip(store, database))]
+pub async fn delete_unreferenced(
+    store: &dyn ImageStore,
+    database: &Database,
+    image: Option<PostImagePath>,
+    avatar: Option<AvatarImagePath>,
+) {
+    let _deletions = lock_deletions().await;
+    let (image, avatar) = match database.unreferenced_images(image, avatar).await {
+        Ok(unreferenced) => unreferenced,
+        Err(err) => {
+            warn!("Failed to count references to images: {}", err);
+            return;
+        }
+    };
+    // It's not a big deal if this fails, since the garbage collector deletes orphaned images too
+    let _ = join!(delete(store, image.as_ref()), delete(store, avatar.as_ref()));
 }
 
-/// Deletes an image from the file system if it exists.
+/// Deletes an image and its thumbnail, if any, from the image store in every format it may be stored in.
+/// Images are shared between posts with identical images,
+/// so only call this for images which are no longer referenced by any post.
 /// This function accepts an optional for convenience (see call site).
-pub async fn delete<I: ImagePath>(image_uuid: Option<&I>) -> Result<(), AppImageError> {
-    match image_uuid {
-        None => Ok(()),
-        #[rustfmt::skip]
-        Some(image) => {
-            let path = image.path();
-            tokio::task::spawn_blocking(move || std::fs::remove_file(path))
-                .await
-                .expect("deleting should not panic")
-                .inspect(|_| debug!("Deleted image from {}", image.path().display()))
-                .inspect_err(|e| warn!("Failed to delete image from {}: {}", image.path().display(), e))
-                .map_err(Into::into)
+#[instrument(level = "info", skip_all)]
+pub async fn delete<I: ImagePath>(store: &dyn ImageStore, image_uuid: Option<&I>) -> Result<(), AppImageError> {
+    let Some(image) = image_uuid else {
+        return Ok(());
+    };
+    let result = async {
+        // An image is only stored under some of its keys, e.g. in a single format, which the store tolerates
+        for key in image.all_keys() {
+            store.delete(&key).await?;
+        }
+        Ok::<_, StorageError>(())
+    }
+    .await;
+    match result {
+        Ok(()) => {
+            debug!("Deleted image {:?}", image);
+            Ok(())
+        }
+        Err(err) => {
+            warn!("Failed to delete image {:?}: {}", image, err);
+            Err(err.into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Grayscale JPEGs of 3x2 blocks of 8x8 pixels, stored rotated or mirrored as a camera would store them,
+    /// each with the EXIF orientation which makes it upright again and an EXIF artist of `Jane Doe`.
+    /// Displayed upright, every one of them is `UPRIGHT_BLOCKS`.
+    const FIXTURES: [(u8, &[u8]); 8] = [
+        (1, include_bytes!("../../tests/fixtures/orientation-1.jpg")),
+        (2, include_bytes!("../../tests/fixtures/orientation-2.jpg")),
+        (3, include_bytes!("../../tests/fixtures/orientation-3.jpg")),
+        (4, include_bytes!("../../tests/fixtures/orientation-4.jpg")),
+        (5, include_bytes!("../../tests/fixtures/orientation-5.jpg")),
+        (6, include_bytes!("../../tests/fixtures/orientation-6.jpg")),
+        (7, include_bytes!("../../tests/fixtures/orientation-7.jpg")),
+        (8, include_bytes!("../../tests/fixtures/orientation-8.jpg")),
+    ];
+
+    /// The gray levels of the blocks of the fixtures when they are upright, row by row.
+    const UPRIGHT_BLOCKS: [[u8; 3]; 2] = [[20, 60, 100], [140, 180, 220]];
+
+    fn decode_fixture(bytes: &[u8]) -> DynamicImage {
+        decode_with(ImageReader::new(Cursor::new(bytes))).expect("fixture should decode")
+    }
+
+    /// The decoder of the image, which reads its metadata without decoding its pixels.
+    fn decoder(bytes: &[u8]) -> impl ImageDecoder + '_ {
+        ImageReader::new(Cursor::new(bytes)).with_guessed_format().unwrap().into_decoder().unwrap()
+    }
+
+    #[test]
+    fn fixtures_carry_their_orientation() {
+        for (orientation, bytes) in FIXTURES {
+            assert_eq!(decoder(bytes).orientation().unwrap(), Orientation::from_exif(orientation).unwrap());
+        }
+    }
+
+    #[test]
+    fn decoding_applies_the_exif_orientation() {
+        for (orientation, bytes) in FIXTURES {
+            let image = decode_fixture(bytes).to_luma8();
+            assert_eq!(image.dimensions(), (24, 16), "orientation {}", orientation);
+            for (row, levels) in UPRIGHT_BLOCKS.iter().enumerate() {
+                for (column, &level) in levels.iter().enumerate() {
+                    // The center of the block, away from any blurring at its edges
+                    let pixel = image.get_pixel(column as u32 * 8 + 4, row as u32 * 8 + 4).0[0];
+                    assert!(
+                        pixel.abs_diff(level) <= 2,
+                        "orientation {}: block ({}, {}) is {} instead of {}",
+                        orientation,
+                        column,
+                        row,
+                        pixel,
+                        level
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn encoding_strips_the_metadata() {
+        let encodings = [ImageEncoding::Png, ImageEncoding::WebP, ImageEncoding::Avif { quality: 80 }];
+        for (orientation, bytes) in FIXTURES {
+            assert!(bytes.windows(8).any(|window| window == b"Jane Doe"));
+            let image = decode_fixture(bytes);
+            for encoding in encodings {
+                let encoded = encode(&image, encoding).expect("image should encode");
+                assert!(!encoded.windows(4).any(|window| window == b"Exif"), "orientation {}", orientation);
+                assert!(!encoded.windows(8).any(|window| window == b"Jane Doe"), "orientation {}", orientation);
+                // The image crate only encodes AVIF, so the metadata of AVIF cannot be read back
+                if !matches!(encoding, ImageEncoding::Avif { .. }) {
+                    let mut reencoded = decoder(&encoded);
+                    assert!(reencoded.exif_metadata().unwrap().is_none());
+                    assert_eq!(reencoded.orientation().unwrap(), Orientation::NoTransforms);
+                }
+            }
         }
     }
 }
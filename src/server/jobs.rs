@@ -0,0 +1,87 @@
+//! Background jobs which run periodically while the server is running.
+//! Modules implement `Job` for their scheduled work and register it with the `JobRunner` of the `ServerState`.
+
+use axum::async_trait;
+use std::fmt::Display;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+use tracing::{debug, info_span, warn, Instrument};
+
+/// How long to wait before retrying a job after its first failure.
+/// The delay doubles with every consecutive failure, but never exceeds the interval of the job.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Work which is done periodically in the background.
+#[async_trait]
+pub trait Job: Send + Sync + 'static {
+    /// The error with which a run of the job can fail.
+    type Error: Display + Send;
+
+    /// The name of the job, which identifies it in logs and metrics.
+    fn name(&self) -> &'static str;
+
+    /// Do the work once.
+    async fn run(&self) -> Result<(), Self::Error>;
+}
+
+/// Runs registered jobs at their intervals until the server shuts down.
+#[derive(Debug, Clone)]
+pub struct JobRunner {
+    tasks: TaskTracker,
+    shutdown: CancellationToken,
+}
+
+impl JobRunner {
+    /// Create a runner whose jobs stop being scheduled once `shutdown` is cancelled.
+    pub fn new(shutdown: CancellationToken) -> Self {
+        Self { tasks: TaskTracker::new(), shutdown }
+    }
+
+    /// Run the job every `interval`, starting one interval from now.
+    /// Every run is traced in a span named after the job and counted in the `job_runs_total` metric.
+    /// If a run fails, the job is retried with exponential backoff starting at `INITIAL_BACKOFF`,
+    /// until it succeeds and returns to its regular interval.
+    pub fn register<J: Job>(&self, job: J, interval: Duration) {
+        let shutdown = self.shutdown.clone();
+        debug!("Registered job {} to run every {:?}", job.name(), interval);
+        self.tasks.spawn(async move {
+            let mut failures = 0;
+            loop {
+                let delay = match failures {
+                    0 => interval,
+                    failures => backoff(failures).min(interval),
+                };
+                tokio::select! {
+                    _ = tokio::time::sleep(delay) => {}
+                    _ = shutdown.cancelled() => break,
+                }
+                let span = info_span!("job", name = job.name(), failures);
+                let outcome = match job.run().instrument(span).await {
+                    Ok(()) => {
+                        failures = 0;
+                        "success"
+                    }
+                    Err(err) => {
+                        failures += 1;
+                        warn!("Job {} failed {} times in a row: {}", job.name(), failures, err);
+                        "failure"
+                    }
+                };
+                metrics::counter!("job_runs_total", "job" => job.name(), "outcome" => outcome).increment(1);
+            }
+        });
+    }
+
+    /// Wait for the jobs which are currently running to finish.
+    /// This should only be called after `shutdown` was cancelled, since the jobs run until then.
+    pub async fn wait(&self) {
+        self.tasks.close();
+        self.tasks.wait().await;
+    }
+}
+
+/// Returns the delay before retrying a job which failed the provided number of times in a row.
+fn backoff(failures: u32) -> Duration {
+    INITIAL_BACKOFF.saturating_mul(2u32.saturating_pow(failures - 1))
+}
@@ -0,0 +1,113 @@
+//! Prometheus metrics of the server, exposed at `GET /metrics`, optionally only to scrapers which know a token.
+
+use crate::server::Database;
+use axum::extract::{MatchedPath, Request};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Extension, Router};
+use http::header::{AUTHORIZATION, WWW_AUTHENTICATE};
+use http::{HeaderMap, StatusCode};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::warn;
+
+/// The upper bounds in seconds of the buckets of the request latency histogram.
+const LATENCY_BUCKETS: [f64; 10] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+
+/// Install the global recorder which collects all metrics of the process.
+/// Returns a handle with which the metrics can be rendered in the Prometheus text format.
+///
+/// # Panics
+/// This function panics if a global metrics recorder has already been installed.
+pub fn install_recorder() -> PrometheusHandle {
+    PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Full("http_request_duration_seconds".to_string()),
+            &LATENCY_BUCKETS,
+        )
+        .expect("latency buckets should not be empty")
+        .set_buckets_for_metric(Matcher::Full("db_pool_wait_seconds".to_string()), &LATENCY_BUCKETS)
+        .expect("latency buckets should not be empty")
+        .install_recorder()
+        .expect("metrics recorder should only be installed once")
+}
+
+/// The token which scrapers must send to read the metrics, if any.
+#[derive(Debug, Clone)]
+struct MetricsToken(Option<Arc<str>>);
+
+/// Returns the router serving the metrics collected by the recorder of the handle,
+/// only to requests which send the token as `Authorization: Bearer <token>` if one is given.
+pub fn router(handle: PrometheusHandle, token: Option<String>) -> Router {
+    Router::new()
+        .route("/metrics", get(render))
+        .layer(Extension(handle))
+        .layer(Extension(MetricsToken(token.map(Arc::from))))
+}
+
+/// Whether the request sends the token, which is compared by hash so that the time taken does not reveal it.
+fn is_authorized(headers: &HeaderMap, token: &str) -> bool {
+    let sent = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    sent.is_some_and(|sent| Sha256::digest(sent.as_bytes()) == Sha256::digest(token.as_bytes()))
+}
+
+/// Render all metrics in the Prometheus text format,
+/// or respond with `401 Unauthorized` if a token is required and the request does not send it.
+/// The database gauges are updated first, since they are cheaper to compute on demand
+/// than to keep up to date on every change.
+/// The size of the stored images is set whenever it is measured in the background, see `StorageUsage`.
+async fn render(
+    Extension(handle): Extension<PrometheusHandle>,
+    Extension(MetricsToken(token)): Extension<MetricsToken>,
+    database: Database,
+    headers: HeaderMap,
+) -> Response {
+    if token.is_some_and(|token| !is_authorized(&headers, &token)) {
+        return (StatusCode::UNAUTHORIZED, [(WWW_AUTHENTICATE, "Bearer")]).into_response();
+    }
+    let pool = database.pool_state();
+    metrics::gauge!("db_pool_connections").set(pool.connections as f64);
+    metrics::gauge!("db_pool_idle_connections").set(pool.idle_connections as f64);
+    metrics::gauge!("db_pool_active_connections").set((pool.connections - pool.idle_connections) as f64);
+    metrics::gauge!("db_pool_max_connections").set(database.pool_max_size() as f64);
+    match database.count_posts().await {
+        Ok(count) => metrics::gauge!("blog_posts").set(count as f64),
+        Err(err) => warn!("Failed to count blog posts for metrics: {}", err),
+    }
+    handle.render().into_response()
+}
+
+/// Middleware which counts every request and records its latency,
+/// labelled by method, route and response status.
+pub async fn track_requests(request: Request, next: Next) -> Response {
+    let start = Instant::now();
+    let method = request.method().to_string();
+    // Label by route pattern rather than concrete path, so that the number of series stays bounded
+    let path = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or_else(|| "unmatched".to_string(), |path| path.as_str().to_string());
+    let response = next.run(request).await;
+    let status = response.status().as_u16().to_string();
+    metrics::counter!(
+        "http_requests_total",
+        "method" => method.clone(),
+        "path" => path.clone(),
+        "status" => status.clone(),
+    )
+    .increment(1);
+    metrics::histogram!(
+        "http_request_duration_seconds",
+        "method" => method,
+        "path" => path,
+        "status" => status,
+    )
+    .record(start.elapsed().as_secs_f64());
+    response
+}
@@ -0,0 +1,104 @@
+//! Middleware which applies to every request of the server.
+
+use crate::error::AppError;
+use crate::server::client_ip::TrustedProxies;
+use crate::server::rest::ApiError;
+use axum::body::Body;
+use axum::error_handling::HandleErrorLayer;
+use axum::extract::ConnectInfo;
+use axum::response::{IntoResponse, Response};
+use axum::{BoxError, Router};
+use dioxus::prelude::server_fn::error::ServerFnErrorSerde;
+use dioxus::prelude::ServerFnError;
+use http::{Request, Uri};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tower::limit::GlobalConcurrencyLimitLayer;
+use tower::load_shed::error::Overloaded;
+use tower::timeout::error::Elapsed;
+use tower::ServiceBuilder;
+use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
+use tower_http::trace::{DefaultOnResponse, TraceLayer};
+use tower_http::LatencyUnit;
+use tracing::{error, warn, Level, Span};
+
+/// Give every request an ID and log it within a tracing span.
+/// Requests without an `X-Request-Id` header are assigned a random UUID.
+/// The span records the ID, method and path of the request and the IP address of the client, as told by the trusted
+/// proxies, and the status and latency of the response are logged when it completes.
+/// The ID is returned in the `X-Request-Id` header of the response, so users can quote it in bug reports.
+pub fn trace_requests(router: Router, proxies: TrustedProxies) -> Router {
+    router.layer(
+        // The first layer is the outermost, so the ID is set before the span is created
+        ServiceBuilder::new()
+            .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+            .layer(
+                TraceLayer::new_for_http()
+                    .make_span_with(move |request: &Request<Body>| request_span(request, &proxies))
+                    .on_response(DefaultOnResponse::new().level(Level::INFO).latency_unit(LatencyUnit::Millis)),
+            )
+            .layer(PropagateRequestIdLayer::x_request_id()),
+    )
+}
+
+/// Bound how long the server may take to respond to a request and how many requests it handles at once,
+/// so that a flood of slow requests, e.g. image uploads, cannot exhaust it.
+/// Requests which are not responded to within `timeout`, including reading their bodies, fail with
+/// `408 Request Timeout`, and requests beyond `max_concurrent_requests` are rejected right away with
+/// `503 Service Unavailable` instead of waiting for a slot.
+/// Only the time until the response starts counts, so streamed responses such as the live feed may stay open.
+pub fn limit_requests(router: Router, timeout: Duration, max_concurrent_requests: usize) -> Router {
+    router.layer(
+        ServiceBuilder::new()
+            .layer(HandleErrorLayer::new(reject_request))
+            .load_shed()
+            // The limit is shared by all routes, which each get their own copy of the layers
+            .layer(GlobalConcurrencyLimitLayer::new(max_concurrent_requests))
+            .timeout(timeout),
+    )
+}
+
+/// Respond to a request which timed out or was shed, in the way the client which sent it expects errors:
+/// as JSON for the REST API, encoded with its code for server functions, and as plain text otherwise.
+async fn reject_request(uri: Uri, err: BoxError) -> Response {
+    let error = if err.is::<Elapsed>() {
+        warn!("Request to {} timed out", uri.path());
+        AppError::TimedOut
+    } else if err.is::<Overloaded>() {
+        warn!("Rejected request to {}, too many requests are in flight", uri.path());
+        AppError::Unavailable
+    } else {
+        error!("Request to {} failed: {}", uri.path(), err);
+        AppError::Internal
+    };
+    let status = error.status();
+    if uri.path().starts_with("/api/v1/") {
+        ApiError::from(error).into_response()
+    } else if uri.path().starts_with("/api/") {
+        let body = ServerFnError::from(error).ser().unwrap_or_default();
+        (status, body).into_response()
+    } else {
+        (status, error.to_string()).into_response()
+    }
+}
+
+/// Create the span which covers the handling of the request.
+fn request_span(request: &Request<Body>, proxies: &TrustedProxies) -> Span {
+    let request_id = request
+        .headers()
+        .get("x-request-id")
+        .and_then(|id| id.to_str().ok())
+        .unwrap_or("unknown");
+    let client = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(peer)| proxies.client_ip(peer.ip(), request.headers()).to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+    tracing::info_span!(
+        "request",
+        id = %request_id,
+        client = %client,
+        method = %request.method(),
+        path = %request.uri().path(),
+    )
+}
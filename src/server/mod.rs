@@ -1,36 +1,335 @@
 //! Server-specific functionality.
 
+use crate::error::AppError;
+use crate::model::User;
+use activitypub::Federation;
+use assets::StaticAssets;
+use audit::AuditLog;
+use auth::Admins;
+use avatar_cache::AvatarCache;
 use axum::async_trait;
+use axum::extract::FromRequestParts;
+use axum::response::{IntoResponse, Response};
+use challenge::ChallengeIssuer;
+use client_ip::{ClientIp, TrustedProxies};
+use dioxus::prelude::{extract, server_context};
+use events::PostEvents;
+use feed_cache::FeedCache;
+use filter::ContentFilter;
+use gc::ImageCollector;
+use jobs::JobRunner;
+use http::header::RETRY_AFTER;
+use http::StatusCode;
+use images::ImagePolicy;
+use opengraph::LinkPreviews;
+use outbound::HttpClient;
+use rate_limit::RateLimiter;
+use storage::ImageStorage;
+use trash::Trash;
+use uploads::Uploads;
+use usage::StorageUsage;
+use webhooks::Webhooks;
+use webmention::Webmentions;
 use std::convert::Infallible;
+use std::net::IpAddr;
+use tower_sessions::Session;
+use tracing::{error, info, warn};
 
+pub mod activitypub;
+pub mod api_keys;
+pub mod archive;
+pub mod assets;
+pub mod audit;
+pub mod auth;
+pub mod avatar_cache;
+pub mod backup;
+pub mod challenge;
+pub mod client_ip;
+pub mod config;
+pub mod erasure;
+pub mod etag;
+pub mod events;
+pub mod feed_cache;
+pub mod filter;
+pub mod gc;
+pub mod image_cache;
 pub mod images;
+pub mod jobs;
+pub mod metrics;
+pub mod middleware;
+pub mod opengraph;
+pub mod outbound;
+pub mod permalinks;
 pub mod persistence;
+pub mod posts;
+pub mod rate_limit;
+pub mod reencode;
+pub mod rest;
+pub mod routes;
+pub mod static_site;
+pub mod storage;
+pub mod telemetry;
+pub mod tls;
+pub mod trash;
+pub mod uploads;
+pub mod usage;
+pub mod webhooks;
+pub mod webmention;
 
 pub use persistence::database::Database;
 
 /// The state of the server.
-/// For now this only holds the database, but it could hold more in the future.
 #[derive(Debug, Clone)]
 pub struct ServerState {
     pub database: Database,
+    pub rate_limiter: RateLimiter,
+    pub images: ImageStorage,
+    pub image_policy: ImagePolicy,
+    pub uploads: Uploads,
+    pub admins: Admins,
+    pub image_collector: ImageCollector,
+    pub jobs: JobRunner,
+    pub trash: Trash,
+    pub content_filter: ContentFilter,
+    pub challenges: ChallengeIssuer,
+    pub events: PostEvents,
+    pub link_previews: LinkPreviews,
+    pub http_client: HttpClient,
+    pub avatar_cache: AvatarCache,
+    pub assets: StaticAssets,
+    pub feed_cache: FeedCache,
+    pub webhooks: Webhooks,
+    pub federation: Federation,
+    pub webmentions: Webmentions,
+    pub audit: AuditLog,
+    pub trusted_proxies: TrustedProxies,
+    pub storage_usage: StorageUsage,
 }
 
-/// Enable the database to be extracted from the request extensions.
+/// Enable a field of the server state to be extracted from the request extensions.
+macro_rules! impl_state_extractor {
+    ($ty:ty, $field:ident) => {
+        #[async_trait]
+        impl<S> FromRequestParts<S> for $ty {
+            type Rejection = Infallible;
+
+            async fn from_request_parts(
+                parts: &mut http::request::Parts,
+                _state: &S,
+            ) -> Result<Self, Infallible> {
+                let server_state: ServerState = parts
+                    .extensions
+                    .get()
+                    .cloned()
+                    // Safety: We know that the server state is present because we put it there.
+                    // See Router creation in main.rs
+                    .expect("Server state should be present in request extensions");
+                Ok(server_state.$field)
+            }
+        }
+    };
+}
+
+/// Enable the whole server state to be extracted, for operations which need many parts of it.
 #[async_trait]
-impl<S> axum::extract::FromRequestParts<S> for Database {
+impl<S> FromRequestParts<S> for ServerState {
     type Rejection = Infallible;
 
-    async fn from_request_parts(
-        parts: &mut http::request::Parts,
-        _state: &S,
-    ) -> Result<Self, Infallible> {
-        let server_state: ServerState = parts
+    async fn from_request_parts(parts: &mut http::request::Parts, _state: &S) -> Result<Self, Infallible> {
+        Ok(parts
             .extensions
-            .get()
+            .get::<ServerState>()
             .cloned()
-            // Safety: We know that the server state is present because we put it there.
-            // See Router creation in main.rs
-            .expect("Server state should be present in request extensions");
-        Ok(server_state.database)
+            .expect("Server state should be present in request extensions"))
+    }
+}
+
+impl_state_extractor!(Database, database);
+impl_state_extractor!(RateLimiter, rate_limiter);
+impl_state_extractor!(ImageStorage, images);
+impl_state_extractor!(ImagePolicy, image_policy);
+impl_state_extractor!(Uploads, uploads);
+impl_state_extractor!(Admins, admins);
+impl_state_extractor!(ImageCollector, image_collector);
+impl_state_extractor!(JobRunner, jobs);
+impl_state_extractor!(Trash, trash);
+impl_state_extractor!(ContentFilter, content_filter);
+impl_state_extractor!(ChallengeIssuer, challenges);
+impl_state_extractor!(PostEvents, events);
+impl_state_extractor!(LinkPreviews, link_previews);
+impl_state_extractor!(HttpClient, http_client);
+impl_state_extractor!(AvatarCache, avatar_cache);
+impl_state_extractor!(StaticAssets, assets);
+impl_state_extractor!(FeedCache, feed_cache);
+impl_state_extractor!(Webhooks, webhooks);
+impl_state_extractor!(Federation, federation);
+impl_state_extractor!(Webmentions, webmentions);
+impl_state_extractor!(AuditLog, audit);
+impl_state_extractor!(TrustedProxies, trusted_proxies);
+impl_state_extractor!(StorageUsage, storage_usage);
+
+/// The user logged in to the session of the current request.
+/// Extracting this fails with `401 Unauthorized` if nobody is logged in,
+/// so extract `Option<CurrentUser>` where anonymous requests are allowed.
+#[derive(Debug, Clone)]
+pub struct CurrentUser(pub User);
+
+impl CurrentUser {
+    /// The session key under which the logged-in user is stored.
+    const SESSION_KEY: &'static str = "user";
+
+    /// Log the user in to the session.
+    /// The session ID is cycled first to prevent session fixation.
+    pub async fn log_in(session: &Session, user: User) -> Result<(), tower_sessions::session::Error> {
+        session.cycle_id().await?;
+        session.insert(Self::SESSION_KEY, user).await
+    }
+
+    /// Log out of the session by deleting it entirely.
+    pub async fn log_out(session: &Session) -> Result<(), tower_sessions::session::Error> {
+        session.flush().await
+    }
+}
+
+/// The reasons why the logged-in user cannot be extracted from a request.
+#[derive(Debug, derive_more::Display, derive_more::Error)]
+pub enum CurrentUserRejection {
+    #[display("You must be logged in")]
+    NotLoggedIn,
+    #[display("Failed to load session")]
+    Session,
+    #[display("Only administrators may do that")]
+    NotAdmin,
+}
+
+impl IntoResponse for CurrentUserRejection {
+    fn into_response(self) -> Response {
+        let status = match self {
+            CurrentUserRejection::NotLoggedIn => StatusCode::UNAUTHORIZED,
+            CurrentUserRejection::Session => StatusCode::INTERNAL_SERVER_ERROR,
+            CurrentUserRejection::NotAdmin => StatusCode::FORBIDDEN,
+        };
+        (status, self.to_string()).into_response()
+    }
+}
+
+/// Enable the logged-in user to be extracted from the session of the request.
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for CurrentUser {
+    type Rejection = CurrentUserRejection;
+
+    async fn from_request_parts(
+        parts: &mut http::request::Parts,
+        state: &S,
+    ) -> Result<Self, CurrentUserRejection> {
+        let session = Session::from_request_parts(parts, state)
+            .await
+            .map_err(|_| CurrentUserRejection::Session)?;
+        match session.get::<User>(Self::SESSION_KEY).await {
+            Ok(Some(user)) => Ok(CurrentUser(user)),
+            Ok(None) => Err(CurrentUserRejection::NotLoggedIn),
+            Err(err) => {
+                warn!("Failed to load user from session: {}", err);
+                Err(CurrentUserRejection::Session)
+            }
+        }
+    }
+}
+
+/// The logged-in user of the current request, who is also an administrator.
+/// Extracting this fails with `401 Unauthorized` if nobody is logged in,
+/// or with `403 Forbidden` if the logged-in user is not an administrator.
+#[derive(Debug, Clone)]
+pub struct AdminUser(pub User);
+
+/// Enable the logged-in administrator to be extracted from the session of the request.
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for AdminUser {
+    type Rejection = CurrentUserRejection;
+
+    async fn from_request_parts(
+        parts: &mut http::request::Parts,
+        state: &S,
+    ) -> Result<Self, CurrentUserRejection> {
+        let CurrentUser(user) = CurrentUser::from_request_parts(parts, state).await?;
+        let admins = Admins::from_request_parts(parts, state)
+            .await
+            .unwrap_or_else(|never| match never {});
+        if admins.contains(&user) {
+            Ok(AdminUser(user))
+        } else {
+            warn!("User {} attempted an administrative task", user.username);
+            Err(CurrentUserRejection::NotAdmin)
+        }
+    }
+}
+
+/// Extract the user logged in to the session of the current request from within a server function.
+/// Fails with `AppError::NotLoggedIn` if nobody is logged in.
+pub async fn current_user() -> Result<User, AppError> {
+    let CurrentUser(user) = extract().await.map_err(AppError::from)?;
+    Ok(user)
+}
+
+/// Extract the administrator logged in to the session of the current request from within a server function.
+/// Fails with `AppError::NotLoggedIn` if nobody is logged in, or with `AppError::NotAdmin` if the user is no administrator.
+pub async fn admin_user() -> Result<User, AppError> {
+    let AdminUser(user) = extract().await.map_err(AppError::from)?;
+    Ok(user)
+}
+
+/// Extract the session of the current request from within a server function.
+/// The rejection of the `Session` extractor is not an error type, so it cannot be propagated with `?`.
+pub async fn session() -> Result<Session, AppError> {
+    extract::<Session, _>().await.map_err(|(_, msg)| {
+        error!("Failed to load session: {}", msg);
+        AppError::Internal
+    })
+}
+
+/// Enforce the rate limit on the IP address of the client of the current request from within a server function.
+/// Call this first in every server function which changes data.
+/// Returns the IP address of the client, e.g. to record it in the audit log.
+/// If the limit is exceeded, the response status is set to `429 Too Many Requests`
+/// and an error telling the client how long to wait is returned.
+pub async fn check_rate_limit() -> Result<IpAddr, AppError> {
+    let rate_limiter: RateLimiter = extract().await?;
+    // The rejection has already been logged
+    let ClientIp(ip) = extract::<ClientIp, _>().await.map_err(|_| AppError::Internal)?;
+    if let Err(retry_after) = rate_limiter.check(ip) {
+        // Round up so that retrying after the advertised time always succeeds
+        let seconds = retry_after.as_secs() + 1;
+        warn!("Rate limit exceeded by {}, retry after {}s", ip, seconds);
+        let mut response = server_context().response_parts_mut();
+        response.status = StatusCode::TOO_MANY_REQUESTS;
+        response.headers.insert(RETRY_AFTER, seconds.into());
+        return Err(AppError::RateLimited { retry_after: seconds });
+    }
+    Ok(ip)
+}
+
+/// Completes when the process is asked to shut down, i.e. on SIGINT (Ctrl-C) or, on Unix, SIGTERM.
+///
+/// # Panics
+/// This function panics if the signal handlers cannot be installed.
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl-C handler");
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
     }
+    info!("Shutdown requested, finishing in-flight requests");
 }
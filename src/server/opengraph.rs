@@ -0,0 +1,155 @@
+//! OpenGraph and Twitter Card meta tags for the pages of blog posts,
+//! so that links to them are previewed when shared on social networks and in chat apps.
+//! The crawlers which build the previews do not run the client,
+//! so the tags are injected into the head of the page rendered by the server.
+
+use crate::markdown;
+use crate::model::BlogPost;
+use crate::server::permalinks::post_id;
+use crate::server::Database;
+use axum::body::Body;
+use axum::extract::Request;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use http::header::{CONTENT_LENGTH, CONTENT_TYPE, HOST};
+use http::{HeaderMap, StatusCode};
+use tracing::warn;
+
+/// Pages larger than this are passed through without meta tags rather than buffered.
+pub(crate) const MAX_PAGE_BYTES: usize = 4 * 1024 * 1024;
+
+/// The number of characters of the text of a blog post which are shown in its preview.
+const DESCRIPTION_LENGTH: usize = 200;
+
+/// Where the server is reachable from the outside, which the URLs in the meta tags must point to.
+#[derive(Debug, Clone)]
+pub struct LinkPreviews {
+    /// The configured URL of the server, without a trailing slash.
+    public_url: Option<String>,
+    /// Whether the server serves HTTPS itself, which decides the scheme if the public URL is not configured.
+    https: bool,
+}
+
+impl LinkPreviews {
+    /// Without a public URL, URLs are built from the `Host` header of each request.
+    pub fn new(public_url: Option<String>, https: bool) -> Self {
+        let public_url = public_url.map(|url| url.trim_end_matches('/').to_string());
+        Self { public_url, https }
+    }
+
+    /// The URL of the server to prefix paths with, or `None` if it cannot be told from the request.
+    fn base_url(&self, headers: &HeaderMap) -> Option<String> {
+        if let Some(url) = &self.public_url {
+            return Some(url.clone());
+        }
+        let host = headers.get(HOST)?.to_str().ok()?;
+        let scheme = if self.https { "https" } else { "http" };
+        Some(format!("{scheme}://{host}"))
+    }
+}
+
+/// Middleware which injects the meta tags describing the blog post into the head of the page of a blog post.
+/// All other responses, including the pages of blog posts which do not exist, are passed through unchanged.
+pub async fn inject_meta_tags(database: Database, previews: LinkPreviews, request: Request, next: Next) -> Response {
+    let Some(post_id) = post_id(request.uri().path()) else {
+        return next.run(request).await;
+    };
+    let base_url = previews.base_url(request.headers());
+    let response = next.run(request).await;
+    let Some(base_url) = base_url.filter(|_| is_html_page(&response)) else {
+        return response;
+    };
+    let post = match database.fetch(post_id).await {
+        Ok(Some(post)) => post,
+        Ok(None) => return response,
+        Err(err) => {
+            warn!("Failed to load post {} for its link preview: {}", post_id, err);
+            return response;
+        }
+    };
+    let (mut parts, body) = response.into_parts();
+    let page = match axum::body::to_bytes(body, MAX_PAGE_BYTES).await {
+        Ok(page) => page,
+        Err(err) => {
+            // The body is consumed, so there is nothing left to pass through
+            warn!("Failed to buffer the page of post {}: {}", post_id, err);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let page = match String::from_utf8(page.to_vec()) {
+        Ok(page) if page.contains("</head>") => {
+            page.replacen("</head>", &format!("{}</head>", meta_tags(&post, &base_url)), 1)
+        }
+        _ => return Response::from_parts(parts, Body::from(page)),
+    };
+    parts.headers.remove(CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(page))
+}
+
+/// Whether the response is a successfully rendered HTML page.
+pub(crate) fn is_html_page(response: &Response) -> bool {
+    response.status() == StatusCode::OK
+        && response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|content_type| content_type.to_str().ok())
+            .is_some_and(|content_type| content_type.starts_with("text/html"))
+}
+
+/// Render the meta tags describing the blog post, with URLs under the base URL.
+fn meta_tags(post: &BlogPost, base_url: &str) -> String {
+    let title = format!("Post {} by {}", post.id, post.username);
+    let description = description(&post.text);
+    let url = format!("{base_url}{}", post.path());
+    let mut tags = vec![
+        ("og:type", "article".to_string()),
+        ("og:title", title.clone()),
+        ("og:description", description.clone()),
+        ("og:url", url),
+        ("twitter:title", title),
+        ("twitter:description", description),
+    ];
+    match &post.image_uuid {
+        Some(image) => {
+            let image_url = format!("{base_url}{}", image.url());
+            let alt = post.image_alt.clone().unwrap_or_else(|| "Post image".to_string());
+            tags.push(("og:image", image_url.clone()));
+            tags.push(("og:image:alt", alt.clone()));
+            tags.push(("twitter:card", "summary_large_image".to_string()));
+            tags.push(("twitter:image", image_url));
+            tags.push(("twitter:image:alt", alt));
+        }
+        None => tags.push(("twitter:card", "summary".to_string())),
+    }
+    tags.into_iter()
+        .map(|(property, content)| {
+            // OpenGraph uses the `property` attribute, while Twitter Cards use `name`
+            let attribute = if property.starts_with("og:") { "property" } else { "name" };
+            format!(r#"<meta {attribute}="{property}" content="{}">"#, escape_attribute(&content))
+        })
+        .collect()
+}
+
+/// The beginning of the text of a blog post without its Markdown formatting.
+fn description(text: &str) -> String {
+    let text = markdown::plain_text(text);
+    match text.char_indices().nth(DESCRIPTION_LENGTH) {
+        Some((end, _)) => format!("{}…", text[..end].trim_end()),
+        None => text,
+    }
+}
+
+/// Escape the text for use in a double-quoted HTML attribute, or as the text of an element.
+pub(crate) fn escape_attribute(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '"' => escaped.push_str("&quot;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,184 @@
+//! The HTTP client which the server makes outgoing requests with, e.g. to download avatars.
+//! A single client is shared by all requests, so that connections to the same host are pooled.
+//! The URLs of most requests are chosen by users or remote servers, so the client only connects to public
+//! addresses, lest anyone make the server request its own or its network's internal services, such as the
+//! metadata service of a cloud provider. Only the destinations which the operator configured may be internal.
+
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::redirect::Policy;
+use reqwest::Url;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::ops::Deref;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How long connecting to a remote server may take.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long an outgoing request may take in total, unless the request itself sets a timeout.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How many redirects are followed before a request is given up.
+const MAX_REDIRECTS: usize = 10;
+
+/// Identifies the server to the remote servers it sends requests to.
+const USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+/// Why a URL which was given by someone else may not be requested.
+#[derive(Debug, derive_more::Display)]
+pub enum BlockedUrl {
+    #[display("Expected an {} URL, got `{}`", _0, _1)]
+    Scheme(&'static str, String),
+    #[display("The URL has no host")]
+    NoHost,
+    #[display("{} is not a public address", _0)]
+    Address(IpAddr),
+}
+
+/// A shared handle to the HTTP client for outgoing requests.
+/// It dereferences to the client for URLs chosen by others, which only connects to public addresses.
+#[derive(Debug, Clone)]
+pub struct HttpClient {
+    public: reqwest::Client,
+    trusted: reqwest::Client,
+}
+
+impl HttpClient {
+    /// Build the client, which sends requests to `https://` URLs through the proxy, if one is given.
+    /// The proxy resolves the hosts of such requests itself, so it should only reach the public internet.
+    /// Returns an error if the URL of the proxy is invalid.
+    pub fn new(https_proxy: Option<&str>) -> Result<Self, reqwest::Error> {
+        let builder = || -> Result<reqwest::ClientBuilder, reqwest::Error> {
+            let mut builder = reqwest::Client::builder()
+                .user_agent(USER_AGENT)
+                .connect_timeout(CONNECT_TIMEOUT)
+                .timeout(REQUEST_TIMEOUT);
+            if let Some(proxy) = https_proxy {
+                builder = builder.proxy(reqwest::Proxy::https(proxy)?);
+            }
+            Ok(builder)
+        };
+        let public = builder()?
+            .dns_resolver(Arc::new(PublicResolver))
+            .redirect(Policy::custom(|attempt| {
+                // Redirects may not lead to internal addresses, nor downgrade HTTPS to plain HTTP
+                let downgrade = attempt.url().scheme() == "http"
+                    && attempt.previous().last().is_some_and(|previous| previous.scheme() == "https");
+                if attempt.previous().len() >= MAX_REDIRECTS {
+                    attempt.error("too many redirects")
+                } else if downgrade {
+                    attempt.error("redirected from HTTPS to plain HTTP")
+                } else if let Err(err) = check_public_url(attempt.url()) {
+                    attempt.error(err.to_string())
+                } else {
+                    attempt.follow()
+                }
+            }))
+            .build()?;
+        let trusted = builder()?.build()?;
+        Ok(Self { public, trusted })
+    }
+
+    /// The client for the destinations which the operator configured, e.g. webhooks and the content filter,
+    /// which may well be internal services.
+    pub fn trusted(&self) -> &reqwest::Client {
+        &self.trusted
+    }
+}
+
+impl Deref for HttpClient {
+    type Target = reqwest::Client;
+
+    fn deref(&self) -> &Self::Target {
+        &self.public
+    }
+}
+
+/// Check that the URL, which was given by someone else, is an `http://` or `https://` URL whose host is not an
+/// internal address. Hosts given by name are checked when they are resolved, since they may resolve to anything.
+pub fn check_public_url(url: &Url) -> Result<(), BlockedUrl> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(BlockedUrl::Scheme("http:// or https://", url.to_string()));
+    }
+    let host = url.host_str().ok_or(BlockedUrl::NoHost)?;
+    // IPv6 addresses are written in brackets
+    match host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+        Ok(ip) if !is_public(ip) => Err(BlockedUrl::Address(ip)),
+        _ => Ok(()),
+    }
+}
+
+/// Like `check_public_url`, but only allows `https://` URLs, e.g. for requests between federated servers.
+pub fn check_https_url(url: &Url) -> Result<(), BlockedUrl> {
+    if url.scheme() != "https" {
+        return Err(BlockedUrl::Scheme("https://", url.to_string()));
+    }
+    check_public_url(url)
+}
+
+/// Whether the address is on the public internet, rather than e.g. loopback, private, link-local or reserved.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_v4(ip),
+            None => is_public_v6(ip),
+        },
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        || a == 0
+        // Shared address space of carrier-grade NAT
+        || (a == 100 && (64..128).contains(&b))
+        // IETF protocol assignments
+        || (a == 192 && b == 0 && c == 0)
+        // Benchmarking
+        || (a == 198 && (18..20).contains(&b))
+        // Reserved
+        || a >= 240)
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    let segments = ip.segments();
+    // NAT64 addresses embed an IPv4 address, which decides
+    if segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0] {
+        let [a, b] = segments[6].to_be_bytes();
+        let [c, d] = segments[7].to_be_bytes();
+        return is_public_v4(Ipv4Addr::new(a, b, c, d));
+    }
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // Unique local addresses
+        || (segments[0] & 0xfe00) == 0xfc00
+        // Link-local addresses
+        || (segments[0] & 0xffc0) == 0xfe80
+        // Documentation
+        || (segments[0] == 0x2001 && segments[1] == 0x0db8))
+}
+
+/// Resolves host names like the system does, but only to public addresses,
+/// and fails if a name has none, so that a name cannot be used to reach an internal address.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> =
+                tokio::net::lookup_host((name.as_str(), 0)).await?.filter(|addr| is_public(addr.ip())).collect();
+            if addrs.is_empty() {
+                return Err(format!("{} does not resolve to a public address", name.as_str()).into());
+            }
+            Ok::<Addrs, Box<dyn std::error::Error + Send + Sync>>(Box::new(addrs.into_iter()))
+        })
+    }
+}
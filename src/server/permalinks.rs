@@ -0,0 +1,74 @@
+//! Permalinks of blog posts, i.e. `/post/:id/:slug`, whose slug is generated from the first words of the blog post.
+//! Only the ID identifies the blog post, so permalinks keep working with a missing or outdated slug,
+//! but they are redirected to the canonical one, so that every blog post has a single URL.
+
+use crate::markdown;
+use crate::model::BlogPostId;
+use crate::server::Database;
+use axum::extract::Request;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Redirect, Response};
+use http::Method;
+use tracing::warn;
+
+/// The maximum number of words from the text of a blog post which make up its slug.
+const SLUG_WORDS: usize = 6;
+
+/// The maximum length of a slug, not counting the suffix which makes it unique.
+const MAX_SLUG_LENGTH: usize = 60;
+
+/// Generate a slug from the first words of the text of a blog post, e.g. `hello-world` for "Hello, *world*!".
+/// Only ASCII letters and digits are kept, and text without any is slugged as `post`.
+pub fn slugify(text: &str) -> String {
+    let plain = markdown::plain_text(text).to_ascii_lowercase();
+    let words = plain
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .take(SLUG_WORDS);
+    let mut slug = String::new();
+    for word in words {
+        if !slug.is_empty() {
+            if slug.len() + 1 + word.len() > MAX_SLUG_LENGTH {
+                break;
+            }
+            slug.push('-');
+        }
+        slug.push_str(word);
+    }
+    // A single long word is cut off, which is safe since the slug is ASCII
+    slug.truncate(MAX_SLUG_LENGTH);
+    if slug.is_empty() {
+        "post".to_string()
+    } else {
+        slug
+    }
+}
+
+/// Parse the ID of the blog post from the path of its page, i.e. `/post/:id` or `/post/:id/:slug`.
+/// Returns `None` for all other paths.
+pub fn post_id(path: &str) -> Option<BlogPostId> {
+    path.strip_prefix("/post/")?.split('/').next()?.parse().ok()
+}
+
+/// Middleware which permanently redirects requests for the page of a blog post to its canonical path,
+/// if they use a different one, e.g. because the slug is missing.
+/// All other requests, including those for blog posts which do not exist, are passed through.
+pub async fn redirect_to_canonical(database: Database, request: Request, next: Next) -> Response {
+    let Some(post_id) = post_id(request.uri().path()).filter(|_| request.method() == Method::GET) else {
+        return next.run(request).await;
+    };
+    match database.fetch(post_id).await {
+        Ok(Some(post)) if post.path() != request.uri().path() => {
+            let location = match request.uri().query() {
+                Some(query) => format!("{}?{}", post.path(), query),
+                None => post.path(),
+            };
+            Redirect::permanent(&location).into_response()
+        }
+        Ok(_) => next.run(request).await,
+        Err(err) => {
+            warn!("Failed to load post {} to find its canonical path: {}", post_id, err);
+            next.run(request).await
+        }
+    }
+}
@@ -1,13 +1,40 @@
-//! Database module for interacting with the SQLite database.
+//! Database module for interacting with the database.
+//! SQLite is always supported, while PostgreSQL is supported with the `postgres` feature.
+//! Which backend is used is decided by the scheme of the database URL.
 
-use crate::model::{BlogPost, BlogPostId, InsertBlogPost};
+use crate::model::{
+    AdminStatistics, ApiKeyId, ApiKeyInfo, AuditAction, AuditEntry, AvatarImagePath, BlogPost, BlogPostDetail,
+    BlogPostId, BlogPostPage, BlogStats, FeedOptions, FeedSort, InsertApiKey, InsertAuditEntry, InsertBlogPost,
+    InsertFollower, InsertUser, InsertUserErasure, InsertWebhookDelivery, InsertWebmention, ModeratedPost,
+    ModerationAction, ModerationPage, OpenReport, PostImagePath, PostLikes, PostRevision, ReportId, ReportResolution,
+    SearchResult, UpdateBlogPost, User, UserId, UserProfile, WebhookAttempt, WebhookDelivery, WebhookStatus, Webmention,
+    HIGHLIGHT_END, HIGHLIGHT_START,
+};
+use crate::server::config::{PoolConfig, RetryConfig, SqliteConfig};
+use crate::server::permalinks::slugify;
 use crate::server::persistence::schema::blog_post::dsl::*;
+use crate::server::persistence::schema::{
+    api_key, audit_log, avatar_cache, blog_post_revision, follower, post_like, post_tag, report, tag, user_erasure, users,
+    webhook_delivery, webmention,
+};
+use diesel::connection::SimpleConnection;
+#[cfg(feature = "postgres")]
+use diesel::pg::PgConnection;
 use diesel::prelude::*;
-use diesel::r2d2::ConnectionManager;
+use diesel::r2d2::{ConnectionManager, CustomizeConnection, R2D2Connection};
 use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
-use tracing::{debug, info};
+use r2d2::event::{CheckoutEvent, HandleEvent, TimeoutEvent};
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::path::PathBuf;
+use std::time::Duration;
+use time::{OffsetDateTime, PrimitiveDateTime};
+use tracing::{debug, info, instrument, warn};
 
-const MIGRATIONS: EmbeddedMigrations = embed_migrations!();
+const SQLITE_MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations/sqlite");
+#[cfg(feature = "postgres")]
+const POSTGRES_MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations/postgres");
 
 #[derive(Debug, derive_more::From, derive_more::Display, derive_more::Error)]
 pub enum DatabaseError {
@@ -17,82 +44,2041 @@ pub enum DatabaseError {
     Migration(Box<dyn std::error::Error + Send + Sync>),
     #[display("SQL error: {}", _0)]
     Sql(diesel::result::Error),
+    #[cfg(not(feature = "postgres"))]
+    #[display("PostgreSQL support is not enabled, rebuild with the `postgres` feature to use it")]
+    #[from(skip)]
+    PostgresDisabled,
+}
+
+impl DatabaseError {
+    /// Whether the operation is likely to succeed if it is retried shortly, because it only failed due to contention:
+    /// no connection became free in time, SQLite was locked by another writer,
+    /// or PostgreSQL aborted a transaction which conflicted with another one.
+    pub fn is_transient(&self) -> bool {
+        use diesel::result::{DatabaseErrorKind, Error};
+        match self {
+            DatabaseError::Connection(_) => true,
+            DatabaseError::Sql(Error::DatabaseError(DatabaseErrorKind::SerializationFailure, _)) => true,
+            // SQLite reports being busy and locked only in the message
+            DatabaseError::Sql(Error::DatabaseError(_, info)) => info.message().contains("is locked"),
+            _ => false,
+        }
+    }
+}
+
+/// Criteria restricting which blog posts are fetched into a page, and in which order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PostFilter {
+    /// Only fetch posts with this tag.
+    pub tag: Option<String>,
+    /// Only fetch posts by the author with this username.
+    pub author: Option<String>,
+    /// Only fetch posts with an image.
+    pub with_image: bool,
+    /// Only fetch posts published on or after this day.
+    pub posted_from: Option<time::Date>,
+    /// Only fetch posts published on or before this day.
+    pub posted_until: Option<time::Date>,
+    /// Fetch the oldest posts first instead of the newest.
+    pub oldest_first: bool,
+}
+
+impl From<FeedOptions> for PostFilter {
+    fn from(options: FeedOptions) -> Self {
+        Self {
+            tag: None,
+            // An empty author field filters nothing
+            author: options.author.map(|author| author.trim().to_string()).filter(|author| !author.is_empty()),
+            with_image: options.with_image,
+            posted_from: options.posted_from,
+            posted_until: options.posted_until,
+            oldest_first: options.sort == FeedSort::Oldest,
+        }
+    }
+}
+
+/// The blog posts which are exported into an archive, see `server::archive`.
+#[derive(Debug)]
+pub struct PostExport {
+    /// The blog posts with whether they are hidden and when they were moved to the trash, if they were, oldest first.
+    pub posts: Vec<(BlogPost, bool, Option<PrimitiveDateTime>)>,
+    /// The likes of the blog posts, as seen by nobody in particular. Posts which nobody has liked are omitted.
+    pub likes: HashMap<BlogPostId, PostLikes>,
+    pub tags: HashMap<BlogPostId, Vec<String>>,
+    pub last_edited: HashMap<BlogPostId, PrimitiveDateTime>,
+}
+
+/// Everything stored about a user account besides its blog posts and its password, as exported on request.
+#[derive(Debug)]
+pub struct AccountData {
+    pub user: User,
+    pub created_on: time::Date,
+    /// The blog posts which the user liked.
+    pub likes: Vec<BlogPostId>,
+    /// The blog posts which the user reported, with the reason and when they were reported.
+    pub reports: Vec<(BlogPostId, String, PrimitiveDateTime)>,
+    /// The API keys of the user, with when they were created and last used, but without the keys themselves.
+    pub api_keys: Vec<(String, PrimitiveDateTime, Option<PrimitiveDateTime>)>,
+}
+
+/// A row of the audit log, whose action is stored by name.
+type AuditRow = (i32, String, Option<String>, Option<String>, Option<BlogPostId>, String, PrimitiveDateTime);
+
+/// A cheap fingerprint of what the feeds show to a viewer, which changes whenever a blog post is created, deleted,
+/// hidden, restored or edited, or a like is added or removed, so that an unchanged feed need not be loaded again.
+/// It is the same for all feeds and pages, which are cached separately by their URLs.
+#[derive(Debug)]
+pub struct FeedVersion {
+    latest_post: Option<BlogPostId>,
+    visible_posts: i64,
+    last_edit: Option<PrimitiveDateTime>,
+    // Changes when one blog post is hidden and another one unhidden, which leaves the number of visible posts the same
+    last_visibility_change: Option<PrimitiveDateTime>,
+    likes: i64,
+    // Changes when a like moves from one blog post to another, which leaves the number of likes the same
+    liked_post_sum: Option<i64>,
+    viewer: Option<UserId>,
+    viewer_likes: i64,
+}
+
+impl FeedVersion {
+    /// The version as a strong HTTP entity tag, e.g. `"3f2a9c0d1b7e4a65"`.
+    /// It also changes with the version of the server, which may send the feeds differently.
+    pub fn etag(&self) -> String {
+        let fingerprint = format!("{} {:?}", env!("CARGO_PKG_VERSION"), self);
+        format!("\"{}\"", &blake3::hash(fingerprint.as_bytes()).to_hex()[..16])
+    }
+}
+
+/// A connection pool for one of the supported database backends.
+#[derive(Debug, Clone)]
+enum ConnectionPool {
+    Sqlite(r2d2::Pool<ConnectionManager<SqliteConnection>>),
+    #[cfg(feature = "postgres")]
+    Postgres(r2d2::Pool<ConnectionManager<PgConnection>>),
+}
+
+/// Run the body with a connection checked out of the pool, whichever backend it belongs to.
+/// The body is compiled once for every backend,
+/// so it can only contain queries which all backends support.
+macro_rules! with_connection {
+    ($pool:expr, |$connection:ident| $body:expr) => {
+        match $pool {
+            ConnectionPool::Sqlite(pool) => {
+                let mut $connection = pool.get()?;
+                $body
+            }
+            #[cfg(feature = "postgres")]
+            ConnectionPool::Postgres(pool) => {
+                let mut $connection = pool.get()?;
+                $body
+            }
+        }
+    };
+}
+
+/// Run the body in a single transaction on a connection checked out of the pool of the database,
+/// on the blocking thread pool, and evaluate to its result.
+/// The transaction is committed if the body returns `Ok`, and rolled back otherwise.
+/// If it failed because of contention, it is retried according to the `RetryConfig` of the database,
+/// so the body must not move the values it captures.
+/// The body returns a `diesel::result::Error` on failure, which is converted into a `DatabaseError`.
+/// Like `with_connection`, the body is compiled once for every backend, which is why this is a macro:
+/// a closure cannot be generic over the connection type.
+macro_rules! transaction {
+    ($database:expr, |$connection:ident| $body:expr) => {{
+        let pool = $database.pool.clone();
+        let retry = $database.retry;
+        tokio::task::spawn_blocking(move || {
+            with_retries(retry, || {
+                with_connection!(&pool, |connection| {
+                    let result = connection.transaction(|$connection| {
+                        let result: Result<_, diesel::result::Error> = $body;
+                        result
+                    })?;
+                    Ok(result)
+                })
+            })
+        })
+        .await
+        .expect("database query should never panic")
+    }};
+}
+
+/// Run the operation until it succeeds, fails with an error which is not transient, or runs out of retries,
+/// waiting for a random delay of up to the backoff of the retry, which doubles with every retry, in between.
+/// This blocks the thread while waiting, so it must only be called on the blocking thread pool.
+fn with_retries<T>(
+    retry: RetryConfig,
+    mut operation: impl FnMut() -> Result<T, DatabaseError>,
+) -> Result<T, DatabaseError> {
+    let mut backoff = retry.backoff;
+    for attempt in 1..=retry.retries {
+        match operation() {
+            Err(err) if err.is_transient() => {
+                let delay = jitter(backoff, attempt);
+                warn!("Retrying database operation in {:?} (retry {} of {}): {}", delay, attempt, retry.retries, err);
+                std::thread::sleep(delay);
+                backoff *= 2;
+            }
+            result => return result,
+        }
+    }
+    operation()
+}
+
+/// Returns a random duration up to the maximum.
+/// The standard library only seeds the first `RandomState` of a thread randomly and derives the later ones from it,
+/// so the attempt and the thread are hashed as well, which is plenty for spreading out retries.
+fn jitter(max: Duration, attempt: u32) -> Duration {
+    let mut hasher = RandomState::new().build_hasher();
+    (attempt, std::thread::current().id()).hash(&mut hasher);
+    let random = hasher.finish();
+    max.mul_f64(random as f64 / u64::MAX as f64)
 }
 
 #[derive(Debug, Clone)]
 pub struct Database {
-    pool: r2d2::Pool<ConnectionManager<SqliteConnection>>,
+    pool: ConnectionPool,
+    retry: RetryConfig,
 }
 
 impl Database {
-    /// Attempt to connect to the SQLite database at the provided URL.
-    /// Create a connection pool and immediately run embedded Diesel migrations
+    /// Attempt to connect to the database at the provided URL.
+    /// URLs starting with `postgres://` or `postgresql://` connect to PostgreSQL,
+    /// while anything else is treated as the path of an SQLite database.
+    /// Create a connection pool and immediately run the embedded Diesel migrations of the backend
     /// to ensure the schema is up-to-date.
+    /// The pool is sized according to the `PoolConfig`,
+    /// and every SQLite connection is set up according to the `SqliteConfig` when it is opened.
     /// Return a `Database` instance if successful.
     /// Returns `DatabaseError::Connection` if connecting to the database fails.
     /// Returns `DatabaseError::Migration` if migrating the database fails.
-    pub async fn try_connect(url: impl Into<String>) -> Result<Self, DatabaseError> {
+    pub async fn try_connect(
+        url: impl Into<String>,
+        pool: PoolConfig,
+        retry: RetryConfig,
+        sqlite: SqliteConfig,
+    ) -> Result<Self, DatabaseError> {
         let url = url.into();
         tokio::task::spawn_blocking(move || {
-            let pool = r2d2::Pool::builder()
-                .max_size(5)
-                .build(ConnectionManager::<SqliteConnection>::new(url))?;
-            let mut conn = pool.get()?;
-            let versions = conn.run_pending_migrations(MIGRATIONS)?;
-            if !versions.is_empty() {
-                info!("Successfully updated database schema.");
-                debug!("Applied migrations: {:?}", versions);
-            }
-            Ok(Self { pool })
+            let pool = if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+                #[cfg(feature = "postgres")]
+                {
+                    ConnectionPool::Postgres(connect(url, POSTGRES_MIGRATIONS, pool, Box::new(r2d2::NopConnectionCustomizer))?)
+                }
+                #[cfg(not(feature = "postgres"))]
+                {
+                    return Err(DatabaseError::PostgresDisabled);
+                }
+            } else {
+                ConnectionPool::Sqlite(connect(url, SQLITE_MIGRATIONS, pool, Box::new(SqliteCustomizer(sqlite)))?)
+            };
+            Ok(Self { pool, retry })
         })
         .await
         .expect("database connection should never panic")
     }
-    /// Fetch all blog posts from the database sorted by ID in descending order.
-    /// Returns a `Vec<BlogPost>` if successful, or `DatabaseError::Sql` if the query fails.
-    pub async fn fetch_all(&self) -> Result<Vec<BlogPost>, DatabaseError> {
-        debug!("Loading blog posts");
+    /// Close the database when the server shuts down.
+    /// SQLite databases are optimized before closing, as recommended for long-lived connections.
+    /// The connections of the pool are only closed once every clone of the `Database` is dropped,
+    /// so this should be called after the server, which holds the other clones, has stopped.
+    /// Returns `DatabaseError::Sql` if optimizing the database fails.
+    pub async fn close(self) -> Result<(), DatabaseError> {
+        let pool = self.pool;
+        tokio::task::spawn_blocking(move || {
+            match &pool {
+                ConnectionPool::Sqlite(pool) => {
+                    diesel::sql_query("PRAGMA optimize").execute(&mut pool.get()?)?;
+                }
+                #[cfg(feature = "postgres")]
+                ConnectionPool::Postgres(_) => {}
+            }
+            drop(pool);
+            info!("Closed database connections");
+            Ok(())
+        })
+        .await
+        .expect("closing the database should never panic")
+    }
+    /// Write a consistent snapshot of an SQLite database to a new file at the path with `VACUUM INTO`.
+    /// It reads the database in a single read transaction, just like the online backup API copying every page
+    /// in one step, which diesel does not expose. So with WAL, other connections keep writing meanwhile,
+    /// while with a rollback journal, they wait for it like for any other reader.
+    /// Unlike copying the file, it never captures a transaction halfway, and the snapshot comes out compacted.
+    /// Returns `false` without writing anything for PostgreSQL, which is backed up with its own tools,
+    /// or `DatabaseError::Sql` if the snapshot fails, e.g. because the file already exists.
+    #[instrument(level = "info", skip(self))]
+    pub async fn snapshot(&self, path: PathBuf) -> Result<bool, DatabaseError> {
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || match &pool {
+            ConnectionPool::Sqlite(pool) => {
+                diesel::sql_query("VACUUM INTO ?")
+                    .bind::<diesel::sql_types::Text, _>(path.to_string_lossy().into_owned())
+                    .execute(&mut pool.get()?)?;
+                Ok(true)
+            }
+            #[cfg(feature = "postgres")]
+            ConnectionPool::Postgres(_) => Ok(false),
+        })
+        .await
+        .expect("database snapshot should never panic")
+    }
+    /// Check that the database is reachable with the cheapest possible query.
+    /// Returns `DatabaseError::Connection` or `DatabaseError::Sql` if it is not.
+    #[instrument(level = "info", skip(self))]
+    pub async fn ping(&self) -> Result<(), DatabaseError> {
         let pool = self.pool.clone();
         tokio::task::spawn_blocking(move || {
-            let mut connection = pool.get()?;
-            let result = blog_post
-                .select(BlogPost::as_select())
-                .order(id.desc())
-                .load(&mut connection)?;
-            Ok(result)
+            with_connection!(pool, |connection| {
+                diesel::sql_query("SELECT 1").execute(&mut connection)?;
+                Ok(())
+            })
         })
         .await
         .expect("database query should never panic")
     }
-    /// Save a new blog post to the database.
-    /// Returns the saved `BlogPost` if successful, or `DatabaseError::Sql` if the query fails.
-    pub async fn save(&self, to_persist: InsertBlogPost) -> Result<BlogPost, DatabaseError> {
-        debug!("Saving blog post: {:?}", to_persist);
+    /// Returns the number of open and idle connections in the connection pool.
+    pub fn pool_state(&self) -> r2d2::State {
+        match &self.pool {
+            ConnectionPool::Sqlite(pool) => pool.state(),
+            #[cfg(feature = "postgres")]
+            ConnectionPool::Postgres(pool) => pool.state(),
+        }
+    }
+    /// Returns the maximum number of connections in the connection pool.
+    pub fn pool_max_size(&self) -> u32 {
+        match &self.pool {
+            ConnectionPool::Sqlite(pool) => pool.max_size(),
+            #[cfg(feature = "postgres")]
+            ConnectionPool::Postgres(pool) => pool.max_size(),
+        }
+    }
+    /// Count all visible blog posts in the database, i.e. those which are neither hidden nor in the trash.
+    /// Returns the count if successful, or `DatabaseError::Sql` if the query fails.
+    #[instrument(level = "info", skip(self))]
+    pub async fn count_posts(&self) -> Result<i64, DatabaseError> {
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || {
+            with_connection!(pool, |connection| {
+                let count = blog_post
+                    .filter(deleted_at.is_null())
+                    .filter(hidden.eq(false))
+                    .count()
+                    .get_result(&mut connection)?;
+                Ok(count)
+            })
+        })
+        .await
+        .expect("database query should never panic")
+    }
+    /// Fetch the profile of the author with the provided username from their visible blog posts,
+    /// i.e. how many they wrote and the avatar of their most recent one which has an avatar.
+    /// Returns the profile if successful, even if the author has no visible blog posts,
+    /// or `DatabaseError::Sql` if a query fails.
+    #[instrument(level = "info", skip(self))]
+    pub async fn fetch_profile(&self, author: String) -> Result<UserProfile, DatabaseError> {
         let pool = self.pool.clone();
         tokio::task::spawn_blocking(move || {
-            let mut connection = pool.get()?;
-            let result = diesel::insert_into(blog_post)
+            with_connection!(pool, |connection| {
+                let post_count = blog_post
+                    .filter(username.eq(&author))
+                    .filter(deleted_at.is_null())
+                    .filter(hidden.eq(false))
+                    .count()
+                    .get_result(&mut connection)?;
+                let avatar = blog_post
+                    .filter(username.eq(&author))
+                    .filter(deleted_at.is_null())
+                    .filter(hidden.eq(false))
+                    .filter(avatar_uuid.is_not_null())
+                    .order(id.desc())
+                    .select(avatar_uuid)
+                    .first::<Option<AvatarImagePath>>(&mut connection)
+                    .optional()?
+                    .flatten();
+                Ok(UserProfile { username: author, post_count, avatar_uuid: avatar })
+            })
+        })
+        .await
+        .expect("database query should never panic")
+    }
+    /// Fetch a page of at most `limit` blog posts from the database sorted by ID in descending order,
+    /// or in ascending order if the `filter` asks for the oldest posts first.
+    /// If `cursor` is provided, only posts after the cursor in that order are returned.
+    /// Only posts matching the `filter` are returned, and hidden posts or posts in the trash never are.
+    /// The likes of the posts are loaded as seen by the `viewer`, if any.
+    /// Returns a `BlogPostPage` if successful, or `DatabaseError::Sql` if the query fails.
+    #[instrument(level = "info", skip(self))]
+    pub async fn fetch_page(
+        &self,
+        cursor: Option<BlogPostId>,
+        limit: i64,
+        filter: PostFilter,
+        viewer: Option<UserId>,
+    ) -> Result<BlogPostPage, DatabaseError> {
+        debug!("Loading page of {limit} blog posts before {cursor:?} matching {filter:?}");
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || {
+            with_connection!(pool, |connection| {
+                let mut query = blog_post
+                    .select(BlogPost::as_select())
+                    .filter(deleted_at.is_null())
+                    .filter(hidden.eq(false))
+                    .into_boxed();
+                match (cursor, filter.oldest_first) {
+                    (Some(cursor), false) => query = query.filter(id.lt(cursor)),
+                    (Some(cursor), true) => query = query.filter(id.gt(cursor)),
+                    (None, _) => {}
+                }
+                if let Some(tag_name) = filter.tag {
+                    let tagged = post_tag::table
+                        .inner_join(tag::table)
+                        .filter(tag::name.eq(tag_name))
+                        .select(post_tag::post_id);
+                    query = query.filter(id.eq_any(tagged));
+                }
+                if let Some(author) = filter.author {
+                    query = query.filter(username.eq(author));
+                }
+                if filter.with_image {
+                    query = query.filter(image_uuid.is_not_null());
+                }
+                if let Some(from) = filter.posted_from {
+                    query = query.filter(posted_on.ge(from));
+                }
+                if let Some(until) = filter.posted_until {
+                    query = query.filter(posted_on.le(until));
+                }
+                query = if filter.oldest_first { query.order(id.asc()) } else { query.order(id.desc()) };
+                // Fetch one extra post to find out whether there is another page after this one
+                let mut posts = query.limit(limit + 1).load(&mut connection)?;
+                let next_cursor = if posts.len() as i64 > limit {
+                    posts.truncate(limit as usize);
+                    posts.last().map(|post| post.id)
+                } else {
+                    None
+                };
+                let post_ids: Vec<BlogPostId> = posts.iter().map(|post| post.id).collect();
+                let likes = connection.load_likes(&post_ids, viewer)?;
+                let tags = connection.load_tags(&post_ids)?;
+                let last_edited = connection.load_last_edits(&post_ids)?;
+                Ok(BlogPostPage { posts, next_cursor, likes, tags, last_edited })
+            })
+        })
+        .await
+        .expect("database query should never panic")
+    }
+
+    /// Fetch the blog posts of the author, including those in the trash, or else every blog post which is not
+    /// in the trash, including hidden ones, oldest first, for exporting them.
+    /// Returns the blog posts with their tags and when they were last edited if successful,
+    /// or `DatabaseError::Sql` if a query fails.
+    #[instrument(level = "info", skip(self))]
+    pub async fn fetch_export(&self, author: Option<String>) -> Result<PostExport, DatabaseError> {
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || {
+            with_connection!(pool, |connection| {
+                let mut query = blog_post.select((BlogPost::as_select(), hidden, deleted_at)).into_boxed();
+                query = match author {
+                    Some(author) => query.filter(username.eq(author)),
+                    None => query.filter(deleted_at.is_null()),
+                };
+                let posts: Vec<(BlogPost, bool, Option<PrimitiveDateTime>)> =
+                    query.order(id.asc()).load(&mut connection)?;
+                let post_ids: Vec<BlogPostId> = posts.iter().map(|(post, ..)| post.id).collect();
+                let likes = connection.load_likes(&post_ids, None)?;
+                let tags = connection.load_tags(&post_ids)?;
+                let last_edited = connection.load_last_edits(&post_ids)?;
+                Ok(PostExport { posts, likes, tags, last_edited })
+            })
+        })
+        .await
+        .expect("database query should never panic")
+    }
+
+    /// Fetch everything stored about the account with the username besides its blog posts, for exporting it.
+    /// Returns `None` if no account with the username exists, e.g. for authors of blog posts from before accounts,
+    /// or `DatabaseError::Sql` if a query fails.
+    #[instrument(level = "info", skip(self))]
+    pub async fn fetch_account_data(&self, name: String) -> Result<Option<AccountData>, DatabaseError> {
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || {
+            with_connection!(pool, |connection| {
+                let account = users::table
+                    .filter(users::username.eq(name))
+                    .select((User::as_select(), users::created_on))
+                    .first::<(User, time::Date)>(&mut connection)
+                    .optional()?;
+                let Some((user, created_on)) = account else {
+                    return Ok(None);
+                };
+                let likes = post_like::table
+                    .filter(post_like::user_id.eq(user.id))
+                    .select(post_like::post_id)
+                    .order(post_like::post_id.asc())
+                    .load(&mut connection)?;
+                let reports = report::table
+                    .filter(report::reporter_id.eq(user.id))
+                    .select((report::post_id, report::reason, report::reported_at))
+                    .order(report::reported_at.asc())
+                    .load(&mut connection)?;
+                let api_keys = api_key::table
+                    .filter(api_key::user_id.eq(user.id))
+                    .select((api_key::name, api_key::created_at, api_key::last_used_at))
+                    .order(api_key::created_at.asc())
+                    .load(&mut connection)?;
+                Ok(Some(AccountData { user, created_on, likes, reports, api_keys }))
+            })
+        })
+        .await
+        .expect("database query should never panic")
+    }
+
+    /// Irreversibly delete the account with the username and every blog post by that name, including those in the
+    /// trash, along with everything that refers to them: likes, reports, API keys, revisions, tags, Webmentions
+    /// and webhook deliveries, as well as the IP addresses in the audit log of the changes the user made.
+    /// The erasure is recorded by the administrator, which keeps only the username.
+    /// Returns the deleted blog posts, whose images the caller must delete, or `None` if there was nothing to erase,
+    /// if successful, or `DatabaseError::Sql` if a query fails.
+    #[instrument(level = "info", skip(self))]
+    pub async fn erase_user(
+        &self,
+        name: String,
+        erased_by: String,
+        erased_at: PrimitiveDateTime,
+    ) -> Result<Option<Vec<BlogPost>>, DatabaseError> {
+        transaction!(self, |connection| {
+            let account: Option<UserId> = users::table
+                .filter(users::username.eq(&name))
+                .select(users::id)
+                .first(connection)
+                .optional()?;
+            let post_ids: Vec<BlogPostId> = blog_post.filter(username.eq(&name)).select(id).load(connection)?;
+            if account.is_none() && post_ids.is_empty() {
+                return Ok(None);
+            }
+            diesel::delete(post_like::table.filter(post_like::post_id.eq_any(&post_ids))).execute(connection)?;
+            diesel::delete(post_tag::table.filter(post_tag::post_id.eq_any(&post_ids))).execute(connection)?;
+            diesel::delete(blog_post_revision::table.filter(blog_post_revision::post_id.eq_any(&post_ids)))
+                .execute(connection)?;
+            diesel::delete(report::table.filter(report::post_id.eq_any(&post_ids))).execute(connection)?;
+            diesel::delete(webmention::table.filter(webmention::post_id.eq_any(&post_ids))).execute(connection)?;
+            // The payloads of the deliveries contain the text of the blog posts
+            diesel::delete(webhook_delivery::table.filter(webhook_delivery::post_id.eq_any(&post_ids)))
+                .execute(connection)?;
+            let posts = diesel::delete(blog_post.filter(id.eq_any(&post_ids)))
+                .returning(BlogPost::as_returning())
+                .get_results(connection)?;
+            if let Some(account) = account {
+                diesel::delete(post_like::table.filter(post_like::user_id.eq(account))).execute(connection)?;
+                diesel::delete(report::table.filter(report::reporter_id.eq(account))).execute(connection)?;
+                diesel::delete(api_key::table.filter(api_key::user_id.eq(account))).execute(connection)?;
+                diesel::delete(users::table.filter(users::id.eq(account))).execute(connection)?;
+            }
+            diesel::update(audit_log::table.filter(audit_log::actor.eq(&name)))
+                .set(audit_log::ip.eq(None::<String>))
+                .execute(connection)?;
+            let record = InsertUserErasure {
+                username: name.clone(),
+                erased_by: erased_by.clone(),
+                erased_at,
+                posts: posts.len() as i32,
+            };
+            diesel::insert_into(user_erasure::table).values(&record).execute(connection)?;
+            Ok(Some(posts))
+        })
+    }
+
+    /// Check whether the author already published a blog post with the text on the date, even if it is in the trash,
+    /// so that importing the same archive twice does not duplicate its blog posts.
+    /// Returns whether there is such a blog post if successful, or `DatabaseError::Sql` if the query fails.
+    #[instrument(level = "info", skip(self, body))]
+    pub async fn contains_post(&self, author: String, body: String, date: time::Date) -> Result<bool, DatabaseError> {
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || {
+            with_connection!(pool, |connection| {
+                let existing = blog_post.filter(username.eq(author)).filter(text.eq(body)).filter(posted_on.eq(date));
+                let result = diesel::select(diesel::dsl::exists(existing)).get_result(&mut connection)?;
+                Ok(result)
+            })
+        })
+        .await
+        .expect("database query should never panic")
+    }
+
+    /// Compute the version of the feeds as seen by the viewer, which is much cheaper than loading a page of them.
+    pub async fn feed_version(&self, viewer: Option<UserId>) -> Result<FeedVersion, DatabaseError> {
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || {
+            with_connection!(pool, |connection| {
+                let (latest_post, visible_posts) = blog_post
+                    .filter(deleted_at.is_null())
+                    .filter(hidden.eq(false))
+                    .select((diesel::dsl::max(id), diesel::dsl::count_star()))
+                    .first::<(Option<BlogPostId>, i64)>(&mut connection)?;
+                let last_edit = blog_post_revision::table
+                    .select(diesel::dsl::max(blog_post_revision::edited_at))
+                    .first::<Option<PrimitiveDateTime>>(&mut connection)?;
+                let last_visibility_change = blog_post
+                    .select(diesel::dsl::max(visibility_changed_at))
+                    .first::<Option<PrimitiveDateTime>>(&mut connection)?;
+                let (likes, liked_post_sum) = post_like::table
+                    .select((diesel::dsl::count_star(), diesel::dsl::sum(post_like::post_id)))
+                    .first::<(i64, Option<i64>)>(&mut connection)?;
+                let viewer_likes = match viewer {
+                    Some(viewer) => post_like::table
+                        .filter(post_like::user_id.eq(viewer))
+                        .count()
+                        .get_result::<i64>(&mut connection)?,
+                    None => 0,
+                };
+                Ok(FeedVersion {
+                    latest_post,
+                    visible_posts,
+                    last_edit,
+                    last_visibility_change,
+                    likes,
+                    liked_post_sum,
+                    viewer,
+                    viewer_likes,
+                })
+            })
+        })
+        .await
+        .expect("database query should never panic")
+    }
+
+    /// Find the usernames starting with the prefix under which visible blog posts were published,
+    /// so that nobody learns of accounts which never published anything.
+    /// The prefix is matched case-sensitively as a range of the index on usernames, which `LIKE` could not use.
+    /// Returns at most `limit` usernames in alphabetical order if successful,
+    /// or `DatabaseError::Sql` if the query fails.
+    #[instrument(level = "info", skip(self))]
+    pub async fn suggest_usernames(&self, prefix: String, limit: i64) -> Result<Vec<String>, DatabaseError> {
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || {
+            with_connection!(pool, |connection| {
+                // Every username starting with the prefix sorts before the prefix followed by the greatest character
+                let upper_bound = format!("{prefix}{}", char::MAX);
+                let usernames = blog_post
+                    .select(username)
+                    .filter(username.ge(&prefix))
+                    .filter(username.lt(&upper_bound))
+                    .filter(deleted_at.is_null())
+                    .filter(hidden.eq(false))
+                    .distinct()
+                    .order(username.asc())
+                    .limit(limit)
+                    .load(&mut connection)?;
+                Ok(usernames)
+            })
+        })
+        .await
+        .expect("database query should never panic")
+    }
+
+    /// Search the text and username of all visible blog posts for the words in the query.
+    /// Returns at most `limit` matches ordered by relevance if successful,
+    /// or `DatabaseError::Sql` if the query fails.
+    #[instrument(level = "info", skip(self))]
+    pub async fn search(&self, query: String, limit: i64) -> Result<Vec<SearchResult>, DatabaseError> {
+        debug!("Searching blog posts for: {}", query);
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || {
+            let rows: Vec<SearchRow> = match pool {
+                ConnectionPool::Sqlite(pool) => {
+                    let mut connection = pool.get()?;
+                    diesel::sql_query(
+                        "SELECT blog_post.*, highlight(blog_post_fts, 0, ?, ?) AS highlighted_text \
+                         FROM blog_post_fts JOIN blog_post ON blog_post.id = blog_post_fts.rowid \
+                         WHERE blog_post_fts MATCH ? AND blog_post.deleted_at IS NULL AND NOT blog_post.hidden \
+                         ORDER BY rank LIMIT ?",
+                    )
+                    .bind::<diesel::sql_types::Text, _>(HIGHLIGHT_START.to_string())
+                    .bind::<diesel::sql_types::Text, _>(HIGHLIGHT_END.to_string())
+                    .bind::<diesel::sql_types::Text, _>(fts_query(&query))
+                    .bind::<diesel::sql_types::BigInt, _>(limit)
+                    .load(&mut connection)?
+                }
+                #[cfg(feature = "postgres")]
+                ConnectionPool::Postgres(pool) => {
+                    let mut connection = pool.get()?;
+                    diesel::sql_query(
+                        "SELECT blog_post.*, ts_headline('english', blog_post.text, query, \
+                         'HighlightAll=true, StartSel=' || $1 || ', StopSel=' || $2) AS highlighted_text \
+                         FROM blog_post, plainto_tsquery('english', $3) AS query \
+                         WHERE to_tsvector('english', blog_post.text || ' ' || blog_post.username) @@ query \
+                         AND blog_post.deleted_at IS NULL AND NOT blog_post.hidden \
+                         ORDER BY ts_rank(to_tsvector('english', blog_post.text || ' ' || blog_post.username), query) DESC \
+                         LIMIT $4",
+                    )
+                    .bind::<diesel::sql_types::Text, _>(HIGHLIGHT_START.to_string())
+                    .bind::<diesel::sql_types::Text, _>(HIGHLIGHT_END.to_string())
+                    .bind::<diesel::sql_types::Text, _>(query)
+                    .bind::<diesel::sql_types::BigInt, _>(limit)
+                    .load(&mut connection)?
+                }
+            };
+            let results = rows
+                .into_iter()
+                .map(|row| SearchResult { post: row.post, highlighted_text: row.highlighted_text })
+                .collect();
+            Ok(results)
+        })
+        .await
+        .expect("database query should never panic")
+    }
+    /// Fetch a single visible blog post from the database by ID.
+    /// Returns `None` if no such blog post exists, or `DatabaseError::Sql` if the query fails.
+    #[instrument(level = "info", skip(self))]
+    pub async fn fetch(&self, post_id: BlogPostId) -> Result<Option<BlogPost>, DatabaseError> {
+        debug!("Loading blog post with id: {}", post_id);
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || {
+            with_connection!(pool, |connection| {
+                let result = blog_post
+                    .find(post_id)
+                    .filter(deleted_at.is_null())
+                    .filter(hidden.eq(false))
+                    .select(BlogPost::as_select())
+                    .first(&mut connection)
+                    .optional()?;
+                Ok(result)
+            })
+        })
+        .await
+        .expect("database query should never panic")
+    }
+    /// Fetch a single visible blog post from the database by ID, along with its likes as seen by the `viewer`, if any,
+    /// its tags and when it was last edited.
+    /// Returns `None` if no such blog post exists, or `DatabaseError::Sql` if a query fails.
+    #[instrument(level = "info", skip(self))]
+    pub async fn fetch_detail(
+        &self,
+        post_id: BlogPostId,
+        viewer: Option<UserId>,
+    ) -> Result<Option<BlogPostDetail>, DatabaseError> {
+        debug!("Loading details of blog post with id: {}", post_id);
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || {
+            with_connection!(pool, |connection| {
+                let post = blog_post
+                    .find(post_id)
+                    .filter(deleted_at.is_null())
+                    .filter(hidden.eq(false))
+                    .select(BlogPost::as_select())
+                    .first(&mut connection)
+                    .optional()?;
+                let Some(post) = post else {
+                    return Ok(None);
+                };
+                let likes = connection.load_likes(&[post_id], viewer)?.remove(&post_id).unwrap_or_default();
+                let tags = connection.load_tags(&[post_id])?.remove(&post_id).unwrap_or_default();
+                let last_edited = connection.load_last_edits(&[post_id])?.remove(&post_id);
+                Ok(Some(BlogPostDetail { post, likes, tags, last_edited }))
+            })
+        })
+        .await
+        .expect("database query should never panic")
+    }
+    /// Fetch the visible blog posts with the IDs in one query, along with their likes as seen by the `viewer`, if any,
+    /// their tags and when they were last edited, in the order of the IDs.
+    /// Blog posts which do not exist, or are hidden or in the trash, are skipped.
+    /// Returns the blog posts if successful, or `DatabaseError::Sql` if a query fails.
+    #[instrument(level = "info", skip(self))]
+    pub async fn fetch_details(
+        &self,
+        post_ids: Vec<BlogPostId>,
+        viewer: Option<UserId>,
+    ) -> Result<Vec<BlogPostDetail>, DatabaseError> {
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || {
+            with_connection!(pool, |connection| {
+                let posts: Vec<BlogPost> = blog_post
+                    .filter(id.eq_any(&post_ids))
+                    .filter(deleted_at.is_null())
+                    .filter(hidden.eq(false))
+                    .select(BlogPost::as_select())
+                    .load(&mut connection)?;
+                let mut likes = connection.load_likes(&post_ids, viewer)?;
+                let mut tags = connection.load_tags(&post_ids)?;
+                let mut last_edits = connection.load_last_edits(&post_ids)?;
+                let mut posts: HashMap<BlogPostId, BlogPost> = posts.into_iter().map(|post| (post.id, post)).collect();
+                // Duplicate IDs are only fetched once, since the blog post is taken out of the map the first time
+                let details = post_ids
+                    .iter()
+                    .filter_map(|post_id| {
+                        let post = posts.remove(post_id)?;
+                        Some(BlogPostDetail {
+                            post,
+                            likes: likes.remove(post_id).unwrap_or_default(),
+                            tags: tags.remove(post_id).unwrap_or_default(),
+                            last_edited: last_edits.remove(post_id),
+                        })
+                    })
+                    .collect();
+                Ok(details)
+            })
+        })
+        .await
+        .expect("database query should never panic")
+    }
+    /// Fetch a single blog post which is in the trash from the database by ID.
+    /// Returns `None` if no such blog post exists, or `DatabaseError::Sql` if the query fails.
+    #[instrument(level = "info", skip(self))]
+    pub async fn fetch_trashed(&self, post_id: BlogPostId) -> Result<Option<BlogPost>, DatabaseError> {
+        debug!("Loading trashed blog post with id: {}", post_id);
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || {
+            with_connection!(pool, |connection| {
+                let result = blog_post
+                    .find(post_id)
+                    .filter(deleted_at.is_not_null())
+                    .select(BlogPost::as_select())
+                    .first(&mut connection)
+                    .optional()?;
+                Ok(result)
+            })
+        })
+        .await
+        .expect("database query should never panic")
+    }
+    /// Save a new blog post with the provided tags to the database.
+    /// Tags which do not exist yet are created.
+    /// The blog post gets a slug generated from its text, which is made unique with a numeric suffix if needed.
+    /// Returns the saved `BlogPost` if successful, or `DatabaseError::Sql` if the query fails.
+    #[instrument(level = "info", skip(self, to_persist))]
+    pub async fn save(
+        &self,
+        to_persist: InsertBlogPost,
+        tags: Vec<String>,
+    ) -> Result<BlogPost, DatabaseError> {
+        debug!("Saving blog post: {:?} with tags {:?}", to_persist, tags);
+        transaction!(self, |connection| {
+            let base_slug = slugify(&to_persist.text);
+            let mut unique_slug = base_slug.clone();
+            let mut suffix = 1;
+            while diesel::select(diesel::dsl::exists(blog_post.filter(slug.eq(&unique_slug))))
+                .get_result::<bool>(connection)?
+            {
+                suffix += 1;
+                unique_slug = format!("{base_slug}-{suffix}");
+            }
+            let to_persist = InsertBlogPost { slug: Some(unique_slug), ..to_persist.clone() };
+            let post = diesel::insert_into(blog_post)
                 .values(&to_persist)
                 .returning(BlogPost::as_returning())
-                .get_result(&mut connection)?;
-            Ok(result)
+                .get_result(connection)?;
+            if !tags.is_empty() {
+                for name in &tags {
+                    diesel::insert_into(tag::table)
+                        .values(tag::name.eq(name))
+                        .on_conflict_do_nothing()
+                        .execute(connection)?;
+                }
+                let tag_ids: Vec<i32> = tag::table
+                    .filter(tag::name.eq_any(&tags))
+                    .select(tag::id)
+                    .load(connection)?;
+                let post_tags: Vec<_> = tag_ids
+                    .into_iter()
+                    .map(|tag_id| (post_tag::post_id.eq(post.id), post_tag::tag_id.eq(tag_id)))
+                    .collect();
+                diesel::insert_into(post_tag::table)
+                    .values(&post_tags)
+                    .execute(connection)?;
+            }
+            Ok(post)
+        })
+    }
+    /// Update the editable fields of a blog post in the database by ID.
+    /// If the text changes, the previous text is kept as a revision of the blog post.
+    /// Returns the updated `BlogPost` if successful, or `DatabaseError::Sql` if the query fails
+    /// (including if no visible blog post with the ID exists).
+    #[instrument(level = "info", skip(self))]
+    pub async fn update(
+        &self,
+        post_id: BlogPostId,
+        changes: UpdateBlogPost,
+    ) -> Result<BlogPost, DatabaseError> {
+        debug!("Updating blog post with id {}: {:?}", post_id, changes);
+        transaction!(self, |connection| {
+            let previous: String = blog_post
+                .find(post_id)
+                .filter(deleted_at.is_null())
+                .filter(hidden.eq(false))
+                .select(text)
+                .first(connection)?;
+            if previous != changes.text {
+                diesel::insert_into(blog_post_revision::table)
+                    .values((
+                        blog_post_revision::post_id.eq(post_id),
+                        blog_post_revision::text.eq(previous),
+                        blog_post_revision::edited_at.eq(now_utc()),
+                    ))
+                    .execute(connection)?;
+            }
+            diesel::update(blog_post.find(post_id))
+                .set(&changes)
+                .returning(BlogPost::as_returning())
+                .get_result(connection)
+        })
+    }
+    /// Fetch the previous texts of a visible blog post, most recent first.
+    /// Returns the revisions if successful, or `DatabaseError::Sql` if the query fails.
+    #[instrument(level = "info", skip(self))]
+    pub async fn fetch_revisions(&self, post_id: BlogPostId) -> Result<Vec<PostRevision>, DatabaseError> {
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || {
+            with_connection!(pool, |connection| {
+                let result = blog_post_revision::table
+                    .inner_join(blog_post)
+                    .filter(blog_post_revision::post_id.eq(post_id))
+                    .filter(deleted_at.is_null())
+                    .filter(hidden.eq(false))
+                    .order(blog_post_revision::edited_at.desc())
+                    .select(PostRevision::as_select())
+                    .load(&mut connection)?;
+                Ok(result)
+            })
         })
         .await
         .expect("database query should never panic")
     }
-    /// Delete a blog post from the database by ID.
-    /// Returns the deleted `BlogPost` if successful, or `DatabaseError::Sql` if the query fails.
-    pub async fn delete(&self, post_id: BlogPostId) -> Result<BlogPost, DatabaseError> {
-        debug!("Deleting blog post with id: {}", post_id);
+    /// Move a blog post to the trash by ID, recording the time at which it was deleted.
+    /// Its likes and tags are kept, so that they are intact if the blog post is restored.
+    /// Returns the trashed `BlogPost` if successful, or `DatabaseError::Sql` if the query fails
+    /// (including if no blog post with the ID exists outside of the trash).
+    #[instrument(level = "info", skip(self))]
+    pub async fn trash(&self, post_id: BlogPostId) -> Result<BlogPost, DatabaseError> {
+        debug!("Moving blog post with id {} to the trash", post_id);
+        transaction!(self, |connection| {
+            let result = diesel::update(blog_post.find(post_id).filter(deleted_at.is_null()))
+                .set((deleted_at.eq(now_utc()), visibility_changed_at.eq(now_utc())))
+                .returning(BlogPost::as_returning())
+                .get_result(connection)?;
+            Ok(result)
+        })
+    }
+    /// Move the blog posts with the IDs which the user may delete to the trash in one statement,
+    /// so the caller limits how many IDs there are.
+    /// Blog posts which do not exist, were written by someone else or are already in the trash are skipped.
+    /// Returns the IDs of the moved blog posts if successful, or `DatabaseError::Sql` if the query fails.
+    #[instrument(level = "info", skip(self))]
+    pub async fn trash_many(&self, post_ids: Vec<BlogPostId>, owner: UserId) -> Result<Vec<BlogPostId>, DatabaseError> {
+        transaction!(self, |connection| {
+            // Blog posts written before accounts existed belong to nobody, see `BlogPost::is_editable_by`
+            let trashed = diesel::update(
+                blog_post.filter(id.eq_any(&post_ids)).filter(deleted_at.is_null()).filter(user_id.eq(owner)),
+            )
+            .set((deleted_at.eq(now_utc()), visibility_changed_at.eq(now_utc())))
+            .returning(id)
+            .get_results(connection)?;
+            Ok(trashed)
+        })
+    }
+    /// Restore a blog post from the trash by ID.
+    /// Returns the restored `BlogPost` if successful, or `DatabaseError::Sql` if the query fails
+    /// (including if no blog post with the ID is in the trash).
+    #[instrument(level = "info", skip(self))]
+    pub async fn restore(&self, post_id: BlogPostId) -> Result<BlogPost, DatabaseError> {
+        debug!("Restoring blog post with id {} from the trash", post_id);
+        transaction!(self, |connection| {
+            let result = diesel::update(blog_post.find(post_id).filter(deleted_at.is_not_null()))
+                .set((deleted_at.eq(None::<PrimitiveDateTime>), visibility_changed_at.eq(now_utc())))
+                .returning(BlogPost::as_returning())
+                .get_result(connection)?;
+            Ok(result)
+        })
+    }
+    /// Fetch the blog posts in the trash which the user wrote, most recently deleted first,
+    /// together with the time at which they were deleted.
+    /// Returns the trashed posts if successful, or `DatabaseError::Sql` if the query fails.
+    #[instrument(level = "info", skip(self))]
+    pub async fn fetch_trash(
+        &self,
+        user: UserId,
+    ) -> Result<Vec<(BlogPost, PrimitiveDateTime)>, DatabaseError> {
         let pool = self.pool.clone();
         tokio::task::spawn_blocking(move || {
-            let mut connection = pool.get()?;
-            let result = diesel::delete(blog_post.find(post_id))
+            with_connection!(pool, |connection| {
+                let rows: Vec<(BlogPost, Option<PrimitiveDateTime>)> = blog_post
+                    .filter(deleted_at.is_not_null())
+                    .filter(user_id.eq(user))
+                    .order(deleted_at.desc())
+                    .select((BlogPost::as_select(), deleted_at))
+                    .load(&mut connection)?;
+                let trash = rows
+                    .into_iter()
+                    .filter_map(|(post, deleted)| Some((post, deleted?)))
+                    .collect();
+                Ok(trash)
+            })
+        })
+        .await
+        .expect("database query should never panic")
+    }
+    /// Permanently delete the blog posts which were moved to the trash before the cutoff,
+    /// together with their likes, tag associations, revisions and reports.
+    /// Returns the deleted `BlogPost`s if successful, or `DatabaseError::Sql` if the query fails.
+    #[instrument(level = "info", skip(self))]
+    pub async fn purge_trash(&self, cutoff: PrimitiveDateTime) -> Result<Vec<BlogPost>, DatabaseError> {
+        transaction!(self, |connection| {
+            let expired: Vec<BlogPostId> = blog_post
+                .filter(deleted_at.lt(cutoff))
+                .select(id)
+                .load(connection)?;
+            diesel::delete(post_like::table.filter(post_like::post_id.eq_any(&expired)))
+                .execute(connection)?;
+            diesel::delete(post_tag::table.filter(post_tag::post_id.eq_any(&expired)))
+                .execute(connection)?;
+            diesel::delete(blog_post_revision::table.filter(blog_post_revision::post_id.eq_any(&expired)))
+                .execute(connection)?;
+            diesel::delete(report::table.filter(report::post_id.eq_any(&expired)))
+                .execute(connection)?;
+            diesel::delete(blog_post.filter(id.eq_any(&expired)))
                 .returning(BlogPost::as_returning())
-                .get_result(&mut connection)?;
+                .get_results(connection)
+        })
+    }
+    /// Fetch a page of at most `limit` blog posts, including hidden posts and posts in the trash,
+    /// sorted by ID in descending order. Pagination works the same as for `fetch_page`.
+    /// Returns a `ModerationPage` if successful, or `DatabaseError::Sql` if the query fails.
+    #[instrument(level = "info", skip(self))]
+    pub async fn fetch_moderation_page(
+        &self,
+        cursor: Option<BlogPostId>,
+        limit: i64,
+    ) -> Result<ModerationPage, DatabaseError> {
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || {
+            with_connection!(pool, |connection| {
+                let mut query = blog_post
+                    .select((BlogPost::as_select(), hidden, deleted_at))
+                    .into_boxed();
+                if let Some(cursor) = cursor {
+                    query = query.filter(id.lt(cursor));
+                }
+                // Fetch one extra post to find out whether there is another page after this one
+                let mut rows: Vec<(BlogPost, bool, Option<PrimitiveDateTime>)> = query
+                    .order(id.desc())
+                    .limit(limit + 1)
+                    .load(&mut connection)?;
+                let next_cursor = if rows.len() as i64 > limit {
+                    rows.truncate(limit as usize);
+                    rows.last().map(|(post, _, _)| post.id)
+                } else {
+                    None
+                };
+                let posts = rows
+                    .into_iter()
+                    .map(|(post, is_hidden, deleted)| ModeratedPost { post, hidden: is_hidden, deleted_at: deleted })
+                    .collect();
+                Ok(ModerationPage { posts, next_cursor })
+            })
+        })
+        .await
+        .expect("database query should never panic")
+    }
+    /// Apply the moderation action to all blog posts with the provided IDs.
+    /// Blog posts to which the action does not apply, e.g. restoring posts which are not in the trash, are skipped.
+    /// Returns the IDs of the affected blog posts if successful, or `DatabaseError::Sql` if the query fails.
+    #[instrument(level = "info", skip(self))]
+    pub async fn moderate(
+        &self,
+        post_ids: Vec<BlogPostId>,
+        action: ModerationAction,
+    ) -> Result<Vec<BlogPostId>, DatabaseError> {
+        transaction!(self, |connection| {
+            let posts = blog_post.filter(id.eq_any(&post_ids));
+            let affected = match action {
+                ModerationAction::Hide => diesel::update(posts.filter(hidden.eq(false)))
+                    .set((hidden.eq(true), visibility_changed_at.eq(now_utc())))
+                    .returning(id)
+                    .get_results(connection)?,
+                ModerationAction::Unhide => diesel::update(posts.filter(hidden.eq(true)))
+                    .set((hidden.eq(false), visibility_changed_at.eq(now_utc())))
+                    .returning(id)
+                    .get_results(connection)?,
+                ModerationAction::Delete => diesel::update(posts.filter(deleted_at.is_null()))
+                    .set((deleted_at.eq(now_utc()), visibility_changed_at.eq(now_utc())))
+                    .returning(id)
+                    .get_results(connection)?,
+                ModerationAction::Restore => diesel::update(posts.filter(deleted_at.is_not_null()))
+                    .set((deleted_at.eq(None::<PrimitiveDateTime>), visibility_changed_at.eq(now_utc())))
+                    .returning(id)
+                    .get_results(connection)?,
+            };
+            Ok(affected)
+        })
+    }
+    /// Count the rows of the database for the statistics shown to administrators.
+    /// The statistics about the connection pool and the image store are left at zero.
+    /// Returns the statistics if successful, or `DatabaseError::Sql` if a query fails.
+    #[instrument(level = "info", skip(self))]
+    pub async fn statistics(&self) -> Result<AdminStatistics, DatabaseError> {
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || {
+            with_connection!(pool, |connection| {
+                let visible_posts = blog_post
+                    .filter(deleted_at.is_null())
+                    .filter(hidden.eq(false))
+                    .count()
+                    .get_result(&mut connection)?;
+                let hidden_posts = blog_post.filter(hidden.eq(true)).count().get_result(&mut connection)?;
+                let trashed_posts = blog_post
+                    .filter(deleted_at.is_not_null())
+                    .count()
+                    .get_result(&mut connection)?;
+                let revisions = blog_post_revision::table.count().get_result(&mut connection)?;
+                let users = users::table.count().get_result(&mut connection)?;
+                let likes = post_like::table.count().get_result(&mut connection)?;
+                let tags = tag::table.count().get_result(&mut connection)?;
+                let open_reports = report::table
+                    .filter(report::resolved_at.is_null())
+                    .count()
+                    .get_result(&mut connection)?;
+                Ok(AdminStatistics {
+                    visible_posts,
+                    hidden_posts,
+                    trashed_posts,
+                    revisions,
+                    users,
+                    likes,
+                    tags,
+                    open_reports,
+                    ..AdminStatistics::default()
+                })
+            })
+        })
+        .await
+        .expect("database query should never panic")
+    }
+    /// Count the blog posts which are neither hidden nor in the trash for the statistics shown on the home page,
+    /// including how many were published on each of the last `days` days.
+    /// The size of the stored images is left at zero.
+    /// Returns the statistics if successful, or `DatabaseError::Sql` if a query fails.
+    #[instrument(level = "info", skip(self))]
+    pub async fn blog_stats(&self, days: i64) -> Result<BlogStats, DatabaseError> {
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || {
+            with_connection!(pool, |connection| {
+                let today = OffsetDateTime::now_utc().date();
+                let first_day = today - time::Duration::days(days - 1);
+                let visible = || blog_post.filter(deleted_at.is_null()).filter(hidden.eq(false));
+                let total_posts = visible().count().get_result(&mut connection)?;
+                let authors = visible()
+                    .select(diesel::dsl::count_distinct(username))
+                    .get_result(&mut connection)?;
+                let counts: HashMap<time::Date, i64> = visible()
+                    .filter(posted_on.ge(first_day))
+                    .group_by(posted_on)
+                    .select((posted_on, diesel::dsl::count_star()))
+                    .load::<(time::Date, i64)>(&mut connection)?
+                    .into_iter()
+                    .collect();
+                // Days without blog posts have no group, so every day is filled in
+                let posts_per_day = (0..days)
+                    .map(|offset| first_day + time::Duration::days(offset))
+                    .map(|day| (day, counts.get(&day).copied().unwrap_or(0)))
+                    .collect();
+                Ok(BlogStats { total_posts, posts_per_day, authors, image_bytes: 0 })
+            })
+        })
+        .await
+        .expect("database query should never panic")
+    }
+    /// Save a report of a blog post by a user.
+    /// Returns `DatabaseError::Sql` if the query fails,
+    /// e.g. with a unique violation if the user already reported the blog post.
+    #[instrument(level = "info", skip(self, reason))]
+    pub async fn create_report(
+        &self,
+        post_id: BlogPostId,
+        reporter: UserId,
+        reason: String,
+    ) -> Result<(), DatabaseError> {
+        transaction!(self, |connection| {
+            diesel::insert_into(report::table)
+                .values((
+                    report::post_id.eq(post_id),
+                    report::reporter_id.eq(reporter),
+                    report::reason.eq(&reason),
+                    report::reported_at.eq(now_utc()),
+                ))
+                .execute(connection)?;
+            Ok(())
+        })
+    }
+    /// Fetch the reports which no administrator has handled yet, oldest first.
+    /// Returns the open reports if successful, or `DatabaseError::Sql` if the query fails.
+    #[instrument(level = "info", skip(self))]
+    pub async fn fetch_open_reports(&self) -> Result<Vec<OpenReport>, DatabaseError> {
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || {
+            with_connection!(pool, |connection| {
+                // Both the report and the blog post reference a user, so the reporter is joined explicitly
+                let rows: Vec<(ReportId, BlogPost, String, String, PrimitiveDateTime)> = report::table
+                    .inner_join(blog_post)
+                    .inner_join(users::table.on(users::id.eq(report::reporter_id)))
+                    .filter(report::resolved_at.is_null())
+                    .order(report::reported_at.asc())
+                    .select((
+                        report::id,
+                        BlogPost::as_select(),
+                        users::username,
+                        report::reason,
+                        report::reported_at,
+                    ))
+                    .load(&mut connection)?;
+                let reports = rows
+                    .into_iter()
+                    .map(|(report_id, post, reporter, reason, reported_at)| OpenReport {
+                        id: report_id,
+                        post,
+                        reporter,
+                        reason,
+                        reported_at,
+                    })
+                    .collect();
+                Ok(reports)
+            })
+        })
+        .await
+        .expect("database query should never panic")
+    }
+    /// Close an open report with the resolution.
+    /// Resolving a report hides the reported blog post and closes all open reports of it,
+    /// while dismissing a report only closes that report.
+    /// Returns the ID of the reported blog post if successful,
+    /// or `DatabaseError::Sql` if the query fails (including if no open report with the ID exists).
+    #[instrument(level = "info", skip(self))]
+    pub async fn resolve_report(
+        &self,
+        report_id: ReportId,
+        resolution: ReportResolution,
+    ) -> Result<BlogPostId, DatabaseError> {
+        transaction!(self, |connection| {
+            let post_id: BlogPostId = report::table
+                .find(report_id)
+                .filter(report::resolved_at.is_null())
+                .select(report::post_id)
+                .first(connection)?;
+            let close = (report::resolution.eq(resolution.as_str()), report::resolved_at.eq(now_utc()));
+            match resolution {
+                ReportResolution::Resolve => {
+                    diesel::update(blog_post.find(post_id))
+                        .set((hidden.eq(true), visibility_changed_at.eq(now_utc())))
+                        .execute(connection)?;
+                    diesel::update(
+                        report::table
+                            .filter(report::post_id.eq(post_id))
+                            .filter(report::resolved_at.is_null()),
+                    )
+                    .set(close)
+                    .execute(connection)?;
+                }
+                ReportResolution::Dismiss => {
+                    diesel::update(report::table.find(report_id))
+                        .set(close)
+                        .execute(connection)?;
+                }
+            }
+            Ok(post_id)
+        })
+    }
+    /// Returns those of the images which are not referenced by any blog post in the database.
+    /// Identical images are shared between blog posts, so this should be checked after purging a blog post,
+    /// or failing to insert one, to find out which of its images can be deleted from the image store.
+    /// Returns the unreferenced images if successful, or `DatabaseError::Sql` if the query fails.
+    #[instrument(level = "info", skip(self))]
+    pub async fn unreferenced_images(
+        &self,
+        image: Option<PostImagePath>,
+        avatar: Option<AvatarImagePath>,
+    ) -> Result<(Option<PostImagePath>, Option<AvatarImagePath>), DatabaseError> {
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || {
+            with_connection!(pool, |connection| {
+                let image = match image {
+                    Some(image) => {
+                        let references: i64 = blog_post
+                            .filter(image_uuid.eq(&image))
+                            .count()
+                            .get_result(&mut connection)?;
+                        (references == 0).then_some(image)
+                    }
+                    None => None,
+                };
+                let avatar = match avatar {
+                    Some(avatar) => {
+                        let references: i64 = blog_post
+                            .filter(avatar_uuid.eq(&avatar))
+                            .count()
+                            .get_result(&mut connection)?;
+                        (references == 0).then_some(avatar)
+                    }
+                    None => None,
+                };
+                Ok((image, avatar))
+            })
+        })
+        .await
+        .expect("database query should never panic")
+    }
+    /// Fetch the distinct post images and avatars referenced by any blog post in the database.
+    /// Returns the referenced images if successful, or `DatabaseError::Sql` if the query fails.
+    #[instrument(level = "info", skip(self))]
+    pub async fn referenced_images(
+        &self,
+    ) -> Result<(Vec<PostImagePath>, Vec<AvatarImagePath>), DatabaseError> {
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || {
+            with_connection!(pool, |connection| {
+                let images: Vec<Option<PostImagePath>> = blog_post
+                    .select(image_uuid)
+                    .filter(image_uuid.is_not_null())
+                    .distinct()
+                    .load(&mut connection)?;
+                let avatars: Vec<Option<AvatarImagePath>> = blog_post
+                    .select(avatar_uuid)
+                    .filter(avatar_uuid.is_not_null())
+                    .distinct()
+                    .load(&mut connection)?;
+                Ok((images.into_iter().flatten().collect(), avatars.into_iter().flatten().collect()))
+            })
+        })
+        .await
+        .expect("database query should never panic")
+    }
+    /// Fetch the avatar downloaded from the URL with the hash, if it was downloaded after the cutoff.
+    /// Returns the avatar if successful, or `DatabaseError::Sql` if the query fails.
+    #[instrument(level = "info", skip(self))]
+    pub async fn fetch_cached_avatar(
+        &self,
+        hash: String,
+        cutoff: PrimitiveDateTime,
+    ) -> Result<Option<AvatarImagePath>, DatabaseError> {
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || {
+            with_connection!(pool, |connection| {
+                let result = avatar_cache::table
+                    .find(hash)
+                    .filter(avatar_cache::cached_at.gt(cutoff))
+                    .select(avatar_cache::avatar_uuid)
+                    .first(&mut connection)
+                    .optional()?;
+                Ok(result)
+            })
+        })
+        .await
+        .expect("database query should never panic")
+    }
+    /// Remember the avatar downloaded from the URL with the hash, replacing the one downloaded from it before, if any.
+    /// Avatars downloaded before the cutoff are forgotten along the way, since they would not be used anymore.
+    /// Returns `DatabaseError::Sql` if the query fails.
+    #[instrument(level = "info", skip(self))]
+    pub async fn cache_avatar(
+        &self,
+        hash: String,
+        avatar: AvatarImagePath,
+        cutoff: PrimitiveDateTime,
+    ) -> Result<(), DatabaseError> {
+        transaction!(self, |connection| {
+            let now = now_utc();
+            diesel::delete(avatar_cache::table.filter(avatar_cache::cached_at.le(cutoff))).execute(connection)?;
+            diesel::insert_into(avatar_cache::table)
+                .values((
+                    avatar_cache::url_hash.eq(&hash),
+                    avatar_cache::avatar_uuid.eq(&avatar),
+                    avatar_cache::cached_at.eq(now),
+                ))
+                .on_conflict(avatar_cache::url_hash)
+                .do_update()
+                .set((avatar_cache::avatar_uuid.eq(&avatar), avatar_cache::cached_at.eq(now)))
+                .execute(connection)?;
+            Ok(())
+        })
+    }
+    /// Record pending deliveries of a post event to webhooks.
+    /// Returns the recorded deliveries if successful, or `DatabaseError::Sql` if the query fails.
+    #[instrument(level = "info", skip_all, fields(count = deliveries.len()))]
+    pub async fn enqueue_webhook_deliveries(
+        &self,
+        deliveries: Vec<InsertWebhookDelivery>,
+    ) -> Result<Vec<WebhookDelivery>, DatabaseError> {
+        transaction!(self, |connection| {
+            let mut saved = Vec::with_capacity(deliveries.len());
+            for delivery in &deliveries {
+                let result = diesel::insert_into(webhook_delivery::table)
+                    .values(delivery)
+                    .returning(WebhookDelivery::as_returning())
+                    .get_result(connection)?;
+                saved.push(result);
+            }
+            Ok(saved)
+        })
+    }
+    /// Fetch at most `limit` pending webhook deliveries which are due to be attempted at `now`, oldest first.
+    /// Returns the deliveries if successful, or `DatabaseError::Sql` if the query fails.
+    #[instrument(level = "info", skip(self))]
+    pub async fn fetch_due_webhook_deliveries(
+        &self,
+        now: PrimitiveDateTime,
+        limit: i64,
+    ) -> Result<Vec<WebhookDelivery>, DatabaseError> {
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || {
+            with_connection!(pool, |connection| {
+                let result = webhook_delivery::table
+                    .filter(webhook_delivery::status.eq(WebhookStatus::Pending.as_str()))
+                    .filter(webhook_delivery::next_attempt_at.le(now))
+                    .order(webhook_delivery::id.asc())
+                    .limit(limit)
+                    .select(WebhookDelivery::as_select())
+                    .load(&mut connection)?;
+                Ok(result)
+            })
+        })
+        .await
+        .expect("database query should never panic")
+    }
+    /// Record the outcome of an attempt to deliver a post event to a webhook.
+    /// Returns `DatabaseError::Sql` if the query fails.
+    #[instrument(level = "info", skip(self))]
+    pub async fn record_webhook_attempt(&self, delivery_id: i32, attempt: WebhookAttempt) -> Result<(), DatabaseError> {
+        transaction!(self, |connection| {
+            diesel::update(webhook_delivery::table.find(delivery_id))
+                .set(&attempt)
+                .execute(connection)?;
+            Ok(())
+        })
+    }
+    /// Delete the webhook deliveries which are no longer pending and were created before the cutoff.
+    /// Returns the number of deleted deliveries if successful, or `DatabaseError::Sql` if the query fails.
+    #[instrument(level = "info", skip(self))]
+    pub async fn purge_webhook_deliveries(&self, cutoff: PrimitiveDateTime) -> Result<usize, DatabaseError> {
+        transaction!(self, |connection| {
+            let purged = diesel::delete(
+                webhook_delivery::table
+                    .filter(webhook_delivery::status.ne(WebhookStatus::Pending.as_str()))
+                    .filter(webhook_delivery::created_at.lt(cutoff)),
+            )
+            .execute(connection)?;
+            Ok(purged)
+        })
+    }
+    /// Save a new API key, which authenticates requests on behalf of the user with the username.
+    /// Returns the saved key if successful, `None` if no user with the username exists,
+    /// or `DatabaseError::Sql` if the query fails.
+    #[instrument(level = "info", skip(self, to_persist), fields(name = %to_persist.name))]
+    pub async fn create_api_key(
+        &self,
+        owner: String,
+        to_persist: InsertApiKey,
+    ) -> Result<Option<ApiKeyInfo>, DatabaseError> {
+        transaction!(self, |connection| {
+            let owner_id: Option<UserId> = users::table
+                .filter(users::username.eq(&owner))
+                .select(users::id)
+                .first(connection)
+                .optional()?;
+            match owner_id {
+                Some(owner_id) => {
+                    let key_id = diesel::insert_into(api_key::table)
+                        .values((&to_persist, api_key::user_id.eq(owner_id)))
+                        .returning(api_key::id)
+                        .get_result(connection)?;
+                    Ok(Some(ApiKeyInfo {
+                        id: key_id,
+                        name: to_persist.name.clone(),
+                        username: owner.clone(),
+                        prefix: to_persist.prefix.clone(),
+                        created_at: to_persist.created_at,
+                        last_used_at: None,
+                    }))
+                }
+                None => Ok(None),
+            }
+        })
+    }
+    /// Fetch all API keys, newest first.
+    /// Returns the keys if successful, or `DatabaseError::Sql` if the query fails.
+    #[instrument(level = "info", skip(self))]
+    pub async fn fetch_api_keys(&self) -> Result<Vec<ApiKeyInfo>, DatabaseError> {
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || {
+            with_connection!(pool, |connection| {
+                let keys = api_key::table
+                    .inner_join(users::table)
+                    .order(api_key::created_at.desc())
+                    .select((
+                        api_key::id,
+                        api_key::name,
+                        users::username,
+                        api_key::prefix,
+                        api_key::created_at,
+                        api_key::last_used_at,
+                    ))
+                    .load(&mut connection)?;
+                Ok(keys)
+            })
+        })
+        .await
+        .expect("database query should never panic")
+    }
+    /// Delete the API key, so that it no longer authenticates requests.
+    /// Returns whether the key existed if successful, or `DatabaseError::Sql` if the query fails.
+    #[instrument(level = "info", skip(self))]
+    pub async fn revoke_api_key(&self, key_id: ApiKeyId) -> Result<bool, DatabaseError> {
+        transaction!(self, |connection| {
+            let deleted = diesel::delete(api_key::table.find(key_id)).execute(connection)?;
+            Ok(deleted > 0)
+        })
+    }
+    /// Look up the user on whose behalf the API key with the hash authenticates, and record that the key was used.
+    /// Returns the user if successful, `None` if no key has the hash (e.g. because it was revoked),
+    /// or `DatabaseError::Sql` if the query fails.
+    #[instrument(level = "info", skip_all)]
+    pub async fn authenticate_api_key(
+        &self,
+        key_hash: String,
+        now: PrimitiveDateTime,
+    ) -> Result<Option<User>, DatabaseError> {
+        transaction!(self, |connection| {
+            let owner: Option<(ApiKeyId, User)> = api_key::table
+                .inner_join(users::table)
+                .filter(api_key::key_hash.eq(&key_hash))
+                .select((api_key::id, User::as_select()))
+                .first(connection)
+                .optional()?;
+            if let Some((key_id, _)) = &owner {
+                diesel::update(api_key::table.find(*key_id))
+                    .set(api_key::last_used_at.eq(now))
+                    .execute(connection)?;
+            }
+            Ok(owner.map(|(_, owner)| owner))
+        })
+    }
+    /// Save a remote actor who follows the blog, or update the inbox of an actor who already follows it.
+    /// Returns `()` if successful, or `DatabaseError::Sql` if the query fails.
+    #[instrument(level = "info", skip(self))]
+    pub async fn add_follower(&self, to_persist: InsertFollower) -> Result<(), DatabaseError> {
+        transaction!(self, |connection| {
+            diesel::insert_into(follower::table)
+                .values(&to_persist)
+                .on_conflict(follower::actor_id)
+                .do_update()
+                .set(follower::inbox.eq(&to_persist.inbox))
+                .execute(connection)?;
+            Ok(())
+        })
+    }
+    /// Delete a remote actor who no longer follows the blog.
+    /// Returns whether the actor followed the blog if successful, or `DatabaseError::Sql` if the query fails.
+    #[instrument(level = "info", skip(self))]
+    pub async fn remove_follower(&self, actor: String) -> Result<bool, DatabaseError> {
+        transaction!(self, |connection| {
+            let deleted = diesel::delete(follower::table.filter(follower::actor_id.eq(&actor))).execute(connection)?;
+            Ok(deleted > 0)
+        })
+    }
+    /// Fetch the distinct inboxes of all followers, to which new blog posts are delivered.
+    /// Returns the inboxes if successful, or `DatabaseError::Sql` if the query fails.
+    #[instrument(level = "info", skip(self))]
+    pub async fn fetch_follower_inboxes(&self) -> Result<Vec<String>, DatabaseError> {
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || {
+            with_connection!(pool, |connection| {
+                let inboxes = follower::table.select(follower::inbox).distinct().load(&mut connection)?;
+                Ok(inboxes)
+            })
+        })
+        .await
+        .expect("database query should never panic")
+    }
+    /// Count the followers of the blog.
+    /// Returns the count if successful, or `DatabaseError::Sql` if the query fails.
+    #[instrument(level = "info", skip(self))]
+    pub async fn count_followers(&self) -> Result<i64, DatabaseError> {
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || {
+            with_connection!(pool, |connection| {
+                let count = follower::table.count().get_result(&mut connection)?;
+                Ok(count)
+            })
+        })
+        .await
+        .expect("database query should never panic")
+    }
+    /// Save a verified Webmention of a blog post, or refresh the title and time of a mention which was sent before.
+    /// Returns `()` if successful, or `DatabaseError::Sql` if the query fails.
+    #[instrument(level = "info", skip(self))]
+    pub async fn save_webmention(&self, to_persist: InsertWebmention) -> Result<(), DatabaseError> {
+        transaction!(self, |connection| {
+            diesel::insert_into(webmention::table)
+                .values(&to_persist)
+                .on_conflict((webmention::post_id, webmention::source))
+                .do_update()
+                .set((
+                    webmention::title.eq(&to_persist.title),
+                    webmention::received_at.eq(to_persist.received_at),
+                ))
+                .execute(connection)?;
+            Ok(())
+        })
+    }
+    /// Delete the Webmention of the blog post by the source, e.g. because the source no longer links to it.
+    /// Returns whether there was such a mention if successful, or `DatabaseError::Sql` if the query fails.
+    #[instrument(level = "info", skip(self))]
+    pub async fn remove_webmention(&self, post_id: BlogPostId, source: String) -> Result<bool, DatabaseError> {
+        transaction!(self, |connection| {
+            let mention = webmention::table
+                .filter(webmention::post_id.eq(post_id))
+                .filter(webmention::source.eq(&source));
+            let deleted = diesel::delete(mention).execute(connection)?;
+            Ok(deleted > 0)
+        })
+    }
+    /// Fetch the Webmentions of a blog post, oldest first.
+    /// Returns an empty list if the blog post does not exist, or is hidden or in the trash.
+    /// Returns the mentions if successful, or `DatabaseError::Sql` if the query fails.
+    #[instrument(level = "info", skip(self))]
+    pub async fn fetch_webmentions(&self, post_id: BlogPostId) -> Result<Vec<Webmention>, DatabaseError> {
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || {
+            with_connection!(pool, |connection| {
+                let result = webmention::table
+                    .inner_join(blog_post)
+                    .filter(webmention::post_id.eq(post_id))
+                    .filter(deleted_at.is_null())
+                    .filter(hidden.eq(false))
+                    .order(webmention::received_at.asc())
+                    .select(Webmention::as_select())
+                    .load(&mut connection)?;
+                Ok(result)
+            })
+        })
+        .await
+        .expect("database query should never panic")
+    }
+    /// Append the entries to the audit log.
+    /// Returns `DatabaseError::Sql` if the query fails.
+    #[instrument(level = "info", skip_all, fields(count = entries.len()))]
+    pub async fn record_audit(&self, entries: Vec<InsertAuditEntry>) -> Result<(), DatabaseError> {
+        transaction!(self, |connection| {
+            for entry in &entries {
+                diesel::insert_into(audit_log::table).values(entry).execute(connection)?;
+            }
+            Ok(())
+        })
+    }
+    /// Fetch the most recent `limit` entries of the audit log which occurred in the time range, newest first,
+    /// optionally only those of one action. Either end of the range may be left open.
+    /// Returns the entries if successful, or `DatabaseError::Sql` if the query fails.
+    #[instrument(level = "info", skip(self))]
+    pub async fn fetch_audit_log(
+        &self,
+        since: Option<PrimitiveDateTime>,
+        until: Option<PrimitiveDateTime>,
+        action: Option<AuditAction>,
+        limit: i64,
+    ) -> Result<Vec<AuditEntry>, DatabaseError> {
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || {
+            with_connection!(pool, |connection| {
+                let mut query = audit_log::table.into_boxed();
+                if let Some(since) = since {
+                    query = query.filter(audit_log::occurred_at.ge(since));
+                }
+                if let Some(until) = until {
+                    query = query.filter(audit_log::occurred_at.lt(until));
+                }
+                if let Some(action) = action {
+                    query = query.filter(audit_log::action.eq(action.as_str()));
+                }
+                let rows: Vec<AuditRow> = query.order(audit_log::id.desc()).limit(limit).load(&mut connection)?;
+                let entries = rows
+                    .into_iter()
+                    .filter_map(|(entry_id, name, actor, ip, post, summary, occurred_at)| {
+                        // Entries written by a newer version of the server may have actions this version doesn't know
+                        let Some(action) = AuditAction::from_name(&name) else {
+                            warn!("Skipping audit log entry {} with unknown action {}", entry_id, name);
+                            return None;
+                        };
+                        Some(AuditEntry { id: entry_id, action, actor, ip, post_id: post, summary, occurred_at })
+                    })
+                    .collect();
+                Ok(entries)
+            })
+        })
+        .await
+        .expect("database query should never panic")
+    }
+    /// Save a new user account to the database.
+    /// Returns the saved `User` if successful, or `DatabaseError::Sql` if the query fails,
+    /// e.g. with a unique violation if the username is already taken.
+    #[instrument(level = "info", skip_all, fields(username = %to_persist.username))]
+    pub async fn create_user(&self, to_persist: InsertUser) -> Result<User, DatabaseError> {
+        debug!("Creating user: {}", to_persist.username);
+        transaction!(self, |connection| {
+            let result = diesel::insert_into(users::table)
+                .values(&to_persist)
+                .returning(User::as_returning())
+                .get_result(connection)?;
             Ok(result)
         })
+    }
+    /// Fetch a user account together with its password hash by username.
+    /// Returns `None` if no account with the username exists,
+    /// or `DatabaseError::Sql` if the query fails.
+    #[instrument(level = "info", skip(self))]
+    pub async fn fetch_credentials(
+        &self,
+        name: String,
+    ) -> Result<Option<(User, String)>, DatabaseError> {
+        debug!("Loading credentials of user: {}", name);
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || {
+            with_connection!(pool, |connection| {
+                let result = users::table
+                    .filter(users::username.eq(name))
+                    .select((User::as_select(), users::password_hash))
+                    .first(&mut connection)
+                    .optional()?;
+                Ok(result)
+            })
+        })
         .await
         .expect("database query should never panic")
     }
+    /// Fetch a user account by username.
+    /// Returns `None` if no account with the username exists,
+    /// or `DatabaseError::Sql` if the query fails.
+    #[instrument(level = "info", skip(self))]
+    pub async fn fetch_user(&self, name: String) -> Result<Option<User>, DatabaseError> {
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || {
+            with_connection!(pool, |connection| {
+                let result = users::table
+                    .filter(users::username.eq(name))
+                    .select(User::as_select())
+                    .first(&mut connection)
+                    .optional()?;
+                Ok(result)
+            })
+        })
+        .await
+        .expect("database query should never panic")
+    }
+    /// Like the blog post on behalf of the user, or remove the like if the user already liked it.
+    /// Returns the resulting `PostLikes` of the blog post as seen by the user if successful,
+    /// or `DatabaseError::Sql` if the query fails.
+    #[instrument(level = "info", skip(self))]
+    pub async fn toggle_like(
+        &self,
+        post: BlogPostId,
+        user: UserId,
+    ) -> Result<PostLikes, DatabaseError> {
+        debug!("Toggling like of user {} on blog post with id: {}", user, post);
+        transaction!(self, |connection| {
+            let removed = diesel::delete(post_like::table.find((post, user))).execute(connection)?;
+            if removed == 0 {
+                diesel::insert_into(post_like::table)
+                    .values((post_like::post_id.eq(post), post_like::user_id.eq(user)))
+                    .execute(connection)?;
+            }
+            let count = post_like::table
+                .filter(post_like::post_id.eq(post))
+                .count()
+                .get_result(connection)?;
+            Ok(PostLikes { count, liked: removed == 0 })
+        })
+    }
+}
+
+/// Connect to the database at the URL with a new connection pool, whose connections are set up by the customizer,
+/// and run the pending migrations from the provided source.
+fn connect<C>(
+    url: String,
+    migrations: EmbeddedMigrations,
+    config: PoolConfig,
+    customizer: Box<dyn CustomizeConnection<C, diesel::r2d2::Error>>,
+) -> Result<r2d2::Pool<ConnectionManager<C>>, DatabaseError>
+where
+    C: R2D2Connection + MigrationHarness<C::Backend> + 'static,
+{
+    let pool = r2d2::Pool::builder()
+        .max_size(config.max_size)
+        .min_idle(config.min_idle)
+        .connection_timeout(config.connection_timeout)
+        .connection_customizer(customizer)
+        .event_handler(Box::new(PoolMetrics))
+        .build(ConnectionManager::<C>::new(url))?;
+    let connection: &mut C = &mut pool.get()?;
+    let versions = connection.run_pending_migrations(migrations)?;
+    if !versions.is_empty() {
+        info!("Successfully updated database schema.");
+        debug!("Applied migrations: {:?}", versions);
+    }
+    Ok(pool)
+}
+
+/// Records how long queries wait for a connection from the pool, and how often they give up waiting,
+/// which shows whether the pool is too small for the load.
+#[derive(Debug)]
+struct PoolMetrics;
+
+impl HandleEvent for PoolMetrics {
+    fn handle_checkout(&self, event: CheckoutEvent) {
+        metrics::histogram!("db_pool_wait_seconds").record(event.duration().as_secs_f64());
+    }
+
+    fn handle_timeout(&self, event: TimeoutEvent) {
+        metrics::histogram!("db_pool_wait_seconds").record(event.timeout().as_secs_f64());
+        metrics::counter!("db_pool_timeouts_total").increment(1);
+    }
+}
+
+/// Sets up every SQLite connection of the pool when it is opened, since SQLite settings only last for a connection.
+/// SQLite only allows one writer at a time, so without a busy timeout concurrent writes fail with `database is locked`
+/// instead of waiting their turn.
+/// Foreign keys are always enforced, since the schema relies on them to delete the likes, tags and reports of posts.
+#[derive(Debug)]
+struct SqliteCustomizer(SqliteConfig);
+
+impl CustomizeConnection<SqliteConnection, diesel::r2d2::Error> for SqliteCustomizer {
+    fn on_acquire(&self, connection: &mut SqliteConnection) -> Result<(), diesel::r2d2::Error> {
+        let SqliteConfig { wal, busy_timeout, synchronous } = self.0;
+        // The busy timeout is set first, so that switching the journal mode waits for locks as well
+        let pragmas = format!(
+            "PRAGMA busy_timeout = {}; PRAGMA journal_mode = {}; PRAGMA synchronous = {}; PRAGMA foreign_keys = ON;",
+            busy_timeout.as_millis(),
+            if wal { "WAL" } else { "DELETE" },
+            synchronous.as_str(),
+        );
+        connection.batch_execute(&pragmas).map_err(diesel::r2d2::Error::QueryError)
+    }
+}
+
+/// Queries which are shared between several `Database` methods.
+/// These are implemented for every backend's connection type by `impl_post_queries`,
+/// since the trait bounds to write them generically over the connection are unwieldy.
+trait PostQueries {
+    /// Load the like counts of the blog posts with the provided IDs,
+    /// and whether the `viewer`, if any, liked each of them.
+    /// Posts which nobody has liked are omitted from the result.
+    fn load_likes(
+        &mut self,
+        post_ids: &[BlogPostId],
+        viewer: Option<UserId>,
+    ) -> QueryResult<HashMap<BlogPostId, PostLikes>>;
+
+    /// Load the tags of the blog posts with the provided IDs, sorted by name.
+    /// Posts without tags are omitted from the result.
+    fn load_tags(&mut self, post_ids: &[BlogPostId]) -> QueryResult<HashMap<BlogPostId, Vec<String>>>;
+
+    /// Load when the blog posts with the provided IDs were last edited.
+    /// Posts which were never edited are omitted from the result.
+    fn load_last_edits(&mut self, post_ids: &[BlogPostId]) -> QueryResult<HashMap<BlogPostId, PrimitiveDateTime>>;
+}
+
+macro_rules! impl_post_queries {
+    ($connection:ty) => {
+        impl PostQueries for $connection {
+            fn load_likes(
+                &mut self,
+                post_ids: &[BlogPostId],
+                viewer: Option<UserId>,
+            ) -> QueryResult<HashMap<BlogPostId, PostLikes>> {
+                let counts: Vec<(BlogPostId, i64)> = post_like::table
+                    .filter(post_like::post_id.eq_any(post_ids.iter().copied()))
+                    .group_by(post_like::post_id)
+                    .select((post_like::post_id, diesel::dsl::count_star()))
+                    .load(self)?;
+                let liked: Vec<BlogPostId> = match viewer {
+                    Some(viewer) => post_like::table
+                        .filter(post_like::post_id.eq_any(post_ids.iter().copied()))
+                        .filter(post_like::user_id.eq(viewer))
+                        .select(post_like::post_id)
+                        .load(self)?,
+                    None => Vec::new(),
+                };
+                let likes = counts
+                    .into_iter()
+                    .map(|(post, count)| (post, PostLikes { count, liked: liked.contains(&post) }))
+                    .collect();
+                Ok(likes)
+            }
+
+            fn load_tags(&mut self, post_ids: &[BlogPostId]) -> QueryResult<HashMap<BlogPostId, Vec<String>>> {
+                let rows: Vec<(BlogPostId, String)> = post_tag::table
+                    .inner_join(tag::table)
+                    .filter(post_tag::post_id.eq_any(post_ids.iter().copied()))
+                    .select((post_tag::post_id, tag::name))
+                    .order(tag::name.asc())
+                    .load(self)?;
+                let mut tags: HashMap<BlogPostId, Vec<String>> = HashMap::new();
+                for (post, name) in rows {
+                    tags.entry(post).or_default().push(name);
+                }
+                Ok(tags)
+            }
+
+            fn load_last_edits(&mut self, post_ids: &[BlogPostId]) -> QueryResult<HashMap<BlogPostId, PrimitiveDateTime>> {
+                let rows: Vec<(BlogPostId, Option<PrimitiveDateTime>)> = blog_post_revision::table
+                    .filter(blog_post_revision::post_id.eq_any(post_ids.iter().copied()))
+                    .group_by(blog_post_revision::post_id)
+                    .select((blog_post_revision::post_id, diesel::dsl::max(blog_post_revision::edited_at)))
+                    .load(self)?;
+                let last_edits = rows
+                    .into_iter()
+                    .filter_map(|(post, edited_at)| Some((post, edited_at?)))
+                    .collect();
+                Ok(last_edits)
+            }
+        }
+    };
+}
+
+impl_post_queries!(SqliteConnection);
+#[cfg(feature = "postgres")]
+impl_post_queries!(PgConnection);
+
+/// A row of the full-text search query.
+#[derive(QueryableByName)]
+struct SearchRow {
+    #[diesel(embed)]
+    post: BlogPost,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    highlighted_text: String,
+}
+
+/// Returns the current time in UTC, in the form in which timestamps are stored in the database.
+fn now_utc() -> PrimitiveDateTime {
+    let now = OffsetDateTime::now_utc();
+    PrimitiveDateTime::new(now.date(), now.time())
+}
+
+/// Convert user input into an SQLite FTS5 query which matches posts containing all of its words.
+/// Every word is quoted so that FTS5 operators and syntax in the input are matched literally.
+fn fts_query(input: &str) -> String {
+    input
+        .split_whitespace()
+        .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::config::SqliteSynchronous;
+    use std::time::Instant;
+
+    /// How many blog posts the benchmark seeds, spread over `AUTHORS` authors with `POSTS_PER_DAY` posts a day.
+    const SEEDED_POSTS: i64 = 100_000;
+    const AUTHORS: i64 = 1_000;
+    const POSTS_PER_DAY: i64 = 100;
+
+    /// How many times each query is run, of which the average is reported.
+    const RUNS: u32 = 20;
+
+    /// The migration which indexes the feeds, whose indexes the benchmark drops by name and creates again,
+    /// so that it does not depend on which migrations come after it.
+    const DROP_FEED_INDEXES: &str =
+        include_str!("../../../migrations/sqlite/2026-10-16-000021_index_blog_post_feeds/down.sql");
+    const CREATE_FEED_INDEXES: &str =
+        include_str!("../../../migrations/sqlite/2026-10-16-000021_index_blog_post_feeds/up.sql");
+
+    /// Connect to a fresh in-memory SQLite database.
+    /// Every connection to `:memory:` opens a database of its own, so the pool holds a single connection.
+    async fn in_memory_database() -> Database {
+        let pool = PoolConfig { max_size: 1, min_idle: Some(1), connection_timeout: Duration::from_secs(5) };
+        let retry = RetryConfig { retries: 0, backoff: Duration::ZERO };
+        let sqlite =
+            SqliteConfig { wal: false, busy_timeout: Duration::from_secs(5), synchronous: SqliteSynchronous::Off };
+        Database::try_connect(":memory:", pool, retry, sqlite).await.expect("in-memory database should open")
+    }
+
+    /// Run the statements on the single connection of the in-memory database.
+    async fn execute(database: &Database, work: impl FnOnce(&mut SqliteConnection) + Send + 'static) {
+        let ConnectionPool::Sqlite(pool) = database.pool.clone() else {
+            unreachable!("in-memory databases are SQLite databases");
+        };
+        tokio::task::spawn_blocking(move || work(&mut pool.get().unwrap())).await.unwrap();
+    }
+
+    /// The average time it takes to fetch the first page of the feed with the filter.
+    async fn time_feed(database: &Database, filter: &PostFilter) -> Duration {
+        let start = Instant::now();
+        for _ in 0..RUNS {
+            database.fetch_page(None, 10, filter.clone(), None).await.unwrap();
+        }
+        start.elapsed() / RUNS
+    }
+
+    /// Seeds 100k blog posts and times the feed queries without and with the indexes of the migration which
+    /// indexes the feeds, which the filters by author and by day should need for a fraction of the time.
+    /// It takes a while, so run it explicitly: `cargo test --features server --release -- --ignored --nocapture feed`
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore]
+    async fn feed_indexes_speed_up_filtered_feeds() {
+        let database = in_memory_database().await;
+        execute(&database, |connection| {
+            connection.batch_execute(DROP_FEED_INDEXES).unwrap();
+            diesel::sql_query(format!(
+                "WITH RECURSIVE seq(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM seq WHERE i < {SEEDED_POSTS}) \
+                 INSERT INTO blog_post (posted_on, username, text, deleted_at) \
+                 SELECT date('2024-01-01', '+' || (i / {POSTS_PER_DAY}) || ' days'), 'user' || (i % {AUTHORS}), \
+                        'Blog post number ' || i, \
+                        CASE WHEN i % 100 = 0 THEN datetime('2024-01-01', '+' || i || ' minutes') END \
+                 FROM seq"
+            ))
+            .execute(connection)
+            .unwrap();
+            diesel::sql_query("ANALYZE").execute(connection).unwrap();
+        })
+        .await;
+
+        let day = time::macros::date!(2025 - 06 - 01);
+        let filters = [
+            ("newest first", PostFilter::default()),
+            ("by author", PostFilter { author: Some("user42".to_string()), ..PostFilter::default() }),
+            ("by day", PostFilter { posted_from: Some(day), posted_until: Some(day), ..PostFilter::default() }),
+            (
+                "by author and day",
+                PostFilter {
+                    author: Some("user42".to_string()),
+                    posted_from: Some(day - time::Duration::days(30)),
+                    posted_until: Some(day),
+                    ..PostFilter::default()
+                },
+            ),
+        ];
+        let mut before = Vec::new();
+        for (_, filter) in &filters {
+            before.push(time_feed(&database, filter).await);
+        }
+
+        execute(&database, |connection| {
+            connection.batch_execute(CREATE_FEED_INDEXES).unwrap();
+            diesel::sql_query("ANALYZE").execute(connection).unwrap();
+        })
+        .await;
+        let mut after = Vec::new();
+        for (_, filter) in &filters {
+            after.push(time_feed(&database, filter).await);
+        }
+
+        println!("Feed query with {SEEDED_POSTS} posts, average of {RUNS} runs:");
+        for (((name, _), before), after) in filters.iter().zip(&before).zip(&after) {
+            println!("{name:>20}: {before:>12?} without the indexes, {after:>12?} with them");
+        }
+        // The filters which the indexes are for should no longer scan the whole table
+        for (index, (name, _)) in filters.iter().enumerate().skip(1) {
+            assert!(after[index] < before[index], "the feed {} did not get faster", name);
+        }
+    }
 }
@@ -1,5 +1,37 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    api_key (id) {
+        id -> Integer,
+        user_id -> Integer,
+        name -> Text,
+        key_hash -> Text,
+        prefix -> Text,
+        created_at -> Timestamp,
+        last_used_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    audit_log (id) {
+        id -> Integer,
+        action -> Text,
+        actor -> Nullable<Text>,
+        ip -> Nullable<Text>,
+        post_id -> Nullable<Integer>,
+        summary -> Text,
+        occurred_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    avatar_cache (url_hash) {
+        url_hash -> Text,
+        avatar_uuid -> Text,
+        cached_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     blog_post (id) {
         id -> Integer,
@@ -8,5 +40,139 @@ diesel::table! {
         text -> Text,
         image_uuid -> Nullable<Text>,
         avatar_uuid -> Nullable<Text>,
+        user_id -> Nullable<Integer>,
+        deleted_at -> Nullable<Timestamp>,
+        hidden -> Bool,
+        image_alt -> Nullable<Text>,
+        slug -> Nullable<Text>,
+        posted_at -> Nullable<Timestamp>,
+        image_width -> Nullable<Integer>,
+        image_height -> Nullable<Integer>,
+        visibility_changed_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    blog_post_revision (id) {
+        id -> Integer,
+        post_id -> Integer,
+        text -> Text,
+        edited_at -> Timestamp,
     }
 }
+
+diesel::table! {
+    follower (id) {
+        id -> Integer,
+        actor_id -> Text,
+        inbox -> Text,
+        followed_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    post_like (post_id, user_id) {
+        post_id -> Integer,
+        user_id -> Integer,
+    }
+}
+
+diesel::table! {
+    post_tag (post_id, tag_id) {
+        post_id -> Integer,
+        tag_id -> Integer,
+    }
+}
+
+diesel::table! {
+    report (id) {
+        id -> Integer,
+        post_id -> Integer,
+        reporter_id -> Integer,
+        reason -> Text,
+        reported_at -> Timestamp,
+        resolution -> Nullable<Text>,
+        resolved_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    tag (id) {
+        id -> Integer,
+        name -> Text,
+    }
+}
+
+diesel::table! {
+    user_erasure (id) {
+        id -> Integer,
+        username -> Text,
+        erased_by -> Text,
+        erased_at -> Timestamp,
+        posts -> Integer,
+    }
+}
+
+diesel::table! {
+    users (id) {
+        id -> Integer,
+        username -> Text,
+        password_hash -> Text,
+        created_on -> Date,
+    }
+}
+
+diesel::table! {
+    webhook_delivery (id) {
+        id -> Integer,
+        webhook_url -> Text,
+        event -> Text,
+        post_id -> Integer,
+        payload -> Text,
+        status -> Text,
+        attempts -> Integer,
+        next_attempt_at -> Nullable<Timestamp>,
+        response_status -> Nullable<Integer>,
+        last_error -> Nullable<Text>,
+        created_at -> Timestamp,
+        delivered_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    webmention (id) {
+        id -> Integer,
+        post_id -> Integer,
+        source -> Text,
+        title -> Nullable<Text>,
+        received_at -> Timestamp,
+    }
+}
+
+diesel::joinable!(api_key -> users (user_id));
+diesel::joinable!(blog_post -> users (user_id));
+diesel::joinable!(blog_post_revision -> blog_post (post_id));
+diesel::joinable!(post_like -> blog_post (post_id));
+diesel::joinable!(post_like -> users (user_id));
+diesel::joinable!(post_tag -> blog_post (post_id));
+diesel::joinable!(post_tag -> tag (tag_id));
+diesel::joinable!(report -> blog_post (post_id));
+diesel::joinable!(report -> users (reporter_id));
+diesel::joinable!(webmention -> blog_post (post_id));
+
+diesel::allow_tables_to_appear_in_same_query!(
+    api_key,
+    audit_log,
+    avatar_cache,
+    blog_post,
+    blog_post_revision,
+    follower,
+    post_like,
+    post_tag,
+    report,
+    tag,
+    user_erasure,
+    users,
+    webhook_delivery,
+    webmention,
+);
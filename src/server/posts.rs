@@ -0,0 +1,215 @@
+//! Operations on blog posts, shared by the server functions and the REST API.
+//! The caller authenticates the user and enforces the rate limit, and decides how errors are sent to the client.
+
+use crate::error::AppError;
+use crate::server::auth::Admins;
+use crate::model::{
+    AuditAction, BlogPost, BlogPostDetail, BlogPostId, BlogPostPage, CreateBlogPostParams, InsertBlogPost, PostEvent,
+    PostSubmission, User, UserId, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE,
+};
+use crate::server::config::FilterAction;
+use crate::server::images::{self, AvatarSource};
+use crate::server::persistence::database::PostFilter;
+use crate::server::uploads;
+use crate::server::webhooks::WebhookEvent;
+use crate::server::{rest, ServerState};
+use crate::validation::{Field, Validate, ValidationErrors};
+use std::net::IpAddr;
+use tracing::debug;
+
+/// Fetch a page of the blog posts matching the filter as seen by the viewer, if any.
+/// The `limit` is clamped between 1 and `MAX_PAGE_SIZE`.
+/// The first page of the unfiltered feed as seen by anonymous visitors is shared through the feed cache.
+pub async fn fetch_page(
+    state: &ServerState,
+    cursor: Option<BlogPostId>,
+    limit: i64,
+    filter: PostFilter,
+    viewer: Option<UserId>,
+) -> Result<BlogPostPage, AppError> {
+    let cacheable =
+        viewer.is_none() && cursor.is_none() && limit == DEFAULT_PAGE_SIZE && filter == PostFilter::default();
+    if cacheable {
+        if let Some(page) = state.feed_cache.first_page().await {
+            return Ok(page);
+        }
+    }
+    let page = state.database.fetch_page(cursor, limit.clamp(1, MAX_PAGE_SIZE), filter, viewer).await?;
+    if cacheable {
+        state.feed_cache.put_first_page(&page).await;
+    }
+    Ok(page)
+}
+
+/// Fetch a single blog post as seen by the viewer, if any.
+/// Returns `None` if the blog post does not exist, or is hidden or in the trash.
+/// Anonymous visitors all see the same blog post, which is shared through the feed cache.
+pub async fn fetch_post(
+    state: &ServerState,
+    post_id: BlogPostId,
+    viewer: Option<UserId>,
+) -> Result<Option<BlogPostDetail>, AppError> {
+    if viewer.is_none() {
+        if let Some(detail) = state.feed_cache.post(post_id).await {
+            return Ok(Some(detail));
+        }
+    }
+    let detail = state.database.fetch_detail(post_id, viewer).await?;
+    if let (None, Some(detail)) = (viewer, &detail) {
+        state.feed_cache.put_post(detail).await;
+    }
+    Ok(detail)
+}
+
+/// Create a blog post by the author, who sent it from the IP address.
+/// The text is checked against the content filter, and the uploaded image and avatar are processed and stored.
+/// Returns whether the blog post was published, or why the content filter queued or rejected it.
+pub async fn create(
+    state: &ServerState,
+    author: User,
+    ip: IpAddr,
+    params: CreateBlogPostParams,
+) -> Result<PostSubmission, AppError> {
+    debug!("Creating blog post");
+    params.validate()?;
+    let policy = state.image_policy;
+    // Blank descriptions are as good as none
+    let image_alt = params.image_alt.map(|alt| alt.trim().to_string()).filter(|alt| !alt.is_empty());
+    if policy.require_alt_text && params.image_upload.is_some() && image_alt.is_none() {
+        let errors = ValidationErrors::of(Field::ImageAlt, "Please describe the image for people who cannot see it");
+        return Err(errors.into());
+    }
+    state.challenges.verify(&params.challenge)?;
+    let database = &state.database;
+    let storage = &state.images;
+    let filter = &state.content_filter;
+    filter
+        .check_length(&params.text)
+        .map_err(|violation| ValidationErrors::of(Field::Text, violation.to_string()))?;
+    // Filter the text before processing images, so that rejected posts leave no images behind
+    let violation = filter.check(&params.text, &author.username).await;
+    if let Some(violation) = &violation {
+        if filter.action() == FilterAction::Reject {
+            return Ok(PostSubmission::Rejected(violation.clone()));
+        }
+    }
+    // Save images to the image store and get their UUIDs
+    debug!("Processing images");
+    let uploads = &state.uploads;
+    let image_path = params.image_upload.map(|token| uploads.claim(&token, author.id)).transpose();
+    let avatar_path = params.avatar_upload.map(|token| uploads.claim(&token, author.id)).transpose();
+    // A claimed upload can't be claimed again, so it must be deleted if the other one is invalid
+    let (image_path, avatar_path) = match (image_path, avatar_path) {
+        (Ok(image_path), Ok(avatar_path)) => (image_path, avatar_path),
+        (Ok(Some(path)), Err(err)) | (Err(err), Ok(Some(path))) => {
+            uploads::discard(&path).await;
+            return Err(err.into());
+        }
+        (Err(err), _) | (_, Err(err)) => return Err(err.into()),
+    };
+    let avatar = match (params.avatar_url, &avatar_path) {
+        (_, Some(path)) => Some(AvatarSource::Upload { path: path.clone(), crop: params.avatar_crop }),
+        (Some(url), None) => Some(AvatarSource::Url(url)),
+        (None, None) => None,
+    };
+    let client = &state.http_client;
+    let avatar_cache = &state.avatar_cache;
+    let processed = images::process_images(&**storage, database, client, avatar_cache, policy, image_path.clone(), avatar).await;
+    // The uploads are no longer needed once they are processed, whether that succeeded or not
+    for path in image_path.iter().chain(&avatar_path) {
+        uploads::discard(path).await;
+    }
+    let (image, mut avatar_uuid, reservation) = processed?;
+    if avatar_uuid.is_none() {
+        avatar_uuid = images::process_fallback_avatar(&**storage, client, avatar_cache, policy, &author.username).await;
+    }
+    debug!("Images processed: image: {image:?}, avatar: {avatar_uuid:?}");
+    let image_uuid = image.as_ref().map(|image| image.path.clone());
+    // Insert the blog post into the database
+    let mut to_persist =
+        InsertBlogPost::new(params.text, author.clone(), image_uuid.clone(), image_alt, avatar_uuid.clone());
+    // Clients reserve space for the image with its size before it loads
+    to_persist.image_width = image.as_ref().map(|image| image.width as i32);
+    to_persist.image_height = image.as_ref().map(|image| image.height as i32);
+    // Posts which failed the filter are queued by hiding them until an administrator unhides them
+    to_persist.hidden = violation.is_some();
+    let post = match database.save(to_persist, params.tags.clone()).await {
+        Ok(post) => post,
+        Err(err) => {
+            // The images were saved for this blog post, so they are orphaned unless other blog posts share them
+            drop(reservation);
+            images::delete_unreferenced(&**storage, database, image_uuid, avatar_uuid).await;
+            return Err(err.into());
+        }
+    };
+    // The blog post references its images now, so they can no longer be deleted as unreferenced
+    drop(reservation);
+    let summary = match &violation {
+        Some(violation) => format!("Queued for review by the content filter: {violation}"),
+        None => "Published".to_string(),
+    };
+    state.audit.record(&author, ip, AuditAction::Create, &[post.id], &summary).await;
+    match violation {
+        Some(violation) => Ok(PostSubmission::Queued(violation)),
+        None => {
+            state.feed_cache.invalidate(&[post.id]).await;
+            let published = rest::Post::new(post.clone(), params.tags.clone(), 0, None);
+            state.federation.publish(&published);
+            state.webmentions.send(&published);
+            state.webhooks.publish(WebhookEvent::Created { post: published });
+            state.events.publish(PostEvent::Created { post: post.clone(), tags: params.tags });
+            Ok(PostSubmission::Published(post))
+        }
+    }
+}
+
+/// Delete a blog post of the user, who asked for it from the IP address, by moving it to the trash.
+/// The blog post can be restored until it is purged from the trash after the retention window.
+pub async fn delete(state: &ServerState, user: &User, ip: IpAddr, post_id: BlogPostId) -> Result<(), AppError> {
+    authorize_owner(state.database.fetch(post_id).await?, user, &state.admins)?;
+    state.database.trash(post_id).await?;
+    state.audit.record(user, ip, AuditAction::Delete, &[post_id], "Moved to the trash").await;
+    state.feed_cache.invalidate(&[post_id]).await;
+    state.webhooks.publish(WebhookEvent::Deleted { post_id });
+    state.events.publish(PostEvent::Deleted(post_id));
+    Ok(())
+}
+
+/// Delete the blog posts with the IDs which the user may delete, who asked for it from the IP address,
+/// by moving them to the trash at once. The other blog posts are skipped.
+/// Like fetching blog posts by ID, this is limited to `MAX_PAGE_SIZE` blog posts, so that a single request
+/// cannot hold the database in one huge statement; more are rejected with `AppError::Validation`.
+/// Returns the IDs of the deleted blog posts.
+pub async fn delete_many(
+    state: &ServerState,
+    user: &User,
+    ip: IpAddr,
+    post_ids: Vec<BlogPostId>,
+) -> Result<Vec<BlogPostId>, AppError> {
+    if post_ids.len() > MAX_PAGE_SIZE as usize {
+        let message = format!("At most {MAX_PAGE_SIZE} blog posts can be moved to the trash at once");
+        return Err(ValidationErrors::of(Field::Posts, message).into());
+    }
+    let deleted = state.database.trash_many(post_ids, user.id).await?;
+    if deleted.is_empty() {
+        return Ok(deleted);
+    }
+    state.feed_cache.invalidate(&deleted).await;
+    state.audit.record(user, ip, AuditAction::Delete, &deleted, "Moved to the trash").await;
+    for &post_id in &deleted {
+        state.webhooks.publish(WebhookEvent::Deleted { post_id });
+        state.events.publish(PostEvent::Deleted(post_id));
+    }
+    Ok(deleted)
+}
+
+/// Ensure that the user may edit, delete and restore the fetched blog post.
+/// Returns an error if the blog post does not exist or was written by someone else,
+/// or if it was written before accounts existed and the user is no administrator.
+pub fn authorize_owner(post: Option<BlogPost>, user: &User, admins: &Admins) -> Result<(), AppError> {
+    let post = post.ok_or(AppError::PostNotFound)?;
+    if !post.is_editable_by(user, admins.contains(user)) {
+        return Err(AppError::NotPostOwner);
+    }
+    Ok(())
+}
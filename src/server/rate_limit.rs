@@ -0,0 +1,52 @@
+//! Per-IP rate limiting for the server functions which change data.
+
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Once this many IP addresses are tracked, the addresses without recent requests are forgotten.
+const PRUNE_THRESHOLD: usize = 1024;
+
+/// A sliding window rate limiter which allows at most `max_requests` requests
+/// per IP address within any period of length `window`.
+/// Clones share the same state.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    max_requests: usize,
+    window: Duration,
+    requests: Arc<Mutex<HashMap<IpAddr, VecDeque<Instant>>>>,
+}
+
+impl RateLimiter {
+    pub fn new(max_requests: usize, window: Duration) -> Self {
+        Self {
+            max_requests,
+            window,
+            requests: Arc::default(),
+        }
+    }
+
+    /// Record a request from the IP address if it is within the limit.
+    /// Returns the time until the next request from the IP address is allowed if it is not,
+    /// in which case the request is not recorded.
+    pub fn check(&self, ip: IpAddr) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut requests = self.requests.lock().expect("rate limiter lock should not be poisoned");
+        if requests.len() >= PRUNE_THRESHOLD {
+            requests.retain(|_, times| times.back().is_some_and(|last| now - *last < self.window));
+        }
+        let times = requests.entry(ip).or_default();
+        // Forget the requests which have left the window
+        while times.front().is_some_and(|first| now - *first >= self.window) {
+            times.pop_front();
+        }
+        if times.len() >= self.max_requests {
+            // The window is full, so the oldest request in it exists
+            let oldest = times.front().expect("full window should not be empty");
+            return Err(self.window - (now - *oldest));
+        }
+        times.push_back(now);
+        Ok(())
+    }
+}
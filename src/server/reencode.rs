@@ -0,0 +1,40 @@
+//! Re-encoding of stored images into the configured format, run with the `--reencode-images` command line flag.
+//! Images are stored in the format which was configured when they were saved,
+//! so changing `image_format` only affects new images until the existing ones are re-encoded.
+
+use crate::server::images::{self, ImagePath, ImagePolicy};
+use crate::server::persistence::database::DatabaseError;
+use crate::server::storage::ImageStorage;
+use crate::server::Database;
+use tracing::{info, instrument, warn};
+
+/// Re-encode every image referenced by a blog post which is not stored in the configured format yet.
+/// Images which fail to be re-encoded are skipped, so running this again retries them.
+/// Returns the number of re-encoded images, or an error if the referenced images cannot be listed.
+#[instrument(level = "info", skip_all)]
+pub async fn reencode_images(
+    database: &Database,
+    images: &ImageStorage,
+    policy: ImagePolicy,
+) -> Result<usize, DatabaseError> {
+    let (post_images, avatars) = database.referenced_images().await?;
+    info!("Re-encoding {} post images and {} avatars as {:?}", post_images.len(), avatars.len(), policy.format());
+    let mut reencoded = 0;
+    for image in &post_images {
+        reencoded += usize::from(reencode(images, policy, image).await);
+    }
+    for avatar in &avatars {
+        reencoded += usize::from(reencode(images, policy, avatar).await);
+    }
+    info!("Re-encoded {} images", reencoded);
+    Ok(reencoded)
+}
+
+/// Re-encode the image, logging rather than returning failures.
+/// Returns whether the image was re-encoded.
+async fn reencode<I: ImagePath>(images: &ImageStorage, policy: ImagePolicy, image: &I) -> bool {
+    images::reencode(&**images, policy, image)
+        .await
+        .inspect_err(|err| warn!("Failed to re-encode image {:?}: {}", image, err))
+        .unwrap_or(false)
+}
@@ -0,0 +1,679 @@
+//! Version 1 of the REST API under `/api/v1`, for third-party tools which cannot call the server functions.
+//! Requests and responses are JSON, except for uploads, which are multipart forms like those of `/uploads`.
+//! Users log in by creating a session, whose cookie authenticates the requests which change data,
+//! while bots and command-line tools send an API key as `Authorization: Bearer <key>` instead, see `server::api_keys`.
+//! The request and response types are defined here rather than reused from the model, so that they only change
+//! when the API does: fields may be added to responses, but never renamed or removed, which requires `/api/v2`.
+//! The handlers share their operations with the server functions, see `server::posts`.
+//! Administrators can also download an archive of the whole blog from `/api/v1/export` and import one with
+//! `/api/v1/import`, see `server::archive`. Users can download all their data from `/api/v1/users/{username}/export`.
+//! The OpenAPI document of the API is derived from the handlers and types, and served at `/api/v1/openapi.json`
+//! along with Swagger UI at `/api/v1/docs`.
+
+use crate::error::AppError;
+use crate::model::{
+    parse_tags, AuditAction, AvatarCrop, BlogPost, BlogPostDetail, BlogPostId, BlogPostPage, Challenge,
+    ChallengeSolution, CreateBlogPostParams, Credentials, PostSubmission, User, DEFAULT_PAGE_SIZE,
+};
+use crate::server::client_ip::ClientIp;
+use crate::server::persistence::database::PostFilter;
+use crate::server::rate_limit::RateLimiter;
+use crate::server::uploads::UploadError;
+use crate::server::{api_keys, archive, auth, posts, CurrentUser, Database, ServerState};
+use crate::validation::{Field, ValidationErrors};
+use axum::async_trait;
+use axum::body::{Body, Bytes};
+use axum::extract::{FromRequestParts, Multipart, Path, Query, Request};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use http::header::{AUTHORIZATION, CONTENT_DISPOSITION, CONTENT_TYPE, RETRY_AFTER};
+use http::request::Parts;
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use time::format_description::well_known::Rfc3339;
+use time::{OffsetDateTime, PrimitiveDateTime};
+use tower_sessions::Session;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, Http, HttpAuthScheme, SecurityScheme};
+use utoipa::{IntoParams, Modify, OpenApi, ToSchema};
+use utoipa_swagger_ui::SwaggerUi;
+
+/// The name of the session cookie, which is the default of `tower_sessions`.
+const SESSION_COOKIE: &str = "id";
+
+/// Create the router for the REST API under `/api/v1`, including its OpenAPI document and Swagger UI.
+pub fn router() -> Router {
+    let api = Router::new()
+        .route("/posts", get(list_posts).post(create_post))
+        .route("/posts/:id", get(get_post).delete(delete_post))
+        .route("/uploads", post(upload_image))
+        .route("/challenge", get(fetch_challenge))
+        .route("/session", post(log_in).delete(log_out))
+        .route("/export", get(export_archive))
+        .route("/import", post(import_archive))
+        .route("/users/:username/export", get(export_user_data))
+        .layer(axum::middleware::from_fn(authenticate_api_key));
+    Router::new()
+        .nest("/api/v1", api)
+        .merge(SwaggerUi::new("/api/v1/docs").url("/api/v1/openapi.json", ApiDoc::openapi()))
+}
+
+/// The OpenAPI document of the API.
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Blog posts API", version = "1.0.0"),
+    paths(
+        list_posts, get_post, create_post, delete_post, upload_image, fetch_challenge, log_in, log_out, export_archive,
+        import_archive, export_user_data,
+    ),
+    components(schemas(
+        Post, Image, PostList, CreatePost, Submission, Upload, UploadForm, Account, ErrorBody, ErrorDetail,
+        AvatarCrop, Challenge, ChallengeSolution, Credentials, ImportSummary, SkippedPost,
+    )),
+    modifiers(&SecuritySchemes),
+)]
+struct ApiDoc;
+
+/// Declares the session cookie and the API keys, either of which authenticates the requests which change data.
+struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            let session = SecurityScheme::ApiKey(ApiKey::Cookie(ApiKeyValue::new(SESSION_COOKIE)));
+            components.add_security_scheme("session", session);
+            components.add_security_scheme("api_key", SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)));
+        }
+    }
+}
+
+/// A blog post as returned by the API.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Post {
+    #[schema(value_type = i32)]
+    pub id: BlogPostId,
+    /// The username of the author.
+    pub author: String,
+    /// The text of the blog post, in Markdown.
+    pub text: String,
+    /// The date on which the blog post was published, e.g. `2024-10-16`.
+    pub posted_on: String,
+    /// The time at which the blog post was published in RFC 3339 format, if it is known.
+    pub posted_at: Option<String>,
+    /// The time at which the blog post was last edited in RFC 3339 format, if it was edited.
+    pub last_edited: Option<String>,
+    /// The path of the page of the blog post.
+    pub path: String,
+    pub image: Option<Image>,
+    /// The path of the avatar of the author, if any.
+    pub avatar_url: Option<String>,
+    pub tags: Vec<String>,
+    /// The number of users who liked the blog post.
+    pub likes: i64,
+}
+
+/// The image of a blog post.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Image {
+    /// The path of the image.
+    pub url: String,
+    /// The path of the downscaled thumbnail of the image.
+    pub thumbnail_url: String,
+    /// The description of the image for people who cannot see it, if the author gave one.
+    pub alt: Option<String>,
+    /// The width in pixels of the image, if it is known.
+    pub width: Option<u32>,
+    /// The height in pixels of the image, if it is known.
+    pub height: Option<u32>,
+}
+
+impl Post {
+    pub(crate) fn new(post: BlogPost, tags: Vec<String>, likes: i64, last_edited: Option<PrimitiveDateTime>) -> Self {
+        let size = post.image_size();
+        let image = post.image_uuid.as_ref().map(|image| Image {
+            url: image.url(),
+            thumbnail_url: image.thumbnail_url(),
+            alt: post.image_alt.clone(),
+            width: size.map(|(width, _)| width),
+            height: size.map(|(_, height)| height),
+        });
+        Self {
+            id: post.id,
+            path: post.path(),
+            posted_on: post.posted_on.to_string(),
+            posted_at: post.posted_at.and_then(rfc3339),
+            last_edited: last_edited.and_then(rfc3339),
+            avatar_url: post.avatar_uuid.as_ref().map(|avatar| avatar.url()),
+            author: post.username,
+            text: post.text,
+            image,
+            tags,
+            likes,
+        }
+    }
+}
+
+impl From<BlogPostDetail> for Post {
+    fn from(detail: BlogPostDetail) -> Self {
+        Post::new(detail.post, detail.tags, detail.likes.count, detail.last_edited)
+    }
+}
+
+/// Format the time, which is in UTC, in RFC 3339 format.
+fn rfc3339(time: PrimitiveDateTime) -> Option<String> {
+    time.assume_utc().format(&Rfc3339).ok()
+}
+
+/// A page of blog posts, newest first.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PostList {
+    pub posts: Vec<Post>,
+    /// The cursor to pass to fetch the next page, or `None` if this is the last page.
+    #[schema(value_type = Option<i32>)]
+    pub next_cursor: Option<BlogPostId>,
+}
+
+impl From<BlogPostPage> for PostList {
+    fn from(mut page: BlogPostPage) -> Self {
+        let posts = page
+            .posts
+            .into_iter()
+            .map(|post| {
+                let tags = page.tags.remove(&post.id).unwrap_or_default();
+                let likes = page.likes.get(&post.id).map_or(0, |likes| likes.count);
+                let last_edited = page.last_edited.get(&post.id).copied();
+                Post::new(post, tags, likes, last_edited)
+            })
+            .collect();
+        Self { posts, next_cursor: page.next_cursor }
+    }
+}
+
+/// The query of `GET /posts`.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListQuery {
+    /// The `next_cursor` of the previous page, or none for the first page.
+    #[param(value_type = Option<i32>)]
+    pub cursor: Option<BlogPostId>,
+    /// The number of blog posts per page, which is clamped between 1 and `MAX_PAGE_SIZE`.
+    pub limit: Option<i64>,
+    /// Only list blog posts with this tag.
+    pub tag: Option<String>,
+    /// Only list blog posts by the author with this username.
+    pub author: Option<String>,
+}
+
+/// The body of `POST /posts`.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreatePost {
+    pub text: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// The token of an image uploaded to `/api/v1/uploads`.
+    pub image_upload: Option<String>,
+    pub image_alt: Option<String>,
+    /// The URL of the avatar of the author, as an alternative to `avatar_upload`.
+    pub avatar_url: Option<String>,
+    /// The token of an avatar uploaded to `/api/v1/uploads`.
+    pub avatar_upload: Option<String>,
+    pub avatar_crop: Option<AvatarCrop>,
+    /// The solution of a challenge fetched from `/api/v1/challenge`.
+    pub challenge: ChallengeSolution,
+}
+
+impl From<CreatePost> for CreateBlogPostParams {
+    fn from(request: CreatePost) -> Self {
+        Self {
+            text: request.text,
+            image_upload: request.image_upload,
+            image_alt: request.image_alt,
+            avatar_url: request.avatar_url,
+            avatar_upload: request.avatar_upload,
+            avatar_crop: request.avatar_crop,
+            tags: parse_tags(&request.tags.join(",")),
+            challenge: request.challenge,
+        }
+    }
+}
+
+/// The response of `POST /posts` if the blog post was saved.
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum Submission {
+    /// The blog post is visible to everyone.
+    Published { post: Post },
+    /// The blog post was flagged by the content filter and is hidden until an administrator unhides it.
+    Queued { reason: String },
+}
+
+/// The response of `POST /uploads`.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Upload {
+    /// The token to pass as `image_upload` or `avatar_upload` when creating a blog post.
+    pub token: String,
+}
+
+/// The multipart form of `POST /uploads`, which is only read field by field and exists for the OpenAPI document.
+#[derive(ToSchema)]
+#[allow(dead_code)]
+struct UploadForm {
+    /// The image file.
+    #[schema(format = Binary)]
+    image: String,
+}
+
+/// The logged-in user, as returned by `POST /session`.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Account {
+    pub id: i32,
+    pub username: String,
+}
+
+/// The response of `POST /import`.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ImportSummary {
+    /// The IDs of the imported blog posts, in the order of the export.
+    #[schema(value_type = Vec<i32>)]
+    pub imported: Vec<BlogPostId>,
+    /// The blog posts which were not imported.
+    pub skipped: Vec<SkippedPost>,
+}
+
+/// A blog post which was not imported.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SkippedPost {
+    /// The position of the blog post in the export, starting at 0.
+    pub entry: usize,
+    /// Why the blog post was not imported, e.g. because it was already imported or failed validation.
+    pub reason: String,
+}
+
+impl From<archive::ImportReport> for ImportSummary {
+    fn from(report: archive::ImportReport) -> Self {
+        Self {
+            imported: report.imported,
+            skipped: report.skipped.into_iter().map(|(entry, reason)| SkippedPost { entry, reason }).collect(),
+        }
+    }
+}
+
+/// The body of error responses: `{"error": {"code": ..., "message": ..., "fields": ...}}`.
+#[derive(Debug, Serialize, ToSchema)]
+struct ErrorBody {
+    error: ErrorDetail,
+}
+
+/// The details of an error.
+#[derive(Debug, Serialize, ToSchema)]
+struct ErrorDetail {
+    /// The stable code of the error, see `AppError::code`.
+    code: &'static str,
+    message: String,
+    /// The errors of the offending fields, by field name, if the request failed validation.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    fields: BTreeMap<&'static str, String>,
+}
+
+/// An error of the REST API, which is sent as JSON with the status and code of the `AppError`.
+#[derive(Debug)]
+pub struct ApiError(AppError);
+
+impl From<AppError> for ApiError {
+    fn from(err: AppError) -> Self {
+        Self(err)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let fields = match &self.0 {
+            AppError::Validation(errors) => {
+                errors.iter().map(|(field, message)| (field.name(), message.to_string())).collect()
+            }
+            _ => BTreeMap::new(),
+        };
+        let body = ErrorBody { error: ErrorDetail { code: self.0.code(), message: self.0.to_string(), fields } };
+        let mut response = (self.0.status(), Json(body)).into_response();
+        if let AppError::RateLimited { retry_after } = self.0 {
+            response.headers_mut().insert(RETRY_AFTER, retry_after.into());
+        }
+        response
+    }
+}
+
+/// The user authenticated by the API key of the request, which `authenticate_api_key` stores in its extensions.
+#[derive(Debug, Clone)]
+struct ApiKeyUser(User);
+
+/// Middleware which authenticates requests with an `Authorization: Bearer <key>` header by their API key.
+/// Requests with an unknown or revoked key are rejected with `401 Unauthorized` rather than falling back to the session,
+/// so that tools notice right away when their key stops working.
+/// Requests without a key are passed through, to be authenticated by their session, if any.
+async fn authenticate_api_key(database: Database, mut request: Request, next: Next) -> Result<Response, ApiError> {
+    let key = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|key| key.trim().to_string());
+    if let Some(key) = key {
+        let user = api_keys::authenticate(&database, &key)
+            .await
+            .map_err(AppError::from)?
+            .ok_or(AppError::InvalidApiKey)?;
+        request.extensions_mut().insert(ApiKeyUser(user));
+    }
+    Ok(next.run(request).await)
+}
+
+/// The user authenticated by the API key of the request, or else the logged-in user.
+/// Extracting this fails with `401 Unauthorized` and a JSON error if neither is present.
+struct ApiUser(User);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ApiUser {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, ApiError> {
+        if let Some(ApiKeyUser(user)) = parts.extensions.get::<ApiKeyUser>() {
+            return Ok(ApiUser(user.clone()));
+        }
+        let CurrentUser(user) = CurrentUser::from_request_parts(parts, state).await.map_err(AppError::from)?;
+        Ok(ApiUser(user))
+    }
+}
+
+/// Enforce the rate limit on the IP address of the client, like the server functions which change data.
+fn check_rate_limit(rate_limiter: &RateLimiter, ip: IpAddr) -> Result<(), ApiError> {
+    rate_limiter.check(ip).map_err(|retry_after| {
+        // Round up so that retrying after the advertised time always succeeds
+        ApiError(AppError::RateLimited { retry_after: retry_after.as_secs() + 1 })
+    })
+}
+
+/// List the visible blog posts, newest first, as seen by anonymous visitors.
+#[utoipa::path(
+    get,
+    path = "/api/v1/posts",
+    params(ListQuery),
+    responses(
+        (status = 200, description = "A page of blog posts", body = PostList),
+        (status = "default", description = "An error, identified by its code", body = ErrorBody),
+    ),
+)]
+async fn list_posts(state: ServerState, Query(query): Query<ListQuery>) -> Result<Json<PostList>, ApiError> {
+    let filter = PostFilter {
+        tag: query.tag.map(|tag| tag.to_lowercase()),
+        author: query.author,
+        ..PostFilter::default()
+    };
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    let page = posts::fetch_page(&state, query.cursor, limit, filter, None).await?;
+    Ok(Json(page.into()))
+}
+
+/// Fetch a visible blog post, or respond with `404 Not Found`.
+#[utoipa::path(
+    get,
+    path = "/api/v1/posts/{id}",
+    params(("id" = i32, Path, description = "The ID of the blog post")),
+    responses(
+        (status = 200, description = "The blog post", body = Post),
+        (status = 404, description = "The blog post does not exist, or is hidden or in the trash", body = ErrorBody),
+        (status = "default", description = "An error, identified by its code", body = ErrorBody),
+    ),
+)]
+async fn get_post(state: ServerState, Path(post_id): Path<BlogPostId>) -> Result<Json<Post>, ApiError> {
+    let detail = posts::fetch_post(&state, post_id, None).await?.ok_or(AppError::PostNotFound)?;
+    Ok(Json(detail.into()))
+}
+
+/// Create a blog post by the logged-in user.
+/// Responds with `201 Created` if it was published, with `202 Accepted` if it was queued for moderation,
+/// or with `422 Unprocessable Entity` if the content filter rejected it.
+#[utoipa::path(
+    post,
+    path = "/api/v1/posts",
+    request_body = CreatePost,
+    security(("session" = []), ("api_key" = [])),
+    responses(
+        (status = 201, description = "The blog post was published", body = Submission),
+        (status = 202, description = "The blog post was queued for moderation", body = Submission),
+        (status = 401, description = "Nobody is logged in, or the API key is invalid", body = ErrorBody),
+        (status = 422, description = "The blog post failed validation or the content filter", body = ErrorBody),
+        (status = "default", description = "An error, identified by its code", body = ErrorBody),
+    ),
+)]
+async fn create_post(
+    state: ServerState,
+    ClientIp(ip): ClientIp,
+    ApiUser(author): ApiUser,
+    Json(request): Json<CreatePost>,
+) -> Result<(StatusCode, Json<Submission>), ApiError> {
+    check_rate_limit(&state.rate_limiter, ip)?;
+    match posts::create(&state, author, ip, request.into()).await? {
+        PostSubmission::Published(post) => {
+            let detail = posts::fetch_post(&state, post.id, None).await?.ok_or(AppError::PostNotFound)?;
+            Ok((StatusCode::CREATED, Json(Submission::Published { post: detail.into() })))
+        }
+        PostSubmission::Queued(violation) => {
+            Ok((StatusCode::ACCEPTED, Json(Submission::Queued { reason: violation.to_string() })))
+        }
+        PostSubmission::Rejected(violation) => Err(ApiError(AppError::ContentRejected(violation.to_string()))),
+    }
+}
+
+/// Move a blog post of the logged-in user to the trash.
+/// Responds with `204 No Content`.
+#[utoipa::path(
+    delete,
+    path = "/api/v1/posts/{id}",
+    params(("id" = i32, Path, description = "The ID of the blog post")),
+    security(("session" = []), ("api_key" = [])),
+    responses(
+        (status = 204, description = "The blog post was moved to the trash"),
+        (status = 403, description = "The blog post was written by someone else", body = ErrorBody),
+        (status = 404, description = "The blog post does not exist", body = ErrorBody),
+        (status = "default", description = "An error, identified by its code", body = ErrorBody),
+    ),
+)]
+async fn delete_post(
+    state: ServerState,
+    ClientIp(ip): ClientIp,
+    ApiUser(user): ApiUser,
+    Path(post_id): Path<BlogPostId>,
+) -> Result<StatusCode, ApiError> {
+    check_rate_limit(&state.rate_limiter, ip)?;
+    posts::delete(&state, &user, ip, post_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Receive the image in the `image` field of a multipart form ahead of creating a blog post.
+/// Responds with `201 Created` and the token of the upload.
+#[utoipa::path(
+    post,
+    path = "/api/v1/uploads",
+    request_body(content = UploadForm, content_type = "multipart/form-data"),
+    security(("session" = []), ("api_key" = [])),
+    responses(
+        (status = 201, description = "The image was received", body = Upload),
+        (status = 413, description = "The image is too large", body = ErrorBody),
+        (status = "default", description = "An error, identified by its code", body = ErrorBody),
+    ),
+)]
+async fn upload_image(
+    state: ServerState,
+    ClientIp(ip): ClientIp,
+    ApiUser(user): ApiUser,
+    mut multipart: Multipart,
+) -> Result<(StatusCode, Json<Upload>), ApiError> {
+    check_rate_limit(&state.rate_limiter, ip)?;
+    while let Some(field) = multipart.next_field().await.map_err(|err| AppError::from(UploadError::from(err)))? {
+        if field.name() == Some("image") {
+            let token = state.uploads.receive(user.id, field).await.map_err(AppError::from)?;
+            return Ok((StatusCode::CREATED, Json(Upload { token })));
+        }
+    }
+    Err(ApiError(UploadError::Missing.into()))
+}
+
+/// Fetch a proof-of-work challenge, which must be solved to create a blog post.
+#[utoipa::path(
+    get,
+    path = "/api/v1/challenge",
+    responses((status = 200, description = "A new challenge", body = Challenge)),
+)]
+async fn fetch_challenge(state: ServerState) -> Json<Challenge> {
+    Json(state.challenges.issue())
+}
+
+/// Log in with the credentials, and set the session cookie which authenticates further requests.
+#[utoipa::path(
+    post,
+    path = "/api/v1/session",
+    request_body = Credentials,
+    responses(
+        (status = 200, description = "The user is logged in", body = Account),
+        (status = 401, description = "The username or password is wrong", body = ErrorBody),
+        (status = "default", description = "An error, identified by its code", body = ErrorBody),
+    ),
+)]
+async fn log_in(
+    state: ServerState,
+    ClientIp(ip): ClientIp,
+    session: Session,
+    Json(credentials): Json<Credentials>,
+) -> Result<Json<Account>, ApiError> {
+    // Rate limiting also slows down guessing passwords
+    check_rate_limit(&state.rate_limiter, ip)?;
+    let user = auth::authenticate(&state.database, credentials).await?;
+    CurrentUser::log_in(&session, user.clone()).await.map_err(AppError::from)?;
+    Ok(Json(Account { id: user.id, username: user.username }))
+}
+
+/// Log out of the session.
+/// Responds with `204 No Content`.
+#[utoipa::path(
+    delete,
+    path = "/api/v1/session",
+    responses((status = 204, description = "The session was deleted")),
+)]
+async fn log_out(session: Session) -> Result<StatusCode, ApiError> {
+    CurrentUser::log_out(&session).await.map_err(AppError::from)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Download a tar archive of every blog post which is not in the trash, with its images, for backups and migrations.
+/// Only administrators may export the blog. The archive is streamed while it is built.
+#[utoipa::path(
+    get,
+    path = "/api/v1/export",
+    security(("session" = []), ("api_key" = [])),
+    responses(
+        (status = 200, description = "The archive", content_type = "application/x-tar", body = Vec<u8>),
+        (status = 401, description = "Nobody is logged in, or the API key is invalid", body = ErrorBody),
+        (status = 403, description = "The user is no administrator", body = ErrorBody),
+        (status = "default", description = "An error, identified by its code", body = ErrorBody),
+    ),
+)]
+async fn export_archive(
+    state: ServerState,
+    ClientIp(ip): ClientIp,
+    ApiUser(user): ApiUser,
+) -> Result<Response, ApiError> {
+    check_rate_limit(&state.rate_limiter, ip)?;
+    if !state.admins.contains(&user) {
+        return Err(ApiError(AppError::NotAdmin));
+    }
+    let filename = format!("blog-export-{}.tar", OffsetDateTime::now_utc().date());
+    let archive = archive::export(state.database, state.images, state.image_policy, None);
+    Ok(tar_download(&filename, archive))
+}
+
+/// Download a tar archive of all data of the user: their account, likes, reports and API keys,
+/// and their blog posts, including those in the trash, with their images.
+/// Users may export their own data, and administrators that of anyone, e.g. before erasing it.
+#[utoipa::path(
+    get,
+    path = "/api/v1/users/{username}/export",
+    params(("username" = String, Path, description = "The username of the user whose data is exported")),
+    security(("session" = []), ("api_key" = [])),
+    responses(
+        (status = 200, description = "The archive", content_type = "application/x-tar", body = Vec<u8>),
+        (status = 401, description = "Nobody is logged in, or the API key is invalid", body = ErrorBody),
+        (status = 403, description = "The user is someone else and no administrator", body = ErrorBody),
+        (status = "default", description = "An error, identified by its code", body = ErrorBody),
+    ),
+)]
+async fn export_user_data(
+    state: ServerState,
+    ClientIp(ip): ClientIp,
+    ApiUser(user): ApiUser,
+    Path(username): Path<String>,
+) -> Result<Response, ApiError> {
+    check_rate_limit(&state.rate_limiter, ip)?;
+    if user.username != username && !state.admins.contains(&user) {
+        return Err(ApiError(AppError::NotAdmin));
+    }
+    let filename = format!("blog-data-{}-{}.tar", sanitize_filename(&username), OffsetDateTime::now_utc().date());
+    let archive = archive::export(state.database, state.images, state.image_policy, Some(username));
+    Ok(tar_download(&filename, archive))
+}
+
+/// Respond with the tar archive, which is saved as the file rather than shown by browsers.
+fn tar_download(
+    filename: &str,
+    archive: impl futures_util::Stream<Item = Result<Bytes, std::io::Error>> + Send + 'static,
+) -> Response {
+    let headers = [
+        (CONTENT_TYPE, "application/x-tar".to_string()),
+        (CONTENT_DISPOSITION, format!("attachment; filename=\"{filename}\"")),
+    ];
+    (headers, Body::from_stream(archive)).into_response()
+}
+
+/// Replace the characters of the username which could break out of the filename in `Content-Disposition`.
+fn sanitize_filename(username: &str) -> String {
+    username.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' }).collect()
+}
+
+/// Import the blog posts of a tar archive from `GET /export`, of its `posts.json` alone, which leaves out the images,
+/// or of a JSON export of Ghost or of the WordPress REST API.
+/// Only administrators may import blog posts. Every blog post is imported on its own, so that invalid ones and
+/// those which were already imported are skipped and reported rather than failing the import.
+/// The upload can be at most `MAX_UPLOAD_BYTES` large.
+#[utoipa::path(
+    post,
+    path = "/api/v1/import",
+    security(("session" = []), ("api_key" = [])),
+    request_body(content = Vec<u8>, description = "The archive or JSON export", content_type = "application/x-tar"),
+    responses(
+        (status = 200, description = "The blog posts were imported except for the skipped ones", body = ImportSummary),
+        (status = 401, description = "Nobody is logged in, or the API key is invalid", body = ErrorBody),
+        (status = 403, description = "The user is no administrator", body = ErrorBody),
+        (status = 422, description = "The upload is no archive or export which can be imported", body = ErrorBody),
+        (status = "default", description = "An error, identified by its code", body = ErrorBody),
+    ),
+)]
+async fn import_archive(
+    state: ServerState,
+    ClientIp(ip): ClientIp,
+    ApiUser(user): ApiUser,
+    upload: Bytes,
+) -> Result<Json<ImportSummary>, ApiError> {
+    check_rate_limit(&state.rate_limiter, ip)?;
+    if !state.admins.contains(&user) {
+        return Err(ApiError(AppError::NotAdmin));
+    }
+    let report = archive::import(&state, &user, upload)
+        .await
+        .map_err(|err| AppError::Validation(ValidationErrors::of(Field::Archive, err.to_string())))?;
+    if !report.imported.is_empty() {
+        state.audit.record(&user, ip, AuditAction::Import, &report.imported, "Imported from an archive").await;
+    }
+    Ok(Json(report.into()))
+}
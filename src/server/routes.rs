@@ -0,0 +1,198 @@
+//! HTTP routes served by axum alongside the Dioxus application.
+//! These are plain HTTP endpoints which do not go through server functions,
+//! for example because their responses should be cacheable by the browser,
+//! or because they are probed by infrastructure such as load balancers.
+
+use crate::error::AppError;
+use crate::model::{AvatarImagePath, PostImagePath, UploadToken};
+use crate::server::activitypub;
+use crate::server::assets;
+use crate::server::client_ip::ClientIp;
+use crate::server::etag;
+use crate::server::events::PostEvents;
+use crate::server::images::{self, ImagePath, ImagePolicy};
+use crate::server::rate_limit::RateLimiter;
+use crate::server::rest;
+use crate::server::storage::{self, ImageStorage};
+use crate::server::uploads::{UploadError, Uploads};
+use crate::server::webmention;
+use crate::server::{CurrentUser, Database};
+use axum::extract::{Multipart, Path};
+use axum::response::sse::{Event, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::Router;
+use http::header::{CACHE_CONTROL, CONTENT_TYPE, ETAG, IF_NONE_MATCH};
+use futures_util::Stream;
+use http::{HeaderMap, StatusCode};
+use std::convert::Infallible;
+use tracing::warn;
+use uuid::Uuid;
+
+/// Images are never modified after they are saved, since their UUID is derived from their content.
+/// This allows browsers to cache them indefinitely.
+const IMAGE_CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+
+/// The entity tag of the image stored under the key.
+/// The key identifies the content of the image, so it doubles as its tag.
+fn image_etag(key: &str) -> String {
+    format!("\"{key}\"")
+}
+
+/// Create the router for the HTTP routes served outside of Dioxus.
+pub fn router() -> Router {
+    Router::new()
+        .route("/images/posts/:uuid", get(serve_image::<PostImagePath>))
+        .route("/images/posts/:uuid/thumbnail", get(serve_thumbnail::<PostImagePath>))
+        .route("/images/avatars/:uuid", get(serve_image::<AvatarImagePath>))
+        .route("/static/*name", get(assets::serve_asset))
+        .route("/uploads", post(upload_image))
+        .route("/events/posts", get(subscribe_post_events))
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .merge(rest::router())
+        .merge(activitypub::router())
+        .merge(webmention::router())
+}
+
+/// Liveness probe: responds with `200 OK` as long as the process is able to handle requests.
+async fn healthz() -> &'static str {
+    "ok"
+}
+
+/// Readiness probe: responds with `200 OK` if the database and the image store are available,
+/// or with `503 Service Unavailable` and the reasons otherwise, so that traffic is routed elsewhere.
+async fn readyz(database: Database, images: ImageStorage) -> (StatusCode, String) {
+    let (database_check, images_check) = tokio::join!(database.ping(), images.check_available());
+    let mut failures = Vec::new();
+    if let Err(err) = database_check {
+        warn!("Readiness check failed, database is unreachable: {}", err);
+        failures.push("database unreachable");
+    }
+    if let Err(err) = images_check {
+        warn!("Readiness check failed, image store is unavailable: {}", err);
+        failures.push("image store unavailable");
+    }
+    if failures.is_empty() {
+        (StatusCode::OK, "ok".to_string())
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, failures.join(", "))
+    }
+}
+
+/// Receive the image in the `image` field of a multipart form ahead of creating a blog post.
+/// Responds with `201 Created` and the token of the upload as plain text,
+/// which is passed to `create_blog_post` in place of the image.
+/// Uploads are rate limited like the server functions which change data.
+async fn upload_image(
+    CurrentUser(user): CurrentUser,
+    rate_limiter: RateLimiter,
+    ClientIp(ip): ClientIp,
+    uploads: Uploads,
+    mut multipart: Multipart,
+) -> Result<(StatusCode, UploadToken), Response> {
+    if let Err(retry_after) = rate_limiter.check(ip) {
+        // Round up so that retrying after the advertised time always succeeds
+        return Err(AppError::RateLimited { retry_after: retry_after.as_secs() + 1 }.into_response());
+    }
+    while let Some(field) = multipart.next_field().await.map_err(|err| UploadError::from(err).into_response())? {
+        if field.name() == Some("image") {
+            let token = uploads.receive(user.id, field).await.map_err(IntoResponse::into_response)?;
+            return Ok((StatusCode::CREATED, token));
+        }
+    }
+    Err(UploadError::Missing.into_response())
+}
+
+/// Stream the changes to the blog post feed as server-sent events until the client disconnects.
+/// Every event carries a `PostEvent` as JSON.
+async fn subscribe_post_events(events: PostEvents) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    events.subscribe()
+}
+
+/// Stream the image with the provided UUID from the image store.
+/// Animated GIFs are served as their animation rather than as their still first frame,
+/// which is only looked for if the UUID says the image is animated.
+/// Images are looked up in the configured format first, and then in the formats they may have been saved in before.
+/// Responds with `404 Not Found` if the UUID is malformed or no such image exists.
+async fn serve_image<I: ImagePath>(
+    images: ImageStorage,
+    policy: ImagePolicy,
+    Path(uuid): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
+    let image = parse_image_path::<I>(&uuid)?;
+    let keys = image
+        .animated_key()
+        .into_iter()
+        .chain(images::stored_formats(policy.format()).map(|format| image.key(format)));
+    serve_first(&images, keys, if_none_match(&headers)).await
+}
+
+/// Stream the thumbnail of the image with the provided UUID from the image store.
+/// Falls back to the original image if it has no thumbnail, e.g. because it predates thumbnails.
+/// Responds with `404 Not Found` if the UUID is malformed or no such image exists.
+async fn serve_thumbnail<I: ImagePath>(
+    images: ImageStorage,
+    policy: ImagePolicy,
+    Path(uuid): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
+    let image = parse_image_path::<I>(&uuid)?;
+    let keys = images::stored_formats(policy.format())
+        .filter_map(|format| image.thumbnail_key(format))
+        .chain(images::stored_formats(policy.format()).map(|format| image.key(format)));
+    serve_first(&images, keys, if_none_match(&headers)).await
+}
+
+/// The value of the `If-None-Match` header of the request, if any, which lists the tags of the cached images.
+fn if_none_match(headers: &HeaderMap) -> Option<&str> {
+    headers.get(IF_NONE_MATCH).and_then(|value| value.to_str().ok())
+}
+
+/// Stream the first image which exists under one of the keys, trying them in order.
+/// Responds with `404 Not Found` if there is no image under any of them.
+async fn serve_first(
+    images: &ImageStorage,
+    keys: impl IntoIterator<Item = String>,
+    if_none_match: Option<&str>,
+) -> Result<Response, StatusCode> {
+    for key in keys {
+        if let Some(response) = serve_key(images, &key, if_none_match).await? {
+            return Ok(response);
+        }
+    }
+    Err(StatusCode::NOT_FOUND)
+}
+
+/// Parse the UUID of an image from a path segment.
+/// Parsing the UUID also guarantees that the resulting key cannot escape the image store.
+fn parse_image_path<I: ImagePath>(uuid: &str) -> Result<I, StatusCode> {
+    Uuid::parse_str(uuid)
+        .map(I::new)
+        .map_err(|_| StatusCode::NOT_FOUND)
+}
+
+/// Stream the image stored under the key with a long-lived cache header and its entity tag.
+/// If the browser already has the image, as told by `If-None-Match`, responds with `304 Not Modified`
+/// without loading it, as long as it still exists.
+/// Returns `None` if no such image exists.
+async fn serve_key(images: &ImageStorage, key: &str, if_none_match: Option<&str>) -> Result<Option<Response>, StatusCode> {
+    let load_failed = |err| {
+        warn!("Failed to load image {}: {}", key, err);
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+    let tag = image_etag(key);
+    if if_none_match.is_some_and(|value| etag::matches(value, &tag)) {
+        let exists = images.exists(key).await.map_err(load_failed)?;
+        let headers = [(ETAG, tag), (CACHE_CONTROL, IMAGE_CACHE_CONTROL.to_string())];
+        return Ok(exists.then(|| (StatusCode::NOT_MODIFIED, headers).into_response()));
+    }
+    let body = images.get(key).await.map_err(load_failed)?;
+    let headers = [
+        (CONTENT_TYPE, storage::content_type(key).to_string()),
+        (CACHE_CONTROL, IMAGE_CACHE_CONTROL.to_string()),
+        (ETAG, tag),
+    ];
+    Ok(body.map(|body| (headers, body).into_response()))
+}
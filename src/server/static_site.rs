@@ -0,0 +1,124 @@
+//! Export of the public pages of the blog to static HTML files, run with the `--export-static <dir>` command line flag.
+//! The home page lists every visible blog post, and every blog post gets a page at its permalink,
+//! rendered by the components of the web app, so that the read-only site can be hosted on a CDN without it.
+//! The pages link to the images and the stylesheet by the same paths as the server,
+//! which is left to serve them along with everything which changes data.
+
+use crate::client::render_static_page;
+use crate::i18n::{t, Locale};
+use crate::model::{BlogPost, BlogPostPage};
+use crate::server::opengraph::escape_attribute;
+use crate::server::persistence::database::DatabaseError;
+use crate::server::Database;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use tracing::{info, instrument, warn};
+use uuid::Uuid;
+
+/// Why exporting the static site failed.
+#[derive(Debug, derive_more::Display, derive_more::From)]
+pub enum StaticExportError {
+    #[display("Failed to load the blog posts: {}", _0)]
+    Database(DatabaseError),
+    #[display("Failed to write the pages: {}", _0)]
+    Io(std::io::Error),
+}
+
+/// The directory given by the `--export-static` command line flag, if the static site should be exported.
+pub fn export_dir() -> Option<PathBuf> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--export-static" {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(dir) = arg.strip_prefix("--export-static=") {
+            return Some(PathBuf::from(dir));
+        }
+    }
+    None
+}
+
+/// Write the home page to `index.html` in the directory, and the page of every visible blog post
+/// to `post/<id>/index.html` and, if it has a slug, `post/<id>/<slug>/index.html`.
+/// The pages are written to a new directory next to it, which then replaces it, so that the pages of blog posts
+/// which were deleted since the previous export do not linger, and a failed export leaves the previous one in place.
+/// Returns the number of exported blog posts.
+#[instrument(level = "info", skip(database))]
+pub async fn export(database: &Database, dir: &Path) -> Result<usize, StaticExportError> {
+    let export = database.fetch_export(None).await?;
+    // The export is sorted oldest first, while the home page lists the newest first
+    let posts: Vec<BlogPost> =
+        export.posts.into_iter().rev().filter(|(_, hidden, _)| !hidden).map(|(post, ..)| post).collect();
+    let count = posts.len();
+    let page = Rc::new(BlogPostPage {
+        posts,
+        next_cursor: None,
+        likes: export.likes,
+        tags: export.tags,
+        last_edited: export.last_edited,
+    });
+    let staging = sibling(dir, "tmp")?;
+    if let Err(err) = write_pages(&page, &staging).await {
+        let _ = tokio::fs::remove_dir_all(&staging).await;
+        return Err(err.into());
+    }
+    let previous = sibling(dir, "old")?;
+    match tokio::fs::rename(dir, &previous).await {
+        Ok(()) => {}
+        Err(err) if err.kind() == ErrorKind::NotFound => {}
+        Err(err) => {
+            let _ = tokio::fs::remove_dir_all(&staging).await;
+            return Err(err.into());
+        }
+    }
+    tokio::fs::rename(&staging, dir).await?;
+    if let Err(err) = tokio::fs::remove_dir_all(&previous).await {
+        if err.kind() != ErrorKind::NotFound {
+            warn!("Failed to remove the previous export at {}: {}", previous.display(), err);
+        }
+    }
+    info!("Exported {} blog posts to {}", count, dir.display());
+    Ok(count)
+}
+
+/// Write the pages of the blog posts on the page, and the home page listing them, to the new directory.
+async fn write_pages(page: &Rc<BlogPostPage>, dir: &Path) -> std::io::Result<()> {
+    let locale = Locale::default();
+    tokio::fs::create_dir_all(dir).await?;
+    let home = render_static_page(page.clone(), None).await;
+    tokio::fs::write(dir.join("index.html"), document(&t!(locale, "home-title"), &home)).await?;
+    for post in &page.posts {
+        let content = render_static_page(page.clone(), Some(post)).await;
+        let html = document(&t!(locale, "post-heading", id = post.id), &content);
+        let post_dir = dir.join("post").join(post.id.to_string());
+        tokio::fs::create_dir_all(&post_dir).await?;
+        tokio::fs::write(post_dir.join("index.html"), &html).await?;
+        if let Some(slug) = &post.slug {
+            tokio::fs::create_dir_all(post_dir.join(slug)).await?;
+            tokio::fs::write(post_dir.join(slug).join("index.html"), &html).await?;
+        }
+    }
+    Ok(())
+}
+
+/// A directory next to the one given, named after it with the suffix and a random ID, e.g. `site.tmp-<uuid>`.
+fn sibling(dir: &Path, suffix: &str) -> std::io::Result<PathBuf> {
+    let name = dir.file_name().ok_or_else(|| {
+        std::io::Error::new(ErrorKind::InvalidInput, format!("'{}' does not name a directory", dir.display()))
+    })?;
+    let mut name = name.to_os_string();
+    name.push(format!(".{suffix}-{}", Uuid::new_v4()));
+    Ok(dir.with_file_name(name))
+}
+
+/// A complete page with the title and the rendered content, styled like the web app.
+fn document(title: &str, content: &str) -> String {
+    let title = escape_attribute(title);
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+         <title>{title}</title>\n<link rel=\"stylesheet\" href=\"/main.css\">\n</head>\n\
+         <body>\n{content}\n</body>\n</html>\n"
+    )
+}
@@ -0,0 +1,411 @@
+//! Storage backends for images.
+//! Images are stored under keys like `posts/12/3e/123e4567-e89b-12d3-a456-426614174000.png`,
+//! either on the local file system or in an S3-compatible bucket, depending on the configuration.
+
+use crate::server::config::ImageStoreConfig;
+use crate::server::image_cache::CachedStore;
+use axum::async_trait;
+use axum::body::{Body, Bytes};
+use image::ImageFormat;
+use s3::creds::error::CredentialsError;
+use s3::creds::Credentials;
+use s3::error::S3Error;
+use s3::{Bucket, Region};
+use std::fmt::Debug;
+use std::io::Write;
+use std::ops::Deref;
+use std::path::PathBuf;
+use std::sync::{Arc, LazyLock};
+use std::time::SystemTime;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use tokio_util::io::ReaderStream;
+use tokio_util::task::TaskTracker;
+use tracing::debug;
+use uuid::Uuid;
+
+/// Errors that can occur when accessing stored images.
+#[derive(Debug, derive_more::From, derive_more::Display, derive_more::Error)]
+pub enum StorageError {
+    #[display("IO error: {}", _0)]
+    Io(std::io::Error),
+    #[display("S3 error: {}", _0)]
+    S3(S3Error),
+    #[display("S3 credentials error: {}", _0)]
+    Credentials(CredentialsError),
+}
+
+/// Tracks the writes and deletions of images which are in progress,
+/// so that shutdown can wait for them to finish instead of leaving partially written images behind.
+static PENDING_WRITES: LazyLock<TaskTracker> = LazyLock::new(TaskTracker::new);
+
+/// Wait for all pending image writes and deletions to finish.
+/// No new writes can be tracked afterward, so this should only be called during shutdown.
+pub async fn flush() {
+    PENDING_WRITES.close();
+    debug!("Waiting for {} pending image writes", PENDING_WRITES.len());
+    PENDING_WRITES.wait().await;
+}
+
+/// An image in the image store, as listed by `ImageStore::list`.
+#[derive(Debug, Clone)]
+pub struct StoredImage {
+    /// The key under which the image is stored.
+    pub key: String,
+    /// The size of the image in bytes.
+    pub size: u64,
+    /// When the image was last written.
+    pub modified: SystemTime,
+}
+
+/// A place where images can be stored, retrieved and deleted by key.
+#[async_trait]
+pub trait ImageStore: Debug + Send + Sync {
+    /// Store the bytes under the key, replacing any image previously stored under it.
+    async fn put(&self, key: &str, bytes: Vec<u8>) -> Result<(), StorageError>;
+    /// Returns the body of the image stored under the key, or `None` if there is no such image.
+    async fn get(&self, key: &str) -> Result<Option<Body>, StorageError>;
+    /// Returns whether an image is stored under the key.
+    async fn exists(&self, key: &str) -> Result<bool, StorageError>;
+    /// Mark the image stored under the key as written just now, so that the garbage collector,
+    /// which only deletes images older than its grace period, leaves it alone for another grace period.
+    /// Stores which cannot change the time an image was written store it again.
+    /// Returns whether an image is stored under the key.
+    async fn touch(&self, key: &str) -> Result<bool, StorageError> {
+        let Some(body) = self.get(key).await? else {
+            return Ok(false);
+        };
+        let bytes = axum::body::to_bytes(body, usize::MAX).await.map_err(std::io::Error::other)?;
+        self.put(key, bytes.to_vec()).await?;
+        Ok(true)
+    }
+    /// Delete the image stored under the key. Deleting an image which does not exist is not an error.
+    async fn delete(&self, key: &str) -> Result<(), StorageError>;
+    /// Returns all stored images.
+    /// This visits every stored image, so it should not be called on every request.
+    async fn list(&self) -> Result<Vec<StoredImage>, StorageError>;
+    /// Returns the total size in bytes of all stored images.
+    /// This lists every stored image, so it should not be called on every request.
+    async fn stored_bytes(&self) -> Result<u64, StorageError> {
+        Ok(self.list().await?.iter().map(|image| image.size).sum())
+    }
+    /// Check that the store is available, e.g. for the readiness probe, which runs every few seconds.
+    /// This must be cheap, so it neither writes nor lists the whole store.
+    async fn check_available(&self) -> Result<(), StorageError>;
+    /// Delete what is left of writes which were interrupted, e.g. by a crash, and return how many were found.
+    /// This must only be called on startup, before any image is written, or it deletes writes in progress.
+    async fn remove_partial_writes(&self) -> Result<usize, StorageError> {
+        Ok(0)
+    }
+}
+
+/// A shared handle to the configured `ImageStore`.
+#[derive(Debug, Clone)]
+pub struct ImageStorage(Arc<dyn ImageStore>);
+
+impl ImageStorage {
+    /// Create the image store described by the configuration.
+    /// Returns `StorageError::S3` or `StorageError::Credentials` if the S3 bucket or its credentials are misconfigured.
+    pub fn from_config(config: &ImageStoreConfig) -> Result<Self, StorageError> {
+        let store: Arc<dyn ImageStore> = match config {
+            ImageStoreConfig::Filesystem { dir } => Arc::new(FilesystemStore::new(dir.clone())),
+            ImageStoreConfig::S3 { bucket, region, endpoint } => {
+                Arc::new(S3Store::new(bucket, region, endpoint.as_deref())?)
+            }
+        };
+        Ok(Self(store))
+    }
+
+    /// Keep up to the number of bytes of recently read images in memory in front of the store.
+    /// A capacity of zero leaves the store uncached.
+    pub fn with_cache(self, capacity: usize) -> Self {
+        if capacity == 0 {
+            return self;
+        }
+        Self(Arc::new(CachedStore::new(self.0, capacity)))
+    }
+}
+
+impl Deref for ImageStorage {
+    type Target = dyn ImageStore;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
+
+/// Returns the MIME type of the image stored under the key, based on its extension.
+pub fn content_type(key: &str) -> &'static str {
+    ImageFormat::from_path(key)
+        .map(|format| format.to_mime_type())
+        .unwrap_or("application/octet-stream")
+}
+
+/// The extension of the temporary files which images are written to before they are renamed to their key.
+/// Image keys always end in the extension of an image format, so they cannot be mistaken for temporary files.
+const TEMP_EXTENSION: &str = "tmp";
+
+/// Stores images as files in a directory on the local file system,
+/// where the key of an image is its path relative to the directory.
+/// Images are written to a temporary file next to their final path, which is renamed once it is complete,
+/// so that an interrupted write never leaves a partial image under its key.
+#[derive(Debug)]
+pub struct FilesystemStore {
+    root: PathBuf,
+}
+
+impl FilesystemStore {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    /// Returns the path of the file of the image stored under the key.
+    /// Keys are generated by the server from UUIDs, so they cannot escape the directory.
+    fn path(&self, key: &str) -> PathBuf {
+        self.root.join(key)
+    }
+}
+
+#[async_trait]
+impl ImageStore for FilesystemStore {
+    async fn put(&self, key: &str, bytes: Vec<u8>) -> Result<(), StorageError> {
+        let path = self.path(key);
+        PENDING_WRITES
+            .spawn_blocking(move || {
+                // Create the directory if it doesn't exist
+                // Safety: We know the parent directory exists because the path is inside the root
+                std::fs::create_dir_all(path.parent().expect("parent dir should exist"))?;
+                // Concurrent writes of the same image must not write to the same temporary file
+                let temp_path = path.with_extension(format!("{}.{}", Uuid::new_v4(), TEMP_EXTENSION));
+                write_atomically(&temp_path, &path, &bytes).inspect_err(|_| {
+                    std::fs::remove_file(&temp_path).ok();
+                })
+            })
+            .await
+            .expect("writing an image should not panic")?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Body>, StorageError> {
+        match tokio::fs::File::open(self.path(key)).await {
+            Ok(file) => Ok(Some(Body::from_stream(ReaderStream::new(file)))),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, StorageError> {
+        Ok(tokio::fs::try_exists(self.path(key)).await?)
+    }
+
+    async fn touch(&self, key: &str) -> Result<bool, StorageError> {
+        let path = self.path(key);
+        let touched = tokio::task::spawn_blocking(move || {
+            match std::fs::File::options().write(true).open(path) {
+                Ok(file) => file.set_modified(SystemTime::now()).map(|()| true),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(false),
+                Err(err) => Err(err),
+            }
+        })
+        .await
+        .expect("touching an image should not panic")?;
+        Ok(touched)
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        let path = self.path(key);
+        PENDING_WRITES
+            .spawn_blocking(move || match std::fs::remove_file(path) {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err),
+                _ => Ok(()),
+            })
+            .await
+            .expect("deleting an image should not panic")?;
+        Ok(())
+    }
+
+    async fn list(&self) -> Result<Vec<StoredImage>, StorageError> {
+        let root = self.root.clone();
+        let images = tokio::task::spawn_blocking(move || {
+            let mut images = Vec::new();
+            list_directory(&root, "", &mut images)?;
+            Ok::<_, std::io::Error>(images)
+        })
+        .await
+        .expect("listing images should not panic")?;
+        Ok(images)
+    }
+
+    /// The directory must be a writable directory, unless it does not exist yet, in which case it is created
+    /// when the first image is stored, so its closest existing ancestor must be one.
+    async fn check_available(&self) -> Result<(), StorageError> {
+        let mut dir = self.root.as_path();
+        let metadata = loop {
+            match tokio::fs::metadata(dir).await {
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => match dir.parent() {
+                    Some(parent) => dir = parent,
+                    None => return Err(err.into()),
+                },
+                result => break result?,
+            }
+        };
+        if !metadata.is_dir() || metadata.permissions().readonly() {
+            let reason = format!("{} is not a writable directory", dir.display());
+            return Err(std::io::Error::new(std::io::ErrorKind::PermissionDenied, reason).into());
+        }
+        Ok(())
+    }
+
+    async fn remove_partial_writes(&self) -> Result<usize, StorageError> {
+        let root = self.root.clone();
+        let removed = tokio::task::spawn_blocking(move || remove_temp_files(&root))
+            .await
+            .expect("removing temporary files should not panic")?;
+        Ok(removed)
+    }
+}
+
+/// Write the bytes to the temporary path and rename it to the final path once they are on disk.
+/// Renaming within a directory is atomic, so the final path holds either the previous image or the complete new one.
+fn write_atomically(temp_path: &std::path::Path, path: &std::path::Path, bytes: &[u8]) -> std::io::Result<()> {
+    let mut file = std::fs::File::create(temp_path)?;
+    file.write_all(bytes)?;
+    // Without syncing, a crash after the rename could leave an empty file under the key
+    file.sync_all()?;
+    std::fs::rename(temp_path, path)
+}
+
+/// Returns whether the file is a temporary file which an image is being written to.
+fn is_temp_file(path: &std::path::Path) -> bool {
+    path.extension().is_some_and(|extension| extension == TEMP_EXTENSION)
+}
+
+/// Delete the temporary files in the directory and its subdirectories, and return how many there were.
+/// A directory which does not exist yet contains no temporary files.
+fn remove_temp_files(dir: &std::path::Path) -> std::io::Result<usize> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(err) => return Err(err),
+    };
+    let mut removed = 0;
+    for entry in entries {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            removed += remove_temp_files(&entry.path())?;
+        } else if file_type.is_file() && is_temp_file(&entry.path()) {
+            debug!("Removing partially written image {}", entry.path().display());
+            std::fs::remove_file(entry.path())?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+/// Collect the files in the directory and its subdirectories into `images`,
+/// keyed by their path relative to the root of the store, which is `prefix` for this directory.
+/// A directory which does not exist yet contains no images.
+fn list_directory(dir: &std::path::Path, prefix: &str, images: &mut Vec<StoredImage>) -> std::io::Result<()> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    };
+    for entry in entries {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        // Keys always use forward slashes, regardless of the platform
+        let key = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+        if file_type.is_dir() {
+            list_directory(&entry.path(), &format!("{key}/"), images)?;
+        } else if file_type.is_file() && !is_temp_file(&entry.path()) {
+            // Temporary files are not images yet, and would otherwise be deleted as orphans while being written
+            let metadata = entry.metadata()?;
+            images.push(StoredImage { key, size: metadata.len(), modified: metadata.modified()? });
+        }
+    }
+    Ok(())
+}
+
+/// Stores images as objects in an S3-compatible bucket, where the key of an image is its object key.
+/// Credentials are read from the standard AWS environment variables, profile or instance metadata.
+#[derive(Debug)]
+pub struct S3Store {
+    bucket: Box<Bucket>,
+}
+
+impl S3Store {
+    /// Create a store for the bucket in the region.
+    /// If an `endpoint` is provided, it is used instead of AWS with path-style requests,
+    /// as expected by most S3-compatible services such as MinIO.
+    pub fn new(bucket: &str, region: &str, endpoint: Option<&str>) -> Result<Self, StorageError> {
+        let region = Region::Custom {
+            region: region.to_string(),
+            endpoint: match endpoint {
+                Some(endpoint) => endpoint.to_string(),
+                None => format!("https://s3.{region}.amazonaws.com"),
+            },
+        };
+        let credentials = Credentials::default()?;
+        let mut bucket = Bucket::new(bucket, region, credentials)?;
+        if endpoint.is_some() {
+            bucket = bucket.with_path_style();
+        }
+        Ok(Self { bucket })
+    }
+}
+
+#[async_trait]
+impl ImageStore for S3Store {
+    async fn put(&self, key: &str, bytes: Vec<u8>) -> Result<(), StorageError> {
+        let upload = self.bucket.put_object_with_content_type(key, &bytes, content_type(key));
+        PENDING_WRITES.track_future(upload).await?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Body>, StorageError> {
+        match self.bucket.get_object(key).await {
+            Ok(response) => Ok(Some(Body::from(Bytes::copy_from_slice(response.bytes())))),
+            Err(S3Error::HttpFailWithBody(404, _)) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, StorageError> {
+        match self.bucket.head_object(key).await {
+            Ok((_, status)) => Ok(status == 200),
+            Err(S3Error::HttpFailWithBody(404, _)) => Ok(false),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        // S3 reports success when deleting objects which do not exist
+        PENDING_WRITES.track_future(self.bucket.delete_object(key)).await?;
+        Ok(())
+    }
+
+    /// A single request for at most one key, which like a `HEAD` on the bucket fails if the bucket does not exist
+    /// or the credentials may not read it, but unlike one also tells a missing bucket from any other failure.
+    async fn check_available(&self) -> Result<(), StorageError> {
+        self.bucket.list_page(String::new(), None, None, None, Some(1)).await?;
+        Ok(())
+    }
+
+    async fn list(&self) -> Result<Vec<StoredImage>, StorageError> {
+        let pages = self.bucket.list(String::new(), None).await?;
+        let images = pages
+            .into_iter()
+            .flat_map(|page| page.contents)
+            .map(|object| StoredImage {
+                // Objects with an unparseable timestamp are treated as brand new, so they are never considered stale
+                modified: OffsetDateTime::parse(&object.last_modified, &Rfc3339)
+                    .map_or_else(|_| SystemTime::now(), SystemTime::from),
+                size: object.size,
+                key: object.key,
+            })
+            .collect();
+        Ok(images)
+    }
+}
@@ -0,0 +1,65 @@
+//! Logging and trace export of the server.
+//! Logs are always written to stdout, while spans are additionally exported over OTLP
+//! to an OpenTelemetry collector if an endpoint is configured.
+//! Which messages are logged is decided by directives in the syntax of `RUST_LOG`, so that e.g. the image pipeline
+//! can be traced while the queries of Diesel stay quiet.
+
+use opentelemetry::trace::{TraceError, TracerProvider as _};
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::trace::{Config, Tracer};
+use opentelemetry_sdk::{runtime, Resource};
+use tracing::{info, warn};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
+
+/// The name under which the spans of the server are exported.
+const SERVICE_NAME: &str = "blogposts";
+
+/// Initialize the global tracing subscriber, which logs the events selected by the filter directives to stdout,
+/// e.g. `info,blogposts::server::images=trace`. Invalid directives are ignored, since the configuration rejects them.
+/// If `otlp_endpoint` is provided, spans are also exported to the OTLP collector at that endpoint.
+/// If the subscriber fails to initialize, we'll just continue without logging,
+/// and if the exporter fails to initialize, we'll just continue without exporting.
+pub fn init(filter: &str, otlp_endpoint: Option<&str>) {
+    let tracer = otlp_endpoint.map(tracer);
+    let (otel_layer, otel_error) = match tracer {
+        Some(Ok(tracer)) => (Some(tracing_opentelemetry::layer().with_tracer(tracer)), None),
+        Some(Err(err)) => (None, Some(err)),
+        None => (None, None),
+    };
+    let initialized = tracing_subscriber::registry()
+        .with(EnvFilter::new(filter))
+        .with(tracing_subscriber::fmt::layer())
+        .with(otel_layer)
+        .try_init();
+    if initialized.is_err() {
+        return;
+    }
+    match (otlp_endpoint, otel_error) {
+        (_, Some(err)) => warn!("Failed to set up OpenTelemetry trace export: {}", err),
+        (Some(endpoint), None) => info!("Exporting traces to {}", endpoint),
+        (None, None) => {}
+    }
+}
+
+/// Export the spans which have not been exported yet.
+/// This should be called right before the server exits, since spans are exported in batches.
+pub fn shutdown() {
+    opentelemetry::global::shutdown_tracer_provider();
+}
+
+/// Create a tracer which exports spans in batches to the OTLP collector at the endpoint.
+/// The tracer provider is registered globally, so that `shutdown` can flush it.
+fn tracer(endpoint: &str) -> Result<Tracer, TraceError> {
+    let provider = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(opentelemetry_otlp::new_exporter().tonic().with_endpoint(endpoint))
+        .with_trace_config(
+            Config::default().with_resource(Resource::new([KeyValue::new("service.name", SERVICE_NAME)])),
+        )
+        .install_batch(runtime::Tokio)?;
+    opentelemetry::global::set_tracer_provider(provider.clone());
+    Ok(provider.tracer(SERVICE_NAME))
+}
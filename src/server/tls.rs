@@ -0,0 +1,71 @@
+//! Serving the application over HTTPS with rustls, including redirecting plain HTTP to HTTPS.
+
+use crate::server::config::TlsConfig;
+use axum::extract::connect_info::IntoMakeServiceWithConnectInfo;
+use axum::extract::Host;
+use axum::response::Redirect;
+use axum::Router;
+use axum_server::tls_rustls::RustlsConfig;
+use axum_server::Handle;
+use http::Uri;
+use std::net::SocketAddr;
+use tokio::net::TcpListener;
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+
+/// Serve the application over HTTPS on the listener with the certificate and key of the `TlsConfig`.
+/// Once `shutdown` is cancelled, no new connections are accepted
+/// and the server stops after in-flight requests are completed.
+/// Returns an error if the certificate or key cannot be loaded, or if the server fails.
+pub async fn serve(
+    listener: TcpListener,
+    service: IntoMakeServiceWithConnectInfo<Router, SocketAddr>,
+    tls: &TlsConfig,
+    shutdown: CancellationToken,
+) -> std::io::Result<()> {
+    let rustls_config = RustlsConfig::from_pem_file(&tls.cert_path, &tls.key_path).await?;
+    info!("Loaded TLS certificate from {}", tls.cert_path.display());
+    let handle = Handle::new();
+    tokio::spawn({
+        let handle = handle.clone();
+        async move {
+            shutdown.cancelled().await;
+            // Wait for in-flight requests for as long as they take, like the plain HTTP server
+            handle.graceful_shutdown(None);
+        }
+    });
+    axum_server::from_tcp_rustls(listener.into_std()?, rustls_config)
+        .handle(handle)
+        .serve(service)
+        .await
+}
+
+/// Redirect all plain HTTP requests on the listener to the same host and path over HTTPS on `https_port`.
+/// The server stops once `shutdown` is cancelled.
+pub async fn redirect_to_https(
+    listener: TcpListener,
+    https_port: u16,
+    shutdown: CancellationToken,
+) -> std::io::Result<()> {
+    let router = Router::new().fallback(move |Host(host): Host, uri: Uri| async move {
+        Redirect::permanent(&https_url(&host, &uri, https_port))
+    });
+    axum::serve(listener, router)
+        .with_graceful_shutdown(shutdown.cancelled_owned())
+        .await
+}
+
+/// Returns the HTTPS URL of the request to the host and URI.
+/// The port of the host, if any, is replaced by `https_port`, which is omitted if it is the default 443.
+fn https_url(host: &str, uri: &Uri, https_port: u16) -> String {
+    // IPv6 hosts are enclosed in brackets, so a colon followed by digits is always a port
+    let hostname = match host.rsplit_once(':') {
+        Some((hostname, port)) if !port.is_empty() && port.bytes().all(|b| b.is_ascii_digit()) => hostname,
+        _ => host,
+    };
+    let path = uri.path_and_query().map_or("/", |path| path.as_str());
+    match https_port {
+        443 => format!("https://{hostname}{path}"),
+        port => format!("https://{hostname}:{port}{path}"),
+    }
+}
@@ -0,0 +1,77 @@
+//! The trash, where deleted blog posts are kept for a retention window before they are purged.
+//! Until then, the author of a deleted blog post can restore it.
+
+use crate::model::{AuditAction, BlogPostId, TrashedPost, UserId};
+use crate::server::audit::AuditLog;
+use crate::server::images;
+use crate::server::jobs::Job;
+use crate::server::persistence::database::DatabaseError;
+use crate::server::storage::ImageStorage;
+use crate::server::Database;
+use axum::async_trait;
+use std::time::Duration;
+use time::{OffsetDateTime, PrimitiveDateTime};
+use tracing::{info, instrument};
+
+/// How often the blog posts whose retention window has passed are purged from the trash.
+pub const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Manages the blog posts in the trash.
+#[derive(Debug, Clone)]
+pub struct Trash {
+    database: Database,
+    images: ImageStorage,
+    audit: AuditLog,
+    retention: Duration,
+}
+
+impl Trash {
+    /// Create a trash which keeps deleted blog posts for the retention window.
+    pub fn new(database: Database, images: ImageStorage, audit: AuditLog, retention: Duration) -> Self {
+        Self { database, images, audit, retention }
+    }
+
+    /// Fetch the blog posts in the trash which the user may restore, most recently deleted first.
+    pub async fn fetch(&self, user: UserId) -> Result<Vec<TrashedPost>, DatabaseError> {
+        let trash = self.database.fetch_trash(user).await?;
+        let trash = trash
+            .into_iter()
+            .map(|(post, deleted_at)| TrashedPost { post, deleted_at, purge_after: deleted_at + self.retention })
+            .collect();
+        Ok(trash)
+    }
+
+    /// Permanently delete the blog posts which have been in the trash for longer than the retention window,
+    /// along with their images, unless other blog posts still reference them.
+    /// Returns the number of purged blog posts.
+    #[instrument(level = "info", skip(self))]
+    pub async fn purge(&self) -> Result<usize, DatabaseError> {
+        let now = OffsetDateTime::now_utc();
+        let cutoff = PrimitiveDateTime::new(now.date(), now.time()) - self.retention;
+        let purged = self.database.purge_trash(cutoff).await?;
+        for post in &purged {
+            let (image, avatar) = (post.image_uuid.clone(), post.avatar_uuid.clone());
+            images::delete_unreferenced(&*self.images, &self.database, image, avatar).await;
+        }
+        let post_ids: Vec<BlogPostId> = purged.iter().map(|post| post.id).collect();
+        if !post_ids.is_empty() {
+            self.audit.record_server(AuditAction::Purge, &post_ids, "Purged from the trash").await;
+        }
+        info!("Purged {} blog posts from the trash", purged.len());
+        Ok(purged.len())
+    }
+}
+
+/// Purging the trash is scheduled as a background job.
+#[async_trait]
+impl Job for Trash {
+    type Error = DatabaseError;
+
+    fn name(&self) -> &'static str {
+        "purge_trash"
+    }
+
+    async fn run(&self) -> Result<(), DatabaseError> {
+        self.purge().await.map(|_| ())
+    }
+}
@@ -0,0 +1,193 @@
+//! Temporary storage of images which are uploaded before the blog post they belong to is created.
+//! Uploads are streamed to files on disk as they arrive instead of being buffered in memory,
+//! and the blog post refers to its image by the token of the upload.
+
+use crate::model::{UploadToken, UserId};
+use crate::server::jobs::Job;
+use axum::async_trait;
+use axum::extract::multipart::{Field, MultipartError};
+use axum::response::{IntoResponse, Response};
+use http::StatusCode;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tracing::{debug, info, instrument, warn};
+use uuid::Uuid;
+
+/// How long an upload can be used to create a blog post before it is deleted.
+const UPLOAD_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// How often uploads which were never used are deleted.
+pub const PURGE_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Errors that can occur when receiving or claiming uploads.
+#[derive(Debug, derive_more::From, derive_more::Display, derive_more::Error)]
+pub enum UploadError {
+    #[display("Failed to read upload: {}", _0)]
+    Multipart(MultipartError),
+    #[display("Failed to store upload: {}", _0)]
+    Io(std::io::Error),
+    #[display("The image is too large, it can be at most {} KiB", max_bytes / 1024)]
+    #[from(skip)]
+    TooLarge { max_bytes: usize },
+    #[display("The upload does not contain an image")]
+    #[from(skip)]
+    Missing,
+    #[display("The uploaded image has expired, please upload it again")]
+    #[from(skip)]
+    Unknown,
+}
+
+impl IntoResponse for UploadError {
+    fn into_response(self) -> Response {
+        let status = match &self {
+            UploadError::Multipart(err) => err.status(),
+            UploadError::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            UploadError::TooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            UploadError::Missing | UploadError::Unknown => StatusCode::BAD_REQUEST,
+        };
+        if status.is_server_error() {
+            warn!("Failed to receive upload: {}", self);
+        }
+        (status, self.to_string()).into_response()
+    }
+}
+
+/// An upload which has not been used to create a blog post yet.
+#[derive(Debug)]
+struct PendingUpload {
+    owner: UserId,
+    uploaded_at: Instant,
+}
+
+/// Manages the uploads in a directory of their own.
+/// Clones share the same pending uploads.
+#[derive(Debug, Clone)]
+pub struct Uploads {
+    dir: PathBuf,
+    max_bytes: usize,
+    pending: Arc<Mutex<HashMap<UploadToken, PendingUpload>>>,
+}
+
+impl Uploads {
+    /// Create the upload directory, deleting uploads left behind by a previous run of the server,
+    /// since their tokens are only known to the process which received them.
+    pub async fn new(dir: PathBuf, max_bytes: usize) -> std::io::Result<Self> {
+        match tokio::fs::remove_dir_all(&dir).await {
+            Err(err) if err.kind() != ErrorKind::NotFound => return Err(err),
+            _ => {}
+        }
+        tokio::fs::create_dir_all(&dir).await?;
+        Ok(Self { dir, max_bytes, pending: Arc::default() })
+    }
+
+    /// Stream the file in the multipart field to disk.
+    /// Returns the token of the upload, which only the owner can claim.
+    #[instrument(level = "info", skip(self, field))]
+    pub async fn receive(&self, owner: UserId, mut field: Field<'_>) -> Result<UploadToken, UploadError> {
+        let token = Uuid::new_v4().simple().to_string();
+        let path = self.path(&token);
+        let result = async {
+            let mut file = tokio::fs::File::create(&path).await?;
+            let mut size = 0;
+            while let Some(chunk) = field.chunk().await? {
+                size += chunk.len();
+                if size > self.max_bytes {
+                    return Err(UploadError::TooLarge { max_bytes: self.max_bytes });
+                }
+                file.write_all(&chunk).await?;
+            }
+            file.flush().await?;
+            debug!("Received upload {} of {} bytes", token, size);
+            Ok(())
+        }
+        .await;
+        if let Err(err) = result {
+            discard(&path).await;
+            return Err(err);
+        }
+        let upload = PendingUpload { owner, uploaded_at: Instant::now() };
+        self.pending.lock().expect("uploads lock should not be poisoned").insert(token.clone(), upload);
+        Ok(token)
+    }
+
+    /// Write the image to a new file in the upload directory, for images which do not arrive as uploads,
+    /// e.g. those of an imported archive.
+    /// Returns the path of the file, which the caller must `discard` once it is processed.
+    pub async fn write(&self, bytes: &[u8]) -> std::io::Result<PathBuf> {
+        let path = self.path(&Uuid::new_v4().simple().to_string());
+        if let Err(err) = tokio::fs::write(&path, bytes).await {
+            discard(&path).await;
+            return Err(err);
+        }
+        Ok(path)
+    }
+
+    /// Claim the upload for a blog post of the owner, after which the token cannot be used again.
+    /// Returns the path of the uploaded file, which the caller must `discard` once it is processed.
+    pub fn claim(&self, token: &str, owner: UserId) -> Result<PathBuf, UploadError> {
+        let mut pending = self.pending.lock().expect("uploads lock should not be poisoned");
+        // Only tokens issued by `receive` are turned into paths, so a token cannot point outside the directory
+        match pending.get(token) {
+            Some(upload) if upload.owner == owner && upload.uploaded_at.elapsed() < UPLOAD_TTL => {
+                pending.remove(token);
+                Ok(self.path(token))
+            }
+            _ => Err(UploadError::Unknown),
+        }
+    }
+
+    /// Delete the uploads which were not claimed within `UPLOAD_TTL`.
+    /// Returns the number of deleted uploads.
+    #[instrument(level = "info", skip(self))]
+    pub async fn purge(&self) -> usize {
+        let expired: Vec<UploadToken> = {
+            let mut pending = self.pending.lock().expect("uploads lock should not be poisoned");
+            let expired = pending
+                .iter()
+                .filter(|(_, upload)| upload.uploaded_at.elapsed() >= UPLOAD_TTL)
+                .map(|(token, _)| token.clone())
+                .collect::<Vec<_>>();
+            for token in &expired {
+                pending.remove(token);
+            }
+            expired
+        };
+        for token in &expired {
+            discard(&self.path(token)).await;
+        }
+        info!("Deleted {} expired uploads", expired.len());
+        expired.len()
+    }
+
+    fn path(&self, token: &str) -> PathBuf {
+        self.dir.join(token)
+    }
+}
+
+/// Delete the uploaded file, e.g. once it is processed.
+/// Failures are only logged, since the upload directory is cleared when the server restarts anyway.
+pub async fn discard(path: &Path) {
+    if let Err(err) = tokio::fs::remove_file(path).await {
+        warn!("Failed to delete upload {}: {}", path.display(), err);
+    }
+}
+
+/// Deleting expired uploads is scheduled as a background job.
+#[async_trait]
+impl Job for Uploads {
+    type Error = Infallible;
+
+    fn name(&self) -> &'static str {
+        "purge_uploads"
+    }
+
+    async fn run(&self) -> Result<(), Infallible> {
+        self.purge().await;
+        Ok(())
+    }
+}
@@ -0,0 +1,57 @@
+//! The total size of the stored images, as shown in the statistics of the blog.
+//! Measuring it lists every stored image, which for S3 means paging through the whole bucket,
+//! so it is measured in the background now and then, and read from memory in between, e.g. by `/metrics`.
+
+use crate::server::jobs::Job;
+use crate::server::storage::{ImageStorage, StorageError};
+use axum::async_trait;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::debug;
+
+/// How often the size of the stored images is measured.
+pub const MEASURE_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Remembers the total size of the stored images as last measured.
+#[derive(Debug, Clone)]
+pub struct StorageUsage {
+    images: ImageStorage,
+    bytes: Arc<AtomicU64>,
+}
+
+impl StorageUsage {
+    /// Create the usage of the image store, which reads as zero until it is first measured.
+    pub fn new(images: ImageStorage) -> Self {
+        Self { images, bytes: Arc::new(AtomicU64::new(0)) }
+    }
+
+    /// The total size in bytes of the stored images as last measured, which may be up to `MEASURE_INTERVAL` old.
+    pub fn stored_bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    /// Measure the total size of the stored images, and remember it until the next measurement,
+    /// also as the `images_stored_bytes` metric.
+    pub async fn measure(&self) -> Result<u64, StorageError> {
+        let bytes = self.images.stored_bytes().await?;
+        self.bytes.store(bytes, Ordering::Relaxed);
+        metrics::gauge!("images_stored_bytes").set(bytes as f64);
+        debug!("Images take up {} bytes", bytes);
+        Ok(bytes)
+    }
+}
+
+/// The usage is measured as a background job.
+#[async_trait]
+impl Job for StorageUsage {
+    type Error = StorageError;
+
+    fn name(&self) -> &'static str {
+        "storage_usage"
+    }
+
+    async fn run(&self) -> Result<(), StorageError> {
+        self.measure().await.map(|_| ())
+    }
+}
@@ -0,0 +1,278 @@
+//! Outbound webhooks, which notify other services when blog posts are created, updated or deleted.
+//! Every event is recorded in the `webhook_delivery` table once for each configured webhook before it is sent,
+//! so that failed deliveries are retried with exponential backoff, even after the server restarts,
+//! and the table doubles as a log of all deliveries for debugging.
+//! Deliveries are signed with the secret of their webhook, see `sign`.
+
+use crate::model::{BlogPostId, InsertWebhookDelivery, WebhookAttempt, WebhookDelivery, WebhookStatus};
+use crate::server::config::WebhookConfig;
+use crate::server::jobs::Job;
+use crate::server::outbound::HttpClient;
+use crate::server::persistence::database::DatabaseError;
+use crate::server::rest;
+use crate::server::Database;
+use axum::async_trait;
+use hmac::{Hmac, Mac};
+use reqwest::header::CONTENT_TYPE;
+use reqwest::StatusCode;
+use serde::Serialize;
+use sha2::Sha256;
+use std::sync::Arc;
+use std::time::Duration;
+use time::format_description::well_known::Rfc3339;
+use time::{OffsetDateTime, PrimitiveDateTime};
+use tracing::{debug, instrument, warn};
+
+/// How often deliveries which are due to be retried are attempted.
+pub const RETRY_INTERVAL: Duration = Duration::from_secs(15);
+
+/// How many times a delivery is attempted before it is given up.
+const MAX_ATTEMPTS: i32 = 8;
+
+/// How long to wait before retrying a failed delivery for the first time.
+/// The delay doubles with every further retry, so that a delivery is given up about an hour after the event.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(30);
+
+/// How long a webhook may take to answer a delivery.
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long deliveries are kept in the log after they succeeded or were given up.
+const LOG_RETENTION: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// How many due deliveries are retried per run of the job, so that a webhook which was down for a while
+/// is not hit with all of its missed events at once.
+const RETRY_BATCH_SIZE: i64 = 100;
+
+/// A change to a blog post which the webhooks are notified of.
+/// Blog posts are sent in the same form in which the REST API returns them.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event")]
+pub enum WebhookEvent {
+    /// A blog post was published.
+    #[serde(rename = "post.created")]
+    Created { post: rest::Post },
+    /// The text of a published blog post was edited.
+    #[serde(rename = "post.updated")]
+    Updated { post: rest::Post },
+    /// A blog post was deleted or hidden.
+    #[serde(rename = "post.deleted")]
+    Deleted { post_id: BlogPostId },
+}
+
+impl WebhookEvent {
+    /// The name of the event, which is sent in the payload and in the `X-Webhook-Event` header.
+    fn name(&self) -> &'static str {
+        match self {
+            WebhookEvent::Created { .. } => "post.created",
+            WebhookEvent::Updated { .. } => "post.updated",
+            WebhookEvent::Deleted { .. } => "post.deleted",
+        }
+    }
+
+    /// The ID of the blog post which changed.
+    fn post_id(&self) -> BlogPostId {
+        match self {
+            WebhookEvent::Created { post } | WebhookEvent::Updated { post } => post.id,
+            WebhookEvent::Deleted { post_id } => *post_id,
+        }
+    }
+}
+
+/// The JSON body of a delivery, e.g. `{"event": "post.deleted", "post_id": 42, "occurred_at": "..."}`.
+#[derive(Serialize)]
+struct Payload<'a> {
+    #[serde(flatten)]
+    event: &'a WebhookEvent,
+    /// When the event happened, in RFC 3339 format.
+    occurred_at: String,
+}
+
+/// Why an attempt to deliver an event failed.
+#[derive(Debug, derive_more::Display)]
+enum DeliveryError {
+    #[display("The webhook is no longer configured")]
+    Unconfigured,
+    #[display("Request failed: {}", _0)]
+    Request(reqwest::Error),
+    #[display("The webhook answered with {}", _0)]
+    Status(StatusCode),
+}
+
+/// Notifies the configured webhooks of post events.
+#[derive(Debug, Clone)]
+pub struct Webhooks {
+    webhooks: Arc<[WebhookConfig]>,
+    database: Database,
+    client: HttpClient,
+}
+
+impl Webhooks {
+    pub fn new(webhooks: Vec<WebhookConfig>, database: Database, client: HttpClient) -> Self {
+        Self { webhooks: webhooks.into(), database, client }
+    }
+
+    /// Notify the webhooks of the event in the background.
+    /// Does nothing if no webhooks are configured.
+    pub fn publish(&self, event: WebhookEvent) {
+        if self.webhooks.is_empty() {
+            return;
+        }
+        let webhooks = self.clone();
+        tokio::spawn(async move {
+            if let Err(err) = webhooks.dispatch(event).await {
+                warn!("Failed to record webhook deliveries: {}", err);
+            }
+        });
+    }
+
+    /// Record a delivery of the event for every webhook, and attempt them right away.
+    #[instrument(level = "info", skip(self))]
+    async fn dispatch(&self, event: WebhookEvent) -> Result<(), DatabaseError> {
+        let now = OffsetDateTime::now_utc();
+        let occurred_at = now.format(&Rfc3339).expect("the current time should be formattable as RFC 3339");
+        let payload = serde_json::to_string(&Payload { event: &event, occurred_at })
+            .expect("webhook events should be serializable");
+        let now = PrimitiveDateTime::new(now.date(), now.time());
+        let deliveries = self
+            .webhooks
+            .iter()
+            .map(|webhook| InsertWebhookDelivery {
+                webhook_url: webhook.url.clone(),
+                event: event.name().to_string(),
+                post_id: event.post_id(),
+                payload: payload.clone(),
+                status: WebhookStatus::Pending.as_str().to_string(),
+                attempts: 0,
+                // The first attempt follows right away, and is only retried from here if the server stops before it ends
+                next_attempt_at: Some(now + INITIAL_BACKOFF),
+                created_at: now,
+            })
+            .collect();
+        let deliveries = self.database.enqueue_webhook_deliveries(deliveries).await?;
+        futures_util::future::join_all(deliveries.into_iter().map(|delivery| self.attempt(delivery))).await;
+        Ok(())
+    }
+
+    /// Attempt the delivery once, and record whether it succeeded, or else when it is retried.
+    /// The delivery is given up after `MAX_ATTEMPTS`, or if its webhook was removed from the configuration.
+    #[instrument(level = "info", skip_all, fields(id = delivery.id, url = %delivery.webhook_url))]
+    async fn attempt(&self, delivery: WebhookDelivery) {
+        let webhook = self.webhooks.iter().find(|webhook| webhook.url == delivery.webhook_url);
+        let result = match webhook {
+            Some(webhook) => self.send(webhook, &delivery).await,
+            None => Err(DeliveryError::Unconfigured),
+        };
+        let attempts = delivery.attempts + 1;
+        let now = OffsetDateTime::now_utc();
+        let now = PrimitiveDateTime::new(now.date(), now.time());
+        let (outcome, attempt) = match result {
+            Ok(status) => {
+                debug!("Delivered {} to {}", delivery.event, delivery.webhook_url);
+                let attempt = WebhookAttempt {
+                    status: WebhookStatus::Delivered.as_str().to_string(),
+                    attempts,
+                    next_attempt_at: None,
+                    response_status: Some(i32::from(status.as_u16())),
+                    last_error: None,
+                    delivered_at: Some(now),
+                };
+                ("delivered", attempt)
+            }
+            Err(err) => {
+                let response_status = match &err {
+                    DeliveryError::Status(status) => Some(i32::from(status.as_u16())),
+                    _ => None,
+                };
+                let give_up = attempts >= MAX_ATTEMPTS || matches!(err, DeliveryError::Unconfigured);
+                let (outcome, status, next_attempt_at) = if give_up {
+                    warn!("Giving up delivering {} to {} after {} attempts: {}", delivery.event, delivery.webhook_url, attempts, err);
+                    ("failed", WebhookStatus::Failed, None)
+                } else {
+                    debug!("Failed to deliver {} to {}, retrying: {}", delivery.event, delivery.webhook_url, err);
+                    ("retrying", WebhookStatus::Pending, Some(now + backoff(attempts)))
+                };
+                let attempt = WebhookAttempt {
+                    status: status.as_str().to_string(),
+                    attempts,
+                    next_attempt_at,
+                    response_status,
+                    last_error: Some(err.to_string()),
+                    delivered_at: None,
+                };
+                (outcome, attempt)
+            }
+        };
+        metrics::counter!("webhook_deliveries_total", "outcome" => outcome).increment(1);
+        if let Err(err) = self.database.record_webhook_attempt(delivery.id, attempt).await {
+            warn!("Failed to record the attempt to deliver {} to {}: {}", delivery.event, delivery.webhook_url, err);
+        }
+    }
+
+    /// Post the payload of the delivery to the webhook.
+    /// Returns the status of the response if it indicates success.
+    async fn send(&self, webhook: &WebhookConfig, delivery: &WebhookDelivery) -> Result<StatusCode, DeliveryError> {
+        let timestamp = OffsetDateTime::now_utc().unix_timestamp();
+        let signature = sign(&webhook.secret, timestamp, &delivery.payload);
+        let response = self
+            .client
+            .trusted()
+            .post(&webhook.url)
+            .timeout(DELIVERY_TIMEOUT)
+            .header(CONTENT_TYPE, "application/json")
+            .header("X-Webhook-Event", &delivery.event)
+            .header("X-Webhook-Delivery", delivery.id)
+            .header("X-Webhook-Timestamp", timestamp)
+            .header("X-Webhook-Signature", format!("sha256={signature}"))
+            .body(delivery.payload.clone())
+            .send()
+            .await
+            .map_err(DeliveryError::Request)?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(DeliveryError::Status(status));
+        }
+        Ok(status)
+    }
+}
+
+/// Sign the payload sent at the timestamp, in seconds since the Unix epoch, with the secret of the webhook.
+/// The signature is the hex-encoded HMAC-SHA256 of `<timestamp>.<payload>`, which receivers recompute
+/// to verify that the delivery was sent by this server, and whose timestamp they check to reject replays.
+fn sign(secret: &str, timestamp: i64, payload: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC should accept keys of any length");
+    mac.update(format!("{timestamp}.").as_bytes());
+    mac.update(payload.as_bytes());
+    format!("{:x}", mac.finalize().into_bytes())
+}
+
+/// Returns the delay before retrying a delivery which failed the provided number of times.
+fn backoff(attempts: i32) -> Duration {
+    INITIAL_BACKOFF.saturating_mul(2u32.saturating_pow(attempts.max(1) as u32 - 1))
+}
+
+/// Retrying due deliveries and purging old ones from the log is scheduled as a background job.
+#[async_trait]
+impl Job for Webhooks {
+    type Error = DatabaseError;
+
+    fn name(&self) -> &'static str {
+        "deliver_webhooks"
+    }
+
+    async fn run(&self) -> Result<(), DatabaseError> {
+        let now = OffsetDateTime::now_utc();
+        let now = PrimitiveDateTime::new(now.date(), now.time());
+        let due = self.database.fetch_due_webhook_deliveries(now, RETRY_BATCH_SIZE).await?;
+        if !due.is_empty() {
+            debug!("Retrying {} webhook deliveries", due.len());
+        }
+        for delivery in due {
+            self.attempt(delivery).await;
+        }
+        let purged = self.database.purge_webhook_deliveries(now - LOG_RETENTION).await?;
+        if purged > 0 {
+            debug!("Purged {} webhook deliveries from the log", purged);
+        }
+        Ok(())
+    }
+}
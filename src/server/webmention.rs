@@ -0,0 +1,421 @@
+//! Webmentions, which tell other sites that a blog post links to them, and let other sites tell the blog the same.
+//! When a blog post is published or edited, every page it links to is checked for a Webmention endpoint,
+//! announced in its `Link` header or by a `<link>` or `<a>` element with `rel="webmention"`,
+//! and the endpoint is sent the URL of the blog post as the source and the page as the target.
+//! The endpoint of the blog at `/webmention` accepts mentions of its blog posts and verifies them in the background,
+//! by fetching the source and checking that it links to the blog post, before the mention is shown under it.
+//! Sending requires `public_url`, since the linked sites must be able to fetch the blog post to verify the mention.
+//! Sources, targets and endpoints are only requested over HTTPS and on public addresses, since they are chosen by
+//! whoever sends or is sent a mention.
+
+use crate::error::AppError;
+use crate::markdown;
+use crate::model::{BlogPostId, InsertWebmention};
+use crate::server::client_ip::ClientIp;
+use crate::server::outbound::{self, BlockedUrl, HttpClient};
+use crate::server::permalinks::post_id;
+use crate::server::rate_limit::RateLimiter;
+use crate::server::{rest, Database};
+use axum::extract::Request;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Form, Router};
+use futures_util::StreamExt;
+use http::header::{CONTENT_TYPE, LINK};
+use http::{HeaderMap, HeaderValue, StatusCode};
+use reqwest::Url;
+use serde::Deserialize;
+use std::time::Duration;
+use time::{OffsetDateTime, PrimitiveDateTime};
+use tracing::{debug, info, warn};
+
+/// How long a remote server may take to answer a request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Pages larger than this are not searched for Webmention endpoints or links.
+const MAX_PAGE_BYTES: usize = 1024 * 1024;
+
+/// How many of the pages linked by a blog post are sent Webmentions at once.
+const MAX_CONCURRENT_SENDS: usize = 4;
+
+/// Titles of mentioning pages longer than this many characters are cut off.
+const MAX_TITLE_LENGTH: usize = 200;
+
+/// Why a remote page could not be fetched, or a Webmention could not be sent.
+#[derive(Debug, derive_more::Display, derive_more::From)]
+enum WebmentionError {
+    #[display("Request failed: {}", _0)]
+    Request(reqwest::Error),
+    #[display("The remote server answered with {}", _0)]
+    Status(StatusCode),
+    #[display("The page is larger than {} bytes", MAX_PAGE_BYTES)]
+    TooLarge,
+    #[display("Refusing to request the URL: {}", _0)]
+    Blocked(BlockedUrl),
+}
+
+/// Sends Webmentions for the links in blog posts, and verifies the Webmentions which the blog receives.
+#[derive(Debug, Clone)]
+pub struct Webmentions {
+    /// The configured URL of the server, without a trailing slash.
+    public_url: Option<String>,
+    database: Database,
+    client: HttpClient,
+}
+
+impl Webmentions {
+    /// Without a public URL, no Webmentions are sent, and mentions of blog posts under any host are accepted.
+    pub fn new(public_url: Option<String>, database: Database, client: HttpClient) -> Self {
+        let public_url = public_url.map(|url| url.trim_end_matches('/').to_string());
+        Self { public_url, database, client }
+    }
+
+    /// Send Webmentions to the pages which the blog post links to in the background.
+    /// Only absolute links to other sites are considered, and pages without a Webmention endpoint are skipped.
+    /// Does nothing if the public URL is not configured.
+    pub fn send(&self, post: &rest::Post) {
+        let Some(base_url) = &self.public_url else {
+            return;
+        };
+        let source = format!("{base_url}{}", post.path);
+        let targets: Vec<Url> = markdown::links(&post.text)
+            .iter()
+            .filter_map(|link| Url::parse(link).ok())
+            .filter(|url| outbound::check_https_url(url).is_ok() && !url.as_str().starts_with(base_url.as_str()))
+            .collect();
+        if targets.is_empty() {
+            return;
+        }
+        let webmentions = self.clone();
+        tokio::spawn(async move {
+            let (webmentions, source) = (&webmentions, &source);
+            let mut sends = futures_util::stream::iter(targets)
+                .map(|target| async move {
+                    let result = webmentions.send_one(source, &target).await;
+                    (target, result)
+                })
+                .buffer_unordered(MAX_CONCURRENT_SENDS);
+            while let Some((target, result)) = sends.next().await {
+                let outcome = match result {
+                    Ok(true) => "sent",
+                    Ok(false) => "unsupported",
+                    Err(err) => {
+                        debug!("Failed to send a Webmention of {} to {}: {}", source, target, err);
+                        "failed"
+                    }
+                };
+                metrics::counter!("webmentions_sent_total", "outcome" => outcome).increment(1);
+            }
+        });
+    }
+
+    /// Send a Webmention of the target by the source to the endpoint of the target.
+    /// Returns whether the target has an endpoint.
+    async fn send_one(&self, source: &str, target: &Url) -> Result<bool, WebmentionError> {
+        let Some(endpoint) = self.discover(target).await? else {
+            return Ok(false);
+        };
+        outbound::check_https_url(&endpoint)?;
+        let response = self
+            .client
+            .post(endpoint)
+            .timeout(REQUEST_TIMEOUT)
+            .form(&[("source", source), ("target", target.as_str())])
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(WebmentionError::Status(response.status()));
+        }
+        Ok(true)
+    }
+
+    /// Discover the Webmention endpoint of the page, from its `Link` header or else from its HTML.
+    /// Returns `None` if the page announces no endpoint.
+    async fn discover(&self, target: &Url) -> Result<Option<Url>, WebmentionError> {
+        outbound::check_https_url(target)?;
+        let response = self.client.get(target.clone()).timeout(REQUEST_TIMEOUT).send().await?;
+        if !response.status().is_success() {
+            return Err(WebmentionError::Status(response.status()));
+        }
+        // Relative endpoints are resolved against the URL of the page after redirects
+        let base = response.url().clone();
+        let from_header = response
+            .headers()
+            .get_all(LINK)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .find_map(link_header_endpoint);
+        if let Some(endpoint) = from_header {
+            return Ok(base.join(&endpoint).ok());
+        }
+        if !is_html(response.headers()) {
+            return Ok(None);
+        }
+        let page = read_page(response).await?;
+        let endpoint = elements(&page, &["link", "a"])
+            .iter()
+            .find(|attributes| has_rel(attributes, "webmention"))
+            .and_then(|attributes| attribute(attributes, "href"))
+            .and_then(|href| base.join(href).ok());
+        Ok(endpoint)
+    }
+
+    /// The blog post which the target of a received Webmention refers to, if it is the page of a blog post.
+    /// If the public URL is configured, the target must be under it.
+    fn target_post(&self, target: &Url) -> Option<BlogPostId> {
+        if let Some(base_url) = &self.public_url {
+            if !target.as_str().starts_with(base_url.as_str()) {
+                return None;
+            }
+        }
+        post_id(target.path())
+    }
+
+    /// Fetch the source of a received Webmention, and save the mention if the source links to the target,
+    /// or delete an earlier mention by the source if it no longer does, as the Webmention specification asks.
+    async fn verify(&self, post_id: BlogPostId, source: Url, target: Url) {
+        let title = match self.fetch_source(&source).await {
+            Ok(Some((page, true))) if links_to(&page, &source, &target) => Some(title(&page)),
+            Ok(Some((page, false))) if page.contains(target.as_str()) => Some(None),
+            Ok(_) => None,
+            // A source which is gone no longer mentions anything
+            Err(WebmentionError::Status(StatusCode::NOT_FOUND | StatusCode::GONE)) => None,
+            Err(err) => {
+                debug!("Failed to verify the Webmention of post {} by {}: {}", post_id, source, err);
+                return;
+            }
+        };
+        let result = match title {
+            Some(title) => {
+                let now = OffsetDateTime::now_utc();
+                let mention = InsertWebmention {
+                    post_id,
+                    source: source.to_string(),
+                    title,
+                    received_at: PrimitiveDateTime::new(now.date(), now.time()),
+                };
+                self.database.save_webmention(mention).await.map(|()| {
+                    info!("{} mentions post {}", source, post_id);
+                })
+            }
+            None => self.database.remove_webmention(post_id, source.to_string()).await.map(|removed| {
+                if removed {
+                    info!("{} no longer mentions post {}", source, post_id);
+                }
+            }),
+        };
+        if let Err(err) = result {
+            warn!("Failed to record the Webmention of post {} by {}: {}", post_id, source, err);
+        }
+    }
+
+    /// Fetch the source of a received Webmention.
+    /// Returns the page and whether it is HTML, or `None` if it is neither HTML nor plain text.
+    async fn fetch_source(&self, source: &Url) -> Result<Option<(String, bool)>, WebmentionError> {
+        outbound::check_https_url(source)?;
+        let response = self.client.get(source.clone()).timeout(REQUEST_TIMEOUT).send().await?;
+        if !response.status().is_success() {
+            return Err(WebmentionError::Status(response.status()));
+        }
+        let html = is_html(response.headers());
+        let text = content_type(response.headers()).is_some_and(|content_type| content_type.starts_with("text/plain"));
+        if !html && !text {
+            return Ok(None);
+        }
+        Ok(Some((read_page(response).await?, html)))
+    }
+}
+
+/// Create the router for the Webmention endpoint of the blog.
+pub fn router() -> Router {
+    Router::new().route("/webmention", post(receive))
+}
+
+/// Middleware which announces the Webmention endpoint in the `Link` header of the pages of blog posts,
+/// so that the sites which link to them can find it.
+pub async fn advertise_endpoint(request: Request, next: Next) -> Response {
+    let is_post_page = post_id(request.uri().path()).is_some();
+    let mut response = next.run(request).await;
+    if is_post_page {
+        response.headers_mut().append(LINK, HeaderValue::from_static(r#"</webmention>; rel="webmention""#));
+    }
+    response
+}
+
+/// The form of a received Webmention.
+#[derive(Debug, Deserialize)]
+struct WebmentionForm {
+    /// The URL of the page which mentions the target.
+    source: String,
+    /// The URL of the mentioned blog post.
+    target: String,
+}
+
+/// Accept a Webmention of a blog post, which is verified in the background.
+/// Responds with `202 Accepted`, or with `400 Bad Request` and the reason if the mention is invalid,
+/// e.g. because the target is not a visible blog post of this blog, or the source is not a public HTTPS URL.
+/// Webmentions are rate limited like the server functions which change data.
+async fn receive(
+    webmentions: Webmentions,
+    rate_limiter: RateLimiter,
+    ClientIp(ip): ClientIp,
+    Form(form): Form<WebmentionForm>,
+) -> Result<StatusCode, Response> {
+    if let Err(retry_after) = rate_limiter.check(ip) {
+        // Round up so that retrying after the advertised time always succeeds
+        return Err(AppError::RateLimited { retry_after: retry_after.as_secs() + 1 }.into_response());
+    }
+    let bad_request = |message: &'static str| (StatusCode::BAD_REQUEST, message).into_response();
+    let source = Url::parse(&form.source)
+        .ok()
+        .filter(|source| outbound::check_https_url(source).is_ok())
+        .ok_or_else(|| bad_request("The source must be a public https URL"))?;
+    let target = Url::parse(&form.target)
+        .ok()
+        .filter(is_web_url)
+        .ok_or_else(|| bad_request("The target must be an http or https URL"))?;
+    if source == target {
+        return Err(bad_request("The source and the target must differ"));
+    }
+    let post_id = webmentions.target_post(&target).ok_or_else(|| bad_request("The target is not a blog post"))?;
+    match webmentions.database.fetch_detail(post_id, None).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return Err(bad_request("The target is not a blog post")),
+        Err(err) => {
+            warn!("Failed to load post {} for a Webmention: {}", post_id, err);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+        }
+    }
+    debug!("Received a Webmention of post {} by {}", post_id, source);
+    tokio::spawn(async move { webmentions.verify(post_id, source, target).await });
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// Whether the URL points to a page on the web, rather than e.g. a local file.
+fn is_web_url(url: &Url) -> bool {
+    matches!(url.scheme(), "http" | "https")
+}
+
+/// The media type of the response, if it has one.
+fn content_type(headers: &HeaderMap) -> Option<&str> {
+    headers.get(CONTENT_TYPE).and_then(|content_type| content_type.to_str().ok())
+}
+
+/// Whether the response is an HTML page.
+fn is_html(headers: &HeaderMap) -> bool {
+    content_type(headers).is_some_and(|content_type| content_type.starts_with("text/html"))
+}
+
+/// Read the body of the response as text, giving up if it is larger than `MAX_PAGE_BYTES`.
+async fn read_page(mut response: reqwest::Response) -> Result<String, WebmentionError> {
+    let mut page = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if page.len() + chunk.len() > MAX_PAGE_BYTES {
+            return Err(WebmentionError::TooLarge);
+        }
+        page.extend_from_slice(&chunk);
+    }
+    Ok(String::from_utf8_lossy(&page).into_owned())
+}
+
+/// The Webmention endpoint in a `Link` header, e.g. `<https://example.com/webmention>; rel="webmention"`.
+fn link_header_endpoint(header: &str) -> Option<String> {
+    header.split(',').find_map(|link| {
+        let (url, params) = link.split_once(';')?;
+        let url = url.trim().strip_prefix('<')?.strip_suffix('>')?;
+        let is_webmention = params.split(';').filter_map(|param| param.split_once('=')).any(|(name, value)| {
+            name.trim().eq_ignore_ascii_case("rel")
+                && value.trim().trim_matches('"').split_whitespace().any(|rel| rel.eq_ignore_ascii_case("webmention"))
+        });
+        is_webmention.then(|| url.to_string())
+    })
+}
+
+/// Whether the HTML page of the source links to the target with an `<a>` or `<link>` element.
+fn links_to(page: &str, source: &Url, target: &Url) -> bool {
+    elements(page, &["a", "link"])
+        .iter()
+        .filter_map(|attributes| attribute(attributes, "href"))
+        .filter_map(|href| source.join(href).ok())
+        .any(|href| href == *target)
+}
+
+/// The title of the HTML page, with its whitespace collapsed and cut off after `MAX_TITLE_LENGTH` characters.
+fn title(page: &str) -> Option<String> {
+    // Lowercasing ASCII keeps all byte offsets intact
+    let lowercase = page.to_ascii_lowercase();
+    let start = lowercase.find("<title")?;
+    let start = start + lowercase[start..].find('>')? + 1;
+    let end = start + lowercase[start..].find("</title")?;
+    let title = unescape(&page[start..end]).split_whitespace().collect::<Vec<_>>().join(" ");
+    let title: String = title.chars().take(MAX_TITLE_LENGTH).collect();
+    (!title.is_empty()).then_some(title)
+}
+
+/// The attributes of all elements with one of the names in the HTML page, e.g. of every `<a>` and `<link>`.
+/// This is a lenient scan rather than a full HTML parser, which is enough to find links.
+fn elements(page: &str, names: &[&str]) -> Vec<Vec<(String, String)>> {
+    let mut elements = Vec::new();
+    for (start, _) in page.match_indices('<') {
+        let tag = &page[start + 1..];
+        let tag = &tag[..tag.find('>').unwrap_or(tag.len())];
+        let name_end = tag.find(|c: char| c.is_whitespace() || c == '/').unwrap_or(tag.len());
+        if names.iter().any(|name| tag[..name_end].eq_ignore_ascii_case(name)) {
+            elements.push(attributes(&tag[name_end..]));
+        }
+    }
+    elements
+}
+
+/// Parse the attributes of an element, e.g. ` href="/post/1" rel=webmention`, with lowercase names.
+fn attributes(tag: &str) -> Vec<(String, String)> {
+    let mut attributes = Vec::new();
+    let mut rest = tag;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == '/');
+        let end = rest.find(|c: char| c.is_whitespace() || c == '=').unwrap_or(rest.len());
+        if end == 0 {
+            break;
+        }
+        let name = rest[..end].to_ascii_lowercase();
+        rest = rest[end..].trim_start();
+        let value = match rest.strip_prefix('=').map(str::trim_start) {
+            Some(quoted) if quoted.starts_with(['"', '\'']) => {
+                let quote = &quoted[..1];
+                let quoted = &quoted[1..];
+                let end = quoted.find(quote).unwrap_or(quoted.len());
+                rest = quoted.get(end + 1..).unwrap_or_default();
+                &quoted[..end]
+            }
+            Some(unquoted) => {
+                let end = unquoted.find(char::is_whitespace).unwrap_or(unquoted.len());
+                rest = &unquoted[end..];
+                &unquoted[..end]
+            }
+            None => "",
+        };
+        attributes.push((name, unescape(value)));
+    }
+    attributes
+}
+
+/// The value of the attribute with the lowercase name, if the element has it.
+fn attribute<'a>(attributes: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    attributes.iter().find(|(attribute, _)| attribute == name).map(|(_, value)| value.as_str())
+}
+
+/// Whether the `rel` attribute of the element includes the relation.
+fn has_rel(attributes: &[(String, String)], rel: &str) -> bool {
+    attribute(attributes, "rel").is_some_and(|rels| rels.split_whitespace().any(|value| value.eq_ignore_ascii_case(rel)))
+}
+
+/// Replace the character references which commonly appear in URLs and titles with the characters they stand for.
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&amp;", "&")
+}
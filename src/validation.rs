@@ -0,0 +1,221 @@
+//! Declarative validation of the params which the client sends to the server.
+//! The client and the server run the same rules: the forms show the errors next to the offending inputs
+//! before anything is sent, and the server rejects params which break the rules with the same errors,
+//! which the forms then show in the same places.
+
+use crate::model::post_length;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// A field of a form which is validated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    /// The text of a blog post.
+    Text,
+    /// The tags of a blog post.
+    Tags,
+    /// The description of the image of a blog post.
+    ImageAlt,
+    /// The avatar of the author of a blog post, given as a URL or an uploaded file.
+    Avatar,
+    Username,
+    Password,
+    /// The reason given when reporting a blog post.
+    Reason,
+    /// The name of an API key.
+    KeyName,
+    /// An archive of blog posts which is imported.
+    Archive,
+    /// The blog posts selected to act on at once, e.g. to move them to the trash.
+    Posts,
+}
+
+impl Field {
+    const ALL: [Field; 10] = [
+        Field::Text,
+        Field::Tags,
+        Field::ImageAlt,
+        Field::Avatar,
+        Field::Username,
+        Field::Password,
+        Field::Reason,
+        Field::KeyName,
+        Field::Archive,
+        Field::Posts,
+    ];
+
+    /// The name of the field in the errors sent by the server, e.g. `image_alt`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Field::Text => "text",
+            Field::Tags => "tags",
+            Field::ImageAlt => "image_alt",
+            Field::Avatar => "avatar",
+            Field::Username => "username",
+            Field::Password => "password",
+            Field::Reason => "reason",
+            Field::KeyName => "key_name",
+            Field::Archive => "archive",
+            Field::Posts => "posts",
+        }
+    }
+
+    /// How the field is referred to in error messages, e.g. "The image description cannot be empty".
+    fn label(self) -> &'static str {
+        match self {
+            Field::Text => "blog post text",
+            Field::Tags => "tags",
+            Field::ImageAlt => "image description",
+            Field::Avatar => "avatar URL",
+            Field::Username => "username",
+            Field::Password => "password",
+            Field::Reason => "reason",
+            Field::KeyName => "key name",
+            Field::Archive => "archive",
+            Field::Posts => "selection of blog posts",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|field| field.name() == name)
+    }
+}
+
+/// A rule which the value of a field must follow.
+#[derive(Debug, Clone, Copy)]
+pub enum Rule {
+    /// The value must contain more than whitespace.
+    Required,
+    /// The value must have at least this many characters.
+    MinChars(usize),
+    /// The value must have at most this many characters.
+    /// Use this for values whose length is constrained by the database schema.
+    MaxChars(usize),
+    /// The value must have at most this many characters as the user perceives them, counted by `post_length`.
+    MaxGraphemes(usize),
+    /// The value must be an `http://` or `https://` URL.
+    HttpUrl,
+    /// Every character of the value must be allowed, which is described in error messages
+    /// as what the value "can only contain", e.g. "letters, digits, '-' and '_'".
+    Chars { allowed: fn(char) -> bool, description: &'static str },
+}
+
+impl Rule {
+    /// Returns the error message if the value of the field breaks the rule.
+    fn check(self, field: Field, value: &str) -> Option<String> {
+        let label = field.label();
+        match self {
+            Rule::Required if value.trim().is_empty() => Some(format!("The {label} cannot be empty")),
+            Rule::MinChars(min) if value.chars().count() < min => {
+                Some(format!("The {label} must be at least {min} characters long"))
+            }
+            Rule::MaxChars(max) if value.chars().count() > max => {
+                Some(format!("The {label} cannot be longer than {max} characters"))
+            }
+            Rule::MaxGraphemes(max) if post_length(value) > max => {
+                Some(format!("The {label} cannot be longer than {max} characters"))
+            }
+            Rule::HttpUrl if !is_http_url(value) => Some(format!("The {label} must start with http:// or https://")),
+            Rule::Chars { allowed, description } if !value.chars().all(allowed) => {
+                Some(format!("The {label} can only contain {description}"))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Whether the value looks like an `http://` or `https://` URL with a host.
+fn is_http_url(value: &str) -> bool {
+    let rest = value.strip_prefix("https://").or_else(|| value.strip_prefix("http://"));
+    rest.is_some_and(|rest| !rest.is_empty() && !rest.contains(char::is_whitespace))
+}
+
+/// The errors of a form, with at most one per field, which is the first rule that the field breaks.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ValidationErrors(Vec<(Field, String)>);
+
+impl ValidationErrors {
+    /// The errors of a form with a single error in the field.
+    pub fn of(field: Field, message: impl Into<String>) -> Self {
+        let mut errors = Self::default();
+        errors.add(field, message);
+        errors
+    }
+
+    /// Check the value of the field against the rules in order, and record the first one it breaks.
+    /// Nothing is checked if the field already has an error.
+    pub fn check(&mut self, field: Field, value: &str, rules: &[Rule]) -> &mut Self {
+        if self.get(field).is_none() {
+            if let Some(message) = rules.iter().find_map(|rule| rule.check(field, value)) {
+                self.0.push((field, message));
+            }
+        }
+        self
+    }
+
+    /// Record an error of the field which does not fit any rule, e.g. one which involves several fields.
+    /// The error is ignored if the field already has one.
+    pub fn add(&mut self, field: Field, message: impl Into<String>) -> &mut Self {
+        if self.get(field).is_none() {
+            self.0.push((field, message.into()));
+        }
+        self
+    }
+
+    /// The error of the field, if any.
+    pub fn get(&self, field: Field) -> Option<&str> {
+        self.0.iter().find(|(other, _)| *other == field).map(|(_, message)| message.as_str())
+    }
+
+    /// The fields and their errors, in the order in which they were recorded.
+    pub fn iter(&self) -> impl Iterator<Item = (Field, &str)> {
+        self.0.iter().map(|(field, message)| (*field, message.as_str()))
+    }
+
+    /// The error messages of all fields, in the order in which they were recorded.
+    pub fn messages(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().map(|(_, message)| message.as_str())
+    }
+
+    /// Returns `Ok` if there are no errors, or else the errors.
+    pub fn into_result(self) -> Result<(), Self> {
+        if self.0.is_empty() {
+            return Ok(());
+        }
+        Err(self)
+    }
+}
+
+/// The errors are sent from the server to the client as one line per field, e.g. `text: The blog post text cannot be empty`.
+impl Display for ValidationErrors {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (index, (field, message)) in self.0.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}: {}", field.name(), message)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses the errors sent by the server.
+/// Fails if any line does not name a known field, i.e. if the server rejected the request for another reason.
+impl FromStr for ValidationErrors {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        let mut errors = Self::default();
+        for line in s.lines() {
+            let (name, message) = line.split_once(": ").ok_or(())?;
+            errors.add(Field::from_name(name).ok_or(())?, message);
+        }
+        errors.into_result().err().ok_or(())
+    }
+}
+
+/// Params which are validated on both the client and the server.
+pub trait Validate {
+    /// Check every field of the params, and return the errors of those which break their rules.
+    fn validate(&self) -> Result<(), ValidationErrors>;
+}